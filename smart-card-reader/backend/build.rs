@@ -0,0 +1,63 @@
+//! Embed build metadata (git commit, build time) for `--version` and `/version`
+
+use std::process::Command;
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", commit);
+
+    // Deterministic: `SOURCE_DATE_EPOCH` for reproducible builds, else the
+    // commit time. The wall clock would stick at the first build of a commit,
+    // as this script only reruns when HEAD moves.
+    let built_at = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .or_else(|| {
+            Command::new("git")
+                .args(["log", "-1", "--format=%ct"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+        })
+        .map(|secs| secs.trim().to_string())
+        .filter(|secs| secs.parse::<i64>().is_ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", built_at);
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // Refresh the commit when HEAD moves
+    if let Some(git_dir) = Command::new("git")
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+    {
+        let git_dir = git_dir.trim();
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/refs/heads", git_dir);
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The `embedded-font` fallback must be a real font file, not e.g. the
+    // HTML page a "download raw file" link can return
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED_FONT").is_some() {
+        const EMBEDDED_FONT: &str = "fonts/NotoSansThai-Regular.ttf";
+        let magic = std::fs::read(EMBEDDED_FONT).ok().and_then(|data| data.get(..4).map(<[u8]>::to_vec));
+        if !matches!(magic.as_deref(), Some([0, 1, 0, 0] | b"OTTO" | b"true")) {
+            println!(
+                "cargo:warning={} is not a TrueType/OpenType font; the embedded Thai font fallback will not load",
+                EMBEDDED_FONT
+            );
+        }
+        println!("cargo:rerun-if-changed={}", EMBEDDED_FONT);
+    }
+}
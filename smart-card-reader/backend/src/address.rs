@@ -0,0 +1,517 @@
+//! Thai ID card address parsing
+//!
+//! The card stores the address as TIS-620 bytes separated by '#':
+//! `house#village#lane#road#[empty#]tambol#amphur#province[garbage]`.
+//! Binary padding may follow the last component, so garbage handling is
+//! applied conservatively: byte-level truncation first, then trailing-word
+//! trimming on the province only, snapped to the list of known provinces.
+//! Second-generation cards may also carry the address in English, in the same
+//! '#'-separated layout ([`parse_address_en`]).
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::decoder::decode_tis620_raw;
+
+/// Thai province names as stored on the card (Bangkok has no "จังหวัด" prefix)
+pub const THAI_PROVINCES: [&str; 77] = [
+    "กรุงเทพมหานคร", "กระบี่", "กาญจนบุรี", "กาฬสินธุ์", "กำแพงเพชร", "ขอนแก่น",
+    "จันทบุรี", "ฉะเชิงเทรา", "ชลบุรี", "ชัยนาท", "ชัยภูมิ", "ชุมพร", "เชียงราย",
+    "เชียงใหม่", "ตรัง", "ตราด", "ตาก", "นครนายก", "นครปฐม", "นครพนม",
+    "นครราชสีมา", "นครศรีธรรมราช", "นครสวรรค์", "นนทบุรี", "นราธิวาส", "น่าน",
+    "บึงกาฬ", "บุรีรัมย์", "ปทุมธานี", "ประจวบคีรีขันธ์", "ปราจีนบุรี", "ปัตตานี",
+    "พระนครศรีอยุธยา", "พะเยา", "พังงา", "พัทลุง", "พิจิตร", "พิษณุโลก", "เพชรบุรี",
+    "เพชรบูรณ์", "แพร่", "ภูเก็ต", "มหาสารคาม", "มุกดาหาร", "แม่ฮ่องสอน", "ยโสธร",
+    "ยะลา", "ร้อยเอ็ด", "ระนอง", "ระยอง", "ราชบุรี", "ลพบุรี", "ลำปาง", "ลำพูน",
+    "เลย", "ศรีสะเกษ", "สกลนคร", "สงขลา", "สตูล", "สมุทรปราการ", "สมุทรสงคราม",
+    "สมุทรสาคร", "สระแก้ว", "สระบุรี", "สิงห์บุรี", "สุโขทัย", "สุพรรณบุรี",
+    "สุราษฎร์ธานี", "สุรินทร์", "หนองคาย", "หนองบัวลำภู", "อ่างทอง", "อำนาจเจริญ",
+    "อุดรธานี", "อุตรดิตถ์", "อุทัยธานี", "อุบลราชธานี",
+];
+
+/// Address components parsed from the card
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedAddress {
+    pub house_no: String,
+    pub village_no: String,
+    pub lane: String,
+    pub road: String,
+    pub tambol: String,
+    pub amphur: String,
+    pub province: String,
+}
+
+impl ParsedAddress {
+    /// Full address: house + village + road + lane + tambol + amphur + province
+    #[must_use]
+    pub fn full(&self) -> String {
+        [
+            &self.house_no,
+            &self.village_no,
+            &self.road,
+            &self.lane,
+            &self.tambol,
+            &self.amphur,
+            &self.province,
+        ]
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+/// Address bytes up to the first padding byte, decoded and split on '#'
+///
+/// Empty parts are kept to preserve positions; whitespace is collapsed.
+fn address_parts(raw: &[u8]) -> Vec<String> {
+    // Valid TIS-620 address bytes: 0x20-0x7E (ASCII printable) and 0xA1-0xFB (Thai).
+    // Garbage padding at end of field uses bytes outside these ranges (e.g. 0x00, 0x80-0x9F, 0xFC+).
+    // Truncate at the first invalid byte to strip garbage BEFORE decoding.
+    let clean: Vec<u8> = raw
+        .iter()
+        .copied()
+        .take_while(|&b| {
+            b == 0x23                           // '#' delimiter
+            || (0x20..=0x7E).contains(&b)       // ASCII printable
+            || (0xA1..=0xFB).contains(&b)       // TIS-620 Thai range
+        })
+        .collect();
+
+    // Split by '#', NFC-normalize (empty parts are kept to preserve positions)
+    let parts: Vec<String> = decode_tis620_raw(&clean)
+        .split('#')
+        .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    log::debug!("Address parts ({}): {:?}", parts.len(), parts);
+    parts
+}
+
+/// Parse raw TIS-620 address bytes into components
+#[must_use]
+pub fn parse_address(raw: &[u8]) -> ParsedAddress {
+    let parts = address_parts(raw);
+    let part = |i: usize| parts.get(i).cloned().unwrap_or_default();
+    let thai_part = |i: usize| parts.get(i).map(|s| thai_letters_only(s)).unwrap_or_default();
+
+    // Thai ID card address can be 7 or 8 fields depending on card variant:
+    //   7-field: house#village#lane#road#tambol#amphur#province         (indices 4,5,6)
+    //   8-field: house#village#lane#road#(empty)#tambol#amphur#province (indices 5,6,7)
+    // Detect by checking if index 4 is empty after cleaning.
+    let (tambol_idx, amphur_idx, province_idx) = if thai_part(4).is_empty() {
+        (5, 6, 7) // 8-field format: index 4 is empty filler
+    } else {
+        (4, 5, 6) // 7-field format: tambol starts at index 4
+    };
+
+    ParsedAddress {
+        house_no: part(0),
+        village_no: part(1),
+        lane: part(2),
+        road: part(3),
+        // Tambol and amphur are followed by '#', so padding cannot reach them
+        tambol: thai_part(tambol_idx),
+        amphur: thai_part(amphur_idx),
+        province: trim_province(&thai_part(province_idx)),
+    }
+}
+
+/// Parse the English address of second-generation cards, laid out like the Thai one
+///
+/// Components are kept as on the card (no Thai-letter filtering or province
+/// snapping); an empty field gives an empty address.
+#[must_use]
+pub fn parse_address_en(raw: &[u8]) -> ParsedAddress {
+    let parts = address_parts(raw);
+    let part = |i: usize| parts.get(i).cloned().unwrap_or_default();
+    // Same 7/8-field variants as the Thai address
+    let offset = usize::from(part(4).is_empty() && parts.len() > 7);
+    ParsedAddress {
+        house_no: part(0),
+        village_no: part(1),
+        lane: part(2),
+        road: part(3),
+        tambol: part(4 + offset),
+        amphur: part(5 + offset),
+        province: part(6 + offset),
+    }
+}
+
+/// Keep only Thai consonants/vowels/tone-marks and single spaces
+///
+/// Thai digits U+0E50-U+0E59 and punctuation are excluded — in place-name
+/// components they only appear as decoded garbage. Words are never dropped
+/// by length: single-syllable names are valid.
+fn thai_letters_only(s: &str) -> String {
+    let clean: String = s
+        .chars()
+        .filter(|&c| {
+            ('\u{0E01}'..='\u{0E2E}').contains(&c)      // Thai consonants
+            || ('\u{0E30}'..='\u{0E3A}').contains(&c)   // Thai vowels/sara
+            || ('\u{0E40}'..='\u{0E4E}').contains(&c)   // Thai vowels/tone marks
+            || c == ' '
+        })
+        .collect();
+    clean.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Drop trailing garbage words from the province, if that yields a known province
+///
+/// The province is the last component, so padding that decodes to valid Thai
+/// letters ends up appended to it. Trailing words are removed one at a time
+/// until the value matches [`THAI_PROVINCES`]; if nothing matches the value
+/// is returned unchanged rather than guessing.
+fn trim_province(value: &str) -> String {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    for end in (1..=words.len()).rev() {
+        let candidate = words[..end].join(" ");
+        if is_known_province(&candidate) {
+            return candidate;
+        }
+    }
+    value.to_string()
+}
+
+/// Check a province component (with or without the "จังหวัด" prefix)
+#[must_use]
+pub fn is_known_province(value: &str) -> bool {
+    let name = value.trim();
+    let name = name.strip_prefix("จังหวัด").unwrap_or(name).trim();
+    THAI_PROVINCES.contains(&name)
+}
+
+/// One row of the address reference dataset
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ReferenceEntry {
+    province: String,
+    amphur: String,
+    tambol: String,
+}
+
+/// Valid province/amphur/tambol names used to clean up parsed addresses
+///
+/// Names are stored without the จังหวัด/อำเภอ/ตำบล prefixes. Rows from a
+/// province-only dataset have empty amphur/tambol, in which case those
+/// components are left as parsed.
+#[derive(Debug, Clone, Default)]
+pub struct AddressReference {
+    entries: Vec<ReferenceEntry>,
+}
+
+impl AddressReference {
+    /// Reference containing only the bundled [`THAI_PROVINCES`]
+    #[must_use]
+    pub fn bundled() -> Self {
+        Self {
+            entries: THAI_PROVINCES
+                .iter()
+                .map(|p| ReferenceEntry {
+                    province: (*p).to_string(),
+                    amphur: String::new(),
+                    tambol: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Parse `province,amphur,tambol` CSV lines on top of the bundled provinces
+    ///
+    /// Blank lines and lines starting with '#' are ignored.
+    ///
+    /// # Errors
+    /// Returns error if a line has no province
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut reference = Self::bundled();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut cols = line.split(',').map(|c| c.trim().nfc().collect::<String>());
+            let province = cols.next().unwrap_or_default();
+            if province.is_empty() {
+                anyhow::bail!("line {}: missing province", i + 1);
+            }
+            reference.entries.push(ReferenceEntry {
+                province,
+                amphur: cols.next().unwrap_or_default(),
+                tambol: cols.next().unwrap_or_default(),
+            });
+        }
+        Ok(reference)
+    }
+
+    /// Load a CSV reference dataset from `path`
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be read or parsed
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read address reference {}", path.display()))?;
+        Self::from_csv(&text).with_context(|| format!("Invalid address reference {}", path.display()))
+    }
+
+    /// Snap tambol/amphur/province to the closest reference entries in place
+    ///
+    /// Amphurs are matched within the matched province and tambols within the
+    /// matched amphur. Returns the names of components that had no close match
+    /// (those are left unchanged).
+    pub fn normalize(&self, addr: &mut ParsedAddress) -> Vec<&'static str> {
+        let mut unmatched = Vec::new();
+
+        let province = snap_component(&mut addr.province, &["จังหวัด"], self.names(|_| true, |e| &e.province));
+        if province.is_none() && !addr.province.is_empty() {
+            unmatched.push("province");
+        }
+
+        let in_province = |e: &ReferenceEntry| province.as_ref().is_none_or(|p| &e.province == p);
+        let amphurs = self.names(in_province, |e| &e.amphur);
+        let amphur = snap_component(&mut addr.amphur, &["อำเภอ", "เขต"], amphurs.clone());
+        if amphur.is_none() && !addr.amphur.is_empty() && !amphurs.is_empty() {
+            unmatched.push("amphur");
+        }
+
+        let tambols = self.names(
+            |e| in_province(e) && amphur.as_ref().is_none_or(|a| &e.amphur == a),
+            |e| &e.tambol,
+        );
+        let tambol = snap_component(&mut addr.tambol, &["ตำบล", "แขวง"], tambols.clone());
+        if tambol.is_none() && !addr.tambol.is_empty() && !tambols.is_empty() {
+            unmatched.push("tambol");
+        }
+
+        unmatched
+    }
+
+    /// Distinct non-empty names of one column for entries matching `filter`
+    fn names<'a>(
+        &'a self,
+        filter: impl Fn(&ReferenceEntry) -> bool,
+        column: impl Fn(&'a ReferenceEntry) -> &'a String,
+    ) -> Vec<&'a str> {
+        let mut names: Vec<&str> = self
+            .entries
+            .iter()
+            .filter(|e| filter(e))
+            .map(|e| column(e).as_str())
+            .filter(|n| !n.is_empty())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+/// Replace `value` with its closest candidate, keeping any known prefix
+///
+/// Returns the matched (prefix-less) name.
+fn snap_component(value: &mut String, prefixes: &[&str], candidates: Vec<&str>) -> Option<String> {
+    if value.is_empty() || candidates.is_empty() {
+        return None;
+    }
+    let (prefix, name) = prefixes
+        .iter()
+        .find_map(|p| value.strip_prefix(p).map(|rest| (*p, rest.trim())))
+        .unwrap_or(("", value.as_str()));
+    let matched = closest_match(name, &candidates)?.to_string();
+    *value = format!("{}{}", prefix, matched);
+    Some(matched)
+}
+
+/// Find the candidate closest to `value` by edit distance
+///
+/// Accepts a match within `max(1, len / 4)` character edits. Returns `None`
+/// when nothing is close enough or when two candidates tie for best.
+#[must_use]
+pub fn closest_match<'a>(value: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    if let Some(exact) = candidates.iter().find(|c| **c == value) {
+        return Some(exact);
+    }
+    let max_distance = (value.chars().count() / 4).max(1);
+    let mut best: Option<(&str, usize)> = None;
+    let mut tied = false;
+    for candidate in candidates {
+        let distance = levenshtein(value, candidate);
+        match best {
+            Some((_, d)) if distance == d => tied = true,
+            Some((_, d)) if distance > d => {}
+            _ => {
+                best = Some((candidate, distance));
+                tied = false;
+            }
+        }
+    }
+    match best {
+        Some((candidate, distance)) if distance <= max_distance && !tied => Some(candidate),
+        _ => None,
+    }
+}
+
+/// Character-level Levenshtein distance
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_874;
+
+    fn tis620(s: &str) -> Vec<u8> {
+        WINDOWS_874.encode(s).0.into_owned()
+    }
+
+    #[test]
+    fn test_parse_seven_field_address() {
+        let raw = tis620("99/1#หมู่ที่ 2#ซอยสุขุมวิท 5#ถนนสุขุมวิท#ตำบลบางรัก#อำเภอเมือง#จังหวัดชลบุรี");
+        let addr = parse_address(&raw);
+        assert_eq!(addr.house_no, "99/1");
+        assert_eq!(addr.village_no, "หมู่ที่ 2");
+        assert_eq!(addr.lane, "ซอยสุขุมวิท 5");
+        assert_eq!(addr.road, "ถนนสุขุมวิท");
+        assert_eq!(addr.tambol, "ตำบลบางรัก");
+        assert_eq!(addr.amphur, "อำเภอเมือง");
+        assert_eq!(addr.province, "จังหวัดชลบุรี");
+    }
+
+    #[test]
+    fn test_parse_eight_field_address() {
+        let raw = tis620("12#หมู่ที่ 4####ตำบลหนองบัว#อำเภอเมือง#จังหวัดอุดรธานี");
+        let addr = parse_address(&raw);
+        assert_eq!(addr.tambol, "ตำบลหนองบัว");
+        assert_eq!(addr.amphur, "อำเภอเมือง");
+        assert_eq!(addr.province, "จังหวัดอุดรธานี");
+    }
+
+    #[test]
+    fn test_parse_english_address() {
+        let addr = parse_address_en(b"99/1#Moo 2#Soi Sukhumvit 5#Sukhumvit Road#Bang Rak#Mueang#Chon Buri\x00\x00");
+        assert_eq!(addr.house_no, "99/1");
+        assert_eq!(addr.village_no, "Moo 2");
+        assert_eq!(addr.lane, "Soi Sukhumvit 5");
+        assert_eq!(addr.road, "Sukhumvit Road");
+        assert_eq!(addr.tambol, "Bang Rak");
+        assert_eq!(addr.amphur, "Mueang");
+        assert_eq!(addr.province, "Chon Buri");
+
+        let addr = parse_address_en(b"12#Moo 4####Nong Bua#Mueang#Udon Thani");
+        assert_eq!(addr.tambol, "Nong Bua");
+        assert_eq!(addr.province, "Udon Thani");
+        assert_eq!(parse_address_en(b""), ParsedAddress::default());
+    }
+
+    #[test]
+    fn test_single_syllable_tambol_is_kept() {
+        // Previously dropped by the ">= 2 chars per word" heuristic
+        let raw = tis620("5#หมู่ที่ 1###ตำบล ณ#อำเภอ ก#จังหวัดระนอง");
+        let addr = parse_address(&raw);
+        assert_eq!(addr.tambol, "ตำบล ณ");
+        assert_eq!(addr.amphur, "อำเภอ ก");
+        assert_eq!(addr.province, "จังหวัดระนอง");
+    }
+
+    #[test]
+    fn test_trailing_garbage_trimmed_from_province() {
+        // Padding bytes inside the Thai range decode to stray letters after the province
+        let mut raw = tis620("5#หมู่ที่ 1###ตำบลบางรัก#อำเภอบางรัก#กรุงเทพมหานคร ฎ ฏ");
+        raw.extend_from_slice(&[0x00, 0x90, 0xFF]);
+        let addr = parse_address(&raw);
+        assert_eq!(addr.province, "กรุงเทพมหานคร");
+    }
+
+    #[test]
+    fn test_unknown_province_kept_unchanged() {
+        assert_eq!(trim_province("จังหวัดไม่มีจริง ก"), "จังหวัดไม่มีจริง ก");
+    }
+
+    #[test]
+    fn test_is_known_province() {
+        assert!(is_known_province("จังหวัดเชียงใหม่"));
+        assert!(is_known_province("กรุงเทพมหานคร"));
+        assert!(!is_known_province("จังหวัดเชียงใหม่ ก"));
+    }
+
+    #[test]
+    fn test_closest_match_known_misreads() {
+        let provinces: Vec<&str> = THAI_PROVINCES.to_vec();
+        // Dropped tone mark / dropped vowel
+        assert_eq!(closest_match("เชียงใหม", &provinces), Some("เชียงใหม่"));
+        assert_eq!(closest_match("ขอนแกน", &provinces), Some("ขอนแก่น"));
+        assert_eq!(closest_match("นครราชสีม", &provinces), Some("นครราชสีมา"));
+        // Exact match wins even among close neighbours
+        assert_eq!(closest_match("ตาก", &provinces), Some("ตาก"));
+        // Too far from anything
+        assert_eq!(closest_match("ไม่มีจริงเลย", &provinces), None);
+    }
+
+    #[test]
+    fn test_closest_match_rejects_ties() {
+        assert_eq!(closest_match("บางรัx", &["บางรัก", "บางรัด"]), None);
+    }
+
+    #[test]
+    fn test_normalize_with_dataset() {
+        let reference = AddressReference::from_csv(
+            "# province,amphur,tambol\n\
+             กรุงเทพมหานคร,บางรัก,สุริยวงศ์\n\
+             กรุงเทพมหานคร,บางรัก,สี่พระยา\n\
+             เชียงใหม่,เมืองเชียงใหม่,ศรีภูมิ\n",
+        )
+        .unwrap();
+
+        let mut addr = ParsedAddress {
+            tambol: "แขวงสุริยวงค์".to_string(),
+            amphur: "เขตบางรัด".to_string(),
+            province: "กรุงเทพมหานคร".to_string(),
+            ..ParsedAddress::default()
+        };
+        assert!(reference.normalize(&mut addr).is_empty());
+        assert_eq!(addr.tambol, "แขวงสุริยวงศ์");
+        assert_eq!(addr.amphur, "เขตบางรัก");
+        assert_eq!(addr.province, "กรุงเทพมหานคร");
+
+        let mut addr = ParsedAddress {
+            tambol: "ตำบลไม่มีจริง".to_string(),
+            amphur: "อำเภอเมืองเชียงใหม่".to_string(),
+            province: "จังหวัดเชียงใหม".to_string(),
+            ..ParsedAddress::default()
+        };
+        assert_eq!(reference.normalize(&mut addr), vec!["tambol"]);
+        assert_eq!(addr.province, "จังหวัดเชียงใหม่");
+        assert_eq!(addr.tambol, "ตำบลไม่มีจริง");
+    }
+
+    #[test]
+    fn test_normalize_bundled_leaves_amphur_tambol() {
+        let mut addr = ParsedAddress {
+            tambol: "ตำบลอะไรก็ได้".to_string(),
+            amphur: "อำเภออะไรก็ได้".to_string(),
+            province: "จังหวัดขอนแกน".to_string(),
+            ..ParsedAddress::default()
+        };
+        assert!(AddressReference::bundled().normalize(&mut addr).is_empty());
+        assert_eq!(addr.province, "จังหวัดขอนแก่น");
+        assert_eq!(addr.amphur, "อำเภออะไรก็ได้");
+    }
+
+    #[test]
+    fn test_from_csv_rejects_missing_province() {
+        assert!(AddressReference::from_csv(",บางรัก,สี่พระยา").is_err());
+    }
+}
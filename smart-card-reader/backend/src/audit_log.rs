@@ -8,9 +8,14 @@
 //! - Configuration changes
 //! - Security errors
 
+use crate::crypto::EncryptFailurePolicy;
 use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
 use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 
 /// Audit event type classification
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -30,13 +35,16 @@ pub enum AuditEventType {
     SecurityError,
     /// Data validation error
     Validation,
+    /// Outbound delivery (webhook)
+    Delivery,
 }
 
 /// Audit event severity level
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum AuditSeverity {
     /// Informational event
+    #[default]
     Info,
     /// Warning event
     Warning,
@@ -55,8 +63,9 @@ pub struct AuditLogEntry {
     pub event_type: AuditEventType,
     /// Severity level
     pub severity: AuditSeverity,
-    /// Client IP address
-    pub client_ip: IpAddr,
+    /// Client IP address, absent for internal events without a network peer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<IpAddr>,
     /// Event action (e.g., "login_success", "rate_limit_exceeded")
     pub action: String,
     /// Detailed event message
@@ -72,7 +81,7 @@ impl AuditLogEntry {
     pub fn new(
         event_type: AuditEventType,
         severity: AuditSeverity,
-        client_ip: IpAddr,
+        client_ip: impl Into<Option<IpAddr>>,
         action: impl Into<String>,
         message: impl Into<String>,
     ) -> Self {
@@ -80,17 +89,30 @@ impl AuditLogEntry {
             timestamp: Utc::now(),
             event_type,
             severity,
-            client_ip,
+            client_ip: client_ip.into(),
             action: action.into(),
             message: message.into(),
             metadata: None,
         }
     }
 
+    /// Attach structured metadata (never raw card values)
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Serialize the entry as one JSON line
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self)
+            .unwrap_or_else(|_| format!("Failed to serialize audit log: {:?}", self))
+    }
+
     /// Log the audit entry to the logger
     pub fn log(&self) {
-        let json = serde_json::to_string(self)
-            .unwrap_or_else(|_| format!("Failed to serialize audit log: {:?}", self));
+        let json = self.to_json();
 
         match self.severity {
             AuditSeverity::Info => log::info!("AUDIT: {}", json),
@@ -101,9 +123,32 @@ impl AuditLogEntry {
     }
 }
 
+/// JSON-lines audit file, flushed after every entry
+struct AuditFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl AuditFile {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
 /// Audit logger for security events
 pub struct AuditLogger {
     enabled: bool,
+    /// Entries below this severity are dropped before serialization
+    min_severity: AuditSeverity,
+    /// Optional file sink in addition to the application log
+    file: Option<Mutex<AuditFile>>,
 }
 
 impl AuditLogger {
@@ -115,102 +160,350 @@ impl AuditLogger {
         } else {
             log::warn!("⚠️ Audit logging DISABLED - Security events will not be recorded!");
         }
-        Self { enabled }
+        Self {
+            enabled,
+            min_severity: AuditSeverity::Info,
+            file: None,
+        }
     }
 
-    /// Log authentication success
-    pub fn log_auth_success(&self, client_ip: IpAddr, api_key_hint: Option<&str>) {
-        if !self.enabled {
-            return;
+    /// Also append recorded entries to `path` as JSON lines
+    ///
+    /// If the file cannot be opened, entries only go to the application log.
+    #[must_use]
+    pub fn with_file(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match AuditFile::open(path) {
+            Ok(file) => {
+                if self.enabled {
+                    log::info!("📝 Audit log file: {}", path.display());
+                }
+                self.file = Some(Mutex::new(file));
+            }
+            Err(e) => log::error!("❌ Cannot open audit log file {}: {}", path.display(), e),
         }
+        self
+    }
+
+    /// Write buffered entries to the audit file
+    ///
+    /// Entries are already flushed as they are recorded; this is a safety net
+    /// for shutdown.
+    pub fn flush(&self) -> std::io::Result<()> {
+        match &self.file {
+            Some(file) => file.lock().writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Close the audit file and open whatever is now at its path (SIGHUP)
+    ///
+    /// For logrotate: after it moved the file away (and with `create` put an
+    /// empty one in its place), new entries go to the file at the path.
+    pub fn reopen(&self) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut file = file.lock();
+        file.writer.flush()?;
+        *file = AuditFile::open(&file.path)?;
+        log::info!("📝 Audit log reopened: {}", file.path.display());
+        Ok(())
+    }
+
+    /// Close the audit file and start a fresh one at the same path
+    ///
+    /// The current file is renamed to `<path>.<timestamp>` and that path is
+    /// returned. If it was already moved away, the file is just reopened and
+    /// `None` is returned. For external rotation use [`Self::reopen`].
+    pub fn rotate(&self) -> std::io::Result<Option<PathBuf>> {
+        let Some(file) = &self.file else {
+            return Ok(None);
+        };
+        let mut file = file.lock();
+        file.writer.flush()?;
+
+        let rotated = if file.path.exists() {
+            let mut name = file.path.clone().into_os_string();
+            name.push(format!(".{}", Utc::now().format("%Y%m%dT%H%M%S%.3f")));
+            let rotated = PathBuf::from(name);
+            std::fs::rename(&file.path, &rotated)?;
+            Some(rotated)
+        } else {
+            None
+        };
+
+        *file = AuditFile::open(&file.path)?;
+        if let Some(rotated) = &rotated {
+            log::info!("📝 Audit log rotated to {}", rotated.display());
+        }
+        Ok(rotated)
+    }
+
+    /// Set the minimum severity an entry must have to be recorded
+    #[must_use]
+    pub fn with_min_severity(mut self, min_severity: AuditSeverity) -> Self {
+        if self.enabled && min_severity > AuditSeverity::Info {
+            log::info!("📝 Audit log threshold: {:?} and above", min_severity);
+        }
+        self.min_severity = min_severity;
+        self
+    }
+
+    /// Check whether an entry of the given severity would be recorded
+    #[must_use]
+    pub fn accepts(&self, severity: AuditSeverity) -> bool {
+        self.enabled && severity >= self.min_severity
+    }
+
+    /// Record an entry if it passes the enabled flag and severity threshold
+    ///
+    /// Returns `true` if the entry was recorded.
+    fn record(&self, entry: AuditLogEntry) -> bool {
+        if !self.accepts(entry.severity) {
+            return false;
+        }
+        entry.log();
+        if let Some(file) = &self.file {
+            // Flushed per entry so nothing is lost if the process is killed
+            let mut file = file.lock();
+            if let Err(e) = writeln!(file.writer, "{}", entry.to_json()).and_then(|()| file.writer.flush()) {
+                log::error!("❌ Failed to write audit log file: {}", e);
+            }
+        }
+        true
+    }
 
+    /// Log authentication success
+    pub fn log_auth_success(&self, client_ip: IpAddr, api_key_hint: Option<&str>) {
         let message = if let Some(hint) = api_key_hint {
             format!("Authentication successful (key: {}...)", hint)
         } else {
             "Authentication successful".to_string()
         };
 
-        AuditLogEntry::new(
+        let entry = AuditLogEntry::new(
             AuditEventType::Authentication,
             AuditSeverity::Info,
             client_ip,
             "auth_success",
             message,
-        )
-        .log();
+        );
+        self.record(entry);
     }
 
     /// Log authentication failure
     pub fn log_auth_failure(&self, client_ip: IpAddr, reason: &str) {
-        if !self.enabled {
-            return;
-        }
-
-        AuditLogEntry::new(
+        let entry = AuditLogEntry::new(
             AuditEventType::Authentication,
             AuditSeverity::Warning,
             client_ip,
             "auth_failure",
             format!("Authentication failed: {}", reason),
-        )
-        .log();
+        );
+        self.record(entry);
     }
 
     /// Log rate limit violation
     pub fn log_rate_limit(&self, client_ip: IpAddr, limit_type: &str) {
-        if !self.enabled {
-            return;
-        }
-
-        AuditLogEntry::new(
+        let entry = AuditLogEntry::new(
             AuditEventType::RateLimit,
             AuditSeverity::Warning,
             client_ip,
             "rate_limit_exceeded",
             format!("{} rate limit exceeded", limit_type),
-        )
-        .log();
+        );
+        self.record(entry);
     }
 
-    /// Log WebSocket connection opened
-    pub fn log_connection_open(&self, client_ip: IpAddr) {
-        if !self.enabled {
-            return;
-        }
+    /// Log a connection refused because the server-wide cap was reached
+    pub fn log_connection_cap(&self, client_ip: IpAddr, max_total: u32) {
+        let entry = AuditLogEntry::new(
+            AuditEventType::RateLimit,
+            AuditSeverity::Warning,
+            client_ip,
+            "connection_cap_reached",
+            format!("Server connection cap of {} reached", max_total),
+        );
+        self.record(entry);
+    }
 
-        AuditLogEntry::new(
+    /// Log a connection refused by `security.geo_allow_countries`
+    pub fn log_geo_blocked(&self, client_ip: IpAddr, country: Option<&str>) {
+        let entry = AuditLogEntry::new(
+            AuditEventType::Connection,
+            AuditSeverity::Warning,
+            client_ip,
+            "geo_blocked",
+            format!("Connection from country {} not allowed", country.unwrap_or("unknown")),
+        );
+        self.record(entry);
+    }
+
+    /// Log a development test card injected over `POST /api/test-event`
+    pub fn log_test_event(&self, client_ip: IpAddr, broadcast: bool) {
+        let entry = AuditLogEntry::new(
+            AuditEventType::CardRead,
+            AuditSeverity::Warning,
+            client_ip,
+            "test_event_injected",
+            if broadcast {
+                "Test card event broadcast"
+            } else {
+                "Test card event rejected"
+            },
+        );
+        self.record(entry);
+    }
+
+    /// Log WebSocket connection opened (`None` for local IPC clients)
+    pub fn log_connection_open(&self, client_ip: impl Into<Option<IpAddr>>) {
+        let entry = AuditLogEntry::new(
             AuditEventType::Connection,
             AuditSeverity::Info,
             client_ip,
             "connection_open",
             "WebSocket connection established",
-        )
-        .log();
+        );
+        self.record(entry);
     }
 
     /// Log WebSocket connection closed
-    pub fn log_connection_close(&self, client_ip: IpAddr, duration_ms: Option<u64>) {
-        if !self.enabled {
-            return;
-        }
-
-        let message = if let Some(ms) = duration_ms {
+    ///
+    /// `reason` is set when the server closed the connection (e.g. "session_timeout").
+    pub fn log_connection_close(
+        &self,
+        client_ip: impl Into<Option<IpAddr>>,
+        duration_ms: Option<u64>,
+        reason: Option<&str>,
+    ) {
+        let mut message = if let Some(ms) = duration_ms {
             format!("WebSocket connection closed (duration: {}ms)", ms)
         } else {
             "WebSocket connection closed".to_string()
         };
+        if let Some(reason) = reason {
+            message.push_str(&format!(" by server: {}", reason));
+        }
 
-        AuditLogEntry::new(
+        let mut entry = AuditLogEntry::new(
             AuditEventType::Connection,
             AuditSeverity::Info,
             client_ip,
             "connection_close",
             message,
-        )
-        .log();
+        );
+        entry.metadata = reason.map(|reason| serde_json::json!({ "reason": reason }));
+        self.record(entry);
+    }
+
+    /// Log a client that fell behind the broadcast channel
+    pub fn log_client_lag(&self, client_ip: IpAddr, skipped: u64, disconnected: bool) {
+        let (action, outcome) = if disconnected {
+            ("client_lag_disconnect", "disconnected")
+        } else {
+            ("client_lag_resync", "resynced")
+        };
+        let message = format!("Client skipped {} messages and was {}", skipped, outcome);
+        let entry = AuditLogEntry::new(
+            AuditEventType::Connection,
+            AuditSeverity::Warning,
+            client_ip,
+            action,
+            message,
+        );
+        self.record(entry);
+    }
+
+    /// Log a request to switch a masked connection to full data
+    pub fn log_reveal(&self, client_ip: IpAddr, granted: bool) {
+        let entry = if granted {
+            AuditLogEntry::new(
+                AuditEventType::Authentication,
+                AuditSeverity::Warning,
+                client_ip,
+                "reveal_granted",
+                "Connection switched to unmasked card data",
+            )
+        } else {
+            AuditLogEntry::new(
+                AuditEventType::Authentication,
+                AuditSeverity::Warning,
+                client_ip,
+                "reveal_denied",
+                "Reveal requested without an authorized key",
+            )
+        };
+        self.record(entry);
+    }
+
+    /// Log a protected field that could not be encrypted, and what was done instead
+    pub fn log_encryption_failure(&self, field: &str, policy: EncryptFailurePolicy) {
+        let entry = AuditLogEntry::new(
+            AuditEventType::SecurityError,
+            AuditSeverity::Error,
+            None,
+            "encryption_failed",
+            format!("Field '{}' could not be encrypted (on_encrypt_failure = \"{}\")", field, policy),
+        );
+        self.record(entry);
+    }
+
+    /// Log a card payload trimmed or dropped for exceeding `server.max_message_bytes`
+    pub fn log_oversized_payload(&self, action: &str, size_bytes: usize, max_bytes: usize) {
+        let message = format!(
+            "Card payload of {} bytes exceeds limit of {} bytes",
+            size_bytes, max_bytes
+        );
+        let entry = AuditLogEntry::new(
+            AuditEventType::SecurityError,
+            AuditSeverity::Warning,
+            None,
+            action,
+            message,
+        );
+        self.record(entry);
+    }
+
+    /// Log the outcome of posting a card message to the webhook
+    ///
+    /// `url` must already be stripped of credentials.
+    pub fn log_webhook_delivery(&self, url: &str, attempts: u8, error: Option<&str>) {
+        let entry = match error {
+            None => AuditLogEntry::new(
+                AuditEventType::Delivery,
+                AuditSeverity::Info,
+                None,
+                "webhook_delivered",
+                format!("Card message delivered to {} (attempt {})", url, attempts),
+            ),
+            Some(error) => AuditLogEntry::new(
+                AuditEventType::Delivery,
+                AuditSeverity::Error,
+                None,
+                "webhook_failed",
+                format!("Card message not delivered to {} after {} attempts: {}", url, attempts, error),
+            ),
+        };
+        self.record(entry);
+    }
+
+    /// Log a card read dropped because the webhook delivery queue was full
+    pub fn log_webhook_dropped(&self, url: &str) {
+        let entry = AuditLogEntry::new(
+            AuditEventType::Delivery,
+            AuditSeverity::Error,
+            None,
+            "webhook_dropped",
+            format!("Card message for {} dropped, delivery queue full", url),
+        );
+        self.record(entry);
     }
 
     /// Log validation failure
+    ///
+    /// `metadata` holds the field, error type and threat flag for SIEM queries;
+    /// the offending value is never included.
     pub fn log_validation_failure(
         &self,
         client_ip: Option<IpAddr>,
@@ -219,13 +512,6 @@ impl AuditLogger {
         details: &str,
         is_security_threat: bool,
     ) {
-        if !self.enabled {
-            return;
-        }
-
-        let ip = client_ip
-            .unwrap_or_else(|| std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)));
-
         let severity = if is_security_threat {
             AuditSeverity::Error
         } else {
@@ -247,7 +533,14 @@ impl AuditLogger {
             )
         };
 
-        AuditLogEntry::new(event_type, severity, ip, "validation_failure", message).log();
+        let entry = AuditLogEntry::new(event_type, severity, client_ip, "validation_failure", message).with_metadata(
+            serde_json::json!({
+                "field": field,
+                "error_type": error_type,
+                "is_security_threat": is_security_threat,
+            }),
+        );
+        self.record(entry);
     }
 }
 
@@ -273,12 +566,114 @@ mod tests {
 
         assert_eq!(entry.event_type, AuditEventType::Authentication);
         assert_eq!(entry.severity, AuditSeverity::Info);
-        assert_eq!(entry.client_ip, ip);
+        assert_eq!(entry.client_ip, Some(ip));
         assert_eq!(entry.action, "test_action");
         assert_eq!(entry.message, "test message");
         assert!(entry.metadata.is_none());
     }
 
+    fn temp_audit_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scr-audit-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_flush_persists_entries() {
+        let path = temp_audit_dir("flush").join("audit.log");
+        let logger = AuditLogger::new(true).with_file(&path);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        logger.log_auth_failure(ip, "bad key");
+        logger.log_rate_limit(ip, "request");
+
+        logger.flush().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let actions: Vec<String> = text
+            .lines()
+            .map(|line| serde_json::from_str::<AuditLogEntry>(line).unwrap().action)
+            .collect();
+        assert_eq!(actions, vec!["auth_failure", "rate_limit_exceeded"]);
+    }
+
+    #[test]
+    fn test_entries_are_written_without_flush() {
+        let path = temp_audit_dir("unflushed").join("audit.log");
+        let logger = AuditLogger::new(true).with_file(&path);
+        logger.log_auth_failure(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), "bad key");
+
+        // As if the process was killed without flushing
+        std::mem::forget(logger);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("auth_failure"));
+    }
+
+    #[test]
+    fn test_reopen_after_logrotate_create() {
+        let dir = temp_audit_dir("reopen");
+        let path = dir.join("audit.log");
+        let logger = AuditLogger::new(true).with_file(&path);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        logger.log_auth_failure(ip, "before rotation");
+
+        // logrotate with `create`: move the file away, put an empty one in place
+        std::fs::rename(&path, dir.join("audit.log.1")).unwrap();
+        std::fs::write(&path, "").unwrap();
+        logger.reopen().unwrap();
+        logger.log_auth_failure(ip, "after rotation");
+
+        assert!(std::fs::read_to_string(&path).unwrap().contains("after rotation"));
+        assert!(std::fs::read_to_string(dir.join("audit.log.1")).unwrap().contains("before rotation"));
+        // No stray timestamped copy
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_validation_failure_metadata() {
+        let path = temp_audit_dir("validation").join("audit.log");
+        let logger = AuditLogger::new(true).with_file(&path);
+        let error = crate::validation::ValidationError::Security("Contains disallowed character '<'".into());
+        logger.log_validation_failure(None, "th_firstname", error.kind(), error.message(), error.is_security());
+
+        logger.flush().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let entry: AuditLogEntry = serde_json::from_str(text.trim()).unwrap();
+        assert_eq!(entry.event_type, AuditEventType::SecurityError);
+        // Read from the card, not a client: no address is made up
+        assert!(!text.contains("client_ip"));
+        assert_eq!(entry.client_ip, None);
+        assert!(entry.message.contains("th_firstname"));
+        assert_eq!(
+            entry.metadata,
+            Some(serde_json::json!({
+                "field": "th_firstname",
+                "error_type": "Security",
+                "is_security_threat": true,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_rotate_keeps_old_file() {
+        let dir = temp_audit_dir("rotate");
+        let path = dir.join("audit.log");
+        let logger = AuditLogger::new(true).with_file(&path);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        logger.log_auth_failure(ip, "before rotation");
+
+        let rotated = logger.rotate().unwrap().expect("existing file is renamed");
+        assert!(std::fs::read_to_string(&rotated).unwrap().contains("before rotation"));
+
+        logger.log_auth_failure(ip, "after rotation");
+        logger.flush().unwrap();
+        let current = std::fs::read_to_string(&path).unwrap();
+        assert!(current.contains("after rotation"));
+        assert!(!current.contains("before rotation"));
+
+        // Already moved away (logrotate): just reopen
+        std::fs::rename(&path, dir.join("moved.log")).unwrap();
+        assert!(logger.rotate().unwrap().is_none());
+        assert!(path.exists());
+    }
+
     #[test]
     fn test_audit_logger_disabled() {
         let logger = AuditLogger::new(false);
@@ -289,7 +684,50 @@ mod tests {
         logger.log_auth_failure(ip, "test");
         logger.log_rate_limit(ip, "request");
         logger.log_connection_open(ip);
-        logger.log_connection_close(ip, Some(1000));
+        logger.log_connection_close(ip, Some(1000), None);
+        logger.log_connection_close(ip, Some(1000), Some("session_timeout"));
+    }
+
+    #[test]
+    fn test_min_severity_suppresses_info() {
+        let logger = AuditLogger::new(true).with_min_severity(AuditSeverity::Warning);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        let auth_success = AuditLogEntry::new(
+            AuditEventType::Authentication,
+            AuditSeverity::Info,
+            ip,
+            "auth_success",
+            "Authentication successful",
+        );
+        assert!(!logger.record(auth_success));
+
+        let auth_failure = AuditLogEntry::new(
+            AuditEventType::Authentication,
+            AuditSeverity::Warning,
+            ip,
+            "auth_failure",
+            "Authentication failed: Invalid API key",
+        );
+        assert!(logger.record(auth_failure));
+
+        assert!(!logger.accepts(AuditSeverity::Info));
+        assert!(logger.accepts(AuditSeverity::Critical));
+    }
+
+    #[test]
+    fn test_min_severity_default_accepts_all() {
+        let logger = AuditLogger::new(true);
+        assert!(logger.accepts(AuditSeverity::Info));
+
+        let disabled = AuditLogger::new(false);
+        assert!(!disabled.accepts(AuditSeverity::Critical));
+    }
+
+    #[test]
+    fn test_min_severity_deserialize() {
+        let severity: AuditSeverity = serde_json::from_str("\"warning\"").unwrap();
+        assert_eq!(severity, AuditSeverity::Warning);
     }
 
     #[test]
@@ -0,0 +1,162 @@
+//! Read latency benchmark (`--benchmark <n>`)
+//!
+//! Each read of the inserted card records how long every stage took (connect,
+//! SELECT, each field, photo, NHSO); the stages are then summarized as
+//! min/max/mean/p95 so operators can check a reader against a latency SLA and
+//! tune `card.apdu_delay_ms` / `card.poll_timeout_ms`.
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// Per-stage durations of one card read, in the order they ran
+#[derive(Debug, Default)]
+pub struct ReadTimings {
+    stages: RefCell<Vec<(String, Duration)>>,
+}
+
+impl ReadTimings {
+    /// Record that `stage` took `elapsed`
+    pub fn record(&self, stage: &str, elapsed: Duration) {
+        self.stages.borrow_mut().push((stage.to_string(), elapsed));
+    }
+
+    /// Recorded stages
+    #[must_use]
+    pub fn into_stages(self) -> Vec<(String, Duration)> {
+        self.stages.into_inner()
+    }
+}
+
+/// Latency summary of one stage across all successful reads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageStats {
+    pub name: String,
+    pub samples: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    /// Nearest-rank 95th percentile
+    pub p95: Duration,
+}
+
+impl StageStats {
+    fn from_samples(name: String, mut samples: Vec<Duration>) -> Self {
+        samples.sort_unstable();
+        let total: Duration = samples.iter().sum();
+        let count = samples.len();
+        // Nearest rank: the smallest sample with at least 95% of samples at or below it
+        let rank = (count * 95).div_ceil(100).max(1);
+        Self {
+            name,
+            samples: count,
+            min: samples[0],
+            max: samples[count - 1],
+            mean: total / u32::try_from(count).unwrap_or(u32::MAX),
+            p95: samples[rank - 1],
+        }
+    }
+}
+
+/// Outcome of `--benchmark <n>`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BenchmarkReport {
+    pub reader: String,
+    pub reads: usize,
+    pub failures: usize,
+    /// One entry per stage, in read order
+    pub stages: Vec<StageStats>,
+}
+
+impl BenchmarkReport {
+    /// Aggregate the timings of successful reads; `failures` counts the others
+    #[must_use]
+    pub fn new(reader: &str, reads: Vec<ReadTimings>, failures: usize) -> Self {
+        let successful = reads.len();
+        let mut by_stage: Vec<(String, Vec<Duration>)> = Vec::new();
+        for (name, elapsed) in reads.into_iter().flat_map(ReadTimings::into_stages) {
+            match by_stage.iter_mut().find(|(stage, _)| *stage == name) {
+                Some((_, samples)) => samples.push(elapsed),
+                None => by_stage.push((name, vec![elapsed])),
+            }
+        }
+        Self {
+            reader: reader.to_string(),
+            reads: successful + failures,
+            failures,
+            stages: by_stage
+                .into_iter()
+                .map(|(name, samples)| StageStats::from_samples(name, samples))
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        writeln!(f, "Reader: {}", self.reader)?;
+        writeln!(f, "Reads: {} ({} failed)", self.reads, self.failures)?;
+        writeln!(f, "{:<16} {:>9} {:>9} {:>9} {:>9}", "stage (ms)", "min", "mean", "p95", "max")?;
+        for stage in &self.stages {
+            writeln!(
+                f,
+                "{:<16} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+                stage.name,
+                ms(stage.min),
+                ms(stage.mean),
+                ms(stage.p95),
+                ms(stage.max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages_are_aggregated_in_read_order() {
+        let ms = Duration::from_millis;
+        let reads: Vec<ReadTimings> = (1..=20)
+            .map(|i| {
+                let timings = ReadTimings::default();
+                timings.record("connect", ms(i));
+                timings.record("citizen_id", ms(10));
+                timings
+            })
+            .collect();
+        let report = BenchmarkReport::new("Mock Reader", reads, 2);
+
+        assert_eq!(report.reads, 22);
+        assert_eq!(report.failures, 2);
+        let names: Vec<&str> = report.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["connect", "citizen_id"]);
+
+        let connect = &report.stages[0];
+        assert_eq!(connect.samples, 20);
+        assert_eq!((connect.min, connect.max), (ms(1), ms(20)));
+        assert_eq!(connect.mean, Duration::from_micros(10_500));
+        assert_eq!(connect.p95, ms(19));
+        let field = &report.stages[1];
+        assert_eq!((field.min, field.mean, field.p95, field.max), (ms(10), ms(10), ms(10), ms(10)));
+
+        let table = report.to_string();
+        assert!(table.contains("connect"));
+        assert!(table.contains("10.5"));
+    }
+
+    #[test]
+    fn test_single_sample_is_its_own_p95() {
+        let timings = ReadTimings::default();
+        timings.record("photo", Duration::from_millis(300));
+        let report = BenchmarkReport::new("Mock Reader", vec![timings], 0);
+        assert_eq!(report.stages[0].p95, Duration::from_millis(300));
+        assert!(BenchmarkReport::new("Mock Reader", Vec::new(), 3).stages.is_empty());
+    }
+}
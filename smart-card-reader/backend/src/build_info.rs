@@ -0,0 +1,44 @@
+//! Version and build metadata embedded at compile time by `build.rs`
+
+use serde::{Deserialize, Serialize};
+
+/// Crate version from Cargo.toml
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short git commit hash, or "unknown" when built outside a git checkout
+pub const COMMIT: &str = env!("BUILD_GIT_COMMIT");
+/// `SOURCE_DATE_EPOCH` or the commit time, seconds since the Unix epoch
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+
+/// Build metadata returned by `GET /version`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: String,
+    pub commit: String,
+    /// RFC 3339 UTC time of the source: `SOURCE_DATE_EPOCH` if set at build
+    /// time, else the commit time ("unknown" outside a git checkout)
+    pub built_at: String,
+}
+
+impl BuildInfo {
+    /// Metadata of the running binary
+    #[must_use]
+    pub fn current() -> Self {
+        let built_at = BUILD_TIMESTAMP
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+            .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            version: VERSION.to_string(),
+            commit: COMMIT.to_string(),
+            built_at,
+        }
+    }
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "smart-card-reader {} ({}, built {})", self.version, self.commit, self.built_at)
+    }
+}
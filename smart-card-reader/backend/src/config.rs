@@ -3,6 +3,13 @@
 //! Provides strongly-typed configuration with sensible defaults,
 //! loaded from TOML files with fallback to environment variables.
 
+use crate::audit_log::AuditSeverity;
+use crate::crypto::{EncryptFailurePolicy, NonceMode};
+use crate::decoder::{Era, KeyCase, Transform};
+use crate::template::{Template, TemplateEscape};
+use crate::validation::{
+    BlockOn, CharsetPolicy, FieldCharsets, DEFAULT_ADDRESS_PUNCTUATION, DEFAULT_NAME_PUNCTUATION,
+};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
@@ -30,6 +37,8 @@ pub const DEFAULT_MAX_HEIGHT: f32 = 750.0;
 pub const DEFAULT_LOG_LEVEL: &str = "info";
 /// Environment variable for config path
 pub const CONFIG_ENV_VAR: &str = "SMART_CARD_CONFIG";
+/// Environment variable marking a non-production (development) host
+pub const DEV_MODE_ENV_VAR: &str = "SMART_CARD_DEV_MODE";
 /// Default config filename
 pub const CONFIG_FILENAME: &str = "config.toml";
 
@@ -44,6 +53,10 @@ pub enum ConfigError {
     Io(io::Error),
     /// Failed to parse TOML
     Parse(toml::de::Error),
+    /// `card.profile` names neither a preset nor a profile file
+    UnknownProfile(String),
+    /// A `/.../` entry of `card.reader_allowlist`/`reader_denylist` is not a valid regex
+    InvalidReaderPattern { pattern: String, reason: String },
 }
 
 impl fmt::Display for ConfigError {
@@ -51,6 +64,10 @@ impl fmt::Display for ConfigError {
         match self {
             Self::Io(e) => write!(f, "Failed to read config: {e}"),
             Self::Parse(e) => write!(f, "Failed to parse config: {e}"),
+            Self::UnknownProfile(name) => write!(f, "Unknown card profile: {name}"),
+            Self::InvalidReaderPattern { pattern, reason } => {
+                write!(f, "Invalid reader pattern {pattern}: {reason}")
+            }
         }
     }
 }
@@ -60,6 +77,7 @@ impl std::error::Error for ConfigError {
         match self {
             Self::Io(e) => Some(e),
             Self::Parse(e) => Some(e),
+            Self::UnknownProfile(_) | Self::InvalidReaderPattern { .. } => None,
         }
     }
 }
@@ -80,6 +98,76 @@ impl From<toml::de::Error> for ConfigError {
 // Output Format Enum
 // ============================================================================
 
+/// HTTP routes mounted at fixed paths, which the WebSocket path must not shadow
+pub const FIXED_ROUTES: [&str; 6] = [
+    "/version",
+    "/health",
+    "/health/security",
+    "/api/decrypt",
+    "/api/test-event",
+    "/api/rate-limit-stats",
+];
+
+/// What to do with a WebSocket client that falls behind the broadcast channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LagPolicy {
+    /// Log the skipped messages and keep streaming from the newest one
+    #[default]
+    Resync,
+    /// Close the connection with a reason so the client reconnects
+    Disconnect,
+}
+
+impl fmt::Display for LagPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resync => write!(f, "resync"),
+            Self::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+/// What to do with a WebSocket client sending more messages than `ws_message_rate` allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageLimitPolicy {
+    /// Ignore messages over the limit and keep the connection
+    #[default]
+    Drop,
+    /// Close the connection with a policy-violation frame
+    Close,
+}
+
+impl fmt::Display for MessageLimitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Drop => write!(f, "drop"),
+            Self::Close => write!(f, "close"),
+        }
+    }
+}
+
+/// Shape of card messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Envelope {
+    /// `mode` and the card fields side by side (original shape)
+    #[default]
+    Flat,
+    /// `mode`, `seq` and `timestamp` at the top, card fields under `data`
+    Nested,
+}
+
+impl fmt::Display for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flat => write!(f, "flat"),
+            Self::Nested => write!(f, "nested"),
+        }
+    }
+}
+
 /// Output format for card data
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -103,6 +191,170 @@ impl fmt::Display for OutputFormat {
     }
 }
 
+/// How date fields are written in the output JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFormat {
+    /// As read from the card: Buddhist Era `YYYY/MM/DD`
+    #[default]
+    Raw,
+    /// ISO-8601 `YYYY-MM-DD` in `date_era`
+    Iso8601,
+    /// Human-readable Thai, e.g. "15 ม.ค. 2567"
+    Thai,
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Raw => write!(f, "raw"),
+            Self::Iso8601 => write!(f, "iso8601"),
+            Self::Thai => write!(f, "thai"),
+        }
+    }
+}
+
+// ============================================================================
+// Read Feedback Enum
+// ============================================================================
+
+/// How much of the card photo the UI keeps once its texture is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PhotoRetention {
+    /// Keep the original base64 JPEG
+    #[default]
+    Full,
+    /// Keep a small re-encoded JPEG (enough for the history panel)
+    Thumbnail,
+    /// Drop the photo; history entries show "No photo"
+    None,
+}
+
+impl fmt::Display for PhotoRetention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+            Self::Thumbnail => write!(f, "thumbnail"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Operator feedback on a successful card read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadFeedback {
+    /// No feedback
+    #[default]
+    None,
+    /// Audible beep (requires the `sound` feature)
+    Sound,
+    /// Brief color pulse on the status bar
+    Flash,
+    /// Beep and flash
+    Both,
+}
+
+impl ReadFeedback {
+    /// Whether this mode plays a sound
+    #[must_use]
+    pub fn sound(self) -> bool {
+        matches!(self, Self::Sound | Self::Both)
+    }
+
+    /// Whether this mode flashes the status bar
+    #[must_use]
+    pub fn flash(self) -> bool {
+        matches!(self, Self::Flash | Self::Both)
+    }
+}
+
+impl fmt::Display for ReadFeedback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Sound => write!(f, "sound"),
+            Self::Flash => write!(f, "flash"),
+            Self::Both => write!(f, "both"),
+        }
+    }
+}
+
+// ============================================================================
+// TLS Format Enum
+// ============================================================================
+
+/// On-disk format of the TLS certificate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsFormat {
+    /// Separate PEM certificate and key files
+    #[default]
+    Pem,
+    /// Single PKCS#12 / PFX bundle (password from `TLS_P12_PASSWORD`)
+    Pkcs12,
+}
+
+impl fmt::Display for TlsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pem => write!(f, "pem"),
+            Self::Pkcs12 => write!(f, "pkcs12"),
+        }
+    }
+}
+
+// ============================================================================
+// Card Connection Enums
+// ============================================================================
+
+/// PC/SC share mode used when connecting to a card
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardShareMode {
+    /// Other applications may use the card concurrently
+    #[default]
+    Shared,
+    /// Block other applications for the duration of the connection
+    Exclusive,
+    /// Direct reader access without a card protocol
+    Direct,
+}
+
+impl fmt::Display for CardShareMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shared => write!(f, "shared"),
+            Self::Exclusive => write!(f, "exclusive"),
+            Self::Direct => write!(f, "direct"),
+        }
+    }
+}
+
+/// Card transmission protocol to negotiate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardProtocol {
+    /// Let the reader choose T=0 or T=1
+    #[default]
+    Any,
+    /// Character-oriented T=0
+    T0,
+    /// Block-oriented T=1
+    T1,
+}
+
+impl fmt::Display for CardProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::T0 => write!(f, "t0"),
+            Self::T1 => write!(f, "t1"),
+        }
+    }
+}
+
 // ============================================================================
 // Configuration Structs
 // ============================================================================
@@ -123,7 +375,7 @@ impl fmt::Display for OutputFormat {
 /// enable_authentication = true
 /// api_keys = ["your-secret-key-here"]
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 #[non_exhaustive]
 pub struct AppConfig {
@@ -143,20 +395,6 @@ pub struct AppConfig {
     pub security: SecurityConfig,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            server: ServerConfig::default(),
-            output: OutputConfig::default(),
-            ui: UiConfig::default(),
-            fonts: FontConfig::default(),
-            logging: LoggingConfig::default(),
-            card: CardConfig::default(),
-            security: SecurityConfig::default(),
-        }
-    }
-}
-
 /// WebSocket server configuration
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
@@ -171,12 +409,57 @@ pub struct ServerConfig {
     /// Allowed CORS origins (used when cors_allow_all = false)
     /// Example: ["https://your-app.com", "https://localhost:3000"]
     pub allowed_origins: Vec<String>,
+    /// Methods allowed by restricted CORS (POST is added when a POST endpoint is on)
+    pub cors_methods: Vec<String>,
+    /// Request headers allowed by restricted CORS, besides `security.api_key_header`
+    pub cors_headers: Vec<String>,
     /// Enable TLS/SSL for secure WebSocket (wss://)
     pub enable_tls: bool,
-    /// Path to TLS certificate file (.pem or .crt)
+    /// Path to TLS certificate file (.pem or .crt), or the .p12/.pfx bundle
     pub tls_cert_path: String,
-    /// Path to TLS private key file (.pem or .key)
+    /// Path to TLS private key file (.pem or .key, unused for PKCS#12)
     pub tls_key_path: String,
+    /// Certificate format (a .p12/.pfx `tls_cert_path` implies pkcs12)
+    pub tls_format: TlsFormat,
+    /// Number of attempts to load the TLS cert/key at startup
+    pub tls_load_retries: u8,
+    /// Delay between TLS load attempts in milliseconds
+    pub tls_retry_delay_ms: u64,
+    /// Interval in seconds to check the cert/key for changes (0 = no hot-reload)
+    pub tls_reload_interval_secs: u64,
+    /// Number of attempts to bind the listening port at startup
+    pub bind_retries: u8,
+    /// Initial delay between bind attempts in milliseconds (doubles each retry)
+    pub bind_retry_delay_ms: u64,
+    /// Local IPC endpoint emitting the same JSON as the WebSocket (NDJSON)
+    /// Unix socket path (Linux/macOS) or named pipe (Windows, `\\.\pipe\name`)
+    pub ipc_path: Option<String>,
+    /// Largest broadcast payload in bytes; bigger card messages lose the photo,
+    /// or are dropped if still too large (0 = no limit)
+    pub max_message_bytes: usize,
+    /// Messages buffered per client before a slow client starts lagging
+    ///
+    /// Worst-case memory is roughly capacity × `max_message_bytes`, shared by
+    /// all clients (each message is stored once, not per client).
+    pub broadcast_capacity: usize,
+    /// What to do with a client that lags behind the broadcast channel
+    pub lag_policy: LagPolicy,
+    /// Path the WebSocket is mounted at (e.g. "/smartcard/ws" behind a reverse proxy)
+    pub ws_path: String,
+    /// Expose `POST /api/test-event` (also requires `SMART_CARD_DEV_MODE=1`)
+    pub enable_test_events: bool,
+    /// Re-reads of the card last broadcast send WebSocket clients that got it
+    /// only the changed fields (`readsmartcard_delta`)
+    pub delta_mode: bool,
+    /// Identical consecutive reads of one card that count as a stuck card and
+    /// broadcast `cardstuck` (0 = off)
+    pub stuck_card_reads: u32,
+    /// Seconds those reads must fall within, counted from the first of them
+    pub stuck_card_window_secs: u64,
+    /// Drop further reads of a stuck card until it is removed
+    pub suppress_stuck_card: bool,
+    /// Listen on a non-loopback `host` even without authentication (unsafe)
+    pub allow_insecure_public_bind: bool,
 }
 
 impl Default for ServerConfig {
@@ -189,19 +472,73 @@ impl Default for ServerConfig {
                 "http://localhost:3000".to_string(),
                 "https://localhost:3000".to_string(),
             ],
+            cors_methods: vec!["GET".to_string()],
+            cors_headers: vec!["Content-Type".to_string(), "Authorization".to_string()],
             enable_tls: false,
             tls_cert_path: "certs/cert.pem".to_string(),
             tls_key_path: "certs/key.pem".to_string(),
+            tls_format: TlsFormat::Pem,
+            tls_load_retries: 5,
+            tls_retry_delay_ms: 2000,
+            tls_reload_interval_secs: 30,
+            bind_retries: 5,
+            bind_retry_delay_ms: 500,
+            ipc_path: None,
+            max_message_bytes: 1024 * 1024,
+            broadcast_capacity: 100,
+            lag_policy: LagPolicy::default(),
+            ws_path: "/".to_string(),
+            enable_test_events: false,
+            delta_mode: false,
+            stuck_card_reads: 0,
+            stuck_card_window_secs: 60,
+            suppress_stuck_card: true,
+            allow_insecure_public_bind: false,
         }
     }
 }
 
 impl ServerConfig {
+    /// Whether `POST /api/test-event` is served
+    ///
+    /// Needs both `enable_test_events` and `SMART_CARD_DEV_MODE=1`, so a
+    /// config copied to a production host cannot turn it on by itself.
+    #[must_use]
+    pub fn test_events_enabled(&self) -> bool {
+        self.test_events_allowed(std::env::var(DEV_MODE_ENV_VAR).ok().as_deref())
+    }
+
+    fn test_events_allowed(&self, dev_mode: Option<&str>) -> bool {
+        if !self.enable_test_events {
+            return false;
+        }
+        if is_dev_mode(dev_mode) {
+            return true;
+        }
+        log::error!("❌ server.enable_test_events is set but {}=1 is not; test events stay disabled", DEV_MODE_ENV_VAR);
+        false
+    }
+
     /// Returns the WebSocket URL for client connections
     #[must_use]
     pub fn websocket_url(&self) -> String {
         let protocol = if self.enable_tls { "wss" } else { "ws" };
-        format!("{}://{}:{}", protocol, self.host, self.port)
+        let path = self.websocket_path();
+        let path = if path == "/" { "" } else { path.as_str() };
+        // SocketAddr brackets IPv6 hosts: ws://[::1]:8182
+        format!("{}://{}{}", protocol, self.socket_addr(), path)
+    }
+
+    /// Normalized `ws_path`: leading '/', no trailing '/', and never one of the fixed routes
+    #[must_use]
+    pub fn websocket_path(&self) -> String {
+        let trimmed = self.ws_path.trim().trim_matches('/');
+        let path = format!("/{}", trimmed);
+        if FIXED_ROUTES.contains(&path.as_str()) {
+            log::warn!("⚠️ server.ws_path \"{}\" collides with a built-in route, using \"/\"", self.ws_path);
+            return "/".to_string();
+        }
+        path
     }
 
     /// Returns the socket address for binding
@@ -210,6 +547,43 @@ impl ServerConfig {
         std::net::SocketAddr::new(self.host, self.port)
     }
 
+    /// `true` when `host` is loopback, including IPv4-mapped `::ffff:127.0.0.1`
+    #[must_use]
+    pub fn is_loopback(&self) -> bool {
+        self.host.to_canonical().is_loopback()
+    }
+
+    /// Why listening on `host` would expose card data to the network, `None` if
+    /// it is loopback, protected enough, or `allow_insecure_public_bind` is set
+    #[must_use]
+    pub fn public_bind_refusal(&self, security: &SecurityConfig) -> Option<&'static str> {
+        if self.allow_insecure_public_bind || self.is_loopback() || security.enable_authentication {
+            return None;
+        }
+        if !self.enable_tls {
+            Some("authentication and TLS are both disabled")
+        } else if self.cors_allow_all {
+            Some("authentication is disabled and server.cors_allow_all lets any website read card data")
+        } else {
+            None
+        }
+    }
+
+    /// Resolve the TLS format, detecting PKCS#12 bundles by extension
+    #[must_use]
+    pub fn resolved_tls_format(&self) -> TlsFormat {
+        let is_pkcs12_ext = Path::new(&self.tls_cert_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("p12") || e.eq_ignore_ascii_case("pfx"));
+
+        if is_pkcs12_ext {
+            TlsFormat::Pkcs12
+        } else {
+            self.tls_format
+        }
+    }
+
     /// Get allowed CORS origins from config or environment variable
     ///
     /// Priority: config.toml > ALLOWED_ORIGINS env var > empty
@@ -251,10 +625,47 @@ pub struct OutputConfig {
     pub format: OutputFormat,
     /// Include base64-encoded photo in output
     pub include_photo: bool,
+    /// Add a computed `Th_Fullname` ("{prefix}{first} {middle} {last}")
+    pub include_full_thai_name: bool,
+    /// Add a computed `Name`: Thai display name with the English one in parentheses
+    pub include_bilingual_name: bool,
     /// Field name mappings (original -> custom)
     pub field_mapping: HashMap<String, String>,
     /// Fields to include (empty = all fields)
     pub enabled_fields: Vec<String>,
+    /// Format of Birthday/Issue/Expire
+    pub date_format: DateFormat,
+    /// Era used by the iso8601 date format
+    pub date_era: Era,
+    /// Transforms per field (internal name -> transforms, applied in order)
+    pub field_transforms: HashMap<String, Vec<Transform>>,
+    /// Fields (internal names) emitted as JSON numbers instead of strings
+    pub numeric_fields: Vec<String>,
+    /// Fields (internal names) masked for display: the citizen ID keeps its
+    /// last 4 digits, other fields become bullets
+    pub masked_fields: Vec<String>,
+    /// Case of emitted keys, applied after `field_mapping`
+    pub key_case: KeyCase,
+    /// Card fields next to `mode` (flat) or under `data` (nested)
+    pub envelope: Envelope,
+    /// Add `changed` (differs from the previous read of the same citizen ID)
+    /// and, with the `full` format, `field_timestamps` to card messages
+    pub change_detection: bool,
+    /// Line template (`{field}` placeholders) for stdout and webhook card output
+    /// instead of JSON
+    pub template: Option<String>,
+    /// Quoting of values substituted into `template`
+    pub template_escape: TemplateEscape,
+    /// POST each card message, as broadcast, to this URL
+    pub webhook_url: Option<String>,
+    /// Bearer token for the webhook (read from WEBHOOK_TOKEN if unset)
+    pub webhook_token: Option<String>,
+    /// Timeout of one webhook request in milliseconds
+    pub webhook_timeout_ms: u64,
+    /// Attempts per card message before it is given up
+    pub webhook_retries: u8,
+    /// Initial delay between webhook attempts in milliseconds (doubles each retry)
+    pub webhook_retry_delay_ms: u64,
 }
 
 impl Default for OutputConfig {
@@ -262,13 +673,50 @@ impl Default for OutputConfig {
         Self {
             format: OutputFormat::default(),
             include_photo: true,
+            include_full_thai_name: false,
+            include_bilingual_name: false,
             field_mapping: HashMap::new(),
             enabled_fields: Vec::new(),
+            date_format: DateFormat::default(),
+            date_era: Era::default(),
+            field_transforms: HashMap::new(),
+            numeric_fields: Vec::new(),
+            masked_fields: Vec::new(),
+            key_case: KeyCase::default(),
+            envelope: Envelope::default(),
+            change_detection: false,
+            template: None,
+            template_escape: TemplateEscape::default(),
+            webhook_url: None,
+            webhook_token: None,
+            webhook_timeout_ms: 5000,
+            webhook_retries: 3,
+            webhook_retry_delay_ms: 1000,
         }
     }
 }
 
 impl OutputConfig {
+    /// Parsed `template`, `None` to output JSON
+    #[must_use]
+    pub fn line_template(&self) -> Option<Template> {
+        self.template
+            .as_deref()
+            .filter(|source| !source.is_empty())
+            .map(|source| Template::parse(source, self.template_escape))
+    }
+
+    /// Bearer token for the webhook
+    ///
+    /// Priority: config.toml > WEBHOOK_TOKEN env var > none
+    #[must_use]
+    pub fn get_webhook_token(&self) -> Option<String> {
+        self.webhook_token
+            .clone()
+            .or_else(|| std::env::var("WEBHOOK_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+    }
+
     /// Checks if a field should be included in output
     ///
     /// Returns `true` if `enabled_fields` is empty (all fields enabled)
@@ -286,6 +734,20 @@ impl OutputConfig {
             .map(String::as_str)
             .unwrap_or(original)
     }
+
+    /// `true` if `PhotoRaw` is emitted (`include_photo` and `enabled_fields`)
+    ///
+    /// The reader skips the photo chunk APDUs when this is off.
+    #[must_use]
+    pub fn photo_enabled(&self) -> bool {
+        self.include_photo && self.is_field_enabled("PhotoRaw")
+    }
+
+    /// Returns the emitted key: mapped name converted to `key_case`
+    #[must_use]
+    pub fn output_key(&self, original: &str) -> String {
+        self.key_case.apply(self.get_field_name(original))
+    }
 }
 
 /// UI window configuration
@@ -306,6 +768,28 @@ pub struct UiConfig {
     pub max_width: f32,
     /// Maximum window height (set equal to min to lock size)
     pub max_height: f32,
+    /// Feedback on successful read: none, sound, flash, both
+    pub read_feedback: ReadFeedback,
+    /// Number of recent reads kept in the history panel (in memory only)
+    pub history_len: usize,
+    /// Cache the last read on disk so it survives a restart
+    pub persist_last_read: bool,
+    /// File holding the last-read cache
+    pub last_read_path: String,
+    /// Delete the last-read cache when the card is removed
+    pub clear_last_read_on_removal: bool,
+    /// Photo kept in memory after display: full, thumbnail, none (WebSocket is unaffected)
+    pub photo_retention: PhotoRetention,
+    /// Show the card photo (also off when the output config drops `PhotoRaw`)
+    pub show_photo: bool,
+    /// Purge the shown card, photo, history and last-read cache this many seconds
+    /// after the last read, even if no removal arrived (0 = never)
+    pub auto_clear_secs: u64,
+    /// Dates in the Thai UI use Thai digits and a "พ.ศ." year (English stays Arabic)
+    pub thai_numerals: bool,
+    /// Seconds each "Show Data" click reveals the card before it is masked
+    /// again (0 = until toggled off)
+    pub reveal_secs: u64,
 }
 
 impl Default for UiConfig {
@@ -318,6 +802,16 @@ impl Default for UiConfig {
             min_height: DEFAULT_MIN_HEIGHT,
             max_width: DEFAULT_MAX_WIDTH,
             max_height: DEFAULT_MAX_HEIGHT,
+            read_feedback: ReadFeedback::None,
+            history_len: 10,
+            persist_last_read: false,
+            last_read_path: "last_read.json".to_owned(),
+            clear_last_read_on_removal: false,
+            photo_retention: PhotoRetention::Full,
+            show_photo: true,
+            auto_clear_secs: 0,
+            thai_numerals: false,
+            reveal_secs: 0,
         }
     }
 }
@@ -330,6 +824,8 @@ pub struct FontConfig {
     pub custom_paths: Vec<String>,
     /// Search system fonts if custom not found
     pub use_system_fonts: bool,
+    /// Use the NotoSansThai copy embedded in the binary when no font file is found
+    pub fallback_embedded: bool,
 }
 
 impl Default for FontConfig {
@@ -337,6 +833,7 @@ impl Default for FontConfig {
         Self {
             custom_paths: Vec::new(),
             use_system_fonts: true,
+            fallback_embedded: true,
         }
     }
 }
@@ -347,12 +844,35 @@ impl Default for FontConfig {
 pub struct LoggingConfig {
     /// Log level: trace, debug, info, warn, error
     pub level: String,
+    /// Output format of application logs
+    pub format: LogFormat,
 }
 
 impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
             level: DEFAULT_LOG_LEVEL.to_owned(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+/// Application log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregators
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
         }
     }
 }
@@ -370,8 +890,11 @@ pub struct SecurityConfig {
     /// Enable PII data encryption before transmission
     pub enable_encryption: bool,
     /// List of field names to encrypt (empty = encrypt all sensitive fields)
-    /// Common sensitive fields: Citizenid, Th_Firstname, Th_Lastname, full_name_en, Address
+    /// Common sensitive fields: Citizenid, Th_Firstname, Th_Lastname, full_name_en, Name, Address, AddressEn
     pub encrypted_fields: Vec<String>,
+    /// Encrypt the photo (when listed in `encrypted_fields`) only if its Base64
+    /// form is larger than this many bytes (0 = always)
+    pub encrypt_photo_over_bytes: usize,
     /// Enable rate limiting for WebSocket connections
     pub enable_rate_limiting: bool,
     /// Maximum requests per time window (per IP)
@@ -380,8 +903,48 @@ pub struct SecurityConfig {
     pub rate_limit_window_secs: u64,
     /// Maximum concurrent connections per IP
     pub rate_limit_max_connections: u32,
+    /// IPv6 prefix length sharing one rate-limit bucket (128 = per address)
+    pub rate_limit_ipv6_prefix: u8,
+    /// Include per-client detail (IP addresses) in `GET /api/rate-limit-stats`
+    pub rate_limit_stats_per_ip: bool,
     /// Enable audit logging for security events
     pub enable_audit_logging: bool,
+    /// Minimum audit severity to record: info, warning, error, critical
+    pub audit_min_severity: AuditSeverity,
+    /// Also write audit entries to this JSON-lines file (SIGHUP reopens it)
+    pub audit_log_path: Option<String>,
+    /// Expose `POST /api/decrypt` for debugging (⚠️ never enable in production)
+    pub enable_decrypt_endpoint: bool,
+    /// GCM nonce generation: random (default) or counter
+    pub nonce_mode: NonceMode,
+    /// Protected field that fails to encrypt: abort (default), omit or plaintext
+    pub on_encrypt_failure: EncryptFailurePolicy,
+    /// Validation severities that prevent broadcast: security, integrity, any, none
+    pub block_on: BlockOn,
+    /// Punctuation allowed in names (Thai, letters, digits and spaces always are)
+    pub name_allowed_punctuation: String,
+    /// Punctuation allowed in addresses (Thai, letters, digits and spaces always are)
+    pub address_allowed_punctuation: String,
+    /// Close WebSocket sessions after this many seconds (unset = no limit)
+    pub max_session_secs: Option<u64>,
+    /// Time a socket opened without a key has to send `{"auth":"<key>"}`
+    pub ws_auth_timeout_ms: u64,
+    /// Maximum concurrent WebSocket connections across all clients (unset = no limit)
+    pub max_total_connections: Option<u32>,
+    /// Inbound messages per second allowed on one WebSocket (0 = no limit)
+    pub ws_message_rate: u32,
+    /// Inbound messages one WebSocket may send in a burst above `ws_message_rate`
+    pub ws_message_burst: u32,
+    /// Messages over the per-socket limit: drop (default) or close
+    pub ws_message_limit_policy: MessageLimitPolicy,
+    /// Broadcast masked PII unless a client sends `{"command":"reveal"}` with a reveal key
+    pub broadcast_masked: bool,
+    /// API keys allowed to switch their connection to full data
+    pub reveal_keys: Vec<String>,
+    /// ISO 3166 country codes allowed to connect, by GeoIP lookup (empty = no filtering)
+    pub geo_allow_countries: Vec<String>,
+    /// MaxMind-format (`.mmdb`) country database used by `geo_allow_countries`
+    pub geoip_database: Option<String>,
 }
 
 impl Default for SecurityConfig {
@@ -396,18 +959,120 @@ impl Default for SecurityConfig {
                 "Th_Firstname".to_string(),
                 "Th_Lastname".to_string(),
                 "full_name_en".to_string(),
+                "Name".to_string(),
                 "Address".to_string(),
+                "AddressEn".to_string(),
             ],
+            encrypt_photo_over_bytes: 0,
             enable_rate_limiting: false,
             rate_limit_requests: 60,
             rate_limit_window_secs: 60,
             rate_limit_max_connections: 5,
+            rate_limit_ipv6_prefix: 64,
+            rate_limit_stats_per_ip: false,
             enable_audit_logging: false,
+            audit_min_severity: AuditSeverity::Info,
+            audit_log_path: None,
+            enable_decrypt_endpoint: false,
+            nonce_mode: NonceMode::Random,
+            on_encrypt_failure: EncryptFailurePolicy::Abort,
+            block_on: BlockOn::Security,
+            name_allowed_punctuation: DEFAULT_NAME_PUNCTUATION.to_string(),
+            address_allowed_punctuation: DEFAULT_ADDRESS_PUNCTUATION.to_string(),
+            max_session_secs: None,
+            ws_auth_timeout_ms: 5000,
+            max_total_connections: None,
+            ws_message_rate: 5,
+            ws_message_burst: 20,
+            ws_message_limit_policy: MessageLimitPolicy::Drop,
+            broadcast_masked: false,
+            reveal_keys: Vec::new(),
+            geo_allow_countries: Vec::new(),
+            geoip_database: None,
+        }
+    }
+}
+
+/// A protection that is switched off, and the setting that turns it on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostureFinding {
+    pub protection: &'static str,
+    pub fix: &'static str,
+}
+
+/// How many of the server's protections are on (see [`SecurityConfig::security_posture`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityPosture {
+    /// Protections enabled, out of [`SecurityPosture::MAX_SCORE`]
+    pub score: usize,
+    /// Disabled protections, most important first
+    pub findings: Vec<PostureFinding>,
+    /// Listening beyond loopback with open CORS and no authentication
+    pub exposed: bool,
+}
+
+impl SecurityPosture {
+    /// Number of protections scored
+    pub const MAX_SCORE: usize = 6;
+
+    /// `true` when every protection is on
+    #[must_use]
+    pub fn is_hardened(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Log one consolidated warning listing every disabled protection
+    pub fn log_summary(&self) {
+        if self.is_hardened() {
+            log::info!("🛡️ Security posture: {}/{} protections enabled", self.score, Self::MAX_SCORE);
+            return;
+        }
+        log::warn!(
+            "⚠️ INSECURE DEFAULTS: {}/{} protections enabled. To harden, set:",
+            self.score,
+            Self::MAX_SCORE
+        );
+        for finding in &self.findings {
+            log::warn!("   - {:<22} {}", finding.protection, finding.fix);
+        }
+        if self.exposed {
+            log::warn!("   Listening beyond loopback with open CORS and no authentication: any website could read card data");
         }
     }
 }
 
 impl SecurityConfig {
+    /// Score the protections of `server` with these security settings
+    #[must_use]
+    pub fn security_posture(&self, server: &ServerConfig) -> SecurityPosture {
+        let checks = [
+            (self.enable_authentication, "authentication", "security.enable_authentication = true (plus API_KEYS)"),
+            (server.enable_tls, "TLS", "server.enable_tls = true (plus tls_cert_path / tls_key_path)"),
+            (self.enable_encryption, "PII encryption", "security.enable_encryption = true (plus ENCRYPTION_KEY)"),
+            (!server.cors_allow_all, "CORS restriction", "server.cors_allow_all = false (plus allowed_origins)"),
+            (self.enable_rate_limiting, "rate limiting", "security.enable_rate_limiting = true"),
+            (self.enable_audit_logging, "audit logging", "security.enable_audit_logging = true"),
+        ];
+        SecurityPosture {
+            score: checks.iter().filter(|(on, ..)| *on).count(),
+            findings: checks
+                .iter()
+                .filter(|(on, ..)| !on)
+                .map(|&(_, protection, fix)| PostureFinding { protection, fix })
+                .collect(),
+            exposed: !server.is_loopback() && server.cors_allow_all && !self.enable_authentication,
+        }
+    }
+
+    /// Name/address character allowlists for validation
+    #[must_use]
+    pub fn field_charsets(&self) -> FieldCharsets {
+        FieldCharsets {
+            name: CharsetPolicy::new(self.name_allowed_punctuation.as_str()),
+            address: CharsetPolicy::new(self.address_allowed_punctuation.as_str()),
+        }
+    }
+
     /// Get API keys from config or environment variable
     #[must_use]
     pub fn get_api_keys(&self) -> Vec<String> {
@@ -440,7 +1105,15 @@ impl SecurityConfig {
             return false;
         }
 
-        valid_keys.iter().any(|k| k == key)
+        valid_keys.iter().any(|k| crate::crypto::constant_time_eq(k, key))
+    }
+
+    /// Whether a connection presenting `key` may receive unmasked data
+    #[must_use]
+    pub fn can_reveal(&self, key: Option<&str>) -> bool {
+        key.is_some_and(|key| {
+            self.is_valid_key(key) && self.reveal_keys.iter().any(|k| crate::crypto::constant_time_eq(k, key))
+        })
     }
 
     /// Check if a field should be encrypted
@@ -461,6 +1134,11 @@ impl SecurityConfig {
         // Check if field is in the encrypted list
         self.encrypted_fields.iter().any(|f| f == field_name)
     }
+
+    /// Whether a protected photo of `encoded_len` Base64 bytes is big enough to encrypt
+    pub fn should_encrypt_photo(&self, encoded_len: usize) -> bool {
+        encoded_len > self.encrypt_photo_over_bytes
+    }
 }
 
 // ============================================================================
@@ -474,9 +1152,8 @@ pub struct ApduCommand {
     pub name: String,
     /// APDU bytes as hex string (e.g., "80B0000402000D")
     pub apdu: String,
-    /// Whether this field is required (reserved for validation logic)
+    /// A failed or blank read of this field fails the card read (`card.enforce_required`)
     #[serde(default = "default_true")]
-    #[allow(dead_code)]
     pub required: bool,
 }
 
@@ -488,6 +1165,17 @@ impl ApduCommand {
     }
 }
 
+/// Built-in alternative `[[card.fields]]` names for a field (canonical, alias)
+pub const FIELD_ALIASES: &[(&str, &str)] = &[
+    ("issuer", "card_issuer"),
+    ("issue", "issue_date"),
+    ("expire", "expire_date"),
+    ("date_of_birth", "birthday"),
+];
+
+/// Lower bound for `card.poll_idle_ms`
+pub const MIN_POLL_IDLE_MS: u64 = 10;
+
 /// Card reading configuration with APDU commands
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
@@ -498,16 +1186,112 @@ pub struct CardConfig {
     pub fields: Vec<ApduCommand>,
     /// Photo chunk APDU commands
     pub photo_chunks: Vec<String>,
+    /// Largest raw photo accepted in bytes; bigger photos are discarded (0 = no limit)
+    pub max_photo_bytes: usize,
+    /// Largest response accepted for a field APDU in bytes (0 = no limit)
+    pub max_field_bytes: usize,
+    /// Largest response accepted for one photo chunk APDU in bytes (0 = no limit)
+    pub max_photo_chunk_bytes: usize,
+    /// Most GET RESPONSE commands followed for one APDU (0 = built-in cap of 256)
+    pub max_get_response_chain: usize,
+    /// Attempts to establish the PC/SC context at startup
+    pub context_retry_attempts: u8,
+    /// Initial delay between context attempts in milliseconds (doubles each retry)
+    pub context_retry_delay_ms: u64,
     /// Number of connection retry attempts
     pub retry_attempts: u8,
     /// Delay between connection retries in milliseconds
     pub retry_delay_ms: u64,
-    /// Delay after card insertion before reading (ms)
+    /// Longest wait for a newly inserted card to accept a connection (ms)
     pub card_settle_delay_ms: u64,
+    /// First wait before re-trying a card that is still settling (ms, doubles each retry)
+    pub card_settle_initial_ms: u64,
     /// Number of read retry attempts per connection
     pub read_retry_attempts: u8,
     /// Delay between read retries in milliseconds
     pub read_retry_delay_ms: u64,
+    /// Give up on a single card read after this many milliseconds (0 = no limit)
+    pub read_timeout_ms: u64,
+    /// Honor each field's `required` flag: reject reads with a failed or blank
+    /// required field, tolerate failures of optional ones
+    pub enforce_required: bool,
+    /// Delay before each APDU transmit in milliseconds (0 = no delay)
+    pub apdu_delay_ms: u64,
+    /// Timeout of the blocking PC/SC status-change wait in milliseconds
+    pub poll_timeout_ms: u64,
+    /// Sleep between monitor poll cycles in milliseconds (floored at `MIN_POLL_IDLE_MS`)
+    pub poll_idle_ms: u64,
+    /// PC/SC share mode for card connections
+    pub share_mode: CardShareMode,
+    /// Card protocol for card connections
+    pub protocol: CardProtocol,
+    /// Snap parsed tambol/amphur/province to a reference dataset
+    pub validate_address: bool,
+    /// Reference dataset CSV (`province,amphur,tambol` per line); bundled provinces if unset
+    pub address_reference_path: Option<String>,
+    /// Named card profile replacing the SELECT/field/photo APDUs above
+    pub profile: Option<String>,
+    /// Directory searched for `<profile>.toml` before the embedded presets
+    pub profiles_dir: String,
+    /// SELECT APDUs tried in order if the primary SELECT fails
+    pub fallback_select_apdus: Vec<String>,
+    /// Extra field name aliases: name used by the reader -> name in `fields`
+    pub field_aliases: HashMap<String, String>,
+    /// Only use readers whose name matches one of these (empty = all readers)
+    ///
+    /// Entries match as case-insensitive substrings; `/.../` entries are regexes.
+    pub reader_allowlist: Vec<String>,
+    /// Never use readers whose name matches one of these (checked after the allowlist)
+    pub reader_denylist: Vec<String>,
+    /// SELECT APDU of the NHSO (health insurance) applet; NHSO data is read only when set
+    pub nhso_select_apdu: Option<String>,
+    /// NHSO field APDU commands, read after the identity fields
+    pub nhso_fields: Vec<ApduCommand>,
+}
+
+/// Named card layout loaded from `<profiles_dir>/<name>.toml` or a preset
+///
+/// # Example
+/// ```toml
+/// select_apdu = "00A4040008A000000054480001"
+/// fallback_select_apdus = ["00A4040008A000000054480002"]
+/// photo_chunks = ["80B0017B0200FF"]
+///
+/// [[fields]]
+/// name = "citizen_id"
+/// apdu = "80B0000402000D"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct CardProfile {
+    /// SELECT APDU for the profile's applet
+    pub select_apdu: String,
+    /// Field APDU commands
+    pub fields: Vec<ApduCommand>,
+    /// Photo chunk APDU commands (current chunks are kept if empty)
+    #[serde(default)]
+    pub photo_chunks: Vec<String>,
+    /// SELECT APDUs tried in order if the primary SELECT fails
+    #[serde(default)]
+    pub fallback_select_apdus: Vec<String>,
+}
+
+impl CardProfile {
+    /// Embedded profile by name
+    #[must_use]
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "thai-id" => {
+                let defaults = CardConfig::default();
+                Some(Self {
+                    select_apdu: defaults.select_apdu,
+                    fields: defaults.fields,
+                    photo_chunks: defaults.photo_chunks,
+                    fallback_select_apdus: Vec::new(),
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 fn default_true() -> bool {
@@ -564,7 +1348,12 @@ impl Default for CardConfig {
                     name: "address".to_owned(),
                     apdu: "80B015790200FF".to_owned(),
                     required: false,
-                }
+                },
+                ApduCommand {
+                    name: "card_serial".to_owned(),
+                    apdu: "80B01559020014".to_owned(),
+                    required: false,
+                },
             ],
             photo_chunks: vec![
                 "80B0017B0200FF".to_owned(),
@@ -588,11 +1377,35 @@ impl Default for CardConfig {
                 "80B013690200FF".to_owned(),
                 "80B014680200FF".to_owned(),
             ],
+            context_retry_attempts: 3,
+            context_retry_delay_ms: 250,
             retry_attempts: 3,
             retry_delay_ms: 500,
             card_settle_delay_ms: 500,
+            card_settle_initial_ms: 50,
             read_retry_attempts: 3,
             read_retry_delay_ms: 300,
+            read_timeout_ms: 15000,
+            enforce_required: true,
+            apdu_delay_ms: 0,
+            poll_timeout_ms: 2000,
+            poll_idle_ms: 500,
+            max_photo_bytes: 64 * 1024,
+            max_field_bytes: 1024,
+            max_photo_chunk_bytes: 4096,
+            max_get_response_chain: 32,
+            share_mode: CardShareMode::Shared,
+            protocol: CardProtocol::Any,
+            profile: None,
+            profiles_dir: "profiles".to_owned(),
+            fallback_select_apdus: Vec::new(),
+            validate_address: false,
+            address_reference_path: None,
+            field_aliases: HashMap::new(),
+            reader_allowlist: Vec::new(),
+            reader_denylist: Vec::new(),
+            nhso_select_apdu: None,
+            nhso_fields: Vec::new(),
         }
     }
 }
@@ -604,19 +1417,122 @@ impl CardConfig {
         hex_to_bytes(&self.select_apdu)
     }
 
+    /// Limit for one card read, `None` when disabled
+    #[must_use]
+    pub fn read_timeout(&self) -> Option<std::time::Duration> {
+        (self.read_timeout_ms > 0).then(|| std::time::Duration::from_millis(self.read_timeout_ms))
+    }
+
+    /// Timeout for `get_status_change`
+    #[must_use]
+    pub fn poll_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.poll_timeout_ms)
+    }
+
+    /// Sleep between poll cycles, never below `MIN_POLL_IDLE_MS` so the monitor can't spin
+    #[must_use]
+    pub fn poll_idle(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.poll_idle_ms.max(MIN_POLL_IDLE_MS))
+    }
+
     /// Get photo chunk APDUs as bytes
     #[must_use]
     pub fn photo_chunk_bytes(&self) -> Vec<Vec<u8>> {
         self.photo_chunks.iter().map(|s| hex_to_bytes(s)).collect()
     }
 
-    /// Get field APDU by name
+    /// Get field APDU by name, falling back to `field_aliases` then [`FIELD_ALIASES`]
+    ///
+    /// A hit through an alias is logged as a warning so naming mismatches stay visible.
     #[must_use]
     pub fn get_field(&self, name: &str) -> Option<&ApduCommand> {
-        self.fields.iter().find(|f| f.name == name)
+        if let Some(field) = self.fields.iter().find(|f| f.name == name) {
+            return Some(field);
+        }
+
+        let configured = self.field_aliases.get(name).map(String::as_str);
+        let built_in = FIELD_ALIASES
+            .iter()
+            .filter(|(canonical, _)| *canonical == name)
+            .map(|(_, alias)| *alias);
+        let field = configured
+            .into_iter()
+            .chain(built_in)
+            .find_map(|alias| self.fields.iter().find(|f| f.name == alias))?;
+
+        log::warn!("⚠️ Card field '{}' resolved via alias '{}'", name, field.name);
+        Some(field)
+    }
+
+    /// Get fallback SELECT APDUs as bytes
+    #[must_use]
+    pub fn fallback_select_apdu_bytes(&self) -> Vec<Vec<u8>> {
+        self.fallback_select_apdus.iter().map(|s| hex_to_bytes(s)).collect()
+    }
+
+    /// NHSO applet SELECT APDU as bytes, `None` when NHSO reading is off
+    #[must_use]
+    pub fn nhso_select_apdu_bytes(&self) -> Option<Vec<u8>> {
+        self.nhso_select_apdu.as_deref().map(hex_to_bytes)
+    }
+
+    /// Apply `profile` (if set), loading `<profiles_dir>/<profile>.toml` or a preset
+    ///
+    /// # Errors
+    /// Returns `ConfigError` if the profile file is invalid or the name is unknown
+    pub fn resolve_profile(&self) -> Result<Self, ConfigError> {
+        let Some(name) = &self.profile else {
+            return Ok(self.clone());
+        };
+
+        let path = Path::new(&self.profiles_dir).join(format!("{name}.toml"));
+        let profile = if path.exists() {
+            toml::from_str::<CardProfile>(&std::fs::read_to_string(&path)?)?
+        } else {
+            CardProfile::preset(name).ok_or_else(|| ConfigError::UnknownProfile(name.clone()))?
+        };
+
+        Ok(self.clone().with_profile(profile))
+    }
+
+    /// Reject settings that would otherwise be silently ignored at runtime
+    ///
+    /// # Errors
+    /// Returns `ConfigError` for a reader pattern that is not a valid regex
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for pattern in self.reader_allowlist.iter().chain(&self.reader_denylist) {
+            if let Some(Err(e)) = reader_pattern_regex(pattern) {
+                return Err(ConfigError::InvalidReaderPattern {
+                    pattern: pattern.clone(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the SELECT/field/photo APDUs with those of `profile`
+    #[must_use]
+    pub fn with_profile(mut self, profile: CardProfile) -> Self {
+        self.select_apdu = profile.select_apdu;
+        self.fields = profile.fields;
+        if !profile.photo_chunks.is_empty() {
+            self.photo_chunks = profile.photo_chunks;
+        }
+        self.fallback_select_apdus.extend(profile.fallback_select_apdus);
+        self
     }
 }
 
+/// Regex of a `/.../` reader pattern, `None` for a plain substring pattern
+#[must_use]
+pub fn reader_pattern_regex(pattern: &str) -> Option<Result<regex::Regex, regex::Error>> {
+    pattern
+        .strip_prefix('/')
+        .and_then(|p| p.strip_suffix('/'))
+        .map(regex::Regex::new)
+}
+
 /// Convert hex string to bytes
 fn hex_to_bytes(hex: &str) -> Vec<u8> {
     let hex = hex.replace(' ', "");
@@ -626,6 +1542,16 @@ fn hex_to_bytes(hex: &str) -> Vec<u8> {
         .collect()
 }
 
+/// `true` when `SMART_CARD_DEV_MODE` marks this host as non-production
+#[must_use]
+pub fn dev_mode_enabled() -> bool {
+    is_dev_mode(std::env::var(DEV_MODE_ENV_VAR).ok().as_deref())
+}
+
+fn is_dev_mode(value: Option<&str>) -> bool {
+    value.is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
 // ============================================================================
 // Custom Deserializers
 // ============================================================================
@@ -737,6 +1663,96 @@ fn build_search_paths(explicit_path: Option<&str>) -> Vec<PathBuf> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_security_posture_scoring() {
+        let posture = SecurityConfig::default().security_posture(&ServerConfig::default());
+        assert_eq!(posture.score, 0);
+        assert_eq!(posture.findings.len(), SecurityPosture::MAX_SCORE);
+        assert!(!posture.is_hardened());
+        assert!(posture.findings.iter().any(|f| f.fix.starts_with("server.enable_tls")));
+        // Default host is loopback
+        assert!(!posture.exposed);
+
+        let server = ServerConfig {
+            enable_tls: true,
+            cors_allow_all: false,
+            ..ServerConfig::default()
+        };
+        let security = SecurityConfig {
+            enable_authentication: true,
+            enable_encryption: true,
+            enable_rate_limiting: true,
+            enable_audit_logging: true,
+            ..SecurityConfig::default()
+        };
+        let posture = security.security_posture(&server);
+        assert_eq!(posture.score, SecurityPosture::MAX_SCORE);
+        assert!(posture.is_hardened());
+
+        // Partially hardened: only what is off is listed
+        let posture = SecurityConfig { enable_authentication: true, ..SecurityConfig::default() }.security_posture(&server);
+        assert_eq!(posture.score, 3);
+        let missing: Vec<&str> = posture.findings.iter().map(|f| f.protection).collect();
+        assert_eq!(missing, ["PII encryption", "rate limiting", "audit logging"]);
+    }
+
+    #[test]
+    fn test_security_posture_flags_exposed_open_server() {
+        let exposed = ServerConfig {
+            host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            ..ServerConfig::default()
+        };
+        assert!(SecurityConfig::default().security_posture(&exposed).exposed);
+        let with_auth = SecurityConfig { enable_authentication: true, ..SecurityConfig::default() };
+        assert!(!with_auth.security_posture(&exposed).exposed);
+        let restricted = ServerConfig { cors_allow_all: false, ..exposed };
+        assert!(!SecurityConfig::default().security_posture(&restricted).exposed);
+    }
+
+    #[test]
+    fn test_public_bind_refusal() {
+        let open = SecurityConfig::default();
+        let with_auth = SecurityConfig { enable_authentication: true, ..SecurityConfig::default() };
+        let public = ServerConfig {
+            host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            cors_allow_all: false,
+            ..ServerConfig::default()
+        };
+
+        // Loopback is always allowed, also written as an IPv4-mapped address
+        assert!(ServerConfig::default().public_bind_refusal(&open).is_none());
+        let mapped = ServerConfig {
+            host: "::ffff:127.0.0.1".parse().unwrap(),
+            ..public.clone()
+        };
+        assert!(mapped.public_bind_refusal(&open).is_none());
+        assert!(!open.security_posture(&mapped).exposed);
+        // Public without authentication and TLS is refused
+        assert!(public.public_bind_refusal(&open).is_some());
+        // ... unless authentication or TLS is on
+        assert!(public.public_bind_refusal(&with_auth).is_none());
+        let public_tls = ServerConfig { enable_tls: true, ..public.clone() };
+        assert!(public_tls.public_bind_refusal(&open).is_none());
+        // TLS alone does not help when any website may connect
+        let public_tls_open_cors = ServerConfig { cors_allow_all: true, ..public_tls };
+        assert!(public_tls_open_cors.public_bind_refusal(&open).is_some());
+        assert!(public_tls_open_cors.public_bind_refusal(&with_auth).is_none());
+        // Explicit override
+        let overridden = ServerConfig { allow_insecure_public_bind: true, ..public };
+        assert!(overridden.public_bind_refusal(&open).is_none());
+    }
+
+    #[test]
+    fn test_test_events_need_dev_mode() {
+        let mut server = ServerConfig::default();
+        assert!(!server.test_events_allowed(Some("1")));
+
+        server.enable_test_events = true;
+        assert!(!server.test_events_allowed(None));
+        assert!(!server.test_events_allowed(Some("0")));
+        assert!(server.test_events_allowed(Some("1")));
+    }
+
     #[test]
     fn test_default_config() {
         let config = AppConfig::default();
@@ -751,6 +1767,19 @@ mod tests {
         assert_eq!(config.websocket_url(), "ws://127.0.0.1:8182");
     }
 
+    #[test]
+    fn test_resolved_tls_format() {
+        let mut config = ServerConfig::default();
+        assert_eq!(config.resolved_tls_format(), TlsFormat::Pem);
+
+        config.tls_cert_path = "certs/bundle.PFX".to_string();
+        assert_eq!(config.resolved_tls_format(), TlsFormat::Pkcs12);
+
+        config.tls_cert_path = "certs/bundle.bin".to_string();
+        config.tls_format = TlsFormat::Pkcs12;
+        assert_eq!(config.resolved_tls_format(), TlsFormat::Pkcs12);
+    }
+
     #[test]
     fn test_output_field_enabled() {
         let mut config = OutputConfig::default();
@@ -796,4 +1825,247 @@ mod tests {
         assert_eq!(config.output.format, OutputFormat::Minimal);
         assert!(!config.output.include_photo);
     }
+
+    #[test]
+    fn test_parse_field_transforms() {
+        let toml = r#"
+            [output.field_transforms]
+            Citizenid = ["dash_group_id"]
+            En_Firstname = ["trim", "upper", "normalize_nfc"]
+        "#;
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        let transforms = &config.output.field_transforms;
+        assert_eq!(transforms["Citizenid"], [Transform::DashGroupId]);
+        assert_eq!(
+            transforms["En_Firstname"],
+            [Transform::Trim, Transform::Upper, Transform::NormalizeNfc]
+        );
+
+        let invalid = "[output.field_transforms]\nCitizenid = [\"reverse\"]";
+        assert!(toml::from_str::<AppConfig>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_poll_settings() {
+        let defaults = CardConfig::default();
+        assert_eq!(defaults.poll_timeout(), std::time::Duration::from_secs(2));
+        assert_eq!(defaults.poll_idle(), std::time::Duration::from_millis(500));
+
+        let toml = r#"
+            [card]
+            poll_timeout_ms = 50
+            poll_idle_ms = 0
+        "#;
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.card.poll_timeout(), std::time::Duration::from_millis(50));
+        // 0 would busy-loop the monitor
+        assert_eq!(config.card.poll_idle(), std::time::Duration::from_millis(MIN_POLL_IDLE_MS));
+    }
+
+    #[test]
+    fn test_parse_broadcast_settings() {
+        let defaults = ServerConfig::default();
+        assert_eq!(defaults.broadcast_capacity, 100);
+        assert_eq!(defaults.lag_policy, LagPolicy::Resync);
+
+        let toml = r#"
+            [server]
+            broadcast_capacity = 16
+            lag_policy = "disconnect"
+        "#;
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.server.broadcast_capacity, 16);
+        assert_eq!(config.server.lag_policy, LagPolicy::Disconnect);
+    }
+
+    #[test]
+    fn test_ws_path() {
+        let mut server = ServerConfig::default();
+        assert_eq!(server.websocket_path(), "/");
+        assert_eq!(server.websocket_url(), "ws://127.0.0.1:8182");
+
+        server.ws_path = "smartcard/ws/".to_string();
+        assert_eq!(server.websocket_path(), "/smartcard/ws");
+        assert_eq!(server.websocket_url(), "ws://127.0.0.1:8182/smartcard/ws");
+
+        server.ws_path = "/version".to_string();
+        assert_eq!(server.websocket_path(), "/");
+        server.ws_path = "/health/security".to_string();
+        assert_eq!(server.websocket_path(), "/");
+    }
+
+    #[test]
+    fn test_ipv6_host() {
+        let toml = r#"
+            [server]
+            host = "::1"
+        "#;
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.server.websocket_url(), "ws://[::1]:8182");
+        assert_eq!(config.server.socket_addr().to_string(), "[::1]:8182");
+        assert_eq!(config.security.rate_limit_ipv6_prefix, 64);
+    }
+
+    #[test]
+    fn test_parse_card_connection_settings() {
+        let toml = r#"
+            [card]
+            share_mode = "exclusive"
+            protocol = "t1"
+        "#;
+
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.card.share_mode, CardShareMode::Exclusive);
+        assert_eq!(config.card.protocol, CardProtocol::T1);
+        assert_eq!(AppConfig::default().card.share_mode, CardShareMode::Shared);
+    }
+
+    #[test]
+    fn test_load_card_profile_from_dir() {
+        let dir = std::env::temp_dir().join(format!("scr-profiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("corporate.toml"),
+            r#"
+                select_apdu = "00A4040008A000000084060002"
+                fallback_select_apdus = ["00A4040008A000000054480001"]
+
+                [[fields]]
+                name = "citizen_id"
+                apdu = "80B0000002000D"
+            "#,
+        )
+        .unwrap();
+
+        let card = CardConfig {
+            profile: Some("corporate".to_owned()),
+            profiles_dir: dir.to_string_lossy().into_owned(),
+            ..CardConfig::default()
+        };
+        let resolved = card.resolve_profile().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_ne!(resolved.select_apdu_bytes(), CardConfig::default().select_apdu_bytes());
+        assert_eq!(resolved.select_apdu_bytes()[..5], [0x00, 0xA4, 0x04, 0x00, 0x08]);
+        assert_eq!(resolved.fields.len(), 1);
+        // Photo chunks are kept when the profile doesn't define any
+        assert_eq!(resolved.photo_chunks, CardConfig::default().photo_chunks);
+        assert_eq!(resolved.fallback_select_apdu_bytes(), vec![CardConfig::default().select_apdu_bytes()]);
+    }
+
+    #[test]
+    fn test_card_profile_preset_and_unknown() {
+        let card = CardConfig {
+            profile: Some("thai-id".to_owned()),
+            profiles_dir: "does-not-exist".to_owned(),
+            ..CardConfig::default()
+        };
+        assert_eq!(card.resolve_profile().unwrap().select_apdu, CardConfig::default().select_apdu);
+
+        let card = CardConfig {
+            profile: Some("nope".to_owned()),
+            profiles_dir: "does-not-exist".to_owned(),
+            ..CardConfig::default()
+        };
+        assert!(matches!(card.resolve_profile(), Err(ConfigError::UnknownProfile(_))));
+    }
+
+    #[test]
+    fn test_invalid_reader_pattern_fails_validation() {
+        let mut card = CardConfig {
+            reader_allowlist: vec!["/^ACS.*PICC/".to_string(), "omnikey".to_string()],
+            ..CardConfig::default()
+        };
+        assert!(card.validate().is_ok());
+
+        card.reader_denylist = vec!["/finger(/".to_string()];
+        match card.validate() {
+            Err(ConfigError::InvalidReaderPattern { pattern, .. }) => assert_eq!(pattern, "/finger(/"),
+            other => panic!("expected InvalidReaderPattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_block_on() {
+        let toml = r#"
+            [security]
+            block_on = "integrity"
+        "#;
+
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.security.block_on, BlockOn::Integrity);
+        assert_eq!(AppConfig::default().security.block_on, BlockOn::Security);
+    }
+
+    #[test]
+    fn test_parse_allowed_punctuation() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [security]
+            address_allowed_punctuation = "/"
+            "#,
+        )
+        .unwrap();
+        let charsets = config.security.field_charsets();
+        assert!(charsets.address.allows('/'));
+        assert!(!charsets.address.allows('&'));
+        assert_eq!(charsets.name, FieldCharsets::default().name);
+    }
+
+    #[test]
+    fn test_get_field_direct_hit() {
+        let card = CardConfig::default();
+        assert_eq!(card.get_field("issuer").unwrap().apdu, "80B000F6020064");
+    }
+
+    #[test]
+    fn test_get_field_built_in_alias() {
+        let mut card = CardConfig::default();
+        card.fields.retain(|f| f.name != "issuer");
+        card.fields.push(ApduCommand {
+            name: "card_issuer".to_owned(),
+            apdu: "80B000F6020064".to_owned(),
+            required: false,
+        });
+        assert_eq!(card.get_field("issuer").unwrap().name, "card_issuer");
+        assert!(card.get_field("card_issuer").is_some());
+    }
+
+    #[test]
+    fn test_get_field_configured_alias() {
+        let toml = r#"
+            [card.field_aliases]
+            gender = "sex_code"
+
+            [[card.fields]]
+            name = "sex_code"
+            apdu = "80B000E1020001"
+        "#;
+
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.card.get_field("gender").unwrap().name, "sex_code");
+    }
+
+    #[test]
+    fn test_get_field_miss() {
+        let mut card = CardConfig::default();
+        card.field_aliases.insert("nickname".to_owned(), "also_missing".to_owned());
+        assert!(card.get_field("nickname").is_none());
+        assert!(card.get_field("photo").is_none());
+    }
+
+    #[test]
+    fn test_parse_audit_min_severity() {
+        let toml = r#"
+            [security]
+            audit_min_severity = "warning"
+        "#;
+
+        let config: AppConfig = toml::from_str(toml).unwrap();
+        assert_eq!(config.security.audit_min_severity, AuditSeverity::Warning);
+        assert_eq!(
+            AppConfig::default().security.audit_min_severity,
+            AuditSeverity::Info
+        );
+    }
 }
@@ -3,21 +3,103 @@
 //! Provides AES-256-GCM authenticated encryption for sensitive personally
 //! identifiable information (PII) before transmission over WebSocket.
 
-#[cfg(test)]
-use aes_gcm::Nonce;
 use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
-    Aes256Gcm, Key,
+    Aes256Gcm, Key, Nonce,
 };
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Size of AES-256 key in bytes
 const KEY_SIZE: usize = 32;
 
 /// Size of GCM nonce in bytes
-#[cfg(test)]
 const NONCE_SIZE: usize = 12;
 
+/// How per-message GCM nonces are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NonceMode {
+    /// 12 random bytes per message
+    #[default]
+    Random,
+    /// Random 4-byte per-process salt + 8-byte monotonic counter
+    Counter,
+}
+
+impl fmt::Display for NonceMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Random => write!(f, "random"),
+            Self::Counter => write!(f, "counter"),
+        }
+    }
+}
+
+/// What to broadcast for a protected field that fails to encrypt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncryptFailurePolicy {
+    /// Send the field unencrypted (⚠️ leaks the PII it was meant to protect)
+    Plaintext,
+    /// Drop the field from the payload
+    Omit,
+    /// Drop the whole payload
+    #[default]
+    Abort,
+}
+
+impl fmt::Display for EncryptFailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plaintext => write!(f, "plaintext"),
+            Self::Omit => write!(f, "omit"),
+            Self::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+/// Nonce generator state for a [`CryptoService`]
+enum NonceSource {
+    Random,
+    Counter { salt: [u8; 4], next: AtomicU64 },
+}
+
+impl NonceSource {
+    fn new(mode: NonceMode) -> Self {
+        match mode {
+            NonceMode::Random => Self::Random,
+            NonceMode::Counter => {
+                let random = Aes256Gcm::generate_nonce(&mut OsRng);
+                let mut salt = [0u8; 4];
+                salt.copy_from_slice(&random[..4]);
+                Self::Counter {
+                    salt,
+                    next: AtomicU64::new(0),
+                }
+            }
+        }
+    }
+
+    fn next_nonce(&self) -> anyhow::Result<Nonce<<Aes256Gcm as AeadCore>::NonceSize>> {
+        match self {
+            Self::Random => Ok(Aes256Gcm::generate_nonce(&mut OsRng)),
+            Self::Counter { salt, next } => {
+                // Never wrap: a repeated counter under the same salt would reuse a nonce
+                let counter = next
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_add(1))
+                    .map_err(|_| anyhow::anyhow!("Nonce counter exhausted, rotate the encryption key"))?;
+                let mut nonce = [0u8; NONCE_SIZE];
+                nonce[..4].copy_from_slice(salt);
+                nonce[4..].copy_from_slice(&counter.to_be_bytes());
+                Ok(*Nonce::from_slice(&nonce))
+            }
+        }
+    }
+}
+
 /// Encrypted data wrapper containing nonce and ciphertext
 #[derive(Debug, Clone)]
 pub struct EncryptedData {
@@ -40,7 +122,6 @@ impl EncryptedData {
     ///
     /// # Errors
     /// Returns error if base64 decoding fails or data is too short
-    #[cfg(test)]
     pub fn from_base64(encoded: &str) -> anyhow::Result<Self> {
         let combined = BASE64
             .decode(encoded)
@@ -61,6 +142,7 @@ impl EncryptedData {
 /// PII encryption service using AES-256-GCM
 pub struct CryptoService {
     cipher: Aes256Gcm,
+    nonces: NonceSource,
 }
 
 impl CryptoService {
@@ -83,7 +165,10 @@ impl CryptoService {
         let key = Key::<Aes256Gcm>::from_slice(key_bytes);
         let cipher = Aes256Gcm::new(key);
 
-        Ok(Self { cipher })
+        Ok(Self {
+            cipher,
+            nonces: NonceSource::new(NonceMode::Random),
+        })
     }
 
     /// Create crypto service from base64-encoded key
@@ -97,6 +182,25 @@ impl CryptoService {
         Self::new(&key_bytes)
     }
 
+    /// Use `mode` to generate nonces for subsequent encryptions
+    #[must_use]
+    pub fn with_nonce_mode(mut self, mode: NonceMode) -> Self {
+        self.nonces = NonceSource::new(mode);
+        self
+    }
+
+    /// Service whose every encryption fails (exhausted nonce counter)
+    #[cfg(test)]
+    pub fn failing() -> Self {
+        let mut service = Self::from_base64_key(&generate_key())
+            .unwrap()
+            .with_nonce_mode(NonceMode::Counter);
+        if let NonceSource::Counter { next, .. } = &mut service.nonces {
+            *next.get_mut() = u64::MAX;
+        }
+        service
+    }
+
     /// Create crypto service from environment variable
     ///
     /// Reads encryption key from `ENCRYPTION_KEY` environment variable.
@@ -115,13 +219,13 @@ impl CryptoService {
     /// * `plaintext` - Data to encrypt
     ///
     /// # Returns
-    /// Encrypted data with random nonce
+    /// Encrypted data with a fresh nonce
     ///
     /// # Errors
-    /// Returns error if encryption fails
+    /// Returns error if encryption fails or the nonce counter is exhausted
     pub fn encrypt(&self, plaintext: &str) -> anyhow::Result<EncryptedData> {
-        // Generate random nonce (12 bytes for GCM)
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        // Fresh 12-byte GCM nonce (random or salt||counter)
+        let nonce = self.nonces.next_nonce()?;
 
         // Encrypt with authentication
         let ciphertext = self
@@ -145,7 +249,6 @@ impl CryptoService {
     ///
     /// # Errors
     /// Returns error if decryption or authentication fails
-    #[cfg(test)]
     pub fn decrypt(&self, encrypted: &EncryptedData) -> anyhow::Result<String> {
         let nonce = Nonce::from_slice(&encrypted.nonce);
 
@@ -176,7 +279,9 @@ impl CryptoService {
     ///
     /// # Returns
     /// Original plaintext
-    #[cfg(test)]
+    ///
+    /// # Errors
+    /// Returns error if decoding, decryption or authentication fails
     pub fn decrypt_from_base64(&self, encoded: &str) -> anyhow::Result<String> {
         let encrypted = EncryptedData::from_base64(encoded)?;
         self.decrypt(&encrypted)
@@ -188,12 +293,19 @@ impl CryptoService {
 /// # Returns
 /// Base64-encoded encryption key suitable for use with `CryptoService`
 #[must_use]
-#[cfg(test)]
 pub fn generate_key() -> String {
     let key = Aes256Gcm::generate_key(&mut OsRng);
     BASE64.encode(key)
 }
 
+/// Compare secrets without an early exit on the first differing byte
+///
+/// Only the length can leak through timing.
+#[must_use]
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -202,6 +314,14 @@ pub fn generate_key() -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("test-key", "test-key"));
+        assert!(!constant_time_eq("test-key", "test-kez"));
+        assert!(!constant_time_eq("test-key", "test-key-2"));
+        assert!(constant_time_eq("", ""));
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let key = Aes256Gcm::generate_key(&mut OsRng);
@@ -238,6 +358,39 @@ mod tests {
         assert_eq!(plaintext, decrypted);
     }
 
+    #[test]
+    fn test_generate_key_is_32_bytes() {
+        let key_b64 = generate_key();
+        assert_eq!(BASE64.decode(&key_b64).unwrap().len(), KEY_SIZE);
+        assert_ne!(key_b64, generate_key());
+    }
+
+    #[test]
+    fn test_counter_nonces_are_distinct_and_decrypt() {
+        let key = Aes256Gcm::generate_key(&mut OsRng);
+        let crypto = CryptoService::new(&key).unwrap().with_nonce_mode(NonceMode::Counter);
+
+        let first = crypto.encrypt("1234567890123").unwrap();
+        let second = crypto.encrypt("1234567890123").unwrap();
+        assert_ne!(first.nonce, second.nonce);
+        // Same salt, counter incremented
+        assert_eq!(first.nonce[..4], second.nonce[..4]);
+        assert_eq!(u64::from_be_bytes(second.nonce[4..].try_into().unwrap()), 1);
+
+        // Wire format still round-trips
+        let decoded = EncryptedData::from_base64(&second.to_base64()).unwrap();
+        assert_eq!(crypto.decrypt(&decoded).unwrap(), "1234567890123");
+    }
+
+    #[test]
+    fn test_counter_nonce_never_wraps() {
+        let source = NonceSource::Counter {
+            salt: [0; 4],
+            next: AtomicU64::new(u64::MAX),
+        };
+        assert!(source.next_nonce().is_err());
+    }
+
     #[test]
     fn test_invalid_key_size() {
         let short_key = vec![0u8; 16]; // Only 16 bytes
@@ -1,19 +1,61 @@
-use crate::config::OutputConfig;
+use crate::address::{parse_address, parse_address_en, ParsedAddress};
+use crate::config::{DateFormat, Envelope, OutputConfig, OutputFormat};
 use encoding_rs::WINDOWS_874;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use unicode_normalization::UnicodeNormalization;
 
 /// Events from the card reader
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
 pub enum CardEvent {
     /// Card was inserted and data was read
     Inserted(ThaiIDData),
     /// Card was removed from the reader
     Removed,
+    /// Reader was unplugged (or became unavailable) mid-read
+    ReaderDisconnected(String),
+    /// A previously disconnected reader is available again
+    ReaderConnected(String),
+    /// PC/SC service or reader availability changed
+    Status(ReaderStatus),
+    /// Reading the card was abandoned (e.g. `card.read_timeout_ms` exceeded)
+    ReadFailed { reader: String, error: String },
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Overall reader availability, reported on transitions only
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReaderStatus {
+    /// PC/SC context is up and at least one reader is attached
+    Ready,
+    /// No PC/SC service or no reader attached
+    Disconnected,
+}
+
+impl std::fmt::Display for ReaderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ready => write!(f, "ready"),
+            Self::Disconnected => write!(f, "disconnected"),
+        }
+    }
+}
+
+/// Whether the card photo came through whole
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PhotoStatus {
+    /// Read completely, or not read at all
+    #[default]
+    Complete,
+    /// Chunks were missing or the data was not a JPEG; `photo` is left empty
+    Incomplete,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct ThaiIDData {
     // --- Identity ---
     pub citizen_id: String,
@@ -31,7 +73,7 @@ pub struct ThaiIDData {
     // --- English name (full, from card) ---
     pub full_name_en: String,
     // --- Date / Sex ---
-    pub birthday: String, // YYYYMMDD (Buddhist Era from card)
+    pub birthday: String, // YYYY/MM/DD (Buddhist Era from card)
     pub sex: String,      // "1" = male, other = female
     // --- Card meta ---
     pub issuer: String,
@@ -46,21 +88,138 @@ pub struct ThaiIDData {
     pub addr_tambol: String,
     pub addr_amphur: String,
     pub addr_province: String,
+    // --- English address (second-generation cards, `address_en` field configured) ---
+    pub address_en: String,
+    pub addr_house_no_en: String,
+    pub addr_village_no_en: String,
+    pub addr_road_en: String,
+    pub addr_lane_en: String,
+    pub addr_tambol_en: String,
+    pub addr_amphur_en: String,
+    pub addr_province_en: String,
     // --- Photo ---
     pub photo: String, // Base64 encoded
+    pub photo_status: PhotoStatus,
 
     // --- Nationality ---
     pub nationality: String, // e.g. "THA"
+
+    // --- Card serial / laser number (only on some card generations) ---
+    pub card_serial: String,
+
+    // --- NHSO health insurance data (only when `card.nhso_select_apdu` is set) ---
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub nhso: BTreeMap<String, String>, // NHSO field name -> decoded value
+
+    // --- Source (not part of the card payload) ---
+    #[serde(default)]
+    pub reader_name: String, // PCSC reader the card was read from
 }
 
-pub fn decode_tis620(bytes: &[u8]) -> String {
+/// Decode TIS-620 bytes to an NFC string, '#' delimiters and spacing kept as on the card
+///
+/// Base of [`decode_tis620`] and [`split_tis620`]; use it for fields whose
+/// '#'-separated structure still has to be parsed (names, address).
+#[must_use]
+pub fn decode_tis620_raw(bytes: &[u8]) -> String {
     let (cow, _encoding_used, _had_errors) = WINDOWS_874.decode(bytes);
+    // Normalize to NFC for proper Thai character composition
+    cow.nfc().collect()
+}
+
+/// Decode a single-value field: '#' becomes a space, whitespace is collapsed and trimmed
+pub fn decode_tis620(bytes: &[u8]) -> String {
     // '#' is used as a field delimiter on Thai ID cards — replace with space
     // then collapse multiple spaces and trim
-    let s = cow.into_owned().replace('#', " ");
-    let s = s.split_whitespace().collect::<Vec<&str>>().join(" ");
-    // Normalize to NFC for proper Thai character composition
-    s.nfc().collect()
+    collapse_whitespace(&decode_tis620_raw(bytes).replace('#', " "))
+}
+
+/// Collapse runs of whitespace into single spaces and trim
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Decode the card serial; unwritten (all `00`/`FF`) areas read as empty
+fn decode_card_serial(bytes: &[u8]) -> String {
+    if bytes.iter().all(|&b| b == 0x00 || b == 0xFF) {
+        return String::new();
+    }
+    decode_tis620(bytes).trim_matches(char::from(0)).to_string()
+}
+
+/// Split TIS-620 bytes on '#' into exactly `n` whitespace-collapsed, NFC parts
+///
+/// Used for name fields stored as `prefix#first#middle#last`. Missing parts
+/// are padded with empty strings; anything past the `n`-th '#' stays in the
+/// last part.
+pub fn split_tis620(bytes: &[u8], n: usize) -> Vec<String> {
+    let mut parts: Vec<String> = decode_tis620_raw(bytes).splitn(n, '#').map(collapse_whitespace).collect();
+    while parts.len() < n {
+        parts.push(String::new());
+    }
+    parts
+}
+
+/// Display form of the Thai name: `"{prefix}{first} {middle} {last}"`
+///
+/// The prefix is written directly before the first name, as on the card
+/// (e.g. "นายสมชาย ใจดี"); empty components are skipped.
+#[must_use]
+pub fn full_thai_name(data: &ThaiIDData) -> String {
+    let first = format!("{}{}", data.th_prefix.trim(), data.th_firstname.trim());
+    [first.as_str(), data.th_middlename.trim(), data.th_lastname.trim()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Thai display name followed by the English one in parentheses
+///
+/// e.g. "นายสมชาย ใจดี (Mr. Somchai Jaidee)"; the parenthetical is omitted
+/// when the card has no English name, and empty components are skipped.
+#[must_use]
+pub fn combined_bilingual_name(data: &ThaiIDData) -> String {
+    let english = [&data.en_prefix, &data.en_firstname, &data.en_middlename, &data.en_lastname]
+        .into_iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let thai = full_thai_name(data);
+    match (thai.is_empty(), english.is_empty()) {
+        (_, true) => thai,
+        (true, false) => english,
+        (false, false) => format!("{} ({})", thai, english),
+    }
+}
+
+/// English name prefixes recognised when splitting an undelimited name
+const ENGLISH_PREFIXES: &[&str] = &["MR", "MRS", "MISS", "MS", "MASTER", "DR"];
+
+/// Split an undelimited English name on spaces into `[prefix, first, middle, last]`
+///
+/// Fallback for cards that store `full_name_en` without '#' delimiters. A
+/// leading "Mr."/"Mrs."/"Miss"/... becomes the prefix, the first and last
+/// remaining words the first and last name, and anything between the middle name.
+#[must_use]
+pub fn split_english_name(full_name: &str) -> Vec<String> {
+    let mut words: Vec<&str> = full_name.split_whitespace().collect();
+    let prefix = match words.first() {
+        Some(word) if words.len() > 1
+            && ENGLISH_PREFIXES.contains(&word.trim_end_matches('.').to_uppercase().as_str()) =>
+        {
+            words.remove(0).to_string()
+        }
+        _ => String::new(),
+    };
+
+    let (first, middle, last) = match words.as_slice() {
+        [] => (String::new(), String::new(), String::new()),
+        [first] => (first.to_string(), String::new(), String::new()),
+        [first, middle @ .., last] => (first.to_string(), middle.join(" "), last.to_string()),
+    };
+    vec![prefix, first, middle, last]
 }
 
 pub fn combine_photo_chunks(chunks: Vec<Vec<u8>>) -> String {
@@ -72,6 +231,144 @@ pub fn combine_photo_chunks(chunks: Vec<Vec<u8>>) -> String {
     base64::engine::general_purpose::STANDARD.encode(&full_data)
 }
 
+/// JPEG start-of-image marker every card photo begins with
+const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
+
+/// Base64 photo from its chunks, or empty and `Incomplete` if it would be a broken image
+fn assemble_photo(chunks: Vec<Vec<u8>>, missing_chunks: bool) -> (String, PhotoStatus) {
+    if chunks.is_empty() && !missing_chunks {
+        return (String::new(), PhotoStatus::Complete);
+    }
+    if missing_chunks || !chunks.iter().flatten().take(2).copied().eq(JPEG_SOI) {
+        log::warn!("⚠️ Photo incomplete or not a JPEG, sending no photo");
+        return (String::new(), PhotoStatus::Incomplete);
+    }
+    (combine_photo_chunks(chunks), PhotoStatus::Complete)
+}
+
+/// Raw field bytes as read from the card, before any decoding
+#[derive(Debug, Clone, Default)]
+pub struct RawCardFields {
+    pub citizen_id: Vec<u8>,
+    pub full_name_th: Vec<u8>,
+    pub full_name_en: Vec<u8>,
+    pub date_of_birth: Vec<u8>,
+    pub gender: Vec<u8>,
+    pub issuer: Vec<u8>,
+    pub issue: Vec<u8>,
+    pub expire: Vec<u8>,
+    pub address: Vec<u8>,
+    /// English address; empty if the card (or the config) has none
+    pub address_en: Vec<u8>,
+    /// Empty if the card (or the config) has no serial field
+    pub card_serial: Vec<u8>,
+    pub photo_chunks: Vec<Vec<u8>>,
+    /// Some photo chunks could not be read from the card
+    pub photo_missing_chunks: bool,
+}
+
+/// Decode, split and format raw card fields into [`ThaiIDData`]
+///
+/// Pure counterpart of the reader's APDU I/O: no PC/SC, no address reference
+/// lookup (see [`ThaiIDData::set_address`]) and an empty `reader_name`.
+#[must_use]
+pub fn assemble_thai_id(raw: RawCardFields) -> ThaiIDData {
+    // Thai name: "คำนำหน้า#ชื่อ#ชื่อกลาง#นามสกุล"
+    let [th_prefix, th_firstname, th_middlename, th_lastname]: [String; 4] =
+        split_tis620(&raw.full_name_th, 4).try_into().unwrap_or_default();
+    let full_name_en = decode_tis620(&raw.full_name_en);
+    let mut en_name_parts = split_tis620(&raw.full_name_en, 4);
+    // Some cards store the English name without '#' delimiters
+    if en_name_parts[1..].iter().all(String::is_empty) && !full_name_en.is_empty() {
+        log::debug!("English name has no '#' delimiters, splitting on spaces");
+        en_name_parts = split_english_name(&full_name_en);
+    }
+    let [en_prefix, en_firstname, en_middlename, en_lastname]: [String; 4] =
+        en_name_parts.try_into().unwrap_or_default();
+
+    // Convert date from YYYYMMDD → YYYY/MM/DD (required by HIS moment() parsing)
+    let format_date_slash = |d: &str| -> String {
+        if d.len() == 8 {
+            format!("{}/{}/{}", &d[0..4], &d[4..6], &d[6..8])
+        } else {
+            d.to_string()
+        }
+    };
+    let mut expire = decode_tis620(&raw.expire);
+    if expire == "99999999" {
+        expire = "29991231".to_string(); // Treat "99999999" as "31 Dec 2599 for practical purposes
+    }
+
+    let (photo, photo_status) = assemble_photo(raw.photo_chunks, raw.photo_missing_chunks);
+
+    let mut data = ThaiIDData {
+        citizen_id: decode_tis620(&raw.citizen_id),
+        th_prefix,
+        th_firstname,
+        th_middlename,
+        th_lastname,
+        en_prefix,
+        en_firstname,
+        en_middlename,
+        en_lastname,
+        full_name_en,
+        birthday: format_date_slash(&decode_tis620(&raw.date_of_birth)),
+        sex: decode_tis620(&raw.gender),
+        issuer: decode_tis620(&raw.issuer),
+        issue: format_date_slash(&decode_tis620(&raw.issue)),
+        expire: format_date_slash(&expire),
+        photo,
+        photo_status,
+        nationality: "THA".to_string(),
+        card_serial: decode_card_serial(&raw.card_serial),
+        ..ThaiIDData::default()
+    };
+    // Address on Thai ID card: เลขที่#หมู่ที่#ซอย#ถนน#[#]ตำบล#อำเภอ#จังหวัด[garbage]
+    data.set_address(parse_address(&raw.address));
+    data.set_address_en(parse_address_en(&raw.address_en));
+    data
+}
+
+impl ThaiIDData {
+    /// Address components, e.g. for normalizing against reference data
+    #[must_use]
+    pub fn parsed_address(&self) -> ParsedAddress {
+        ParsedAddress {
+            house_no: self.addr_house_no.clone(),
+            village_no: self.addr_village_no.clone(),
+            lane: self.addr_lane.clone(),
+            road: self.addr_road.clone(),
+            tambol: self.addr_tambol.clone(),
+            amphur: self.addr_amphur.clone(),
+            province: self.addr_province.clone(),
+        }
+    }
+
+    /// Replace the address components and the combined `address`
+    pub fn set_address(&mut self, parsed: ParsedAddress) {
+        self.address = parsed.full();
+        self.addr_house_no = parsed.house_no;
+        self.addr_village_no = parsed.village_no;
+        self.addr_lane = parsed.lane;
+        self.addr_road = parsed.road;
+        self.addr_tambol = parsed.tambol;
+        self.addr_amphur = parsed.amphur;
+        self.addr_province = parsed.province;
+    }
+
+    /// Replace the English address components and the combined `address_en`
+    pub fn set_address_en(&mut self, parsed: ParsedAddress) {
+        self.address_en = collapse_whitespace(&parsed.full());
+        self.addr_house_no_en = parsed.house_no;
+        self.addr_village_no_en = parsed.village_no;
+        self.addr_lane_en = parsed.lane;
+        self.addr_road_en = parsed.road;
+        self.addr_tambol_en = parsed.tambol;
+        self.addr_amphur_en = parsed.amphur;
+        self.addr_province_en = parsed.province;
+    }
+}
+
 /// Mask citizen ID for logging - shows only last 4 digits with asterisks
 /// Example: "3100600123456" → "****0123456"
 pub fn mask_citizen_id(citizen_id: &str) -> String {
@@ -83,16 +380,89 @@ pub fn mask_citizen_id(citizen_id: &str) -> String {
     }
 }
 
-/// Format date from YYYYMMDD to "DD MMM YYYY" in Buddhist Era (พ.ศ.)
-/// Input is already in Buddhist Era from the card
-pub fn format_thai_date(date_str: &str) -> String {
-    if date_str.len() != 8 {
-        return date_str.to_string();
+/// Mask a name keeping only its first character of each word: "สมชาย ใจดี" → "ส**** ใ***"
+pub fn mask_name(name: &str) -> String {
+    name.split(' ')
+        .map(|word| {
+            word.chars()
+                .enumerate()
+                .map(|(i, c)| if i == 0 { c } else { '*' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Mask free text entirely, keeping only its length
+pub fn mask_text(text: &str) -> String {
+    "*".repeat(text.chars().count())
+}
+
+/// Mask text for display with bullets, keeping spaces: "สมชาย ใจดี" → "••••• ••••"
+#[must_use]
+pub fn mask_bullets(text: &str) -> String {
+    text.chars().map(|c| if c.is_whitespace() { c } else { '•' }).collect()
+}
+
+/// `value` of `field_name` masked for `output.masked_fields`
+fn mask_output_field(field_name: &str, value: &str) -> String {
+    match field_name {
+        "Citizenid" => mask_citizen_id(value),
+        _ => mask_bullets(value),
     }
+}
+
+/// Calendar era for machine-readable dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Era {
+    /// Buddhist Era (พ.ศ.), as stored on the card
+    #[default]
+    Buddhist,
+    /// Common Era (ค.ศ.), Buddhist year − 543
+    Gregorian,
+}
+
+impl std::fmt::Display for Era {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Buddhist => write!(f, "buddhist"),
+            Self::Gregorian => write!(f, "gregorian"),
+        }
+    }
+}
+
+/// ISO date used for lifetime cards (no expiry)
+pub const LIFETIME_ISO_DATE: &str = "9999-12-31";
 
-    let year = &date_str[0..4];
-    let month: u32 = date_str[4..6].parse().unwrap_or(0);
-    let day = &date_str[6..8];
+/// Split a card date (`YYYYMMDD` or `YYYY/MM/DD`) into year, month, day digits
+fn split_card_date(date: &str) -> Option<(&str, &str, &str)> {
+    let (year, month, day) = match date.len() {
+        8 => (&date[0..4], &date[4..6], &date[6..8]),
+        10 if &date[4..5] == "/" && &date[7..8] == "/" => (&date[0..4], &date[5..7], &date[8..10]),
+        _ => return None,
+    };
+    [year, month, day]
+        .iter()
+        .all(|part| part.bytes().all(|b| b.is_ascii_digit()))
+        .then_some((year, month, day))
+}
+
+/// `true` for the lifetime-card expiry, raw (`99999999`) or as stored by the reader (`2999/12/31`)
+fn is_lifetime_date(date: &str) -> bool {
+    matches!(split_card_date(date), Some(("9999", "99", "99") | ("2999", "12", "31")))
+}
+
+/// Format date from YYYYMMDD or YYYY/MM/DD to "DD MMM YYYY" in Buddhist Era (พ.ศ.)
+/// Input is already in Buddhist Era from the card; malformed dates are returned unchanged
+pub fn format_thai_date(date_str: &str) -> String {
+    if is_lifetime_date(date_str) {
+        return "ตลอดชีพ".to_string();
+    }
+    let Some((year, month, day)) = split_card_date(date_str) else {
+        return date_str.to_string();
+    };
+    let month: u32 = month.parse().unwrap_or(0);
 
     let thai_months = [
         "ม.ค.",
@@ -109,7 +479,7 @@ pub fn format_thai_date(date_str: &str) -> String {
         "ธ.ค.",
     ];
 
-    let month_name = if month >= 1 && month <= 12 {
+    let month_name = if (1..=12).contains(&month) {
         thai_months[(month - 1) as usize]
     } else {
         return date_str.to_string();
@@ -121,12 +491,225 @@ pub fn format_thai_date(date_str: &str) -> String {
     format!("{} {} {}", day_num, month_name, year)
 }
 
+/// `text` with ASCII digits replaced by Thai digits (๐-๙)
+#[must_use]
+pub fn thai_digits(text: &str) -> String {
+    text.chars()
+        .map(|c| match c.to_digit(10) {
+            Some(d) => char::from_u32('๐' as u32 + d).unwrap_or(c),
+            None => c,
+        })
+        .collect()
+}
+
+/// [`format_thai_date`] for a fully Thai display: Thai digits and a "พ.ศ." year,
+/// e.g. "๑๕ ม.ค. พ.ศ. ๒๕๖๗"; lifetime and malformed dates as in [`format_thai_date`]
+#[must_use]
+pub fn format_thai_date_thai_numerals(date_str: &str) -> String {
+    let formatted = format_thai_date(date_str);
+    if formatted == date_str || is_lifetime_date(date_str) {
+        return formatted;
+    }
+    match formatted.rsplit_once(' ') {
+        Some((day_month, year)) => thai_digits(&format!("{} พ.ศ. {}", day_month, year)),
+        None => formatted,
+    }
+}
+
+/// Format a Buddhist Era card date (`YYYYMMDD` or `YYYY/MM/DD`) as ISO-8601 `YYYY-MM-DD`
+///
+/// Lifetime expiry becomes [`LIFETIME_ISO_DATE`]. Returns `None` for malformed
+/// dates, including the partial dates (day or month `00`) some cards carry.
+#[must_use]
+pub fn format_iso8601(date_be: &str, era: Era) -> Option<String> {
+    if is_lifetime_date(date_be) {
+        return Some(LIFETIME_ISO_DATE.to_string());
+    }
+    let (year, month, day) = split_card_date(date_be)?;
+    let be_year: i32 = year.parse().ok()?;
+    let month: u32 = month.parse().ok()?;
+    let day: u32 = day.parse().ok()?;
+    // Reject impossible dates (e.g. 31 Feb) using the real CE calendar
+    chrono::NaiveDate::from_ymd_opt(be_year - 543, month, day)?;
+    let year = match era {
+        Era::Buddhist => be_year,
+        Era::Gregorian => be_year - 543,
+    };
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// `value` as a JSON number for `output.numeric_fields`, `None` to keep the string
+///
+/// Dates drop their `/` or `-` separators. Values with a leading zero are kept
+/// as strings so IDs are not altered.
+fn numeric_value(field_name: &str, value: &str) -> Option<u64> {
+    let digits: String = if matches!(field_name, "Birthday" | "Issue" | "Expire") {
+        value.chars().filter(|c| !matches!(c, '/' | '-')).collect()
+    } else {
+        value.to_string()
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || (digits.len() > 1 && digits.starts_with('0')) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Declarative per-field transform applied to output values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Uppercase
+    Upper,
+    /// Lowercase
+    Lower,
+    /// Trim surrounding whitespace
+    Trim,
+    /// Group a 13-digit citizen ID as `1-2345-67890-12-3` (other values unchanged)
+    DashGroupId,
+    /// Unicode NFC normalization
+    NormalizeNfc,
+}
+
+impl Transform {
+    /// Apply this transform to `value`
+    #[must_use]
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Trim => value.trim().to_string(),
+            Self::DashGroupId => {
+                if value.len() == 13 && value.bytes().all(|b| b.is_ascii_digit()) {
+                    format!("{}-{}-{}-{}-{}", &value[0..1], &value[1..5], &value[5..10], &value[10..12], &value[12..13])
+                } else {
+                    value.to_string()
+                }
+            }
+            Self::NormalizeNfc => value.nfc().collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Upper => write!(f, "upper"),
+            Self::Lower => write!(f, "lower"),
+            Self::Trim => write!(f, "trim"),
+            Self::DashGroupId => write!(f, "dash_group_id"),
+            Self::NormalizeNfc => write!(f, "normalize_nfc"),
+        }
+    }
+}
+
+/// Case convention for output keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCase {
+    /// Keys as mapped, e.g. `Th_Firstname`
+    #[default]
+    AsIs,
+    /// `th_firstname`
+    Snake,
+    /// `thFirstname`
+    Camel,
+}
+
+impl KeyCase {
+    /// Convert `key` to this case
+    ///
+    /// Words are split on `_` and at lower-to-upper boundaries, so
+    /// `addrHouseNo`, `Th_Firstname` and `full_name_en` all convert cleanly.
+    #[must_use]
+    pub fn apply(self, key: &str) -> String {
+        match self {
+            Self::AsIs => key.to_string(),
+            Self::Snake => key_words(key).join("_"),
+            Self::Camel => key_words(key)
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) if i > 0 => first.to_uppercase().chain(chars).collect(),
+                        _ => word.clone(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Lowercase words of a key, split on `_` and at lower-to-upper boundaries
+fn key_words(key: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in key.split('_').filter(|p| !p.is_empty()) {
+        let mut word = String::new();
+        let mut prev_lower = false;
+        for c in part.chars() {
+            if c.is_uppercase() && prev_lower {
+                words.push(std::mem::take(&mut word));
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            word.extend(c.to_lowercase());
+        }
+        words.push(word);
+    }
+    words
+}
+
+impl std::fmt::Display for KeyCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AsIs => write!(f, "asis"),
+            Self::Snake => write!(f, "snake"),
+            Self::Camel => write!(f, "camel"),
+        }
+    }
+}
+
 /// Apply output configuration to card data
 /// - Filter enabled fields
 /// - Apply field mapping
 /// - Optionally exclude photo
+/// - Format dates per `date_format` (malformed dates pass through unchanged)
+/// - Apply `field_transforms` in the configured order
+/// - Convert keys to `key_case`
 pub fn apply_output_config(data: &ThaiIDData, config: &OutputConfig) -> Value {
     let mut result = serde_json::Map::new();
+    let transform = |field_name: &str, value: &str| -> Value {
+        let value = match config.field_transforms.get(field_name) {
+            Some(transforms) => transforms.iter().fold(value.to_string(), |v, t| t.apply(&v)),
+            None => value.to_string(),
+        };
+        if config.masked_fields.iter().any(|f| f == field_name) {
+            return json!(mask_output_field(field_name, &value));
+        }
+        if !config.numeric_fields.iter().any(|f| f == field_name) {
+            return json!(value);
+        }
+        match numeric_value(field_name, &value) {
+            Some(number) => json!(number),
+            None => {
+                if !value.is_empty() {
+                    log::warn!("⚠️ Field '{}' is not a plain number, sending it as a string", field_name);
+                }
+                json!(value)
+            }
+        }
+    };
+
+    // Dates are stored on the card as Buddhist Era YYYY/MM/DD
+    let format_date = |date: &str| -> String {
+        match config.date_format {
+            DateFormat::Raw => date.to_string(),
+            DateFormat::Iso8601 => format_iso8601(date, config.date_era).unwrap_or_else(|| date.to_string()),
+            DateFormat::Thai => format_thai_date(date),
+        }
+    };
+    let birthday = format_date(&data.birthday);
+    let issue = format_date(&data.issue);
+    let expire = format_date(&data.expire);
 
     // Define all available fields (internal_name, value)
     let fields: &[(&str, &str)] = &[
@@ -140,11 +723,11 @@ pub fn apply_output_config(data: &ThaiIDData, config: &OutputConfig) -> Value {
         ("En_Middlename", &data.en_middlename),
         ("En_Lastname", &data.en_lastname),
         ("full_name_en", &data.full_name_en),
-        ("Birthday", &data.birthday),
+        ("Birthday", &birthday),
         ("Sex", &data.sex),
         ("Issuer", &data.issuer),
-        ("Issue", &data.issue),
-        ("Expire", &data.expire),
+        ("Issue", &issue),
+        ("Expire", &expire),
         ("Address", &data.address),
         ("addrHouseNo", &data.addr_house_no),
         ("addrVillageNo", &data.addr_village_no),
@@ -159,27 +742,755 @@ pub fn apply_output_config(data: &ThaiIDData, config: &OutputConfig) -> Value {
     // Process each field
     for &(field_name, field_value) in fields {
         if config.is_field_enabled(field_name) {
-            let output_name = config.get_field_name(field_name).to_owned();
-            result.insert(output_name, json!(field_value));
+            let output_name = config.output_key(field_name);
+            result.insert(output_name, transform(field_name, field_value));
         }
     }
 
+    // Computed display name, opt-in
+    if config.include_full_thai_name && config.is_field_enabled("Th_Fullname") {
+        let output_name = config.output_key("Th_Fullname");
+        result.insert(output_name, transform("Th_Fullname", &full_thai_name(data)));
+    }
+    if config.include_bilingual_name && config.is_field_enabled("Name") {
+        let output_name = config.output_key("Name");
+        result.insert(output_name, transform("Name", &combined_bilingual_name(data)));
+    }
+
+    // English address, only for cards that carry it
+    if !data.address_en.is_empty() {
+        let fields_en: &[(&str, &str)] = &[
+            ("AddressEn", &data.address_en),
+            ("addrHouseNoEn", &data.addr_house_no_en),
+            ("addrVillageNoEn", &data.addr_village_no_en),
+            ("addrRoadEn", &data.addr_road_en),
+            ("addrLaneEn", &data.addr_lane_en),
+            ("addrTambolEn", &data.addr_tambol_en),
+            ("addrAmphurEn", &data.addr_amphur_en),
+            ("addrProvinceEn", &data.addr_province_en),
+        ];
+        for &(field_name, field_value) in fields_en {
+            if config.is_field_enabled(field_name) {
+                result.insert(config.output_key(field_name), transform(field_name, field_value));
+            }
+        }
+    }
+
+    // Card serial is only part of the full format
+    if config.format == OutputFormat::Full && config.is_field_enabled("CardSerial") {
+        let output_name = config.output_key("CardSerial");
+        result.insert(output_name, transform("CardSerial", &data.card_serial));
+    }
+
+    // NHSO data as a nested object, only for cards that carry the applet
+    if !data.nhso.is_empty() && config.is_field_enabled("nhso") {
+        let output_name = config.output_key("nhso");
+        result.insert(output_name, json!(&data.nhso));
+    }
+
     // Handle photo separately (can be large)
-    if config.include_photo && config.is_field_enabled("PhotoRaw") {
-        let output_name = config.get_field_name("PhotoRaw").to_owned();
+    if config.photo_enabled() {
+        let output_name = config.output_key("PhotoRaw");
         result.insert(output_name, json!(&data.photo));
+        // Lets clients tell a broken read from a card without a photo
+        if data.photo_status == PhotoStatus::Incomplete {
+            result.insert(config.output_key("photo_status"), json!(data.photo_status));
+        }
     }
 
     // Handle nationality separately
     if config.is_field_enabled("Nationality") {
-        let output_name = config.get_field_name("Nationality").to_owned();
+        let output_name = config.output_key("Nationality");
         result.insert(output_name, json!(&data.nationality));
     } else {
         // If nationality is not enabled, you can choose to insert a default value or skip it
         // For example, inserting a null value:
-        let output_name = config.get_field_name("Nationality").to_owned();
+        let output_name = config.output_key("Nationality");
         result.insert(output_name, json!("THA"));
     }
 
     Value::Object(result)
 }
+
+/// `readsmartcard` message: `mode` plus the mapped fields, unencrypted
+#[must_use]
+pub fn card_json(data: &ThaiIDData, config: &OutputConfig) -> Value {
+    let mut obj = serde_json::Map::new();
+    obj.insert("mode".to_string(), json!("readsmartcard"));
+    if let Value::Object(fields) = apply_output_config(data, config) {
+        obj.extend(fields);
+    }
+    Value::Object(obj)
+}
+
+/// Key holding the card fields of a nested card message
+pub const ENVELOPE_DATA_KEY: &str = "data";
+
+/// `true` for card reads (`readsmartcard` / `readsmartcard_delta`)
+#[must_use]
+pub fn is_card_message(payload: &Value) -> bool {
+    payload
+        .get("mode")
+        .and_then(Value::as_str)
+        .is_some_and(|mode| mode.starts_with("readsmartcard"))
+}
+
+/// Top-level key marking development cards from `/api/test-event`
+pub const TEST_EVENT_KEY: &str = "test";
+
+/// `true` for a test card injected over `/api/test-event` (not a real read)
+#[must_use]
+pub fn is_test_message(payload: &Value) -> bool {
+    payload.get(TEST_EVENT_KEY).and_then(Value::as_bool) == Some(true)
+}
+
+/// Card payload (`readsmartcard`/`readsmartcard_delta`) in the configured envelope
+///
+/// `nested` moves everything but `mode` under [`ENVELOPE_DATA_KEY`]; other
+/// messages and the `flat` envelope pass through unchanged.
+#[must_use]
+pub fn build_broadcast_message(payload: Value, envelope: Envelope) -> Value {
+    if envelope == Envelope::Flat || !is_card_message(&payload) {
+        return payload;
+    }
+    let Value::Object(mut fields) = payload else {
+        return payload;
+    };
+    let mode = fields.remove("mode").unwrap_or_default();
+    json!({ "mode": mode, ENVELOPE_DATA_KEY: fields })
+}
+
+/// Client message for a card event (no validation, encryption or stamping)
+#[must_use]
+pub fn event_json(event: &CardEvent, config: &OutputConfig) -> Value {
+    match event {
+        CardEvent::Inserted(data) => card_json(data, config),
+        CardEvent::Removed => json!({
+            "mode": "removedsmartcard"
+        }),
+        CardEvent::ReaderDisconnected(reader) => json!({
+            "mode": "readerdisconnected",
+            "reader": reader
+        }),
+        CardEvent::ReaderConnected(reader) => json!({
+            "mode": "readerconnected",
+            "reader": reader
+        }),
+        CardEvent::Status(status) => json!({
+            "mode": "status",
+            "reader": status.to_string()
+        }),
+        CardEvent::ReadFailed { reader, error } => json!({
+            "mode": "readfailed",
+            "reader": reader,
+            "error": error
+        }),
+    }
+}
+
+/// How a PII field is masked for display-only clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MaskKind {
+    CitizenId,
+    Name,
+    Text,
+    /// Dropped to an empty string (photo)
+    Remove,
+}
+
+/// Masks PII fields of broadcast card payloads (`security.broadcast_masked`)
+///
+/// Works on the mapped payload, so fields are found by their configured output key.
+/// Applied per output: WebSocket clients until they reveal, and always on
+/// stdout, IPC and the webhook.
+#[derive(Debug, Clone)]
+pub struct PiiMask {
+    fields: Vec<(String, MaskKind)>,
+}
+
+impl PiiMask {
+    pub fn new(config: &OutputConfig) -> Self {
+        let kinds = [
+            ("Citizenid", MaskKind::CitizenId),
+            ("Th_Prefix", MaskKind::Name),
+            ("Th_Firstname", MaskKind::Name),
+            ("Th_Middlename", MaskKind::Name),
+            ("Th_Lastname", MaskKind::Name),
+            ("Th_Fullname", MaskKind::Name),
+            ("Name", MaskKind::Name),
+            ("En_Prefix", MaskKind::Name),
+            ("En_Firstname", MaskKind::Name),
+            ("En_Middlename", MaskKind::Name),
+            ("En_Lastname", MaskKind::Name),
+            ("full_name_en", MaskKind::Name),
+            ("Birthday", MaskKind::Text),
+            ("Issue", MaskKind::Text),
+            ("Expire", MaskKind::Text),
+            ("CardSerial", MaskKind::Text),
+            ("Address", MaskKind::Text),
+            ("addrHouseNo", MaskKind::Text),
+            ("addrVillageNo", MaskKind::Text),
+            ("addrRoad", MaskKind::Text),
+            ("addrLane", MaskKind::Text),
+            ("addrTambol", MaskKind::Text),
+            ("addrAmphur", MaskKind::Text),
+            ("addrProvince", MaskKind::Text),
+            ("AddressEn", MaskKind::Text),
+            ("addrHouseNoEn", MaskKind::Text),
+            ("addrVillageNoEn", MaskKind::Text),
+            ("addrRoadEn", MaskKind::Text),
+            ("addrLaneEn", MaskKind::Text),
+            ("addrTambolEn", MaskKind::Text),
+            ("addrAmphurEn", MaskKind::Text),
+            ("addrProvinceEn", MaskKind::Text),
+            ("nhso", MaskKind::Text),
+            ("PhotoRaw", MaskKind::Remove),
+        ];
+        Self {
+            fields: kinds
+                .into_iter()
+                .map(|(name, kind)| (config.output_key(name), kind))
+                .collect(),
+        }
+    }
+
+    /// Mask the PII fields present in `payload` (or its nested `data`); other fields are untouched
+    pub fn apply(&self, payload: &mut Value) {
+        let Value::Object(obj) = payload else {
+            return;
+        };
+        if let Some(data @ Value::Object(_)) = obj.get_mut(ENVELOPE_DATA_KEY) {
+            self.apply(data);
+        }
+        for (key, kind) in &self.fields {
+            if let Some(value) = obj.get_mut(key) {
+                Self::mask_value(value, *kind);
+            }
+        }
+    }
+
+    /// Mask one value in place; objects (`nhso`) have each member masked
+    fn mask_value(value: &mut Value, kind: MaskKind) {
+        // Numbers come from `output.numeric_fields` and are masked as text
+        let text = match value {
+            Value::String(text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+            Value::Object(members) => {
+                members.values_mut().for_each(|member| Self::mask_value(member, kind));
+                return;
+            }
+            _ => return,
+        };
+        *value = Value::String(match kind {
+            MaskKind::CitizenId => mask_citizen_id(&text),
+            MaskKind::Name => mask_name(&text),
+            MaskKind::Text => mask_text(&text),
+            MaskKind::Remove => String::new(),
+        });
+    }
+
+    /// Mask a serialized payload, passing through anything that is not JSON
+    #[must_use]
+    pub fn apply_str(&self, payload: &str) -> String {
+        match serde_json::from_str::<Value>(payload) {
+            Ok(mut value) => {
+                self.apply(&mut value);
+                value.to_string()
+            }
+            Err(_) => payload.to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card_with_dates() -> ThaiIDData {
+        ThaiIDData {
+            birthday: "2520/04/13".to_string(),
+            issue: "2566/03/01".to_string(),
+            expire: "2999/12/31".to_string(),
+            ..ThaiIDData::default()
+        }
+    }
+
+    fn tis620(s: &str) -> Vec<u8> {
+        WINDOWS_874.encode(s).0.into_owned()
+    }
+
+    fn raw_card(address: &str) -> RawCardFields {
+        RawCardFields {
+            citizen_id: tis620("1101700230708"),
+            full_name_th: tis620("นาย#สมชาย#กลาง#ใจดี     "),
+            full_name_en: tis620("Mr.#Somchai#Klang#Jaidee     "),
+            date_of_birth: tis620("25330115"),
+            gender: tis620("1"),
+            issue: tis620("25660301"),
+            expire: tis620("99999999"),
+            address: tis620(address),
+            card_serial: Vec::new(),
+            photo_chunks: vec![vec![0xFF, 0xD8], vec![0xFF, 0xD9]],
+            ..RawCardFields::default()
+        }
+    }
+
+    #[test]
+    fn test_assemble_seven_field_address() {
+        let data = assemble_thai_id(raw_card(
+            "99/1#หมู่ที่ 2#ซอยสุขุมวิท 5#ถนนสุขุมวิท#ตำบลบางรัก#อำเภอเมือง#จังหวัดชลบุรี",
+        ));
+        assert_eq!(data.addr_house_no, "99/1");
+        assert_eq!(data.addr_lane, "ซอยสุขุมวิท 5");
+        assert_eq!(data.addr_road, "ถนนสุขุมวิท");
+        assert_eq!(data.addr_tambol, "ตำบลบางรัก");
+        assert_eq!(data.addr_province, "จังหวัดชลบุรี");
+        assert_eq!(
+            data.address,
+            "99/1 หมู่ที่ 2 ถนนสุขุมวิท ซอยสุขุมวิท 5 ตำบลบางรัก อำเภอเมือง จังหวัดชลบุรี"
+        );
+    }
+
+    #[test]
+    fn test_assemble_eight_field_address() {
+        let mut raw = raw_card("12#หมู่ที่ 4####ตำบลหนองบัว#อำเภอเมือง#จังหวัดอุดรธานี");
+        raw.address.extend_from_slice(&[0x00, 0x90, 0xFF]);
+        let data = assemble_thai_id(raw);
+        assert_eq!(data.addr_house_no, "12");
+        assert_eq!(data.addr_tambol, "ตำบลหนองบัว");
+        assert_eq!(data.addr_amphur, "อำเภอเมือง");
+        assert_eq!(data.addr_province, "จังหวัดอุดรธานี");
+        assert_eq!(data.address, "12 หมู่ที่ 4   ตำบลหนองบัว อำเภอเมือง จังหวัดอุดรธานี");
+    }
+
+    #[test]
+    fn test_assemble_multi_part_names() {
+        let data = assemble_thai_id(raw_card(""));
+        assert_eq!(
+            [&data.th_prefix, &data.th_firstname, &data.th_middlename, &data.th_lastname],
+            ["นาย", "สมชาย", "กลาง", "ใจดี"]
+        );
+        assert_eq!(
+            [&data.en_prefix, &data.en_firstname, &data.en_middlename, &data.en_lastname],
+            ["Mr.", "Somchai", "Klang", "Jaidee"]
+        );
+        assert_eq!(data.full_name_en, "Mr. Somchai Klang Jaidee");
+
+        // English name without '#' delimiters falls back to splitting on spaces
+        let mut raw = raw_card("");
+        raw.full_name_en = tis620("Mrs. Malee Jaidee");
+        let data = assemble_thai_id(raw);
+        assert_eq!(data.en_prefix, "Mrs.");
+        assert_eq!(data.en_firstname, "Malee");
+        assert_eq!(data.en_lastname, "Jaidee");
+    }
+
+    #[test]
+    fn test_assemble_dates_and_photo() {
+        let data = assemble_thai_id(raw_card(""));
+        assert_eq!(data.citizen_id, "1101700230708");
+        assert_eq!(data.birthday, "2533/01/15");
+        assert_eq!(data.issue, "2566/03/01");
+        assert_eq!(data.expire, "2999/12/31");
+        assert_eq!(data.sex, "1");
+        assert_eq!(data.issuer, "");
+        assert_eq!(data.nationality, "THA");
+        assert_eq!(data.photo, combine_photo_chunks(vec![vec![0xFF, 0xD8, 0xFF, 0xD9]]));
+        assert_eq!(data.photo_status, PhotoStatus::Complete);
+
+        // Data that is not a JPEG would only render as a broken image
+        let data = assemble_thai_id(RawCardFields {
+            photo_chunks: vec![vec![0xAA], vec![0xFF, 0xD9]],
+            ..raw_card("")
+        });
+        assert!(data.photo.is_empty());
+        assert_eq!(data.photo_status, PhotoStatus::Incomplete);
+    }
+
+    #[test]
+    fn test_pii_mask() {
+        let data = ThaiIDData {
+            citizen_id: "3100600123456".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            full_name_en: "Mr. Somchai Jaidee".to_string(),
+            address: "99/1 หมู่ที่ 2".to_string(),
+            sex: "1".to_string(),
+            photo: "/9j/4AAQ".to_string(),
+            nhso: [("main_inscl".to_string(), "UCS".to_string())].into(),
+            ..card_with_dates()
+        };
+        let config = OutputConfig::default();
+        let mut payload = apply_output_config(&data, &config);
+        PiiMask::new(&config).apply(&mut payload);
+
+        let key = |name: &str| config.output_key(name);
+        assert_eq!(payload[key("Citizenid")], mask_citizen_id("3100600123456"));
+        assert_eq!(payload[key("Th_Firstname")], "ส****");
+        assert_eq!(payload[key("full_name_en")], "M** S****** J*****");
+        assert_eq!(payload[key("Address")], "*".repeat("99/1 หมู่ที่ 2".chars().count()));
+        assert_eq!(payload[key("Sex")], "1");
+        assert_eq!(payload[key("nhso")]["main_inscl"], "***");
+        if config.include_photo {
+            assert_eq!(payload[key("PhotoRaw")], "");
+        }
+        assert!(!payload.to_string().contains("3100600123456"));
+    }
+
+    #[test]
+    fn test_full_thai_name() {
+        let mut data = ThaiIDData {
+            th_prefix: "นาย".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            th_middlename: "มั่นคง".to_string(),
+            th_lastname: "ใจดี".to_string(),
+            ..ThaiIDData::default()
+        };
+        assert_eq!(full_thai_name(&data), "นายสมชาย มั่นคง ใจดี");
+
+        data.th_middlename.clear();
+        assert_eq!(full_thai_name(&data), "นายสมชาย ใจดี");
+
+        data.th_prefix = " ".to_string();
+        data.th_lastname = "ใจดี ".to_string();
+        assert_eq!(full_thai_name(&data), "สมชาย ใจดี");
+
+        assert_eq!(full_thai_name(&ThaiIDData::default()), "");
+    }
+
+    #[test]
+    fn test_full_thai_name_output_is_opt_in() {
+        let data = ThaiIDData {
+            th_prefix: "นาง".to_string(),
+            th_firstname: "สมศรี".to_string(),
+            th_lastname: "ใจดี".to_string(),
+            ..ThaiIDData::default()
+        };
+        let mut config = OutputConfig::default();
+        assert!(apply_output_config(&data, &config).get("Th_Fullname").is_none());
+
+        config.include_full_thai_name = true;
+        assert_eq!(apply_output_config(&data, &config)["Th_Fullname"], "นางสมศรี ใจดี");
+    }
+
+    fn bilingual_data() -> ThaiIDData {
+        ThaiIDData {
+            th_prefix: "นาย".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            th_lastname: "ใจดี".to_string(),
+            en_prefix: "Mr.".to_string(),
+            en_firstname: "Somchai".to_string(),
+            en_lastname: "Jaidee".to_string(),
+            ..ThaiIDData::default()
+        }
+    }
+
+    #[test]
+    fn test_combined_bilingual_name() {
+        let mut data = bilingual_data();
+        assert_eq!(combined_bilingual_name(&data), "นายสมชาย ใจดี (Mr. Somchai Jaidee)");
+
+        data.th_middlename = "มั่นคง".to_string();
+        data.en_middlename = "Mankong".to_string();
+        assert_eq!(combined_bilingual_name(&data), "นายสมชาย มั่นคง ใจดี (Mr. Somchai Mankong Jaidee)");
+
+        assert_eq!(combined_bilingual_name(&ThaiIDData::default()), "");
+    }
+
+    #[test]
+    fn test_combined_bilingual_name_thai_only() {
+        let data = ThaiIDData {
+            en_prefix: " ".to_string(),
+            en_firstname: String::new(),
+            en_lastname: String::new(),
+            ..bilingual_data()
+        };
+        assert_eq!(combined_bilingual_name(&data), "นายสมชาย ใจดี");
+    }
+
+    #[test]
+    fn test_combined_bilingual_name_empty_middle_name() {
+        let data = ThaiIDData {
+            th_middlename: "  ".to_string(),
+            en_middlename: " ".to_string(),
+            ..bilingual_data()
+        };
+        assert_eq!(combined_bilingual_name(&data), "นายสมชาย ใจดี (Mr. Somchai Jaidee)");
+
+        let mut config = OutputConfig::default();
+        assert!(apply_output_config(&data, &config).get("Name").is_none());
+        config.include_bilingual_name = true;
+        config.key_case = KeyCase::Snake;
+        assert_eq!(apply_output_config(&data, &config)["name"], "นายสมชาย ใจดี (Mr. Somchai Jaidee)");
+    }
+
+    #[test]
+    fn test_card_serial_only_in_full_format() {
+        let data = ThaiIDData {
+            card_serial: "JT1234567890".to_string(),
+            ..ThaiIDData::default()
+        };
+        let mut config = OutputConfig::default();
+        assert!(apply_output_config(&data, &config).get("CardSerial").is_none());
+
+        config.format = OutputFormat::Full;
+        assert_eq!(apply_output_config(&data, &config)["CardSerial"], "JT1234567890");
+        assert_eq!(decode_card_serial(&[0xFF; 20]), "");
+    }
+
+    #[test]
+    fn test_dash_group_id_transform() {
+        assert_eq!(Transform::DashGroupId.apply("1234567890123"), "1-2345-67890-12-3");
+        // Not a citizen ID: left alone
+        assert_eq!(Transform::DashGroupId.apply("12345"), "12345");
+        assert_eq!(Transform::DashGroupId.apply("1-2345-67890-12-3"), "1-2345-67890-12-3");
+    }
+
+    #[test]
+    fn test_field_transforms_applied_in_order() {
+        let data = ThaiIDData {
+            citizen_id: "1234567890123".to_string(),
+            en_firstname: " Somchai ".to_string(),
+            en_lastname: "Jaidee".to_string(),
+            ..ThaiIDData::default()
+        };
+        let mut config = OutputConfig::default();
+        config.field_mapping.insert("En_Firstname".to_string(), "first_name".to_string());
+        config.field_transforms.insert("Citizenid".to_string(), vec![Transform::DashGroupId]);
+        config.field_transforms.insert("En_Firstname".to_string(), vec![Transform::Trim, Transform::Upper]);
+        config.field_transforms.insert("En_Lastname".to_string(), vec![Transform::Upper, Transform::Lower]);
+
+        let output = apply_output_config(&data, &config);
+        assert_eq!(output["Citizenid"], "1-2345-67890-12-3");
+        // Keyed by internal name, written under the mapped name
+        assert_eq!(output["first_name"], "SOMCHAI");
+        assert_eq!(output["En_Lastname"], "jaidee");
+    }
+
+    #[test]
+    fn test_numeric_fields() {
+        let data = ThaiIDData {
+            citizen_id: "0101700230708".to_string(),
+            birthday: "2533/01/15".to_string(),
+            issue: "2567/01/15".to_string(),
+            expire: "ตลอดชีพ".to_string(),
+            ..ThaiIDData::default()
+        };
+        let mut config = OutputConfig::default();
+        // IDs and dates are strings by default
+        let output = apply_output_config(&data, &config);
+        assert_eq!(output["Citizenid"], "0101700230708");
+        assert_eq!(output["Birthday"], "2533/01/15");
+
+        config.numeric_fields = ["Citizenid", "Birthday", "Issue", "Expire"].map(String::from).to_vec();
+        config.date_format = DateFormat::Iso8601;
+        let output = apply_output_config(&data, &config);
+        assert_eq!(output["Birthday"], 25_330_115);
+        assert_eq!(output["Issue"], 25_670_115);
+        // A leading zero would be lost, and text is not a number: both stay strings
+        assert_eq!(output["Citizenid"], "0101700230708");
+        assert_eq!(output["Expire"], "ตลอดชีพ");
+
+        assert_eq!(numeric_value("Citizenid", "1101700230708"), Some(1_101_700_230_708));
+        assert_eq!(numeric_value("Citizenid", "1-1017-00230-70-8"), None);
+        assert_eq!(numeric_value("addrHouseNo", "0"), Some(0));
+
+        // Masking still applies to a numeric ID
+        config.numeric_fields = vec!["Citizenid".to_string()];
+        let mut payload = json!({ "Citizenid": 1_101_700_230_708_u64 });
+        PiiMask::new(&config).apply(&mut payload);
+        assert_eq!(payload["Citizenid"], mask_citizen_id("1101700230708"));
+    }
+
+    #[test]
+    fn test_masked_fields() {
+        let data = ThaiIDData {
+            citizen_id: "1101700230708".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            th_lastname: "ใจดี".to_string(),
+            en_firstname: "Somchai Jr".to_string(),
+            ..ThaiIDData::default()
+        };
+        let mut config = OutputConfig {
+            masked_fields: ["Citizenid", "Th_Firstname", "En_Firstname"].map(String::from).to_vec(),
+            numeric_fields: vec!["Citizenid".to_string()],
+            ..OutputConfig::default()
+        };
+        config.field_mapping.insert("Th_Firstname".to_string(), "first_name".to_string());
+
+        let output = apply_output_config(&data, &config);
+        // Masking wins over numeric coercion
+        assert_eq!(output["Citizenid"], mask_citizen_id("1101700230708"));
+        // Keyed by internal name, written under the mapped name
+        assert_eq!(output["first_name"], "•••••");
+        assert_eq!(output["En_Firstname"], "••••••• ••");
+        assert_eq!(output["Th_Lastname"], "ใจดี");
+    }
+
+    #[test]
+    fn test_key_case_conversion() {
+        assert_eq!(KeyCase::Camel.apply("Th_Firstname"), "thFirstname");
+        assert_eq!(KeyCase::Snake.apply("Th_Firstname"), "th_firstname");
+        assert_eq!(KeyCase::Camel.apply("full_name_en"), "fullNameEn");
+        assert_eq!(KeyCase::Snake.apply("addrHouseNo"), "addr_house_no");
+        assert_eq!(KeyCase::Camel.apply("addrHouseNo"), "addrHouseNo");
+        assert_eq!(KeyCase::Snake.apply("Citizenid"), "citizenid");
+        assert_eq!(KeyCase::AsIs.apply("Th_Firstname"), "Th_Firstname");
+    }
+
+    #[test]
+    fn test_key_case_applied_after_mapping() {
+        let data = ThaiIDData {
+            th_firstname: "สมชาย".to_string(),
+            ..ThaiIDData::default()
+        };
+        let mut config = OutputConfig {
+            key_case: KeyCase::Snake,
+            ..OutputConfig::default()
+        };
+        config.field_mapping.insert("Citizenid".to_string(), "NationalId".to_string());
+
+        let output = apply_output_config(&data, &config);
+        assert_eq!(output["th_firstname"], "สมชาย");
+        assert!(output.get("national_id").is_some());
+        assert!(output.get("photo_raw").is_some());
+        assert!(output.get("Th_Firstname").is_none());
+    }
+
+    #[test]
+    fn test_decode_tis620_keeps_or_collapses_delimiters() {
+        let raw = tis620("นาย#สมชาย##ใจดี  ");
+        assert_eq!(decode_tis620_raw(&raw), "นาย#สมชาย##ใจดี  ");
+        assert_eq!(decode_tis620(&raw), "นาย สมชาย ใจดี");
+
+        assert_eq!(decode_tis620_raw(&tis620("  #  ")), "  #  ");
+        assert_eq!(decode_tis620(&tis620("  #  ")), "");
+        assert_eq!(split_tis620(&raw, 4), ["นาย", "สมชาย", "", "ใจดี"]);
+    }
+
+    #[test]
+    fn test_broadcast_envelopes() {
+        let data = ThaiIDData {
+            citizen_id: "1101700230708".to_string(),
+            ..ThaiIDData::default()
+        };
+        let config = OutputConfig::default();
+
+        let flat = build_broadcast_message(card_json(&data, &config), Envelope::Flat);
+        assert_eq!(flat["mode"], "readsmartcard");
+        assert_eq!(flat["Citizenid"], "1101700230708");
+        assert!(flat.get(ENVELOPE_DATA_KEY).is_none());
+
+        let nested = build_broadcast_message(card_json(&data, &config), Envelope::Nested);
+        assert_eq!(nested.as_object().unwrap().len(), 2);
+        assert_eq!(nested["mode"], "readsmartcard");
+        assert_eq!(nested["data"]["Citizenid"], "1101700230708");
+        assert!(nested["data"].get("mode").is_none());
+
+        // Masking reaches into the envelope
+        let mut masked = nested.clone();
+        PiiMask::new(&config).apply(&mut masked);
+        assert_eq!(masked["data"]["Citizenid"], mask_citizen_id("1101700230708"));
+
+        // Status messages keep their shape
+        let removed = build_broadcast_message(event_json(&CardEvent::Removed, &config), Envelope::Nested);
+        assert_eq!(removed, json!({ "mode": "removedsmartcard" }));
+    }
+
+    #[test]
+    fn test_split_english_name_without_prefix() {
+        assert_eq!(split_english_name("Somchai Jaidee"), ["", "Somchai", "", "Jaidee"]);
+        assert_eq!(split_english_name("  Somchai  Mai Jaidee "), ["", "Somchai", "Mai", "Jaidee"]);
+        assert_eq!(split_english_name("Somchai"), ["", "Somchai", "", ""]);
+    }
+
+    #[test]
+    fn test_split_english_name_detects_prefix() {
+        assert_eq!(split_english_name("Mr. Somchai Jaidee"), ["Mr.", "Somchai", "", "Jaidee"]);
+        assert_eq!(split_english_name("MRS Somsri Jaidee"), ["MRS", "Somsri", "", "Jaidee"]);
+        assert_eq!(split_english_name("Miss Malee"), ["Miss", "Malee", "", ""]);
+        // A lone word is a name, never a prefix
+        assert_eq!(split_english_name("Miss"), ["", "Miss", "", ""]);
+    }
+
+    #[test]
+    fn test_format_iso8601() {
+        assert_eq!(format_iso8601("2520/04/13", Era::Buddhist).as_deref(), Some("2520-04-13"));
+        assert_eq!(format_iso8601("2520/04/13", Era::Gregorian).as_deref(), Some("1977-04-13"));
+        assert_eq!(format_iso8601("25200413", Era::Gregorian).as_deref(), Some("1977-04-13"));
+    }
+
+    #[test]
+    fn test_format_iso8601_lifetime_sentinel() {
+        for era in [Era::Buddhist, Era::Gregorian] {
+            assert_eq!(format_iso8601("2999/12/31", era).as_deref(), Some(LIFETIME_ISO_DATE));
+            assert_eq!(format_iso8601("99999999", era).as_deref(), Some(LIFETIME_ISO_DATE));
+        }
+    }
+
+    #[test]
+    fn test_format_iso8601_rejects_malformed() {
+        assert_eq!(format_iso8601("", Era::Buddhist), None);
+        assert_eq!(format_iso8601("2520-04-13", Era::Buddhist), None);
+        assert_eq!(format_iso8601("2520/13/01", Era::Buddhist), None);
+        assert_eq!(format_iso8601("2520/02/31", Era::Buddhist), None);
+        // Partial birth date: day unknown
+        assert_eq!(format_iso8601("2520/04/00", Era::Buddhist), None);
+        assert_eq!(format_iso8601("25x0/04/13", Era::Buddhist), None);
+    }
+
+    #[test]
+    fn test_format_thai_date() {
+        assert_eq!(format_thai_date("25670115"), "15 ม.ค. 2567");
+        assert_eq!(format_thai_date("2567/01/15"), "15 ม.ค. 2567");
+        assert_eq!(format_thai_date("2999/12/31"), "ตลอดชีพ");
+        assert_eq!(format_thai_date("garbage"), "garbage");
+    }
+
+    #[test]
+    fn test_format_thai_date_thai_numerals() {
+        assert_eq!(thai_digits("0123456789/ab"), "๐๑๒๓๔๕๖๗๘๙/ab");
+        assert_eq!(format_thai_date_thai_numerals("25670115"), "๑๕ ม.ค. พ.ศ. ๒๕๖๗");
+        assert_eq!(format_thai_date_thai_numerals("2533/12/31"), "๓๑ ธ.ค. พ.ศ. ๒๕๓๓");
+        assert_eq!(format_thai_date_thai_numerals("2999/12/31"), "ตลอดชีพ");
+        assert_eq!(format_thai_date_thai_numerals("2567/13/01"), "2567/13/01");
+    }
+
+    #[test]
+    fn test_output_date_formats() {
+        let data = card_with_dates();
+        let mut config = OutputConfig::default();
+
+        let raw = apply_output_config(&data, &config);
+        assert_eq!(raw["Birthday"], "2520/04/13");
+        assert_eq!(raw["Expire"], "2999/12/31");
+
+        config.date_format = DateFormat::Iso8601;
+        config.date_era = Era::Gregorian;
+        let iso = apply_output_config(&data, &config);
+        assert_eq!(iso["Birthday"], "1977-04-13");
+        assert_eq!(iso["Issue"], "2023-03-01");
+        assert_eq!(iso["Expire"], LIFETIME_ISO_DATE);
+
+        config.date_format = DateFormat::Thai;
+        let thai = apply_output_config(&data, &config);
+        assert_eq!(thai["Birthday"], "13 เม.ย. 2520");
+        assert_eq!(thai["Expire"], "ตลอดชีพ");
+    }
+
+    #[test]
+    fn test_malformed_date_passes_through() {
+        let data = ThaiIDData {
+            birthday: "2520/04/00".to_string(),
+            ..card_with_dates()
+        };
+        let config = OutputConfig {
+            date_format: DateFormat::Iso8601,
+            ..OutputConfig::default()
+        };
+        assert_eq!(apply_output_config(&data, &config)["Birthday"], "2520/04/00");
+    }
+}
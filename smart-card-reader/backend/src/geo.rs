@@ -0,0 +1,155 @@
+//! Country allow-list for WebSocket clients (`security.geo_allow_countries`)
+//!
+//! Client IPs are resolved to an ISO 3166 country code with a MaxMind-format
+//! (`.mmdb`) database such as GeoLite2-Country.
+
+use crate::config::SecurityConfig;
+use std::net::IpAddr;
+
+/// Looks up the country of an IP address
+pub trait GeoResolver: Send + Sync {
+    /// ISO 3166 alpha-2 code, `None` when the address is not in the database
+    fn country(&self, ip: IpAddr) -> Option<String>;
+}
+
+/// Closures resolve too, e.g. a fixed table in tests
+impl<F: Fn(IpAddr) -> Option<String> + Send + Sync> GeoResolver for F {
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        self(ip)
+    }
+}
+
+/// [`GeoResolver`] backed by a MaxMind GeoIP2/GeoLite2 Country database
+pub struct MaxMindResolver {
+    reader: maxminddb::Reader<Vec<u8>>,
+}
+
+impl MaxMindResolver {
+    /// Load the database at `path` into memory
+    pub fn open(path: &str) -> Result<Self, maxminddb::MaxMindDBError> {
+        Ok(Self { reader: maxminddb::Reader::open_readfile(path)? })
+    }
+}
+
+impl GeoResolver for MaxMindResolver {
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        let record: maxminddb::geoip2::Country = self.reader.lookup(ip).ok()?;
+        // Fall back to the registering country for anonymous/satellite ranges
+        record
+            .country
+            .or(record.registered_country)
+            .and_then(|country| country.iso_code)
+            .map(str::to_string)
+    }
+}
+
+/// Outcome of [`GeoFilter::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeoVerdict {
+    /// Country is on the allow-list
+    Allowed,
+    /// Loopback or private-network client with no country, allowed
+    Local,
+    /// Country is not on the allow-list (`None` = could not be resolved)
+    Blocked(Option<String>),
+}
+
+/// Rejects clients from countries outside `security.geo_allow_countries`
+pub struct GeoFilter {
+    allowed: Vec<String>,
+    resolver: Box<dyn GeoResolver>,
+}
+
+impl GeoFilter {
+    /// Filter allowing `countries` (case-insensitive ISO codes)
+    pub fn new(countries: &[String], resolver: Box<dyn GeoResolver>) -> Self {
+        Self {
+            allowed: countries.iter().map(|c| c.trim().to_ascii_uppercase()).collect(),
+            resolver,
+        }
+    }
+
+    /// Build the filter from config; `None` when it is off or the database is
+    /// missing or unreadable (logged as a warning)
+    pub fn from_config(security: &SecurityConfig) -> Option<Self> {
+        if security.geo_allow_countries.is_empty() {
+            return None;
+        }
+        let Some(path) = security.geoip_database.as_deref() else {
+            log::warn!("⚠️ geo_allow_countries is set but geoip_database is not, country filtering is OFF");
+            return None;
+        };
+        match MaxMindResolver::open(path) {
+            Ok(resolver) => {
+                log::info!("🌍 Country filter ENABLED (allowed: {})", security.geo_allow_countries.join(", "));
+                Some(Self::new(&security.geo_allow_countries, Box::new(resolver)))
+            }
+            Err(e) => {
+                log::warn!("⚠️ Cannot read GeoIP database {}: {}, country filtering is OFF", path, e);
+                None
+            }
+        }
+    }
+
+    /// Decide whether `ip` may connect
+    pub fn check(&self, ip: IpAddr) -> GeoVerdict {
+        match self.resolver.country(ip) {
+            Some(country) if self.allowed.iter().any(|c| c.eq_ignore_ascii_case(&country)) => GeoVerdict::Allowed,
+            Some(country) => GeoVerdict::Blocked(Some(country)),
+            None if is_local(ip) => GeoVerdict::Local,
+            None => GeoVerdict::Blocked(None),
+        }
+    }
+}
+
+/// Addresses that never appear in a GeoIP database (same host or LAN)
+fn is_local(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local(),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            // Unique local fc00::/7 and link-local fe80::/10
+            v6.is_loopback() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_check_allowed_blocked_and_local() {
+        let table: HashMap<IpAddr, &str> = HashMap::from([
+            ("203.0.113.7".parse().unwrap(), "TH"),
+            ("198.51.100.9".parse().unwrap(), "US"),
+        ]);
+        let resolver = move |ip: IpAddr| table.get(&ip).map(|c| c.to_string());
+        let filter = GeoFilter::new(&["th".to_string()], Box::new(resolver));
+
+        assert_eq!(filter.check("203.0.113.7".parse().unwrap()), GeoVerdict::Allowed);
+        assert_eq!(filter.check("198.51.100.9".parse().unwrap()), GeoVerdict::Blocked(Some("US".to_string())));
+        // Unresolved public address is blocked, unresolved LAN address is not
+        assert_eq!(filter.check("192.0.2.1".parse().unwrap()), GeoVerdict::Blocked(None));
+        assert_eq!(filter.check("192.168.1.20".parse().unwrap()), GeoVerdict::Local);
+        assert_eq!(filter.check("::1".parse().unwrap()), GeoVerdict::Local);
+        assert_eq!(filter.check("fd00::1".parse().unwrap()), GeoVerdict::Local);
+    }
+
+    #[test]
+    fn test_from_config_skips_without_database() {
+        let mut security = SecurityConfig::default();
+        assert!(GeoFilter::from_config(&security).is_none());
+
+        security.geo_allow_countries = vec!["TH".to_string()];
+        assert!(GeoFilter::from_config(&security).is_none());
+
+        security.geoip_database = Some("/nonexistent/GeoLite2-Country.mmdb".to_string());
+        assert!(GeoFilter::from_config(&security).is_none());
+    }
+}
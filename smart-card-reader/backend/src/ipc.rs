@@ -0,0 +1,295 @@
+//! Local IPC output for card events
+//!
+//! Serves the same JSON as the WebSocket broadcast over a Unix domain socket
+//! (Linux/macOS) or a named pipe (Windows), one message per line (NDJSON).
+//! Intended for integrations on the same machine, e.g. a POS application.
+//! There is no client IP, so IP-based rate limiting does not apply.
+//! The same stream can be written to stdout with `--stdout-ndjson`.
+
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+
+use crate::audit_log::AuditLogger;
+use crate::decoder::{is_test_message, PiiMask};
+use crate::stats::AppStats;
+use crate::template::Template;
+
+/// Shared state for IPC client tasks
+#[derive(Clone)]
+pub struct IpcState {
+    pub tx: broadcast::Sender<String>,
+    pub audit_logger: Arc<AuditLogger>,
+    pub stats: Arc<AppStats>,
+    /// Set when `security.broadcast_masked` is on (IPC clients cannot reveal)
+    pub pii_mask: Option<PiiMask>,
+}
+
+/// Write broadcast messages to `writer` one per line, flushing each line
+///
+/// `/api/test-event` cards are skipped and PII is masked with `pii_mask`.
+/// Returns when the channel closes or a write fails (reader went away).
+pub async fn write_ndjson<W>(rx: broadcast::Receiver<String>, writer: W, pii_mask: Option<PiiMask>)
+where
+    W: AsyncWrite + Unpin,
+{
+    write_lines(rx, writer, None, pii_mask).await;
+}
+
+/// Like [`write_ndjson`], but with a `template` only card reads are written,
+/// one rendered line each
+pub async fn write_lines<W>(
+    mut rx: broadcast::Receiver<String>,
+    mut writer: W,
+    template: Option<Template>,
+    pii_mask: Option<PiiMask>,
+) where
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let msg = match rx.recv().await {
+            Ok(msg) => msg,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::warn!("NDJSON output lagged, skipped {} messages", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if is_test_event(&msg) {
+            continue;
+        }
+        let msg = match &pii_mask {
+            Some(mask) => mask.apply_str(&msg),
+            None => msg,
+        };
+        let msg = match &template {
+            Some(template) => match template.render_message(&msg) {
+                Some(line) => line,
+                None => continue,
+            },
+            None => msg,
+        };
+
+        let mut line = msg.into_bytes();
+        line.push(b'\n');
+        if writer.write_all(&line).await.is_err() || writer.flush().await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Whether `msg` is a development card from `/api/test-event`
+pub(crate) fn is_test_event(msg: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(msg).is_ok_and(|payload| is_test_message(&payload))
+}
+
+/// Forward broadcast messages to one IPC client until it disconnects
+async fn handle_client<W>(writer: W, state: IpcState)
+where
+    W: AsyncWrite + Unpin,
+{
+    let connection_start = std::time::Instant::now();
+    let rx = state.tx.subscribe();
+    state.stats.client_connected();
+    state.audit_logger.log_connection_open(None);
+
+    write_ndjson(rx, writer, state.pii_mask.clone()).await;
+
+    state.stats.client_disconnected();
+    let duration_ms = connection_start.elapsed().as_millis() as u64;
+    state
+        .audit_logger
+        .log_connection_close(None, Some(duration_ms), None);
+}
+
+/// Serve card events on a Unix domain socket at `path`
+///
+/// A stale socket left by a previous run is removed before binding; any other
+/// file at `path` is left alone and the bind fails. The socket is created
+/// owner-only (0600): clients get unmasked card data without authenticating,
+/// so file permissions are the only access control.
+///
+/// # Errors
+/// Returns error if `path` holds something other than a socket, or the socket
+/// cannot be bound
+#[cfg(unix)]
+pub async fn serve(path: &str, state: IpcState) -> std::io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket, refusing to replace it", path),
+            ))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    log::info!("🔌 IPC socket listening on {} (owner only)", path);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                log::debug!("IPC client connected");
+                tokio::spawn(handle_client(stream, state.clone()));
+            }
+            Err(e) => log::error!("IPC accept error: {}", e),
+        }
+    }
+}
+
+/// Serve card events on a Windows named pipe at `path` (e.g. `\\.\pipe\smart-card-reader`)
+///
+/// # Errors
+/// Returns error if the pipe cannot be created
+#[cfg(windows)]
+pub async fn serve(path: &str, state: IpcState) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(path)?;
+    log::info!("🔌 IPC named pipe listening on {}", path);
+
+    loop {
+        server.connect().await?;
+        let client = server;
+        // Create the next instance before handing off so clients never see "no pipe"
+        server = ServerOptions::new().create(path)?;
+        log::debug!("IPC client connected");
+        tokio::spawn(handle_client(client, state.clone()));
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::UnixStream;
+
+    #[tokio::test]
+    async fn test_ipc_client_receives_broadcast() {
+        let path = std::env::temp_dir().join(format!("scr-ipc-{}.sock", std::process::id()));
+        let path_str = path.to_str().unwrap().to_string();
+
+        let (tx, _rx) = broadcast::channel::<String>(16);
+        let state = IpcState {
+            tx: tx.clone(),
+            audit_logger: Arc::new(AuditLogger::new(false)),
+            stats: Arc::new(AppStats::new()),
+            pii_mask: None,
+        };
+        let stats = state.stats.clone();
+
+        let server = tokio::spawn({
+            let path_str = path_str.clone();
+            async move { serve(&path_str, state).await }
+        });
+
+        // Wait for the socket to be bound
+        let stream = loop {
+            if let Ok(stream) = UnixStream::connect(&path).await {
+                break stream;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+
+        // Wait for the client task to subscribe (plus the initial `_rx`)
+        while tx.receiver_count() < 2 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(stats.snapshot().clients, 1);
+
+        tx.send(r#"{"mode":"readsmartcard"}"#.to_string()).unwrap();
+
+        let mut lines = BufReader::new(stream).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+        assert_eq!(line, r#"{"mode":"readsmartcard"}"#);
+
+        // Only the owning user may connect
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_serve_refuses_to_replace_regular_file() {
+        let path = std::env::temp_dir().join(format!("scr-ipc-file-{}.sock", std::process::id()));
+        std::fs::write(&path, "keep me").unwrap();
+        let state = IpcState {
+            tx: broadcast::channel::<String>(1).0,
+            audit_logger: Arc::new(AuditLogger::new(false)),
+            stats: Arc::new(AppStats::new()),
+            pii_mask: None,
+        };
+
+        let err = serve(path.to_str().unwrap(), state).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_write_ndjson_one_line_per_message() {
+        let (tx, rx) = broadcast::channel::<String>(16);
+        let (writer, reader) = tokio::io::duplex(1024);
+        let task = tokio::spawn(write_ndjson(rx, writer, None));
+
+        tx.send(r#"{"mode":"readsmartcard","Citizenid":"1101700230708","test":true}"#.to_string()).unwrap();
+        tx.send(r#"{"mode":"readsmartcard","Citizenid":"1234567890121"}"#.to_string()).unwrap();
+        drop(tx);
+        task.await.unwrap();
+
+        // The test card was skipped
+        let mut lines = BufReader::new(reader).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["Citizenid"], "1234567890121");
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_lines_with_template() {
+        use crate::template::TemplateEscape;
+
+        let (tx, rx) = broadcast::channel::<String>(16);
+        let (writer, reader) = tokio::io::duplex(1024);
+        let template = Template::parse("{Citizenid},{Th_Firstname}", TemplateEscape::Csv);
+        let task = tokio::spawn(write_lines(rx, writer, Some(template), None));
+
+        tx.send(r#"{"mode":"readsmartcard","Citizenid":"1234567890121","Th_Firstname":"สมชาย"}"#.to_string()).unwrap();
+        tx.send(r#"{"mode":"removedsmartcard"}"#.to_string()).unwrap();
+        drop(tx);
+        task.await.unwrap();
+
+        let mut lines = BufReader::new(reader).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "1234567890121,สมชาย");
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_ndjson_masks_pii() {
+        let (tx, rx) = broadcast::channel::<String>(16);
+        let (writer, reader) = tokio::io::duplex(1024);
+        let mask = PiiMask::new(&crate::config::OutputConfig::default());
+        let task = tokio::spawn(write_ndjson(rx, writer, Some(mask)));
+
+        tx.send(r#"{"mode":"readsmartcard","Citizenid":"1234567890121","addrProvince":"กรุงเทพมหานคร"}"#.to_string()).unwrap();
+        drop(tx);
+        task.await.unwrap();
+
+        let line = BufReader::new(reader).lines().next_line().await.unwrap().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["Citizenid"], crate::decoder::mask_citizen_id("1234567890121"));
+        assert_eq!(value["addrProvince"], "*".repeat("กรุงเทพมหานคร".chars().count()));
+    }
+}
@@ -0,0 +1,182 @@
+//! On-disk cache of the most recent card read
+//!
+//! Lets the desktop UI restore its last history entry after a restart. The
+//! masked summary is always stored; the full record is only written when a
+//! `CryptoService` is available, and then only as AES-256-GCM ciphertext.
+
+use crate::config::UiConfig;
+use crate::crypto::CryptoService;
+use crate::decoder::ThaiIDData;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Serialized form of the cached read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedRead {
+    /// Local time of the read (HH:MM:SS)
+    pub time: String,
+    /// Masked one-line summary as shown in the history panel
+    pub summary: String,
+    /// Base64 ciphertext of the full `ThaiIDData` JSON, if encryption is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_data: Option<String>,
+}
+
+/// A cached read restored from disk
+#[derive(Debug, Clone)]
+pub struct RestoredRead {
+    pub time: String,
+    pub summary: String,
+    /// Full record, present only if it was cached encrypted and decrypts with the current key
+    pub data: Option<ThaiIDData>,
+}
+
+/// File-backed cache of the last read
+pub struct LastReadCache {
+    path: PathBuf,
+    crypto: Option<Arc<CryptoService>>,
+}
+
+impl LastReadCache {
+    pub fn new(path: impl Into<PathBuf>, crypto: Option<Arc<CryptoService>>) -> Self {
+        Self {
+            path: path.into(),
+            crypto,
+        }
+    }
+
+    /// Cache for the UI settings, or `None` when `ui.persist_last_read` is off
+    pub fn from_config(config: &UiConfig, crypto: Option<Arc<CryptoService>>) -> Option<Self> {
+        config
+            .persist_last_read
+            .then(|| Self::new(&config.last_read_path, crypto))
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether full records are cached (encrypted) rather than summaries only
+    #[must_use]
+    pub fn stores_full_data(&self) -> bool {
+        self.crypto.is_some()
+    }
+
+    /// Build the cache record for a read; full data is included only as ciphertext
+    pub fn encode(&self, time: &str, summary: &str, data: &ThaiIDData) -> anyhow::Result<CachedRead> {
+        let encrypted_data = match &self.crypto {
+            Some(crypto) => Some(crypto.encrypt_to_base64(&serde_json::to_string(data)?)?),
+            None => None,
+        };
+        Ok(CachedRead {
+            time: time.to_string(),
+            summary: summary.to_string(),
+            encrypted_data,
+        })
+    }
+
+    /// Write a read to disk, replacing any previous entry
+    pub fn save(&self, time: &str, summary: &str, data: &ThaiIDData) -> anyhow::Result<()> {
+        let record = self.encode(time, summary, data)?;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename so a crash never leaves a truncated cache behind
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&record)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Load the cached read; `Ok(None)` if there is no cache file
+    pub fn load(&self) -> anyhow::Result<Option<RestoredRead>> {
+        let bytes = match std::fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let record: CachedRead = serde_json::from_slice(&bytes)?;
+        let data = match (&self.crypto, &record.encrypted_data) {
+            (Some(crypto), Some(encrypted)) => match crypto.decrypt_from_base64(encrypted) {
+                Ok(json) => Some(serde_json::from_str(&json)?),
+                Err(e) => {
+                    log::warn!("⚠️ Cached last read could not be decrypted (key changed?): {}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        Ok(Some(RestoredRead {
+            time: record.time,
+            summary: record.summary,
+            data,
+        }))
+    }
+
+    /// Delete the cache file if it exists
+    pub fn clear(&self) -> anyhow::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("scr-last-read-{}-{}.json", std::process::id(), name))
+    }
+
+    fn sample() -> ThaiIDData {
+        ThaiIDData {
+            citizen_id: "3100600123456".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            ..ThaiIDData::default()
+        }
+    }
+
+    #[test]
+    fn test_summary_only_without_crypto() {
+        let cache = LastReadCache::new(temp_path("plain"), None);
+        let record = cache.encode("10:00:00", "10:00:00 *********3456", &sample()).unwrap();
+        assert!(record.encrypted_data.is_none());
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(!json.contains("3100600123456"));
+        assert!(!json.contains("encrypted_data"));
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let crypto = Arc::new(CryptoService::from_base64_key(&crate::crypto::generate_key()).unwrap());
+        let path = temp_path("encrypted");
+        let cache = LastReadCache::new(&path, Some(crypto));
+        cache.save("10:00:00", "masked", &sample()).unwrap();
+
+        let on_disk = std::fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains("3100600123456"));
+
+        let restored = cache.load().unwrap().unwrap();
+        assert_eq!(restored.time, "10:00:00");
+        assert_eq!(restored.summary, "masked");
+        assert_eq!(restored.data.unwrap().citizen_id, "3100600123456");
+
+        cache.clear().unwrap();
+        assert!(!path.exists());
+        assert!(cache.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        assert!(LastReadCache::from_config(&UiConfig::default(), None).is_none());
+    }
+}
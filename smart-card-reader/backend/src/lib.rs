@@ -0,0 +1,115 @@
+//! Thai national ID card reading, decoding and validation
+//!
+//! The stable API is [`decoder`] (TIS-620 decoding and [`ThaiIDData`]),
+//! [`validation`], [`crypto`], [`config`] and the `CardReader` facade.
+//! The other modules back the bundled `smart-card-reader` binary and may
+//! change between releases.
+//!
+//! # Features
+//! - `reader` (default): PC/SC card access (`CardReader`)
+//! - `server` (default): WebSocket/HTTP server and TLS (axum)
+//! - `ui` (default): desktop window (egui)
+//! - `embedded-font` (default): Thai font built into the binary (implies `ui`)
+//! - `sound`: beep on successful read (implies `ui`)
+//!
+//! Build with `default-features = false` to use the decoding without them.
+//! The binary adapts to what is enabled: `reader` alone gives a CLI that
+//! prints one JSON line per card event, `server` runs without a window
+//! unless `ui` is also on.
+//!
+//! # Example
+//!
+//! ```
+//! use smart_card_reader::{assemble_thai_id, RawCardFields};
+//! use smart_card_reader::validation::ThaiCitizenIdValidator;
+//!
+//! // Card fields are TIS-620 encoded ('#' separates name parts)
+//! let tis620 = |s: &str| encoding_rs::WINDOWS_874.encode(s).0.into_owned();
+//! let data = assemble_thai_id(RawCardFields {
+//!     citizen_id: tis620("1101700230708"),
+//!     full_name_th: tis620("นาย#สมชาย##ใจดี"),
+//!     full_name_en: tis620("Mr.#Somchai##Jaidee"),
+//!     date_of_birth: tis620("25330115"),
+//!     ..RawCardFields::default()
+//! });
+//!
+//! assert_eq!(data.th_firstname, "สมชาย");
+//! assert_eq!(data.en_lastname, "Jaidee");
+//! assert_eq!(data.birthday, "2533/01/15");
+//! assert!(ThaiCitizenIdValidator::check(&data.citizen_id).is_ok());
+//! ```
+
+pub mod config;
+pub mod crypto;
+pub mod decoder;
+#[cfg(feature = "reader")]
+pub mod reader;
+pub mod validation;
+
+#[doc(hidden)]
+pub mod address;
+#[doc(hidden)]
+pub mod audit_log;
+#[doc(hidden)]
+pub mod benchmark;
+#[doc(hidden)]
+pub mod build_info;
+#[doc(hidden)]
+pub mod ipc;
+#[doc(hidden)]
+pub mod last_read;
+#[doc(hidden)]
+pub mod logging;
+#[doc(hidden)]
+pub mod rate_limiter;
+#[doc(hidden)]
+pub mod self_test;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod template;
+
+#[cfg(feature = "server")]
+#[doc(hidden)]
+pub mod geo;
+#[cfg(feature = "server")]
+#[doc(hidden)]
+pub mod server;
+#[cfg(feature = "server")]
+#[doc(hidden)]
+pub mod tls;
+#[cfg(feature = "server")]
+#[doc(hidden)]
+pub mod webhook;
+#[cfg(feature = "ui")]
+#[doc(hidden)]
+pub mod ui;
+
+pub use decoder::{assemble_thai_id, CardEvent, RawCardFields, ThaiIDData};
+#[cfg(feature = "reader")]
+pub use reader::{CardReader, ContextError, ReaderError};
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    /// Feature sets CI builds besides the default one
+    const FEATURE_COMBINATIONS: &[&str] = &["", "reader", "server", "ui", "reader,ui", "reader,server", "server,ui", "reader,server,ui", "server,embedded-font"];
+
+    /// Compiles the crate under each feature set (slow, needs the PCSC and
+    /// windowing system libraries): `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_feature_combinations_compile() {
+        for features in FEATURE_COMBINATIONS {
+            let status = std::process::Command::new(env!("CARGO"))
+                .args(["check", "--all-targets", "--no-default-features", "--features", features])
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .status()
+                .expect("failed to run cargo");
+            assert!(status.success(), "build failed with features [{}]", features);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Application logger setup
+//!
+//! Human-readable text by default, or one JSON object per line for log
+//! aggregators. This is separate from the audit log.
+
+use crate::config::{LogFormat, LoggingConfig};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+type BoxedSubscriber = Box<dyn tracing::Subscriber + Send + Sync>;
+
+/// Build the application subscriber writing to `writer`
+///
+/// `RUST_LOG` takes precedence over `config.level` when set.
+pub fn subscriber<W>(config: &LoggingConfig, writer: W) -> BoxedSubscriber
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(writer);
+    match config.format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().flatten_event(true).finish()),
+    }
+}
+
+/// Install the application logger on stderr
+///
+/// Records from the `log` facade are bridged into the same output.
+pub fn init(config: &LoggingConfig) {
+    subscriber(config, std::io::stderr).init();
+}
+
+/// `MakeWriter` collecting formatted log output for assertions in tests
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedLogs {
+    /// Everything written so far
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_format_emits_json_lines() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let config = LoggingConfig {
+            level: "info".to_string(),
+            format: LogFormat::Json,
+        };
+
+        tracing::subscriber::with_default(subscriber(&config, move || writer.clone()), || {
+            tracing::info!(reader = "Reader 00", "✅ Card read in {}ms", 42);
+            tracing::warn!("second line");
+        });
+
+        let output = logs.contents();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each log line is JSON"))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["message"], "✅ Card read in 42ms");
+        assert_eq!(lines[0]["reader"], "Reader 00");
+        assert!(lines[0]["timestamp"].is_string());
+        assert!(lines[0]["target"].is_string());
+        assert_eq!(lines[1]["level"], "WARN");
+    }
+}
@@ -1,72 +1,339 @@
-mod audit_log;
-mod config;
-mod crypto;
-mod decoder;
-mod rate_limiter;
-mod reader;
-mod server;
-mod ui;
-mod validation;
-
-use axum::{routing::get, Router};
+use smart_card_reader::{build_info, config, crypto, logging, self_test, validation};
+#[cfg(feature = "reader")]
+use smart_card_reader::{address, reader};
+#[cfg(feature = "server")]
+use smart_card_reader::{audit_log, decoder, geo, ipc, rate_limiter, server, stats, tls, webhook};
+#[cfg(all(feature = "ui", feature = "server"))]
+use smart_card_reader::{last_read, ui};
+
 use log::info;
-use serde_json::json;
+#[cfg(feature = "server")]
 use std::sync::Arc;
+#[cfg(feature = "server")]
 use tokio::sync::broadcast;
 
-// TLS/SSL imports (only axum_server needed for RustlsConfig)
+fn main() {
+    // Print build metadata for support
+    if std::env::args().skip(1).any(|arg| arg == "--version" || arg == "-V") {
+        println!("{}", build_info::BuildInfo::current());
+        return;
+    }
+
+    // Print a fresh ENCRYPTION_KEY (no reader or config needed)
+    if std::env::args().skip(1).any(|arg| arg == "--generate-key") {
+        println!("{}", crypto::generate_key());
+        return;
+    }
 
-/// Load TLS configuration from certificate and key files
-///
-/// # Returns
-/// Returns `axum_server::tls_rustls::RustlsConfig` configured with the provided certificates
+    // Verify a citizen ID checksum (no reader or config needed)
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--check-id") {
+        let Some(id) = args.get(pos + 1) else {
+            eprintln!("Usage: smart-card-reader --check-id <13 digits>");
+            std::process::exit(2);
+        };
+        match validation::ThaiCitizenIdValidator::check(id) {
+            Ok(message) => println!("{}", message),
+            Err(message) => {
+                println!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Hardware-free decode pipeline check
+    if std::env::args().skip(1).any(|arg| arg == "--self-test") {
+        let report = self_test::run(&self_test::SelfTestFixture::known_good());
+        report.print();
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    // PC/SC environment snapshot for support tickets
+    #[cfg(feature = "reader")]
+    if args.iter().any(|arg| arg == "--diagnostics") {
+        println!("{}", build_info::BuildInfo::current());
+        println!("{}", reader::diagnostics());
+        return;
+    }
+
+    // Raw APDU probe for onboarding new readers/cards (debugging only)
+    #[cfg(feature = "reader")]
+    if args.iter().any(|arg| arg == "--apdu") {
+        std::process::exit(run_apdu_probe(&args));
+    }
+
+    // Read latency of the inserted card, for SLA checks and tuning
+    #[cfg(feature = "reader")]
+    if let Some(pos) = args.iter().position(|arg| arg == "--benchmark") {
+        std::process::exit(run_benchmark(args.get(pos + 1).map(String::as_str)));
+    }
+
+    // Load configuration first (before logger init)
+    let app_config = config::load();
+
+    // Initialize logger with configured level and format (RUST_LOG still wins when set)
+    logging::init(&app_config.logging);
+
+    info!("Starting Smart Card Reader Service {}...", build_info::BuildInfo::current());
+    info!("Config: server={}", app_config.server);
+
+    run(app_config);
+}
+
+/// Card config with the configured profile applied
 ///
 /// # Errors
-/// Returns error if certificate or key files cannot be read or parsed
-async fn load_tls_config(cert_path: &str, key_path: &str) -> anyhow::Result<axum_server::tls_rustls::RustlsConfig> {
-    // Use axum_server's RustlsConfig for easier integration
-    let config = axum_server::tls_rustls::RustlsConfig::from_pem_file(cert_path, key_path)
+/// Returns `ConfigError` for an unknown or unreadable profile, or card settings
+/// that fail [`config::CardConfig::validate`]
+#[cfg(feature = "reader")]
+fn resolve_card_config(app_config: &config::AppConfig) -> Result<config::CardConfig, config::ConfigError> {
+    let card_config = app_config.card.resolve_profile()?;
+    card_config.validate()?;
+    if let Some(profile) = &card_config.profile {
+        info!("Card profile: {}", profile);
+    }
+    Ok(card_config)
+}
+
+/// [`resolve_card_config`], exiting on an invalid card config
+#[cfg(feature = "reader")]
+fn resolve_card_config_or_exit(app_config: &config::AppConfig) -> config::CardConfig {
+    resolve_card_config(app_config).unwrap_or_else(|e| {
+        log::error!("❌ {}", e);
+        std::process::exit(2);
+    })
+}
+
+/// Open the reader, or wait for PCSC in the background if it is not up yet
+///
+/// Context retries sleep between attempts, so they run on a blocking thread.
+#[cfg(feature = "reader")]
+async fn open_card_reader(card_config: config::CardConfig) -> reader::CardReader {
+    tokio::task::spawn_blocking(move || open_card_reader_blocking(card_config))
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to load TLS config: {}", e))?;
+        .expect("card reader setup panicked")
+}
+
+#[cfg(feature = "reader")]
+fn open_card_reader_blocking(card_config: config::CardConfig) -> reader::CardReader {
+    let address_reference = card_config.validate_address.then(|| {
+        match card_config.address_reference_path.as_deref() {
+            Some(path) => address::AddressReference::load(path).unwrap_or_else(|e| {
+                log::warn!("⚠️ {:#}; falling back to bundled province list", e);
+                address::AddressReference::bundled()
+            }),
+            None => address::AddressReference::bundled(),
+        }
+    });
 
-    Ok(config)
+    let card_reader = match reader::CardReader::new(card_config.clone()) {
+        Ok(card_reader) => card_reader,
+        Err(e) => {
+            log::warn!("⚠️ {:#}. Waiting for it in the background...", e);
+            reader::CardReader::deferred(card_config)
+        }
+    };
+    match address_reference {
+        Some(reference) => card_reader.with_address_reference(reference),
+        None => card_reader,
+    }
 }
 
-fn main() {
-    // Load configuration first (before logger init)
+/// Hex value following `flag`, parsed as an APDU
+#[cfg(feature = "reader")]
+fn apdu_arg(args: &[String], flag: &str) -> Result<Option<Vec<u8>>, String> {
+    let Some(pos) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    let hex = args.get(pos + 1).ok_or_else(|| format!("{} needs a hex value", flag))?;
+    reader::parse_apdu_hex(hex).map(Some).map_err(|e| format!("{}: {}", flag, e))
+}
+
+/// `--apdu <hex> [--select <hex>]`: send one APDU to the first card and print the answer
+#[cfg(feature = "reader")]
+fn run_apdu_probe(args: &[String]) -> i32 {
+    if !config::dev_mode_enabled() {
+        eprintln!("--apdu is a debugging tool that sends raw commands to the card; set {}=1 to use it", config::DEV_MODE_ENV_VAR);
+        return 2;
+    }
+    let (apdu, select) = match (apdu_arg(args, "--apdu"), apdu_arg(args, "--select")) {
+        (Ok(Some(apdu)), Ok(select)) => (apdu, select),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: smart-card-reader --apdu <hex> [--select <hex>]");
+            return 2;
+        }
+        (Ok(None), _) => unreachable!("--apdu is present"),
+    };
+
     let app_config = config::load();
+    logging::init(&app_config.logging);
+    log::warn!("🛠️ APDU probe (debugging only): commands are sent to the card as given");
+
+    let card_config = match resolve_card_config(&app_config) {
+        Ok(card_config) => card_config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return 2;
+        }
+    };
+    let card_reader = match reader::CardReader::new(card_config) {
+        Ok(card_reader) => card_reader,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return 1;
+        }
+    };
+    match card_reader.probe_first_card(select.as_deref(), &apdu) {
+        Ok((reader_name, probe)) => {
+            println!("Reader: {}", reader_name);
+            println!("{}", probe);
+            i32::from(!probe.is_success())
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            1
+        }
+    }
+}
 
-    // Initialize logger with configured level
-    std::env::set_var("RUST_LOG", &app_config.logging.level);
-    env_logger::init();
+/// `--benchmark <n>`: read the inserted card `n` times and print per-stage latency
+#[cfg(feature = "reader")]
+fn run_benchmark(reads: Option<&str>) -> i32 {
+    let Some(reads) = reads.and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0) else {
+        eprintln!("Usage: smart-card-reader --benchmark <n>");
+        return 2;
+    };
 
-    info!("Starting Smart Card Reader Service...");
-    info!("Config: server={}", app_config.server);
+    let app_config = config::load();
+    logging::init(&app_config.logging);
+
+    let card_config = match resolve_card_config(&app_config) {
+        Ok(card_config) => card_config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return 2;
+        }
+    };
+    let card_reader = match reader::CardReader::new(card_config) {
+        Ok(card_reader) => card_reader.with_photo(app_config.output.photo_enabled()),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return 1;
+        }
+    };
+    match card_reader.benchmark_first_card(reads) {
+        Ok(report) => {
+            print!("{}", report);
+            i32::from(report.failures > 0)
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            1
+        }
+    }
+}
 
+/// CLI reader (built without `server`): one JSON line per card event on stdout
+#[cfg(all(feature = "reader", not(feature = "server")))]
+fn run(app_config: config::AppConfig) {
+    let card_config = resolve_card_config_or_exit(&app_config);
+    let output_config = app_config.output.clone();
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    rt.block_on(async {
+        let mut card_reader = open_card_reader(card_config).await.with_photo(output_config.photo_enabled());
+        let monitor = card_reader.run_monitor(move |event| {
+            let message = smart_card_reader::decoder::event_json(&event, &output_config);
+            println!("{}", smart_card_reader::decoder::build_broadcast_message(message, output_config.envelope));
+        });
+
+        // Release PCSC resources deterministically on Ctrl-C
+        tokio::select! {
+            () = monitor => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Ctrl-C received, releasing card reader...");
+                card_reader.disconnect();
+            }
+        }
+    });
+}
+
+/// Built with neither `reader` nor `server`: only the one-shot flags work
+#[cfg(not(any(feature = "reader", feature = "server")))]
+fn run(_app_config: config::AppConfig) {
+    eprintln!("Built without the `reader` and `server` features; only --version, --generate-key, --check-id and --self-test are available");
+    std::process::exit(2);
+}
+
+/// WebSocket service, with the card monitor (`reader`) and window (`ui`) when enabled
+#[cfg(feature = "server")]
+fn run(app_config: config::AppConfig) {
     // Channel for UI updates (card events)
+    #[cfg(feature = "ui")]
     let (tx_ui, rx_ui) = std::sync::mpsc::channel::<decoder::CardEvent>();
+    // Channel mirroring the exact broadcast JSON for the UI raw payload view
+    #[cfg(feature = "ui")]
+    let (tx_payload, rx_payload) = std::sync::mpsc::channel::<String>();
+    // Nothing feeds the window without a reader
+    #[cfg(all(feature = "ui", not(feature = "reader")))]
+    drop((tx_ui, tx_payload));
+
+    // Activity counters shown in the UI status line
+    let app_stats = Arc::new(stats::AppStats::new());
+    #[cfg(feature = "ui")]
+    let ui_stats = app_stats.clone();
 
     // Clone config for background thread
     let server_config = app_config.server.clone();
     let output_config = app_config.output.clone();
-    let card_config = app_config.card.clone();
+    let security_config = app_config.security.clone();
+    let stdout_ndjson = std::env::args().skip(1).any(|arg| arg == "--stdout-ndjson");
+
+    // rustls needs a crypto provider before the webhook client or TLS is set up
+    tls::install_crypto_provider();
+
+    // Every disabled protection in one place, with the settings that fix it
+    security_config.security_posture(&server_config).log_summary();
+
+    // Never expose unauthenticated card data to the network by accident
+    if let Some(reason) = server_config.public_bind_refusal(&security_config) {
+        if std::env::args().skip(1).any(|arg| arg == "--i-know-this-is-insecure") {
+            log::warn!("⚠️ Listening on {} although {} (--i-know-this-is-insecure)", server_config.host, reason);
+        } else {
+            log::error!("❌ Refusing to listen on {}: {}", server_config.host, reason);
+            log::error!("   Enable security.enable_authentication, bind to 127.0.0.1, or set server.allow_insecure_public_bind = true");
+            std::process::exit(2);
+        }
+    }
+    #[cfg(feature = "reader")]
+    let card_config = resolve_card_config_or_exit(&app_config);
+
+    // Initialize audit logger (outside the server thread so it can be flushed on exit)
+    let mut audit_logger = audit_log::AuditLogger::new(app_config.security.enable_audit_logging)
+        .with_min_severity(app_config.security.audit_min_severity);
+    if let Some(path) = &app_config.security.audit_log_path {
+        audit_logger = audit_logger.with_file(path);
+    }
+    let audit_logger = Arc::new(audit_logger);
+    let exit_audit_logger = audit_logger.clone();
 
     // Background thread for card reader + WebSocket server
-    std::thread::spawn(move || {
+    let service = std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
         rt.block_on(async {
             // Channel for broadcasting card data to WebSockets
-            let (tx_ws, _rx) = broadcast::channel::<String>(100);
+            let (tx_ws, _rx) = broadcast::channel::<String>(server_config.broadcast_capacity.max(1));
 
             // Spawn WebSocket server
-            let security_config = app_config.security.clone();
-
             // Initialize rate limiter if enabled
             let rate_limiter = if security_config.enable_rate_limiting {
                 let config = rate_limiter::RateLimitConfig {
                     max_requests: security_config.rate_limit_requests,
                     window: std::time::Duration::from_secs(security_config.rate_limit_window_secs),
                     max_connections: security_config.rate_limit_max_connections,
+                    ipv6_prefix_len: security_config.rate_limit_ipv6_prefix,
                 };
                 info!("🚦 Rate limiting ENABLED:");
                 info!("   Max requests: {} per {} seconds", config.max_requests, config.window.as_secs());
@@ -81,250 +348,349 @@ fn main() {
                         interval.tick().await;
                         limiter_clone.cleanup(std::time::Duration::from_secs(600)); // cleanup entries older than 10 minutes
                         let stats = limiter_clone.get_stats();
-                        log::debug!("🚦 Rate limiter stats: {} tracked IPs, {} active connections",
-                            stats.tracked_ips, stats.total_active_connections);
+                        log::debug!("🚦 Rate limiter stats: {} tracked IPs, {} active connections, {} throttled",
+                            stats.tracked_ips, stats.total_active_connections, stats.throttled_ips);
                     }
                 });
 
                 Some(limiter)
             } else {
-                log::warn!("⚠️ Rate limiting DISABLED - Vulnerable to DoS attacks!");
                 None
             };
 
-            // Initialize audit logger
-            let audit_logger = Arc::new(audit_log::AuditLogger::new(
-                security_config.enable_audit_logging
-            ));
-
-            let app_state = Arc::new(server::AppState {
-                tx: tx_ws.clone(),
-                security: security_config.clone(),
-                rate_limiter,
-                audit_logger: audit_logger.clone(),
-            });
-
-            // Log security status
-            if security_config.enable_authentication {
-                let key_count = security_config.get_api_keys().len();
-                if key_count > 0 {
-                    info!("🔐 WebSocket authentication ENABLED ({} API keys configured)", key_count);
-                } else {
-                    log::error!("❌ Authentication enabled but NO API keys configured!");
-                }
-            } else {
-                log::warn!("⚠️ WebSocket authentication DISABLED - Anyone can connect!");
-            }
-
             // Initialize encryption service if enabled
             let crypto_service = if security_config.enable_encryption {
                 match crypto::CryptoService::from_env() {
                     Ok(service) => {
+                        let service = service.with_nonce_mode(security_config.nonce_mode);
                         let field_count = security_config.encrypted_fields.len();
-                        info!("🔒 PII encryption ENABLED ({} fields protected)", field_count);
+                        info!("🔒 PII encryption ENABLED ({} fields protected, {} nonces)", field_count, security_config.nonce_mode);
                         info!("   Encrypted fields: {:?}", security_config.encrypted_fields);
                         Some(Arc::new(service))
                     }
                     Err(e) => {
                         log::error!("❌ Encryption enabled but failed to initialize: {}", e);
                         log::error!("   Set ENCRYPTION_KEY environment variable:");
-                        log::error!("   export ENCRYPTION_KEY=$(openssl rand -base64 32)");
+                        log::error!("   export ENCRYPTION_KEY=$(smart-card-reader --generate-key)");
                         panic!("Encryption configuration error");
                     }
                 }
             } else {
-                log::warn!("⚠️ PII encryption DISABLED - Sensitive data transmitted in plaintext!");
                 None
             };
 
-            use tower_http::cors::{Any, CorsLayer};
+            // Masking applies to every output; only WebSocket clients can reveal
+            let pii_mask = security_config
+                .broadcast_masked
+                .then(|| decoder::PiiMask::new(&output_config));
 
-            // Configure CORS based on settings
-            let cors_layer = if server_config.cors_allow_all {
-                log::warn!("⚠️ CORS allow_all is ENABLED - This is INSECURE for production!");
-                CorsLayer::new()
-                    .allow_origin(Any)
-                    .allow_methods(Any)
-                    .allow_headers(Any)
-            } else {
-                let allowed_origins = server_config.get_allowed_origins();
-                if allowed_origins.is_empty() {
-                    log::error!("❌ CORS restricted mode enabled but NO allowed origins configured!");
-                    log::error!("   Set ALLOWED_ORIGINS env var or add to config.toml");
+            let app_state = Arc::new(server::AppState {
+                tx: tx_ws.clone(),
+                seq: std::sync::atomic::AtomicU64::new(0),
+                lag_policy: server_config.lag_policy,
+                security: security_config.clone(),
+                rate_limiter,
+                audit_logger: audit_logger.clone(),
+                crypto: crypto_service.clone(),
+                stats: app_stats.clone(),
+                pii_mask: pii_mask.clone(),
+                active_connections: std::sync::atomic::AtomicU32::new(0),
+                output: output_config.clone(),
+                max_message_bytes: server_config.max_message_bytes,
+                delta_mode: server_config.delta_mode,
+                last_card: parking_lot::Mutex::new(None),
+                card_frame: parking_lot::Mutex::new(None),
+                last_read: parking_lot::Mutex::new(None),
+                security_status: server::SecurityStatus::new(&server_config, &security_config),
+                stuck_card: parking_lot::Mutex::new(server::StuckCardDetector::new(&server_config)),
+                geo_filter: geo::GeoFilter::from_config(&security_config),
+                diagnostics: tokio::sync::Mutex::new(None),
+            });
+
+            if security_config.broadcast_masked {
+                info!("🙈 Broadcasting masked PII ({} reveal keys configured)", security_config.reveal_keys.len());
+            }
+
+            // Log security status
+            if security_config.enable_authentication {
+                let key_count = security_config.get_api_keys().len();
+                if key_count > 0 {
+                    info!("🔐 WebSocket authentication ENABLED ({} API keys configured)", key_count);
                 } else {
-                    log::info!("✓ CORS restricted to allowed origins: {:?}", allowed_origins);
+                    log::error!("❌ Authentication enabled but NO API keys configured!");
                 }
+            }
 
-                let origins: Vec<_> = allowed_origins
-                    .iter()
-                    .filter_map(|origin| origin.parse().ok())
-                    .collect();
-
-                CorsLayer::new()
-                    .allow_origin(origins)
-                    .allow_methods([axum::http::Method::GET])
-                    .allow_headers([
-                        axum::http::header::CONTENT_TYPE,
-                        axum::http::header::AUTHORIZATION,
-                    ])
-            };
+            let enable_test_events = server_config.test_events_enabled();
+            if enable_test_events {
+                log::warn!("🧪 /api/test-event is ENABLED - Anyone with an API key can broadcast fake card reads!");
+                log::warn!("   Development only: unset {} before deploying to production", config::DEV_MODE_ENV_VAR);
+                if security_config.get_api_keys().is_empty() {
+                    log::error!("❌ /api/test-event enabled but NO API keys configured - all requests will be rejected");
+                }
+            }
+
+            // Configure CORS based on settings
+            let cors_layer = server::cors_layer(
+                &server_config,
+                &security_config.api_key_header,
+                security_config.enable_decrypt_endpoint || enable_test_events,
+            );
+
+            if security_config.enable_decrypt_endpoint {
+                log::warn!("⚠️ /api/decrypt debugging endpoint is ENABLED - Encrypted PII can be decrypted over HTTP!");
+                log::warn!("   Disable security.enable_decrypt_endpoint before deploying to production");
+                if security_config.get_api_keys().is_empty() {
+                    log::error!("❌ /api/decrypt enabled but NO API keys configured - all requests will be rejected");
+                }
+            }
 
-            let app = Router::new()
-                .route("/", get(server::ws_handler))
-                .with_state(app_state)
+            let ws_path = server_config.websocket_path();
+            info!("WebSocket path: {}", ws_path);
+            let app = server::router(&ws_path, security_config.enable_decrypt_endpoint, enable_test_events)
+                .with_state(app_state.clone())
                 .layer(cors_layer);
 
             let addr = server_config.socket_addr();
 
-            // Start server with or without TLS
-            if server_config.enable_tls {
-                info!("🔒 Starting HTTPS WebSocket server (wss://) on {addr}");
+            // Bind up front, retrying while the port is still held (e.g. TIME_WAIT
+            // after a kiosk reboot); card reading goes on without the server
+            let listener = match server::bind_with_retry(&server_config).await {
+                Ok(listener) => Some(listener),
+                Err(e) => {
+                    log::error!("❌ Failed to bind WebSocket server on {addr}: {e}");
+                    log::error!("   Card reading continues, but clients cannot connect");
+                    app_stats.set_server_error(e.to_string());
+                    None
+                }
+            };
 
-                // Load TLS configuration
-                let tls_config = match load_tls_config(&server_config.tls_cert_path, &server_config.tls_key_path).await {
-                    Ok(config) => config,
-                    Err(e) => {
-                        log::error!("❌ Failed to load TLS config: {}", e);
-                        log::error!("   Cert: {}", server_config.tls_cert_path);
-                        log::error!("   Key: {}", server_config.tls_key_path);
-                        panic!("TLS configuration error");
+            // Start server with or without TLS
+            if let Some(listener) = listener {
+                if server_config.enable_tls {
+                    info!("🔒 Starting HTTPS WebSocket server (wss://) on {addr}");
+
+                    // Load TLS configuration (retried to ride out cert rotation)
+                    let tls_format = server_config.resolved_tls_format();
+                    info!("   Certificate format: {}", tls_format);
+                    let tls_config = match tls::load_tls_config(
+                        tls_format,
+                        &server_config.tls_cert_path,
+                        &server_config.tls_key_path,
+                        server_config.tls_load_retries,
+                        std::time::Duration::from_millis(server_config.tls_retry_delay_ms),
+                    )
+                    .await
+                    {
+                        Ok(config) => config,
+                        Err(e) => {
+                            log::error!("❌ Failed to load TLS config: {}", e);
+                            log::error!("   Cert: {}", server_config.tls_cert_path);
+                            log::error!("   Key: {}", server_config.tls_key_path);
+                            panic!("TLS configuration error");
+                        }
+                    };
+
+                    // Hot-reload rotated certificates without restarting
+                    if server_config.tls_reload_interval_secs > 0 {
+                        info!("🔄 Watching TLS certificate for changes every {}s", server_config.tls_reload_interval_secs);
+                        tokio::spawn(tls::watch_and_reload(
+                            tls_config.clone(),
+                            tls_format,
+                            server_config.tls_cert_path.clone(),
+                            server_config.tls_key_path.clone(),
+                            std::time::Duration::from_secs(server_config.tls_reload_interval_secs),
+                        ));
                     }
-                };
 
-                tokio::spawn(async move {
-                    if let Err(e) = axum_server::bind_rustls(addr, tls_config)
-                        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                    tokio::spawn(async move {
+                        let served = match listener.into_std() {
+                            Ok(listener) => axum_server::from_tcp_rustls(listener, tls_config)
+                                .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                                .await,
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = served {
+                            log::error!("WebSocket server error: {}", e);
+                        }
+                    });
+                } else {
+                    info!("WebSocket server listening on {addr}");
+
+                    tokio::spawn(async move {
+                        if let Err(e) = axum::serve(
+                            listener,
+                            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+                        )
                         .await
-                    {
-                        log::error!("WebSocket server error: {}", e);
-                    }
-                });
-            } else {
-                info!("WebSocket server listening on {addr}");
-                log::warn!("⚠️ TLS is DISABLED - Communication is NOT encrypted!");
+                        {
+                            log::error!("WebSocket server error: {}", e);
+                        }
+                    });
+                }
+            }
 
-                let listener = tokio::net::TcpListener::bind(addr)
-                    .await
-                    .expect("Failed to bind WebSocket server");
+            // Mirror the broadcast to stdout for shell pipelines
+            if stdout_ndjson {
+                info!("📤 Writing card events to stdout as NDJSON");
+                tokio::spawn(ipc::write_lines(
+                    tx_ws.subscribe(),
+                    tokio::io::stdout(),
+                    output_config.line_template(),
+                    pii_mask.clone(),
+                ));
+            }
+
+            // Optional outbound webhook, independent of WebSocket clients
+            match webhook::Webhook::from_config(&output_config) {
+                Ok(Some(hook)) => {
+                    info!("📮 Posting card reads to webhook {}", hook.display_url());
+                    let hook = hook.with_mask(pii_mask.clone());
+                    tokio::spawn(webhook::run(hook, tx_ws.subscribe(), audit_logger.clone()));
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("❌ Failed to set up webhook: {}", e),
+            }
 
+            // Optional local IPC output (Unix socket / named pipe)
+            if let Some(ipc_path) = server_config.ipc_path.clone() {
+                let ipc_state = ipc::IpcState {
+                    tx: tx_ws.clone(),
+                    audit_logger: audit_logger.clone(),
+                    stats: app_stats.clone(),
+                    pii_mask: pii_mask.clone(),
+                };
                 tokio::spawn(async move {
-                    if let Err(e) = axum::serve(
-                        listener,
-                        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
-                    )
-                    .await
-                    {
-                        log::error!("WebSocket server error: {}", e);
+                    if let Err(e) = ipc::serve(&ipc_path, ipc_state).await {
+                        log::error!("❌ IPC server error on {}: {}", ipc_path, e);
                     }
                 });
             }
 
             // Run card reader monitor with card config
-            let mut card_reader =
-                reader::CardReader::new(card_config).expect("Failed to initialize Card Reader");
-
-            let output_config_clone = output_config.clone();
-            let security_config_clone = security_config.clone();
-            let audit_logger_clone = audit_logger.clone();
-            card_reader
-                .run_monitor(move |event| {
-                    // Send to WebSocket clients with field mapping applied
-                    let msg = match &event {
-                        decoder::CardEvent::Inserted(data) => {
-                            // Validate card data
-                            let thai_name = format!("{} {} {} {}", data.th_prefix, data.th_firstname, data.th_middlename, data.th_lastname);
-                            let validation_errors = crate::validation::CardDataValidator::validate_all(
-                                Some(&data.citizen_id),
-                                Some(&data.birthday),
-                                Some(&data.issue),
-                                Some(&data.expire),
-                                Some(&data.sex),
-                                Some(&thai_name),
-                                Some(&data.full_name_en),
-                                Some(&data.address),
-                            );
-
-                            let mut has_security_threat = false;
-
-                            for (field, err) in validation_errors {
-                                let (err_type, details, is_security) = match err {
-                                    crate::validation::ValidationError::Format(msg) => ("Format", msg, false),
-                                    crate::validation::ValidationError::Integrity(msg) => ("Integrity", msg, false),
-                                    crate::validation::ValidationError::Security(msg) => ("Security", msg, true),
-                                };
-
-                                if is_security {
-                                    has_security_threat = true;
-                                }
-
-                                audit_logger_clone.log_validation_failure(
-                                    None,
-                                    &field,
-                                    err_type,
-                                    &details,
-                                    is_security,
-                                );
-                            }
-
-                            if has_security_threat {
-                                log::error!("❌ Card data contains security threats. Payload rejected.");
-                                return; // Abort processing and do not broadcast
-                            }
-
-                            let mapped_data = decoder::apply_output_config(data, &output_config_clone);
-                            // Flatten mapped_data into the top-level object alongside "mode"
-                            let mut obj = serde_json::Map::new();
-                            obj.insert("mode".to_string(), json!("readsmartcard"));
-                            if let serde_json::Value::Object(fields) = mapped_data {
-                                for (k, v) in fields {
-                                    // Encrypt sensitive fields if encryption is enabled
-                                    let final_value = if security_config_clone.should_encrypt_field(&k) {
-                                        if let Some(ref crypto) = crypto_service {
-                                            if let Some(plaintext) = v.as_str() {
-                                                match crypto.encrypt_to_base64(plaintext) {
-                                                    Ok(encrypted) => {
-                                                        log::debug!("🔒 Encrypted field: {}", k);
-                                                        json!(encrypted)
-                                                    }
-                                                    Err(e) => {
-                                                        log::error!("❌ Failed to encrypt field '{}': {}", k, e);
-                                                        v // Keep original value on encryption failure
-                                                    }
-                                                }
-                                            } else {
-                                                v // Non-string value, keep original
-                                            }
-                                        } else {
-                                            v // No crypto service available
-                                        }
-                                    } else {
-                                        v // Field not in encrypted list
-                                    };
-                                    obj.insert(k, final_value);
-                                }
-                            }
-                            serde_json::Value::Object(obj)
+            #[cfg(feature = "reader")]
+            let mut card_reader = open_card_reader(card_config)
+                .await
+                .with_stats(app_stats)
+                .with_photo(output_config.photo_enabled());
+
+            #[cfg(feature = "reader")]
+            let monitor = card_reader.run_monitor(move |event| {
+                // Validate, map, encrypt and size-check the event for clients
+                let Some(msg) = app_state.card_message(&event) else {
+                    return; // Do not broadcast
+                };
+
+                #[cfg(feature = "ui")]
+                let _ = tx_payload.send(msg.clone());
+
+                if let Err(e) = tx_ws.send(msg) {
+                    log::debug!("No WebSocket clients connected: {}", e);
+                }
+
+                // Send to UI
+                #[cfg(feature = "ui")]
+                if let Err(e) = tx_ui.send(event) {
+                    log::error!("Failed to send to UI: {}", e);
+                }
+            });
+
+            // Reopen the audit file on SIGHUP (logrotate postrotate hook)
+            #[cfg(unix)]
+            {
+                let audit_logger = audit_logger.clone();
+                tokio::spawn(async move {
+                    use tokio::signal::unix::{signal, SignalKind};
+                    let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+                        log::warn!("⚠️ Cannot listen for SIGHUP, audit log rotation on signal disabled");
+                        return;
+                    };
+                    while hangup.recv().await.is_some() {
+                        info!("SIGHUP received, reopening audit log...");
+                        if let Err(e) = audit_logger.reopen() {
+                            log::error!("❌ Failed to reopen audit log: {}", e);
                         }
-                        decoder::CardEvent::Removed => json!({
-                            "mode": "removedsmartcard"
-                        }),
                     }
-                    .to_string();
+                });
+            }
 
-                    if let Err(e) = tx_ws.send(msg) {
-                        log::debug!("No WebSocket clients connected: {}", e);
+            // Release PCSC resources deterministically on Ctrl-C / SIGTERM
+            #[cfg(feature = "reader")]
+            tokio::select! {
+                () = monitor => {}
+                () = shutdown_signal() => {
+                    info!("Shutdown requested, releasing card reader...");
+                    card_reader.disconnect();
+                    drop(card_reader);
+                    if let Err(e) = audit_logger.flush() {
+                        log::error!("❌ Failed to flush audit log: {}", e);
                     }
+                    std::process::exit(0);
+                }
+            }
 
-                    // Send to UI
-                    if let Err(e) = tx_ui.send(event) {
-                        log::error!("Failed to send to UI: {}", e);
-                    }
-                })
-                .await;
+            #[cfg(not(feature = "reader"))]
+            {
+                shutdown_signal().await;
+                info!("Shutdown requested, shutting down...");
+                if let Err(e) = audit_logger.flush() {
+                    log::error!("❌ Failed to flush audit log: {}", e);
+                }
+                std::process::exit(0);
+            }
         });
     });
 
-    // Run egui on main thread (required by most platforms)
+    #[cfg(feature = "ui")]
+    {
+        run_window(&app_config, rx_ui, rx_payload, ui_stats);
+        // Window closed: the service thread ends with the process
+        drop(service);
+    }
+    #[cfg(not(feature = "ui"))]
+    if service.join().is_err() {
+        log::error!("❌ Service thread panicked");
+    }
+
+    // Persist buffered audit entries before the process exits
+    if let Err(e) = exit_audit_logger.flush() {
+        log::error!("❌ Failed to flush audit log: {}", e);
+    }
+}
+
+/// Wait for Ctrl-C, or SIGTERM (service managers, `kill`) on Unix
+#[cfg(feature = "server")]
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                log::warn!("⚠️ Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Run egui on the main thread (required by most platforms)
+///
+/// The window shows what the service broadcasts, so it needs `server`.
+#[cfg(all(feature = "ui", feature = "server"))]
+fn run_window(
+    app_config: &config::AppConfig,
+    rx_ui: std::sync::mpsc::Receiver<decoder::CardEvent>,
+    rx_payload: std::sync::mpsc::Receiver<String>,
+    ui_stats: Arc<stats::AppStats>,
+) {
     let ui_config = &app_config.ui;
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
@@ -338,11 +704,44 @@ fn main() {
 
     let ws_url = app_config.server.websocket_url();
     let font_config = app_config.fonts.clone();
+    let mut ui_settings = app_config.ui.clone();
+    ui_settings.show_photo &= app_config.output.photo_enabled();
+
+    // Full records are only cached when they can be encrypted at rest
+    let cache_crypto = if app_config.ui.persist_last_read && app_config.security.enable_encryption {
+        match crypto::CryptoService::from_env() {
+            Ok(service) => Some(Arc::new(service)),
+            Err(e) => {
+                log::warn!("⚠️ Last-read cache will hold the masked summary only: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let last_read_cache = last_read::LastReadCache::from_config(&app_config.ui, cache_crypto);
+    if let Some(cache) = &last_read_cache {
+        info!(
+            "💾 Last-read cache: {} ({})",
+            cache.path().display(),
+            if cache.stores_full_data() { "encrypted full record" } else { "masked summary only" }
+        );
+    }
 
     if let Err(e) = eframe::run_native(
         &app_config.ui.window_title,
         options,
-        Box::new(move |_cc| Ok(Box::new(ui::SmartCardApp::new(rx_ui, ws_url, font_config)))),
+        Box::new(move |_cc| {
+            Ok(Box::new(ui::SmartCardApp::new(
+            rx_ui,
+            rx_payload,
+            ws_url,
+            font_config,
+            ui_settings,
+            ui_stats,
+            last_read_cache,
+        )))
+        }),
     ) {
         log::error!("Failed to run egui: {}", e);
     }
@@ -3,8 +3,9 @@
 //! Implements token bucket algorithm to prevent abuse and ensure fair resource allocation.
 
 use parking_lot::RwLock;
+use serde::Serialize;
 use std::collections::HashMap;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
 use std::time::{Duration, Instant};
 
 /// Rate limit configuration
@@ -16,6 +17,8 @@ pub struct RateLimitConfig {
     pub window: Duration,
     /// Maximum concurrent connections per IP
     pub max_connections: u32,
+    /// IPv6 clients sharing this prefix length share one bucket (128 = per address)
+    pub ipv6_prefix_len: u8,
 }
 
 impl Default for RateLimitConfig {
@@ -24,6 +27,7 @@ impl Default for RateLimitConfig {
             max_requests: 60,                // 60 requests per window
             window: Duration::from_secs(60), // 1 minute window
             max_connections: 5,              // 5 concurrent connections per IP
+            ipv6_prefix_len: 64,             // one bucket per IPv6 /64 (typical host allocation)
         }
     }
 }
@@ -37,6 +41,8 @@ struct RateLimitState {
     last_refill: Instant,
     /// Number of active connections
     active_connections: u32,
+    /// Requests seen in the current window, including rejected ones
+    requests: u32,
 }
 
 impl RateLimitState {
@@ -45,6 +51,7 @@ impl RateLimitState {
             tokens: max_tokens,
             last_refill: Instant::now(),
             active_connections: 0,
+            requests: 0,
         }
     }
 }
@@ -72,6 +79,23 @@ impl RateLimiter {
         Self::new(RateLimitConfig::default())
     }
 
+    /// Bucket key for a peer address
+    ///
+    /// IPv4-mapped IPv6 peers (seen on dual-stack `::` listeners) count as their
+    /// IPv4 address; other IPv6 peers are grouped by `ipv6_prefix_len` so a client
+    /// cannot dodge limits by rotating addresses within its own prefix.
+    #[must_use]
+    pub fn client_key(&self, ip: IpAddr) -> IpAddr {
+        match ip.to_canonical() {
+            IpAddr::V6(v6) => {
+                let len = u32::from(self.config.ipv6_prefix_len.min(128));
+                let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+            }
+            v4 => v4,
+        }
+    }
+
     /// Check if a request from the given IP is allowed
     ///
     /// Returns `true` if the request is allowed, `false` if rate limited
@@ -79,7 +103,7 @@ impl RateLimiter {
         let mut states = self.states.write();
 
         let state = states
-            .entry(ip)
+            .entry(self.client_key(ip))
             .or_insert_with(|| RateLimitState::new(self.config.max_requests));
 
         // Refill tokens based on elapsed time
@@ -87,7 +111,9 @@ impl RateLimiter {
         if elapsed >= self.config.window {
             state.tokens = self.config.max_requests;
             state.last_refill = Instant::now();
+            state.requests = 0;
         }
+        state.requests = state.requests.saturating_add(1);
 
         // Check if tokens available
         if state.tokens > 0 {
@@ -106,7 +132,7 @@ impl RateLimiter {
         let mut states = self.states.write();
 
         let state = states
-            .entry(ip)
+            .entry(self.client_key(ip))
             .or_insert_with(|| RateLimitState::new(self.config.max_requests));
 
         if state.active_connections < self.config.max_connections {
@@ -132,7 +158,7 @@ impl RateLimiter {
     pub fn release_connection(&self, ip: IpAddr) {
         let mut states = self.states.write();
 
-        if let Some(state) = states.get_mut(&ip) {
+        if let Some(state) = states.get_mut(&self.client_key(ip)) {
             if state.active_connections > 0 {
                 state.active_connections -= 1;
                 log::debug!(
@@ -163,6 +189,12 @@ impl RateLimiter {
         });
     }
 
+    /// Whether a client is currently refused requests or connections
+    fn is_throttled(&self, state: &RateLimitState) -> bool {
+        let window_open = state.last_refill.elapsed() < self.config.window;
+        (window_open && state.tokens == 0) || state.active_connections >= self.config.max_connections
+    }
+
     /// Get current statistics for monitoring
     #[must_use]
     pub fn get_stats(&self) -> RateLimitStats {
@@ -171,17 +203,113 @@ impl RateLimiter {
         RateLimitStats {
             tracked_ips: states.len(),
             total_active_connections: states.values().map(|s| s.active_connections).sum(),
+            throttled_ips: states.values().filter(|s| self.is_throttled(s)).count(),
+            clients: Vec::new(),
         }
     }
+
+    /// [`Self::get_stats`] plus the `top_n` clients by requests in the current window
+    ///
+    /// Throttled clients are always listed, even outside the top `top_n`.
+    #[must_use]
+    pub fn get_detailed_stats(&self, top_n: usize) -> RateLimitStats {
+        let mut stats = self.get_stats();
+        let states = self.states.read();
+
+        let mut clients: Vec<ClientRateStats> = states
+            .iter()
+            .map(|(key, state)| ClientRateStats {
+                client: key.to_string(),
+                requests: if state.last_refill.elapsed() < self.config.window { state.requests } else { 0 },
+                active_connections: state.active_connections,
+                throttled: self.is_throttled(state),
+            })
+            .collect();
+        clients.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.client.cmp(&b.client)));
+        let mut rank = 0;
+        clients.retain(|c| {
+            rank += 1;
+            rank <= top_n || c.throttled
+        });
+
+        stats.clients = clients;
+        stats
+    }
 }
 
-/// Rate limiter statistics for monitoring
+/// Token bucket for the inbound messages of one WebSocket connection
+///
+/// Unlike [`RateLimiter`] it is owned by the socket task, so it needs no lock
+/// and goes away with the connection.
 #[derive(Debug, Clone)]
+pub struct MessageBucket {
+    /// Tokens refilled per second
+    rate: f64,
+    /// Bucket capacity
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl MessageBucket {
+    /// Bucket allowing `burst` messages at once and `rate` per second after that
+    ///
+    /// `None` when `rate` is 0 (no limit).
+    #[must_use]
+    pub fn new(rate: u32, burst: u32) -> Option<Self> {
+        if rate == 0 {
+            return None;
+        }
+        let burst = f64::from(burst.max(1));
+        Some(Self {
+            rate: f64::from(rate),
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        })
+    }
+
+    /// Take a token for one message; `false` if the message is over the limit
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Rate limiter statistics for monitoring
+#[derive(Debug, Clone, Serialize)]
 pub struct RateLimitStats {
     /// Number of IP addresses being tracked
     pub tracked_ips: usize,
     /// Total active connections across all IPs
     pub total_active_connections: u32,
+    /// Clients currently out of request tokens or connection slots
+    pub throttled_ips: usize,
+    /// Per-client breakdown, only filled by [`RateLimiter::get_detailed_stats`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub clients: Vec<ClientRateStats>,
+}
+
+/// Rate limit state of one client bucket
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ClientRateStats {
+    /// Bucket key: the IPv4 address or the masked IPv6 prefix
+    pub client: String,
+    /// Requests in the current window (including rejected ones)
+    pub requests: u32,
+    pub active_connections: u32,
+    pub throttled: bool,
 }
 
 // ============================================================================
@@ -191,7 +319,7 @@ pub struct RateLimitStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::net::Ipv4Addr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
 
     #[test]
     fn test_rate_limiting() {
@@ -199,6 +327,7 @@ mod tests {
             max_requests: 3,
             window: Duration::from_secs(60),
             max_connections: 2,
+            ..RateLimitConfig::default()
         };
         let limiter = RateLimiter::new(config);
         let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
@@ -212,12 +341,44 @@ mod tests {
         assert!(!limiter.check_request(ip));
     }
 
+    #[test]
+    fn test_detailed_stats() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            max_requests: 3,
+            max_connections: 1,
+            ..RateLimitConfig::default()
+        });
+        let busy = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let quiet = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let connected = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        for _ in 0..5 {
+            limiter.check_request(busy);
+        }
+        limiter.check_request(quiet);
+        assert!(limiter.check_connection(connected));
+
+        let stats = limiter.get_stats();
+        assert_eq!(stats.tracked_ips, 3);
+        assert_eq!(stats.throttled_ips, 2);
+        assert!(stats.clients.is_empty());
+
+        let stats = limiter.get_detailed_stats(1);
+        let summary: Vec<_> = stats
+            .clients
+            .iter()
+            .map(|c| (c.client.as_str(), c.requests, c.throttled))
+            .collect();
+        // Top client by requests, plus the throttled one outside the top 1
+        assert_eq!(summary, [("10.0.0.1", 5, true), ("10.0.0.3", 0, true)]);
+    }
+
     #[test]
     fn test_connection_limiting() {
         let config = RateLimitConfig {
             max_requests: 100,
             window: Duration::from_secs(60),
             max_connections: 2,
+            ..RateLimitConfig::default()
         };
         let limiter = RateLimiter::new(config);
         let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
@@ -236,6 +397,54 @@ mod tests {
         assert!(limiter.check_connection(ip));
     }
 
+    #[test]
+    fn test_ipv6_keying() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            max_connections: 1,
+            ..RateLimitConfig::default()
+        });
+        let v6 = |s: &str| IpAddr::V6(s.parse::<Ipv6Addr>().unwrap());
+
+        // Addresses in the same /64 share a bucket, other prefixes do not
+        assert!(limiter.check_connection(v6("2001:db8:1:2::10")));
+        assert!(!limiter.check_connection(v6("2001:db8:1:2:ffff::1")));
+        assert!(limiter.check_connection(v6("2001:db8:1:3::10")));
+
+        // IPv4-mapped peers are keyed as plain IPv4
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+        assert_eq!(limiter.client_key(v6("::ffff:192.0.2.7")), v4);
+        assert!(limiter.check_connection(v4));
+        assert!(!limiter.check_connection(v6("::ffff:192.0.2.7")));
+        limiter.release_connection(v6("::ffff:192.0.2.7"));
+        assert!(limiter.check_connection(v4));
+
+        // /128 disables grouping
+        let per_address = RateLimiter::new(RateLimitConfig {
+            ipv6_prefix_len: 128,
+            ..RateLimitConfig::default()
+        });
+        assert_eq!(per_address.client_key(v6("2001:db8::1")), v6("2001:db8::1"));
+    }
+
+    #[test]
+    fn test_message_bucket() {
+        assert!(MessageBucket::new(0, 10).is_none());
+
+        let mut bucket = MessageBucket::new(2, 3).unwrap();
+        let start = bucket.last_refill;
+        // Burst, then throttled until tokens refill at 2/s
+        assert!((0..3).all(|_| bucket.try_take_at(start)));
+        assert!(!bucket.try_take_at(start));
+        assert!(!bucket.try_take_at(start + Duration::from_millis(400)));
+        assert!(bucket.try_take_at(start + Duration::from_millis(600)));
+        assert!(!bucket.try_take_at(start + Duration::from_millis(600)));
+
+        // A long pause refills no more than the burst
+        let later = start + Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take_at(later)));
+        assert!(!bucket.try_take_at(later));
+    }
+
     #[test]
     fn test_cleanup() {
         let limiter = RateLimiter::default_config();
@@ -1,27 +1,596 @@
 use pcsc::{Context, Card, Scope, ShareMode, Protocols};
-use std::collections::HashSet;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::CString;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
-use log::{info, error, warn, debug};
-use anyhow::{Result, anyhow};
-use crate::config::CardConfig;
+use tracing::{info, error, warn, debug, Instrument};
+use crate::config::{CardConfig, CardProtocol, CardShareMode};
+use crate::address;
+use crate::benchmark::{BenchmarkReport, ReadTimings};
 use crate::decoder;
+use crate::stats::AppStats;
+use std::sync::Arc;
+
+/// APDU exchange with a card, implemented by `pcsc::Card` (and test mocks)
+pub trait ApduTransport {
+    fn transmit<'buf>(&self, apdu: &[u8], buf: &'buf mut [u8]) -> Result<&'buf [u8], pcsc::Error>;
+}
+
+impl ApduTransport for Card {
+    fn transmit<'buf>(&self, apdu: &[u8], buf: &'buf mut [u8]) -> Result<&'buf [u8], pcsc::Error> {
+        Card::transmit(self, apdu, buf)
+    }
+}
+
+/// Why reading a card failed
+///
+/// [`ReaderError::root`] gives the underlying cause behind the `Select`/`Field` context.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReaderError {
+    /// The reader itself is missing or went away (e.g. USB unplugged)
+    NoReader(pcsc::Error),
+    /// No card in the reader
+    NoCard,
+    /// The card was pulled out mid-read
+    CardRemoved,
+    /// The card answered with an error status word
+    Apdu { sw1: u8, sw2: u8 },
+    /// The card's response could not be decoded (e.g. truncated)
+    Decode(String),
+    /// Any other PC/SC transmit failure
+    Transmit(pcsc::Error),
+    /// SELECT of the card applet failed
+    Select(Box<ReaderError>),
+    /// Reading a configured field failed
+    Field { name: String, source: Box<ReaderError> },
+    /// The read did not finish within `card.read_timeout_ms`
+    Timeout(Duration),
+    /// A required field read back blank
+    Empty,
+    /// The response grew past the configured byte limit
+    TooLong(usize),
+}
+
+impl ReaderError {
+    /// Underlying cause, without the `Select`/`Field` context
+    #[must_use]
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Select(source) | Self::Field { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// `true` if the reader, not the card, went away
+    #[must_use]
+    pub fn is_reader_gone(&self) -> bool {
+        matches!(self.root(), Self::NoReader(_))
+    }
+
+    fn field(name: &str, source: Self) -> Self {
+        Self::Field {
+            name: name.to_string(),
+            source: Box::new(source),
+        }
+    }
+}
+
+impl From<pcsc::Error> for ReaderError {
+    /// Keep reader removal and card removal distinguishable from other card errors
+    fn from(e: pcsc::Error) -> Self {
+        match e {
+            e if is_reader_error(e) => Self::NoReader(e),
+            pcsc::Error::NoSmartcard => Self::NoCard,
+            pcsc::Error::RemovedCard => Self::CardRemoved,
+            e => Self::Transmit(e),
+        }
+    }
+}
+
+impl std::fmt::Display for ReaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoReader(e) => write!(f, "Reader unavailable: {}", e),
+            Self::NoCard => write!(f, "No card in reader"),
+            Self::CardRemoved => write!(f, "Card removed during read"),
+            Self::Apdu { sw1, sw2 } => write!(
+                f,
+                "APDU failed with status: SW1={:02X} SW2={:02X} ({})",
+                sw1,
+                sw2,
+                CardReader::interpret_sw(*sw1, *sw2)
+            ),
+            Self::Decode(msg) => write!(f, "Invalid card response: {}", msg),
+            Self::Transmit(e) => write!(f, "Card transmit failed: {}", e),
+            Self::Select(source) => write!(f, "Failed to SELECT Thai ID applet: {}", source),
+            Self::Field { name, source } => write!(f, "Failed to read field '{}': {}", name, source),
+            Self::Timeout(limit) => write!(f, "Card read timed out after {}ms", limit.as_millis()),
+            Self::Empty => write!(f, "Required field is empty"),
+            Self::TooLong(limit) => write!(f, "Card response exceeds {} bytes", limit),
+        }
+    }
+}
+
+impl std::error::Error for ReaderError {}
+
+/// Split a response APDU into its data and status words
+fn split_status(rapdu: &[u8]) -> Result<(&[u8], u8, u8), ReaderError> {
+    match rapdu {
+        [data @ .., sw1, sw2] => Ok((data, *sw1, *sw2)),
+        _ => Err(ReaderError::Decode(format!(
+            "APDU response length {} bytes (expected >= 2)",
+            rapdu.len()
+        ))),
+    }
+}
+
+/// `apdu` with its last byte, the Le, set to `le`
+///
+/// A case 1 command (header only) has no Le and ends in P2, so `le` is
+/// appended instead.
+fn with_le(apdu: &[u8], le: u8) -> Vec<u8> {
+    let mut corrected = apdu.to_vec();
+    match corrected.last_mut() {
+        Some(last) if apdu.len() > 4 => *last = le,
+        _ => corrected.push(le),
+    }
+    corrected
+}
+
+/// `true` if a field response holds no data (padding, or only name separators)
+fn is_blank(bytes: &[u8]) -> bool {
+    bytes.iter().all(|b| matches!(b, 0x00 | b' ' | b'#' | 0xFF))
+}
+
+/// Parse an APDU given as hex, e.g. `"00A4 0400 08"` (spaces allowed)
+///
+/// # Errors
+/// Returns a message for non-hex input, an odd digit count or a missing header
+pub fn parse_apdu_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a hex digit", c));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits ({})", digits.len()));
+    }
+    let bytes: Vec<u8> = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).expect("checked hex digits"))
+        .collect();
+    if bytes.len() < 4 {
+        return Err(format!("APDU needs at least CLA INS P1 P2, got {} bytes", bytes.len()));
+    }
+    Ok(bytes)
+}
+
+/// Card answer to a diagnostic APDU (`--apdu`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApduProbe {
+    pub data: Vec<u8>,
+    pub sw1: u8,
+    pub sw2: u8,
+}
+
+impl ApduProbe {
+    /// `true` for `90 00`
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        (self.sw1, self.sw2) == (0x90, 0x00)
+    }
+
+    /// ISO 7816-4 meaning of the status word
+    #[must_use]
+    pub fn meaning(&self) -> &'static str {
+        CardReader::interpret_sw(self.sw1, self.sw2)
+    }
+}
+
+impl std::fmt::Display for ApduProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let data: Vec<String> = self.data.iter().map(|b| format!("{:02X}", b)).collect();
+        writeln!(f, "Response ({} bytes): {}", self.data.len(), data.join(" "))?;
+        write!(f, "SW: {:02X} {:02X} ({})", self.sw1, self.sw2, self.meaning())
+    }
+}
+
+/// Reader enumeration, implemented by `pcsc::Context` (and test mocks)
+pub trait ReaderBackend {
+    /// Names and current states of the attached readers
+    fn reader_states(&self) -> Result<Vec<(String, pcsc::State)>, pcsc::Error>;
+}
+
+impl ReaderBackend for Context {
+    fn reader_states(&self) -> Result<Vec<(String, pcsc::State)>, pcsc::Error> {
+        let names = match self.list_readers_owned() {
+            Ok(names) => names,
+            Err(pcsc::Error::NoReadersAvailable) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut states: Vec<pcsc::ReaderState> = names
+            .into_iter()
+            .map(|name| pcsc::ReaderState::new(name, pcsc::State::UNAWARE))
+            .collect();
+        self.get_status_change(Duration::ZERO, &mut states)?;
+        Ok(states
+            .iter()
+            .map(|rs| (rs.name().to_string_lossy().to_string(), rs.event_state()))
+            .collect())
+    }
+}
+
+/// One attached reader in a [`Diagnostics`] snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReaderDiagnostics {
+    pub name: String,
+    /// empty, present, in_use, exclusive, mute, unavailable or unknown
+    pub state: &'static str,
+}
+
+/// PC/SC environment snapshot for support tickets (`--diagnostics`, `/health?detail=true`)
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostics {
+    /// PC/SC library in use; on Linux the loaded file, whose name carries its version
+    pub pcsc_library: Option<String>,
+    /// Attached readers (empty when there are none)
+    pub readers: Vec<ReaderDiagnostics>,
+    /// Why the readers could not be listed, e.g. the PC/SC service is not running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "PC/SC library: {}", self.pcsc_library.as_deref().unwrap_or("unknown"))?;
+        if let Some(error) = &self.error {
+            return write!(f, "Readers: unavailable ({})", error);
+        }
+        if self.readers.is_empty() {
+            return write!(f, "Readers: none attached");
+        }
+        write!(f, "Readers ({}):", self.readers.len())?;
+        for reader in &self.readers {
+            write!(f, "\n  {} [{}]", reader.name, reader.state)?;
+        }
+        Ok(())
+    }
+}
+
+/// Short name of a reader's state, most significant flag first
+fn describe_state(state: pcsc::State) -> &'static str {
+    [
+        (pcsc::State::UNAVAILABLE, "unavailable"),
+        (pcsc::State::MUTE, "mute"),
+        (pcsc::State::EXCLUSIVE, "exclusive"),
+        (pcsc::State::INUSE, "in_use"),
+        (pcsc::State::PRESENT, "present"),
+        (pcsc::State::EMPTY, "empty"),
+    ]
+    .into_iter()
+    .find(|(flag, _)| state.contains(*flag))
+    .map_or("unknown", |(_, name)| name)
+}
+
+/// PC/SC library of this platform, as the loaded file on Linux
+fn pcsc_library() -> Option<String> {
+    if cfg!(target_os = "windows") {
+        return Some("winscard.dll".to_string());
+    }
+    if cfg!(target_os = "macos") {
+        return Some("PCSC.framework".to_string());
+    }
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines()
+        .filter_map(|line| line.split_whitespace().nth(5))
+        .find(|path| path.contains("libpcsclite"))
+        .map(str::to_owned)
+}
+
+/// Snapshot of the readers seen through `backend`
+pub fn diagnostics_with<B: ReaderBackend>(backend: &B) -> Diagnostics {
+    let (readers, error) = match backend.reader_states() {
+        Ok(states) => (
+            states
+                .into_iter()
+                .map(|(name, state)| ReaderDiagnostics { name, state: describe_state(state) })
+                .collect(),
+            None,
+        ),
+        Err(e) => (Vec::new(), Some(e.to_string())),
+    };
+    Diagnostics { pcsc_library: pcsc_library(), readers, error }
+}
+
+/// Snapshot of the PC/SC environment through a fresh context; never fails
+#[must_use]
+pub fn diagnostics() -> Diagnostics {
+    match Context::establish(Scope::User) {
+        Ok(ctx) => diagnostics_with(&ctx),
+        Err(e) => Diagnostics {
+            pcsc_library: pcsc_library(),
+            readers: Vec::new(),
+            error: Some(ContextError::from(e).to_string()),
+        },
+    }
+}
+
+/// PC/SC context could not be established
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ContextError {
+    /// The PC/SC service (pcscd / Windows Smart Card service) is not running
+    ServiceUnavailable(pcsc::Error),
+    /// Any other PC/SC failure
+    Other(pcsc::Error),
+}
+
+impl From<pcsc::Error> for ContextError {
+    fn from(e: pcsc::Error) -> Self {
+        match e {
+            pcsc::Error::NoService | pcsc::Error::ServiceStopped => Self::ServiceUnavailable(e),
+            e => Self::Other(e),
+        }
+    }
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ServiceUnavailable(e) => write!(
+                f,
+                "PC/SC service is not running ({}); start pcscd (Linux) or the Smart Card service (Windows)",
+                e
+            ),
+            Self::Other(e) => write!(f, "Failed to establish PC/SC context: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+/// `true` for PC/SC errors that mean the reader, not the card, went away
+///
+/// `NoSmartcard`/`RemovedCard` are card removal, which the monitor already
+/// reports as `CardEvent::Removed`.
+fn is_reader_error(e: pcsc::Error) -> bool {
+    matches!(
+        e,
+        pcsc::Error::ReaderUnavailable | pcsc::Error::UnknownReader | pcsc::Error::NoReadersAvailable
+    )
+}
+
+/// GET RESPONSE commands followed for one APDU when `max_get_response_chain`
+/// is 0, so a card that never stops chaining can't hang the reader
+const GET_RESPONSE_HARD_CAP: usize = 256;
+
+/// Monotonic id shared by every log line of one card read
+static NEXT_READ_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Span for one card read, tagging its events with `read_id` and `reader`
+fn read_span(reader: &str) -> tracing::Span {
+    let read_id = NEXT_READ_ID.fetch_add(1, Ordering::Relaxed);
+    tracing::info_span!("card_read", read_id, reader = %reader)
+}
+
+/// Call `establish` up to `context_retry_attempts` times, doubling the delay each time
+///
+/// Blocks between attempts: async callers run it on a blocking thread.
+fn establish_with_retry<C>(
+    config: &CardConfig,
+    mut establish: impl FnMut() -> Result<C, pcsc::Error>,
+) -> Result<C, ContextError> {
+    let attempts = config.context_retry_attempts.max(1);
+    let mut delay = Duration::from_millis(config.context_retry_delay_ms);
+    let mut attempt = 1;
+    loop {
+        match establish() {
+            Ok(ctx) => return Ok(ctx),
+            Err(e) if attempt >= attempts => return Err(e.into()),
+            Err(e) => {
+                debug!("Failed to establish PCSC context (attempt {}/{}): {}", attempt, attempts, e);
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// PC/SC errors of a card that is still being seated, worth another connect
+fn is_settling_error(e: pcsc::Error) -> bool {
+    matches!(
+        e,
+        pcsc::Error::UnresponsiveCard
+            | pcsc::Error::UnpoweredCard
+            | pcsc::Error::ResetCard
+            | pcsc::Error::NoSmartcard
+            | pcsc::Error::RemovedCard
+    )
+}
+
+/// Connect to a newly inserted card as soon as it answers
+///
+/// Tries right away, then after `card_settle_initial_ms`, doubling the delay
+/// while the card is still settling; the total wait never exceeds
+/// `card_settle_delay_ms`.
+async fn connect_when_settled<C>(
+    config: &CardConfig,
+    mut connect: impl FnMut() -> Result<C, pcsc::Error>,
+) -> Result<C, pcsc::Error> {
+    let budget = Duration::from_millis(config.card_settle_delay_ms);
+    let mut delay = Duration::from_millis(config.card_settle_initial_ms.max(1));
+    let mut waited = Duration::ZERO;
+    loop {
+        match connect() {
+            Err(e) if is_settling_error(e) && waited < budget => {
+                let step = delay.min(budget - waited);
+                debug!("Card not settled yet ({}), connecting again in {:?}", e, step);
+                sleep(step).await;
+                waited += step;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// One `reader_allowlist`/`reader_denylist` entry
+#[derive(Debug)]
+enum ReaderPattern {
+    /// Case-insensitive substring, stored lowercased
+    Substring(String),
+    Regex(regex::Regex),
+}
+
+impl ReaderPattern {
+    /// `/.../` is a regex, anything else a substring
+    ///
+    /// Invalid regexes are rejected at startup by [`CardConfig::validate`].
+    fn parse(pattern: &str) -> Result<Self, regex::Error> {
+        match crate::config::reader_pattern_regex(pattern) {
+            Some(regex) => regex.map(Self::Regex),
+            None => Ok(Self::Substring(pattern.to_lowercase())),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Substring(part) => name.to_lowercase().contains(part.as_str()),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Which readers the monitor uses, per `card.reader_allowlist`/`reader_denylist`
+#[derive(Debug)]
+struct ReaderFilter {
+    allow: Vec<ReaderPattern>,
+    deny: Vec<ReaderPattern>,
+}
+
+impl ReaderFilter {
+    fn new(config: &CardConfig) -> Self {
+        let parse = |patterns: &[String]| patterns.iter().filter_map(|p| ReaderPattern::parse(p).ok()).collect();
+        Self {
+            allow: parse(&config.reader_allowlist),
+            deny: parse(&config.reader_denylist),
+        }
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|p| p.matches(name)))
+            && !self.deny.iter().any(|p| p.matches(name))
+    }
+}
+
+/// Map the configured share mode to the PC/SC flag
+fn pcsc_share_mode(mode: CardShareMode) -> ShareMode {
+    match mode {
+        CardShareMode::Shared => ShareMode::Shared,
+        CardShareMode::Exclusive => ShareMode::Exclusive,
+        CardShareMode::Direct => ShareMode::Direct,
+    }
+}
+
+/// Map the configured protocol to the PC/SC flags
+fn pcsc_protocols(protocol: CardProtocol) -> Protocols {
+    match protocol {
+        CardProtocol::Any => Protocols::ANY,
+        CardProtocol::T0 => Protocols::T0,
+        CardProtocol::T1 => Protocols::T1,
+    }
+}
 
 pub struct CardReader {
     ctx: Option<Context>,
     config: CardConfig,
+    stats: Option<Arc<AppStats>>,
+    address_reference: Option<Arc<address::AddressReference>>,
+    /// Readers that already have a card processed
+    card_present: HashSet<String>,
+    /// Last reported [`decoder::ReaderStatus`], for debouncing
+    last_status: Option<decoder::ReaderStatus>,
+    /// Read the photo chunks (off when the output drops the photo)
+    read_photo: bool,
+    /// Readers considered by the monitor
+    reader_filter: Arc<ReaderFilter>,
 }
 
 impl CardReader {
-    pub fn new(config: CardConfig) -> Result<Self> {
-        match Context::establish(Scope::User) {
-            Ok(ctx) => Ok(Self { ctx: Some(ctx), config }),
-            Err(e) => {
-                warn!("Failed to establish PCSC context: {}. Retrying later.", e);
-                Ok(Self { ctx: None, config })
-            }
+    /// Create a reader with an established PCSC context
+    ///
+    /// Retries `context_retry_attempts` times with doubling backoff, sleeping
+    /// the calling thread; use `spawn_blocking` from async code.
+    ///
+    /// # Errors
+    /// Returns a [`ContextError`] telling a stopped PC/SC service apart from other failures
+    pub fn new(config: CardConfig) -> Result<Self, ContextError> {
+        let ctx = establish_with_retry(&config, || Context::establish(Scope::User))?;
+        let mut reader = Self::deferred(config);
+        reader.ctx = Some(ctx);
+        Ok(reader)
+    }
+
+    /// Create a reader without a context; `run_monitor` establishes it lazily
+    #[must_use]
+    pub fn deferred(config: CardConfig) -> Self {
+        Self {
+            ctx: None,
+            stats: None,
+            address_reference: None,
+            card_present: HashSet::new(),
+            last_status: None,
+            read_photo: true,
+            reader_filter: Arc::new(ReaderFilter::new(&config)),
+            config,
+        }
+    }
+
+    /// Record read successes/failures into shared activity counters
+    pub fn with_stats(mut self, stats: Arc<AppStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Snap parsed address components to a reference dataset
+    pub fn with_address_reference(mut self, reference: address::AddressReference) -> Self {
+        self.address_reference = Some(Arc::new(reference));
+        self
+    }
+
+    /// Skip the photo chunk APDUs, e.g. when [`OutputConfig::photo_enabled`] is off
+    ///
+    /// [`OutputConfig::photo_enabled`]: crate::config::OutputConfig::photo_enabled
+    pub fn with_photo(mut self, read_photo: bool) -> Self {
+        self.read_photo = read_photo;
+        self
+    }
+
+    /// Context-free copy for reading a card on a blocking thread
+    fn detached(&self) -> Self {
+        Self {
+            ctx: None,
+            stats: None,
+            address_reference: self.address_reference.clone(),
+            card_present: HashSet::new(),
+            last_status: None,
+            read_photo: self.read_photo,
+            reader_filter: self.reader_filter.clone(),
+            config: self.config.clone(),
+        }
+    }
+
+    /// Release the PCSC context and forget per-reader state
+    ///
+    /// The next `run_monitor` iteration re-establishes a fresh context.
+    pub fn disconnect(&mut self) {
+        if self.ctx.take().is_some() {
+            debug!("PCSC context released");
         }
+        self.card_present.clear();
     }
 
     /// Check if PCSC context is healthy by attempting to list readers
@@ -40,32 +609,66 @@ impl CardReader {
         }
     }
 
+    /// Make sure a healthy PCSC context exists, establishing one if needed
+    ///
+    /// Returns `false` (after reporting `Disconnected`) if none could be established.
+    fn ensure_context<F>(
+        &mut self,
+        establish: impl FnOnce() -> Result<Context, pcsc::Error>,
+        on_card_event: &F,
+    ) -> bool
+    where F: Fn(decoder::CardEvent)
+    {
+        if self.is_context_healthy() {
+            return true;
+        }
+        if self.ctx.is_some() {
+            warn!("PCSC Context unhealthy, resetting...");
+            self.disconnect();
+        }
+
+        match establish() {
+            Ok(ctx) => {
+                info!("PCSC Context established.");
+                self.ctx = Some(ctx);
+                true
+            }
+            Err(e) => {
+                debug!("Failed to establish context: {}, retrying...", e);
+                self.report_status(decoder::ReaderStatus::Disconnected, on_card_event);
+                false
+            }
+        }
+    }
+
+    /// Emit a `Status` event only when the status actually changes
+    fn report_status<F>(&mut self, status: decoder::ReaderStatus, on_card_event: &F)
+    where F: Fn(decoder::CardEvent)
+    {
+        if self.last_status == Some(status) {
+            return;
+        }
+        match status {
+            decoder::ReaderStatus::Ready => info!("✅ Card reader ready"),
+            decoder::ReaderStatus::Disconnected => warn!("🔌 No card reader available"),
+        }
+        self.last_status = Some(status);
+        on_card_event(decoder::CardEvent::Status(status));
+    }
+
     pub async fn run_monitor<F>(&mut self, on_card_event: F)
     where F: Fn(decoder::CardEvent) + Send + Sync + 'static + Clone
     {
-        // Track readers that already have a card processed
-        let mut card_present: HashSet<String> = HashSet::new();
+        // Readers that were unplugged mid-read, reported again when they return
+        let mut disconnected_readers: HashSet<String> = HashSet::new();
+        // Readers left out by the allow/deny lists, logged once each
+        let mut filtered_readers: HashSet<String> = HashSet::new();
 
         loop {
             // Check context health and re-establish if needed
-            if !self.is_context_healthy() {
-                if self.ctx.is_some() {
-                    warn!("PCSC Context unhealthy, resetting...");
-                    self.ctx = None;
-                    card_present.clear();
-                }
-
-                match Context::establish(Scope::User) {
-                    Ok(ctx) => {
-                        info!("PCSC Context established.");
-                        self.ctx = Some(ctx);
-                    }
-                    Err(e) => {
-                        debug!("Failed to establish context: {}, retrying...", e);
-                        sleep(Duration::from_secs(2)).await;
-                        continue;
-                    }
-                }
+            if !self.ensure_context(|| Context::establish(Scope::User), &on_card_event) {
+                sleep(Duration::from_secs(2)).await;
+                continue;
             }
 
             let ctx = self.ctx.as_ref().unwrap();
@@ -73,22 +676,39 @@ impl CardReader {
 
             // Collect reader names into owned CStrings so they live long enough
             let reader_names: Vec<CString> = match ctx.list_readers(&mut readers_buf) {
-                Ok(readers) => readers
-                    .filter_map(|r| CString::new(r.to_bytes()).ok())
-                    .collect(),
+                Ok(readers) => {
+                    let names = readers.filter_map(|r| CString::new(r.to_bytes()).ok()).collect();
+                    self.filter_readers(names, &mut filtered_readers)
+                }
                 Err(e) => {
-                    error!("Failed to list readers: {}", e);
-                    self.ctx = None;
-                    card_present.clear();
+                    if e == pcsc::Error::NoReadersAvailable {
+                        debug!("No readers attached");
+                    } else {
+                        error!("Failed to list readers: {}", e);
+                    }
+                    self.report_status(decoder::ReaderStatus::Disconnected, &on_card_event);
+                    self.disconnect();
                     sleep(Duration::from_secs(2)).await;
                     continue;
                 }
             };
 
+            disconnected_readers.retain(|reader| {
+                let back = reader_names.iter().any(|n| n.to_string_lossy() == reader.as_str());
+                if back {
+                    info!("🔌 Reader reconnected: {}", reader);
+                    on_card_event(decoder::CardEvent::ReaderConnected(reader.clone()));
+                }
+                !back
+            });
+
             if reader_names.is_empty() {
+                self.report_status(decoder::ReaderStatus::Disconnected, &on_card_event);
                 sleep(Duration::from_secs(1)).await;
                 continue;
             }
+            self.report_status(decoder::ReaderStatus::Ready, &on_card_event);
+            let ctx = self.ctx.as_ref().unwrap();
 
             // Build reader states with UNAWARE for initial poll
             let mut reader_states: Vec<pcsc::ReaderState> = reader_names
@@ -97,368 +717,618 @@ impl CardReader {
                 .collect();
 
             // Wait for status change
-            if let Err(e) = ctx.get_status_change(Duration::from_secs(2), &mut reader_states) {
+            if let Err(e) = ctx.get_status_change(self.config.poll_timeout(), &mut reader_states) {
                 if e != pcsc::Error::Timeout {
                     error!("Get status change error: {}", e);
-                    self.ctx = None;
-                    card_present.clear();
+                    self.disconnect();
                 }
-                sleep(Duration::from_millis(500)).await;
+                sleep(self.config.poll_idle()).await;
                 continue;
             }
 
             // Process each reader
+            let mut reset_context = false;
             for rs in &reader_states {
                 let name = rs.name().to_string_lossy().to_string();
                 let state = rs.event_state();
                 let is_present = state.contains(pcsc::State::PRESENT)
                     && !state.contains(pcsc::State::EMPTY);
 
-                if is_present && !card_present.contains(&name) {
+                if is_present && !self.card_present.contains(&name) {
                     // New card detected
                     info!("Card detected in reader: {}", name);
 
                     let retry_attempts = self.config.retry_attempts;
                     let retry_delay = Duration::from_millis(self.config.retry_delay_ms);
-                    let settle_delay = Duration::from_millis(self.config.card_settle_delay_ms);
-                    let read_retry_attempts = self.config.read_retry_attempts;
-                    let read_retry_delay = Duration::from_millis(self.config.read_retry_delay_ms);
-
-                    let mut read_success = false;
-                    for attempt in 1..=retry_attempts {
-                        // Wait for card to settle after insertion
-                        sleep(settle_delay).await;
-
-                        match ctx.connect(rs.name(), ShareMode::Shared, Protocols::ANY) {
-                            Ok(card) => {
-                                info!("Card connected in reader: {} (attempt {})", name, attempt);
-
-                                // Retry read operation with configurable attempts
-                                for read_attempt in 1..=read_retry_attempts {
-                                    match self.read_thai_id(&card) {
-                                        Ok(data) => {
-                                            info!("Successfully read Thai ID: {} (read attempt {}/{})",
-                                                decoder::mask_citizen_id(&data.citizen_id), read_attempt, read_retry_attempts);
+                    let share_mode = pcsc_share_mode(self.config.share_mode);
+                    let protocols = pcsc_protocols(self.config.protocol);
+
+                    let (read_success, reader_gone, timed_out) = async {
+                        let mut read_success = false;
+                        let mut reader_gone = false;
+                        let mut timed_out = None;
+                        for attempt in 1..=retry_attempts {
+                            // Connect as soon as the card has settled after insertion
+                            match connect_when_settled(&self.config, || ctx.connect(rs.name(), share_mode, protocols)).await {
+                                Ok(card) => {
+                                    info!("Card connected in reader: {} (attempt {})", name, attempt);
+                                    let card = Arc::new(card);
+
+                                    let read_started = Instant::now();
+                                    let result = self.read_with_retries(&card).await;
+                                    if let Some(stats) = &self.stats {
+                                        let error = result.as_ref().err().map(|e| format!("{:#}", e));
+                                        stats.record_reader_read(&name, read_started.elapsed(), error.as_deref());
+                                    }
+
+                                    match result {
+                                        Ok(mut data) => {
+                                            data.reader_name = name.clone();
                                             on_card_event(decoder::CardEvent::Inserted(data));
                                             read_success = true;
                                             break;
                                         }
-                                        Err(e) => {
-                                            warn!("Failed to read card data (read attempt {}/{}): {}", read_attempt, read_retry_attempts, e);
-                                            if read_attempt < read_retry_attempts {
-                                                sleep(read_retry_delay).await;
-                                            }
+                                        Err(e) if e.is_reader_gone() => {
+                                            reader_gone = true;
+                                            break;
+                                        }
+                                        Err(e @ ReaderError::Timeout(_)) => {
+                                            timed_out = Some(e.to_string());
+                                            break;
                                         }
+                                        Err(_) => {}
                                     }
                                 }
-
-                                if read_success {
+                                Err(e) if is_reader_error(e) => {
+                                    reader_gone = true;
                                     break;
                                 }
-                            }
-                            Err(e) => {
-                                warn!("Failed to connect to card (attempt {}/{}): {}", attempt, retry_attempts, e);
-                                if attempt < retry_attempts {
-                                    sleep(retry_delay).await;
+                                Err(pcsc::Error::SharingViolation) => {
+                                    warn!("⚠️ Card in reader {} is in use by another application (share_mode = \"{}\") (attempt {}/{})",
+                                        name, self.config.share_mode, attempt, retry_attempts);
+                                    if attempt < retry_attempts {
+                                        sleep(retry_delay).await;
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!("Failed to connect to card (attempt {}/{}): {}", attempt, retry_attempts, e);
+                                    if attempt < retry_attempts {
+                                        sleep(retry_delay).await;
+                                    }
                                 }
                             }
                         }
+                        (read_success, reader_gone, timed_out)
+                    }
+                    .instrument(read_span(&name))
+                    .await;
+
+                    // Reader unplugged mid-read: not a card failure, reset and wait for it
+                    if reader_gone {
+                        warn!("🔌 Reader disconnected: {}", name);
+                        disconnected_readers.insert(name.clone());
+                        on_card_event(decoder::CardEvent::ReaderDisconnected(name));
+                        reset_context = true;
+                        break;
+                    }
+
+                    // Read stuck on a transmit: abandon it and start over with a fresh context
+                    if let Some(error) = timed_out {
+                        error!("⏱️ {} in reader {}, resetting PCSC context", error, name);
+                        if let Some(stats) = &self.stats {
+                            stats.record_failure();
+                        }
+                        on_card_event(decoder::CardEvent::ReadFailed { reader: name, error });
+                        reset_context = true;
+                        break;
                     }
 
                     // Only mark as present if read was successful
+                    if let Some(stats) = &self.stats {
+                        if read_success {
+                            stats.record_read();
+                        } else {
+                            stats.record_failure();
+                        }
+                    }
+
                     if read_success {
-                        card_present.insert(name);
+                        self.card_present.insert(name);
                     } else {
-                        error!("Failed to read card after {} connection attempts with {} read retries each. Will retry on next poll cycle.", retry_attempts, read_retry_attempts);
+                        error!("Failed to read card after {} connection attempts with {} read retries each. Will retry on next poll cycle.", retry_attempts, self.config.read_retry_attempts);
                     }
-                } else if !is_present && card_present.contains(&name) {
+                } else if !is_present && self.card_present.contains(&name) {
                     // Card removed — allow re-read on next insert
                     info!("Card removed from reader: {}", name);
-                    card_present.remove(&name);
+                    self.card_present.remove(&name);
                     on_card_event(decoder::CardEvent::Removed);
                 }
             }
 
-            sleep(Duration::from_millis(500)).await;
+            if reset_context {
+                self.disconnect();
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+
+            sleep(self.config.poll_idle()).await;
         }
     }
 
-    pub fn read_thai_id(&self, card: &Card) -> Result<decoder::ThaiIDData> {
-        // SELECT Thai ID Applet from config
-        let select_apdu = self.config.select_apdu_bytes();
-        debug!("SELECT APDU: {:02X?}", select_apdu);
-        self.send_apdu(card, &select_apdu)
-            .map_err(|e| anyhow!("Failed to SELECT Thai ID applet: {}", e))?;
-
-        // Helper to read field by name from config
-        let read_field = |name: &str| -> Result<String> {
-            if let Some(field) = self.config.get_field(name) {
-                let apdu = field.to_bytes();
-                debug!("Reading {}: APDU {:02X?}", name, apdu);
-                let data = self.send_apdu(card, &apdu)
-                    .map_err(|e| anyhow!("Failed to read field '{}': {}", name, e))?;
-                Ok(decoder::decode_tis620(&data))
-            } else {
-                warn!("Field '{}' not found in config, using empty string", name);
-                Ok(String::new())
-            }
-        };
+    /// Keep the readers allowed by `reader_allowlist`/`reader_denylist`
+    ///
+    /// Each reader filtered out is logged the first time it is seen.
+    fn filter_readers(&self, names: Vec<CString>, filtered: &mut HashSet<String>) -> Vec<CString> {
+        names
+            .into_iter()
+            .filter(|name| {
+                let name = name.to_string_lossy();
+                let allowed = self.reader_filter.allows(&name);
+                if !allowed && filtered.insert(name.to_string()) {
+                    info!("Ignoring reader (reader_allowlist/reader_denylist): {}", name);
+                }
+                allowed
+            })
+            .collect()
+    }
 
-        // Helper: read raw bytes without stripping '#' delimiters
-        let read_field_raw = |name: &str| -> Result<Vec<u8>> {
-            if let Some(field) = self.config.get_field(name) {
-                let apdu = field.to_bytes();
-                let data = self.send_apdu(card, &apdu)
-                    .map_err(|e| anyhow!("Failed to read raw field '{}': {}", name, e))?;
-                Ok(data)
-            } else {
-                Ok(Vec::new())
-            }
-        };
+    /// Read a connected card, retrying up to `read_retry_attempts` times
+    ///
+    /// Gives up immediately if the reader itself disappears or a read times out.
+    async fn read_with_retries<T>(&self, card: &Arc<T>) -> Result<decoder::ThaiIDData, ReaderError>
+    where T: ApduTransport + Send + Sync + 'static
+    {
+        let read_retry_attempts = self.config.read_retry_attempts.max(1);
+        let read_retry_delay = Duration::from_millis(self.config.read_retry_delay_ms);
 
-        // Helper: split TIS-620 bytes by '#' into up to `n` parts
-        let split_tis620 = |bytes: Vec<u8>, n: usize| -> Vec<String> {
-            use encoding_rs::WINDOWS_874;
-            use unicode_normalization::UnicodeNormalization;
-            let (cow, _, _) = WINDOWS_874.decode(&bytes);
-            let raw = cow.into_owned();
-            let mut parts: Vec<String> = raw
-                .splitn(n, '#')
-                .map(|s| s.split_whitespace().collect::<Vec<&str>>().join(" ").nfc().collect())
-                .collect();
-            while parts.len() < n {
-                parts.push(String::new());
+        let mut read_attempt = 1;
+        loop {
+            match self.read_with_timeout(card).await {
+                Ok(data) => {
+                    info!("Successfully read Thai ID: {} (read attempt {}/{})",
+                        decoder::mask_citizen_id(&data.citizen_id), read_attempt, read_retry_attempts);
+                    return Ok(data);
+                }
+                Err(e) if e.is_reader_gone() => return Err(e),
+                Err(e @ ReaderError::Timeout(_)) => return Err(e),
+                Err(e) => {
+                    warn!("Failed to read card data (read attempt {}/{}): {}", read_attempt, read_retry_attempts, e);
+                    if read_attempt >= read_retry_attempts {
+                        return Err(e);
+                    }
+                    sleep(read_retry_delay).await;
+                    read_attempt += 1;
+                }
             }
-            parts
-        };
-
-        // Read all configured fields
-        let citizen_id   = read_field("citizen_id")?;
-        let date_of_birth = read_field("date_of_birth")?;
-        let sex           = read_field("gender")?;
-        let issuer        = read_field("issuer").unwrap_or_default();
-        let issue    = read_field("issue")?;
-        let mut expire   = read_field("expire")?;
-        let full_name_en  = read_field("full_name_en")?;
-
-        // Thai name: "คำนำหน้า#ชื่อ#ชื่อกลาง#นามสกุล"
-        let name_th_raw = read_field_raw("full_name_th")?;
-        let name_parts = split_tis620(name_th_raw, 4);
-        let name_en_raw = read_field_raw("full_name_en")?;
-        let en_name_parts = split_tis620(name_en_raw, 4);
-        let th_prefix     = name_parts[0].clone();
-        let th_firstname  = name_parts[1].clone();
-        let th_middlename = name_parts[2].clone();
-        let th_lastname   = name_parts[3].clone();
-        let en_prefix     = en_name_parts[0].clone();
-        let en_firstname  = en_name_parts[1].clone();
-        let en_middlename = en_name_parts[2].clone();
-        let en_lastname   = en_name_parts[3].clone();
-
-        // Address on Thai ID card
-        // Thai ID card address format: [#]เลขที่#หมู่ที่#ตำบล#อำเภอ#จังหวัด#...
-        // We take the raw bytes, decode TIS-620, split by '#', take first 6 parts max,
-        // and keep only parts that contain at least one Thai or ASCII printable character
-        // (filtering out garbage binary padding that may appear after the real data).
-        // Address on Thai ID card: เลขที่#หมู่ที่###ตำบล#อำเภอ#จังหวัด[garbage]
-        // Split by '#', strip garbage from each part (keep only Thai + basic ASCII),
-        // then filter out empty parts → gives clean ordered list.
-        let addr_raw = read_field_raw("address")?;
-
-        // Thai ID card stores address as TIS-620 bytes separated by '#' (0x23).
-        // Valid TIS-620 address bytes: 0x20-0x7E (ASCII printable) and 0xA1-0xFB (Thai).
-        // Garbage padding at end of field uses bytes outside these ranges (e.g. 0x00, 0x80-0x9F, 0xFC+).
-        // Truncate at the first invalid byte to strip garbage BEFORE decoding.
-        let addr_raw_clean: Vec<u8> = addr_raw.iter()
-            .copied()
-            .take_while(|&b| {
-                b == 0x23           // '#' delimiter
-                || (b >= 0x20 && b <= 0x7E)   // ASCII printable
-                || (b >= 0xA1 && b <= 0xFB)   // TIS-620 Thai range
-            })
-            .collect();
+        }
+    }
 
-        // Split by '#', filter empty parts, NFC-normalize
-        let addr_meaningful_parts: Vec<String> = {
-            use encoding_rs::WINDOWS_874;
-            use unicode_normalization::UnicodeNormalization;
-            let (cow, _, _) = WINDOWS_874.decode(&addr_raw_clean);
-            cow.split('#')
-                .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" ").nfc().collect::<String>())
-                // .filter(|s| !s.is_empty())
-                .collect()
-        };
-        debug!("Address meaningful parts ({}): {:?}", addr_meaningful_parts.len(), addr_meaningful_parts);
-
-        // Strip any trailing non-Thai-letter content from a part
-        // (Thai letters: U+0E01-U+0E2E, U+0E30-U+0E3A, U+0E40-U+0E45, U+0E47-U+0E4E)
-        // Thai digits U+0E50-U+0E59 and punctuation are excluded — they indicate garbage
-        let strip_garbage = |s: &str| -> String {
-            // Keep only Thai consonants/vowels/tone-marks and space
-            let clean: String = s.chars()
-                .filter(|&c| {
-                    (c >= '\u{0E01}' && c <= '\u{0E2E}')   // Thai consonants
-                    || (c >= '\u{0E30}' && c <= '\u{0E3A}')// Thai vowels/sara
-                    || (c >= '\u{0E40}' && c <= '\u{0E4E}')// Thai vowels/tone marks
-                    || c == ' '
-                })
-                .collect();
-            // Thai place names never have single-character words; filter them out
-            // to eliminate stray garbage bytes that happen to decode as valid Thai chars
-            clean.split_whitespace()
-                .filter(|w| w.chars().count() >= 2)
-                .collect::<Vec<_>>()
-                .join(" ")
+    /// `read_thai_id` on a blocking thread, abandoned after `card.read_timeout_ms`
+    ///
+    /// An abandoned read keeps its thread until the stuck transmit returns.
+    async fn read_with_timeout<T>(&self, card: &Arc<T>) -> Result<decoder::ThaiIDData, ReaderError>
+    where T: ApduTransport + Send + Sync + 'static
+    {
+        let Some(limit) = self.config.read_timeout() else {
+            return self.read_thai_id(card.as_ref());
         };
 
-        let addr_house_no   = addr_meaningful_parts.get(0).cloned().unwrap_or_default();
-        let addr_village_no = addr_meaningful_parts.get(1).cloned().unwrap_or_default();
-        let addr_lane = addr_meaningful_parts.get(2).cloned().unwrap_or_default();
-        let addr_road = addr_meaningful_parts.get(3).cloned().unwrap_or_default();
-
-        // Thai ID card address can be 7 or 8 fields depending on card variant:
-        //   7-field: house#village#lane#road#tambol#amphur#province         (indices 4,5,6)
-        //   8-field: house#village#lane#road#(empty)#tambol#amphur#province (indices 5,6,7)
-        // Detect by checking if index 4 is non-empty after strip_garbage.
-        let part4_clean = addr_meaningful_parts.get(4).map(|s| strip_garbage(s)).unwrap_or_default();
-
-        debug!("Determined address format: part4='{}' → {}", part4_clean, if part4_clean.is_empty() { "8-field" } else { "7-field" });
+        let reader = self.detached();
+        let card = Arc::clone(card);
+        let span = tracing::Span::current();
+        let read = tokio::task::spawn_blocking(move || span.in_scope(|| reader.read_thai_id(card.as_ref())));
+        match tokio::time::timeout(limit, read).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => Err(ReaderError::Timeout(limit)),
+        }
+    }
 
-        let (tambol_idx, amphur_idx, province_idx) = if part4_clean.is_empty() {
-            (5, 6, 7) // 8-field format: index 4 is empty filler
-        } else {
-            (4, 5, 6) // 7-field format: tambol starts at index 4
+    /// Send `apdu` (after `select`, if given) and report the card's answer
+    ///
+    /// Both are transmitted as given: no 61 XX / 6C XX follow-up commands and
+    /// no `max_field_bytes` limit. An error status word is part of the report,
+    /// not an error.
+    ///
+    /// # Errors
+    /// Returns a [`ReaderError`] if the SELECT or the transmit itself fails
+    pub fn probe_apdu<T: ApduTransport>(&self, card: &T, select: Option<&[u8]>, apdu: &[u8]) -> Result<ApduProbe, ReaderError> {
+        let transmit_raw = |apdu: &[u8]| -> Result<ApduProbe, ReaderError> {
+            let mut rapdu_buf = vec![0u8; pcsc::MAX_BUFFER_SIZE_EXTENDED];
+            let (data, sw1, sw2) = split_status(card.transmit(apdu, &mut rapdu_buf)?)?;
+            Ok(ApduProbe { data: data.to_vec(), sw1, sw2 })
         };
-        let addr_tambol   = addr_meaningful_parts.get(tambol_idx).map(|s| strip_garbage(s)).unwrap_or_default();
-        let addr_amphur   = addr_meaningful_parts.get(amphur_idx).map(|s| strip_garbage(s)).unwrap_or_default();
-        let addr_province = addr_meaningful_parts.get(province_idx).map(|s| strip_garbage(s)).unwrap_or_default();
+        if let Some(select) = select {
+            let selected = transmit_raw(select).map_err(|e| ReaderError::Select(Box::new(e)))?;
+            // 61 XX: selected, with a response left unfetched
+            if !selected.is_success() && selected.sw1 != 0x61 {
+                let (sw1, sw2) = (selected.sw1, selected.sw2);
+                return Err(ReaderError::Select(Box::new(ReaderError::Apdu { sw1, sw2 })));
+            }
+        }
+        transmit_raw(apdu)
+    }
 
-        debug!("Cleaned address components: house_no='***', village_no='***', road='***', lane='***', tambol='***', amphur='***', province='***'");
-        // Note: Actual address data available in debug logs only (set RUST_LOG=debug to enable)
+    /// Probe the card in the first reader that has one (debugging tool)
+    ///
+    /// # Errors
+    /// Returns [`ReaderError::NoCard`] if no reader holds a card
+    pub fn probe_first_card(&self, select: Option<&[u8]>, apdu: &[u8]) -> Result<(String, ApduProbe), ReaderError> {
+        let ctx = self.ctx.as_ref().ok_or(ReaderError::NoReader(pcsc::Error::InvalidHandle))?;
+        let mut readers_buf = [0; 2048];
+        let readers: Vec<CString> = ctx
+            .list_readers(&mut readers_buf)?
+            .filter_map(|r| CString::new(r.to_bytes()).ok())
+            .collect();
 
-        // Full address: house + village + road + lane + tambol + amphur + province
-        let address = [&addr_house_no, &addr_village_no, &addr_road, &addr_lane, &addr_tambol, &addr_amphur, &addr_province]
-            .iter()
-            .map(|s| s.as_str())
-            .collect::<Vec<_>>()
-            .join(" "); 
+        for reader in &readers {
+            let card = match ctx.connect(reader, pcsc_share_mode(self.config.share_mode), pcsc_protocols(self.config.protocol)) {
+                Ok(card) => card,
+                Err(e) => {
+                    debug!("No card in {:?}: {}", reader, e);
+                    continue;
+                }
+            };
+            let probe = self.probe_apdu(&card, select, apdu)?;
+            return Ok((reader.to_string_lossy().to_string(), probe));
+        }
+        Err(ReaderError::NoCard)
+    }
 
-        // Read Photo using configured chunk APDUs
-        let mut photo_chunks = Vec::new();
-        let photo_apdus = self.config.photo_chunk_bytes();
-        let total_chunks = photo_apdus.len();
+    /// Read the card in the first reader that has one `reads` times, reconnecting
+    /// for each read, and summarize the latency of every stage
+    ///
+    /// Failed reads are counted and left out of the timings.
+    ///
+    /// # Errors
+    /// Returns [`ReaderError::NoCard`] if no reader holds a card
+    pub fn benchmark_first_card(&self, reads: usize) -> Result<BenchmarkReport, ReaderError> {
+        let ctx = self.ctx.as_ref().ok_or(ReaderError::NoReader(pcsc::Error::InvalidHandle))?;
+        let mut readers_buf = [0; 2048];
+        let readers: Vec<CString> = ctx
+            .list_readers(&mut readers_buf)?
+            .filter_map(|r| CString::new(r.to_bytes()).ok())
+            .collect();
+        let connect = |reader: &CString| ctx.connect(reader, pcsc_share_mode(self.config.share_mode), pcsc_protocols(self.config.protocol));
+        let reader = readers.iter().find(|reader| connect(reader).is_ok()).ok_or(ReaderError::NoCard)?;
 
-        for (i, apdu) in photo_apdus.iter().enumerate() {
-            match self.send_apdu(card, apdu) {
-                Ok(data) => {
-                    debug!("Photo chunk {}/{}: {} bytes", i + 1, total_chunks, data.len());
-                    photo_chunks.push(data);
+        let mut timed = Vec::with_capacity(reads);
+        let mut failures = 0;
+        for i in 1..=reads {
+            let timings = ReadTimings::default();
+            let started = Instant::now();
+            let result = connect(reader).map_err(ReaderError::from).and_then(|card| {
+                timings.record("connect", started.elapsed());
+                self.read_thai_id_timed(&card, &timings)
+            });
+            match result {
+                Ok(_) => {
+                    timings.record("total", started.elapsed());
+                    timed.push(timings);
                 }
+                Err(e) if e.is_reader_gone() => return Err(e),
                 Err(e) => {
-                    warn!("Failed to read photo chunk {}/{}: {}", i + 1, total_chunks, e);
+                    warn!("Benchmark read {}/{} failed: {}", i, reads, e);
+                    failures += 1;
                 }
             }
         }
+        Ok(BenchmarkReport::new(&reader.to_string_lossy(), timed, failures))
+    }
 
-        let total_bytes: usize = photo_chunks.iter().map(|c| c.len()).sum();
-        if photo_chunks.len() < total_chunks {
-            warn!("Photo incomplete: read {}/{} chunks ({} bytes)",
-                photo_chunks.len(), total_chunks, total_bytes);
-        } else {
-            info!("Photo complete: {}/{} chunks ({} bytes)",
-                photo_chunks.len(), total_chunks, total_bytes);
-        }
-        let photo = decoder::combine_photo_chunks(photo_chunks);
+    /// Read and assemble one card
+    ///
+    /// # Errors
+    /// Returns a [`ReaderError`] for SELECT, required field or reader failures
+    pub fn read_thai_id<T: ApduTransport>(&self, card: &T) -> Result<decoder::ThaiIDData, ReaderError> {
+        self.read_thai_id_timed(card, &ReadTimings::default())
+    }
+
+    /// [`Self::read_thai_id`], recording how long each stage took into `timings`
+    ///
+    /// # Errors
+    /// Returns a [`ReaderError`] for SELECT, required field or reader failures
+    pub fn read_thai_id_timed<T: ApduTransport>(&self, card: &T, timings: &ReadTimings) -> Result<decoder::ThaiIDData, ReaderError> {
+        // SELECT Thai ID Applet from config
+        let select_apdu = self.config.select_apdu_bytes();
+        debug!("SELECT APDU: {:02X?}", select_apdu);
+        let started = Instant::now();
+        self.select_applet(card, &select_apdu)
+            .map_err(|e| ReaderError::Select(Box::new(e)))?;
+        timings.record("select", started.elapsed());
 
-        // Convert date from YYYYMMDD → YYYY/MM/DD (required by HIS moment() parsing)
-        let format_date_slash = |d: &str| -> String {
-            if d.len() == 8 {
-                format!("{}/{}/{}", &d[0..4], &d[4..6], &d[6..8])
-            } else {
-                d.to_string()
+        // Helper: read raw bytes of a field by name from config
+        let enforce_required = self.config.enforce_required;
+        let read_field = |name: &str| -> Result<Vec<u8>, ReaderError> {
+            let Some(field) = self.config.get_field(name) else {
+                warn!("Field '{}' not found in config, using empty string", name);
+                return Ok(Vec::new());
+            };
+            let apdu = field.to_bytes();
+            debug!("Reading {}: APDU {:02X?}", name, apdu);
+            let started = Instant::now();
+            let result = self.send_apdu(card, &apdu);
+            timings.record(name, started.elapsed());
+            if !enforce_required {
+                return result.map_err(|e| ReaderError::field(name, e));
+            }
+            match result {
+                Ok(bytes) if field.required && is_blank(&bytes) => Err(ReaderError::field(name, ReaderError::Empty)),
+                Ok(bytes) => Ok(bytes),
+                Err(e) if field.required || e.is_reader_gone() => Err(ReaderError::field(name, e)),
+                Err(e) => {
+                    warn!("Optional field '{}' unreadable, leaving it empty: {}", name, e);
+                    Ok(Vec::new())
+                }
+            }
+        };
+
+        // Read all configured fields
+        let citizen_id    = read_field("citizen_id")?;
+        let date_of_birth = read_field("date_of_birth")?;
+        let gender        = read_field("gender")?;
+        // Always optional unless enforce_required defers to the field's flag:
+        // a card error leaves them empty, a missing reader aborts the read
+        let read_optional_field = |name: &str| -> Result<Vec<u8>, ReaderError> {
+            if enforce_required {
+                return read_field(name);
+            }
+            match read_field(name) {
+                Ok(bytes) => Ok(bytes),
+                Err(e) if e.is_reader_gone() => Err(e),
+                Err(e) => {
+                    warn!("{}", e);
+                    Ok(Vec::new())
+                }
             }
         };
+        let issuer = read_optional_field("issuer")?;
+        let issue        = read_field("issue")?;
+        let expire       = read_field("expire")?;
+        let full_name_en = read_field("full_name_en")?;
+        let full_name_th = read_field("full_name_th")?;
+        let address      = read_field("address")?;
+        // Serial / laser number exists on some card generations only; configs
+        // without the field skip it quietly
+        let card_serial = if self.config.get_field("card_serial").is_some() {
+            read_optional_field("card_serial")?
+        } else {
+            Vec::new()
+        };
+        // English address likewise (second-generation cards)
+        let address_en = if self.config.get_field("address_en").is_some() {
+            read_optional_field("address_en")?
+        } else {
+            Vec::new()
+        };
+
+        // Read Photo using configured chunk APDUs, unless it is never emitted
+        let photo_chunks = if self.read_photo {
+            let started = Instant::now();
+            let chunks = self.read_photo_chunks(card)?;
+            timings.record("photo", started.elapsed());
+            chunks
+        } else {
+            debug!("Photo disabled in output config, skipping photo chunks");
+            Vec::new()
+        };
+        let photo_missing_chunks = photo_chunks.iter().any(Option::is_none);
 
-        let nationality: String = "THA".to_string();
-        if expire == "99999999" {
-            expire = "29991231".to_string(); // Treat "99999999" as "31 Dec 2599 for practical purposes
+        // NHSO applet last: selecting it leaves the identity applet
+        let started = Instant::now();
+        let nhso = self.read_nhso(card)?;
+        if self.config.nhso_select_apdu_bytes().is_some() {
+            timings.record("nhso", started.elapsed());
         }
 
-        Ok(decoder::ThaiIDData {
+        let mut data = decoder::assemble_thai_id(decoder::RawCardFields {
             citizen_id,
-            th_prefix,
-            th_firstname,
-            th_middlename,
-            th_lastname,
-            en_prefix,
-            en_firstname,
-            en_middlename,
-            en_lastname,
+            full_name_th,
             full_name_en,
-            birthday: format_date_slash(&date_of_birth),
-            sex,
+            date_of_birth,
+            gender,
             issuer,
-            issue: format_date_slash(&issue),
-            expire: format_date_slash(&expire),
+            issue,
+            expire,
             address,
-            addr_house_no,
-            addr_village_no,
-            addr_road,
-            addr_lane,
-            addr_tambol,
-            addr_amphur,
-            addr_province,
-            photo,
-            nationality
-        })
+            address_en,
+            card_serial,
+            photo_chunks: photo_chunks.into_iter().flatten().collect(),
+            photo_missing_chunks,
+        });
+        data.nhso = nhso;
+
+        if let Some(reference) = &self.address_reference {
+            let mut parsed_address = data.parsed_address();
+            let unmatched = reference.normalize(&mut parsed_address);
+            if !unmatched.is_empty() {
+                warn!("⚠️ Address components not found in reference data: {}", unmatched.join(", "));
+            }
+            data.set_address(parsed_address);
+        }
+        debug!("Cleaned address components: house_no='***', village_no='***', road='***', lane='***', tambol='***', amphur='***', province='***'");
+        // Note: Actual address data available in debug logs only (set RUST_LOG=debug to enable)
+
+        Ok(data)
     }
 
-    fn send_apdu(&self, card: &Card, apdu: &[u8]) -> Result<Vec<u8>> {
-        let mut rapdu_buf = [0u8; 514]; // 512 data + 2 SW bytes
-        let rapdu = card.transmit(apdu, &mut rapdu_buf)
-            .map_err(|e| anyhow!("Card transmit failed: {}", e))?;
+    /// SELECT the applet, trying `fallback_select_apdus` in order if the primary fails
+    fn select_applet<T: ApduTransport>(&self, card: &T, select_apdu: &[u8]) -> Result<(), ReaderError> {
+        let primary_error = match self.send_apdu(card, select_apdu) {
+            Ok(_) => return Ok(()),
+            Err(e) if e.is_reader_gone() => return Err(e),
+            Err(e) => e,
+        };
 
-        if rapdu.len() < 2 {
-            return Err(anyhow!("Invalid APDU response length: {} bytes (expected >= 2)", rapdu.len()));
+        for fallback in self.config.fallback_select_apdu_bytes() {
+            debug!("SELECT failed ({}), trying fallback APDU: {:02X?}", primary_error, fallback);
+            match self.send_apdu(card, &fallback) {
+                Ok(_) => {
+                    info!("Applet selected with fallback APDU {:02X?}", fallback);
+                    return Ok(());
+                }
+                Err(e) if e.is_reader_gone() => return Err(e),
+                Err(e) => debug!("Fallback SELECT failed: {}", e),
+            }
         }
+        Err(primary_error)
+    }
 
-        let sw1 = rapdu[rapdu.len() - 2];
-        let sw2 = rapdu[rapdu.len() - 1];
+    /// Read all configured photo chunks, one entry per chunk (`None` if unreadable)
+    ///
+    /// Failed chunks are retried once after the others. Fails only if the
+    /// reader disappears mid-read. A photo larger than `max_photo_bytes` is
+    /// discarded (empty result).
+    fn read_photo_chunks<T: ApduTransport>(&self, card: &T) -> Result<Vec<Option<Vec<u8>>>, ReaderError> {
+        let photo_apdus = self.config.photo_chunk_bytes();
+        let total_chunks = photo_apdus.len();
+        let mut photo_chunks: Vec<Option<Vec<u8>>> = vec![None; total_chunks];
+        let max_bytes = self.config.max_photo_bytes;
+        let mut total_bytes = 0;
+
+        for attempt in 0..2 {
+            for (i, apdu) in photo_apdus.iter().enumerate() {
+                if photo_chunks[i].is_some() {
+                    continue;
+                }
+                match self.send_apdu_limited(card, apdu, self.config.max_photo_chunk_bytes) {
+                    Ok(data) => {
+                        debug!("Photo chunk {}/{}: {} bytes", i + 1, total_chunks, data.len());
+                        total_bytes += data.len();
+                        photo_chunks[i] = Some(data);
 
-        // Handle chained T=0 GET RESPONSE (61 XX)
-        if sw1 == 0x61 {
-            let mut result = Vec::new();
-            // Collect data before SW if any
-            if rapdu.len() > 2 {
-                result.extend_from_slice(&rapdu[..rapdu.len() - 2]);
+                        if max_bytes > 0 && total_bytes > max_bytes {
+                            warn!("⚠️ Photo exceeds {} bytes after chunk {}/{}, discarding photo",
+                                max_bytes, i + 1, total_chunks);
+                            return Ok(Vec::new());
+                        }
+                    }
+                    Err(e) if e.is_reader_gone() => return Err(e),
+                    Err(e) if attempt == 0 => {
+                        warn!("Failed to read photo chunk {}/{}, retrying later: {}", i + 1, total_chunks, e);
+                    }
+                    Err(e) => {
+                        warn!("Failed to read photo chunk {}/{}: {}", i + 1, total_chunks, e);
+                    }
+                }
             }
-            let mut remaining = sw2;
-            loop {
-                let get_response_cmd = [0x00, 0xC0, 0x00, 0x00, remaining];
-                let resp = card.transmit(&get_response_cmd, &mut rapdu_buf)?;
-                if resp.len() < 2 {
-                    return Err(anyhow!("Invalid GET RESPONSE length"));
+        }
+
+        let read_chunks = photo_chunks.iter().flatten().count();
+        if read_chunks < total_chunks {
+            warn!("Photo incomplete: read {}/{} chunks ({} bytes)",
+                read_chunks, total_chunks, total_bytes);
+        } else {
+            info!("Photo complete: {}/{} chunks ({} bytes)",
+                read_chunks, total_chunks, total_bytes);
+        }
+        Ok(photo_chunks)
+    }
+
+    /// Read the NHSO (health insurance) fields if `nhso_select_apdu` is configured
+    ///
+    /// Cards without the applet (failed SELECT) and unreadable fields are
+    /// skipped; fails only if the reader disappears mid-read.
+    fn read_nhso<T: ApduTransport>(&self, card: &T) -> Result<BTreeMap<String, String>, ReaderError> {
+        let mut nhso = BTreeMap::new();
+        let Some(select_apdu) = self.config.nhso_select_apdu_bytes() else {
+            return Ok(nhso);
+        };
+
+        debug!("NHSO SELECT APDU: {:02X?}", select_apdu);
+        match self.send_apdu(card, &select_apdu) {
+            Ok(_) => {}
+            Err(e) if e.is_reader_gone() => return Err(e),
+            Err(e) => {
+                debug!("No NHSO applet on card ({}), skipping NHSO data", e);
+                return Ok(nhso);
+            }
+        }
+
+        // Same rules as the identity fields: with `enforce_required`, a
+        // required field that fails or reads back blank fails the read
+        let enforce = self.config.enforce_required;
+        for field in &self.config.nhso_fields {
+            match self.send_apdu(card, &field.to_bytes()) {
+                Ok(bytes) if enforce && field.required && is_blank(&bytes) => {
+                    return Err(ReaderError::field(&field.name, ReaderError::Empty));
+                }
+                Ok(bytes) => {
+                    nhso.insert(field.name.clone(), decoder::decode_tis620(&bytes));
                 }
-                let rsw1 = resp[resp.len() - 2];
-                let rsw2 = resp[resp.len() - 1];
-                result.extend_from_slice(&resp[..resp.len() - 2]);
-
-                if rsw1 == 0x61 {
-                    // More data available
-                    remaining = rsw2;
-                } else if rsw1 == 0x90 && rsw2 == 0x00 {
-                    break;
-                } else {
-                    return Err(anyhow!("GET RESPONSE failed with status: SW1={:02X} SW2={:02X} ({})",
-                        rsw1, rsw2, Self::interpret_sw(rsw1, rsw2)));
+                Err(e) if e.is_reader_gone() || (enforce && field.required) => {
+                    return Err(ReaderError::field(&field.name, e));
                 }
+                Err(e) => warn!("NHSO field '{}' unreadable, skipping: {}", field.name, e),
+            }
+        }
+        info!("NHSO data read: {}/{} fields", nhso.len(), self.config.nhso_fields.len());
+        Ok(nhso)
+    }
+
+    /// Send one APDU, rejecting responses longer than `card.max_field_bytes`
+    fn send_apdu<T: ApduTransport>(&self, card: &T, apdu: &[u8]) -> Result<Vec<u8>, ReaderError> {
+        self.send_apdu_limited(card, apdu, self.config.max_field_bytes)
+    }
+
+    /// Send one APDU, following Wrong Le and GET RESPONSE chains
+    ///
+    /// A response over `max_bytes` (0 = no limit) is rejected as soon as it
+    /// crosses the limit, before the rest of a chain is fetched.
+    fn send_apdu_limited<T: ApduTransport>(&self, card: &T, apdu: &[u8], max_bytes: usize) -> Result<Vec<u8>, ReaderError> {
+        let check_len = |len: usize| -> Result<(), ReaderError> {
+            if max_bytes > 0 && len > max_bytes {
+                warn!("⚠️ Card response exceeds {} bytes, rejecting it (APDU {:02X?})", max_bytes, apdu);
+                return Err(ReaderError::TooLong(max_bytes));
             }
-            Ok(result)
-        } else if sw1 == 0x90 && sw2 == 0x00 {
-            Ok(rapdu[..rapdu.len() - 2].to_vec())
+            Ok(())
+        };
+
+        // Pace commands for readers that can't keep up with back-to-back APDUs
+        if self.config.apdu_delay_ms > 0 {
+            std::thread::sleep(Duration::from_millis(self.config.apdu_delay_ms));
+        }
+
+        let mut rapdu_buf = [0u8; 514]; // 512 data + 2 SW bytes
+
+        let (mut data, mut sw1, mut sw2) = split_status(card.transmit(apdu, &mut rapdu_buf)?)
+            .map(|(data, sw1, sw2)| (data.to_vec(), sw1, sw2))?;
+
+        // Wrong Le (6C XX): re-issue once with Le = XX (ISO 7816-4)
+        if sw1 == 0x6C {
+            let corrected = with_le(apdu, sw2);
+            debug!("Wrong Le, re-issuing with Le={:02X}", sw2);
+            (data, sw1, sw2) = split_status(card.transmit(&corrected, &mut rapdu_buf)?)
+                .map(|(data, sw1, sw2)| (data.to_vec(), sw1, sw2))?;
+        }
+        check_len(data.len())?;
+
+        // Handle chained T=0 GET RESPONSE (61 XX), bounded against cards that never finish
+        let max_responses = match self.config.max_get_response_chain {
+            0 => GET_RESPONSE_HARD_CAP,
+            n => n,
+        };
+        let mut responses = 0;
+        while sw1 == 0x61 {
+            responses += 1;
+            if responses > max_responses {
+                warn!("⚠️ Card kept answering 61 XX, giving up after {} GET RESPONSE commands", max_responses);
+                return Err(ReaderError::Decode(format!(
+                    "GET RESPONSE chain longer than {} responses",
+                    max_responses
+                )));
+            }
+            let get_response_cmd = [0x00, 0xC0, 0x00, 0x00, sw2];
+            let (chunk, rsw1, rsw2) = split_status(card.transmit(&get_response_cmd, &mut rapdu_buf)?)?;
+            data.extend_from_slice(chunk);
+            check_len(data.len())?;
+            (sw1, sw2) = (rsw1, rsw2);
+        }
+
+        if sw1 == 0x90 && sw2 == 0x00 {
+            Ok(data)
         } else {
-            Err(anyhow!("APDU failed with status: SW1={:02X} SW2={:02X} ({})",
-                sw1, sw2, Self::interpret_sw(sw1, sw2)))
+            Err(ReaderError::Apdu { sw1, sw2 })
         }
     }
 
@@ -501,3 +1371,972 @@ impl CardReader {
         }
     }
 }
+
+impl Drop for CardReader {
+    fn drop(&mut self) {
+        debug!("Card reader dropped (PCSC context held: {})", self.ctx.is_some());
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::time::Instant;
+
+    /// Scripted transport: pops queued responses, then answers from
+    /// `by_apdu` (data + `90 00`), then `AA 90 00`
+    #[derive(Default)]
+    struct MockTransport {
+        responses: parking_lot::Mutex<VecDeque<Result<Vec<u8>, pcsc::Error>>>,
+        by_apdu: std::collections::HashMap<Vec<u8>, Vec<u8>>,
+        sent: parking_lot::Mutex<Vec<Vec<u8>>>,
+        /// Stall before answering each APDU
+        delay: Duration,
+    }
+
+    impl MockTransport {
+        fn with_responses(responses: Vec<Result<Vec<u8>, pcsc::Error>>) -> Self {
+            Self {
+                responses: parking_lot::Mutex::new(responses.into()),
+                ..Self::default()
+            }
+        }
+
+        /// Card that answers each configured field APDU with TIS-620 `value`
+        fn with_fields(config: &CardConfig, fields: &[(&str, &str)]) -> Self {
+            let by_apdu = fields
+                .iter()
+                .map(|(name, value)| {
+                    let apdu = config.get_field(name).unwrap().to_bytes();
+                    (apdu, encoding_rs::WINDOWS_874.encode(value).0.into_owned())
+                })
+                .collect();
+            Self {
+                by_apdu,
+                ..Self::default()
+            }
+        }
+    }
+
+    impl ApduTransport for MockTransport {
+        fn transmit<'buf>(&self, apdu: &[u8], buf: &'buf mut [u8]) -> Result<&'buf [u8], pcsc::Error> {
+            self.sent.lock().push(apdu.to_vec());
+            std::thread::sleep(self.delay);
+            let queued = self.responses.lock().pop_front();
+            let response = match queued {
+                Some(response) => response?,
+                None => {
+                    let mut data = self.by_apdu.get(apdu).cloned().unwrap_or_else(|| vec![0xAA]);
+                    data.extend_from_slice(&[0x90, 0x00]);
+                    data
+                }
+            };
+            buf[..response.len()].copy_from_slice(&response);
+            Ok(&buf[..response.len()])
+        }
+    }
+
+    fn test_reader(config: CardConfig) -> CardReader {
+        CardReader::deferred(config)
+    }
+
+    #[test]
+    fn test_apdu_delay_applied_per_chunk() {
+        let config = CardConfig {
+            apdu_delay_ms: 10,
+            ..CardConfig::default()
+        };
+        let chunks = config.photo_chunks.len();
+        let reader = test_reader(config);
+        let card = MockTransport::default();
+
+        let start = Instant::now();
+        let photo = reader.read_photo_chunks(&card).unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(photo.len(), chunks);
+        assert_eq!(card.sent.lock().len(), chunks);
+        assert!(
+            elapsed >= Duration::from_millis(10 * chunks as u64),
+            "expected >= {} delays, took {:?}",
+            chunks,
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_no_apdu_delay_by_default() {
+        let reader = test_reader(CardConfig::default());
+        let card = MockTransport::default();
+
+        let start = Instant::now();
+        reader.read_photo_chunks(&card).unwrap();
+        // 20 chunks would take >= 200ms even with a 10ms delay
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_wrong_le_is_reissued_with_card_length() {
+        let reader = test_reader(CardConfig::default());
+        let card = MockTransport::with_responses(vec![
+            Ok(vec![0x6C, 0x0D]),
+            Ok(b"1101700230708\x90\x00".to_vec()),
+        ]);
+
+        let data = reader.send_apdu(&card, &[0x80, 0xB0, 0x00, 0x04, 0x02, 0x00, 0x20]).unwrap();
+        assert_eq!(data, b"1101700230708");
+
+        let sent = card.sent.lock();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1], vec![0x80, 0xB0, 0x00, 0x04, 0x02, 0x00, 0x0D]);
+    }
+
+    #[test]
+    fn test_wrong_le_keeps_p2_of_commands_without_le() {
+        // Case 1 (header only): Le is appended, P2 stays
+        assert_eq!(with_le(&[0x80, 0xCA, 0x00, 0x01], 0x0D), [0x80, 0xCA, 0x00, 0x01, 0x0D]);
+        // Otherwise the trailing Le is replaced
+        assert_eq!(with_le(&[0x80, 0xCA, 0x00, 0x01, 0x00], 0x0D), [0x80, 0xCA, 0x00, 0x01, 0x0D]);
+
+        let reader = test_reader(CardConfig::default());
+        let card = MockTransport::with_responses(vec![Ok(vec![0x6C, 0x02]), Ok(vec![0x12, 0x34, 0x90, 0x00])]);
+        assert_eq!(reader.send_apdu(&card, &[0x80, 0xCA, 0x00, 0x01]).unwrap(), [0x12, 0x34]);
+        assert_eq!(card.sent.lock()[1], vec![0x80, 0xCA, 0x00, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_wrong_le_is_retried_only_once() {
+        let reader = test_reader(CardConfig::default());
+        let card = MockTransport::with_responses(vec![Ok(vec![0x6C, 0x0D]), Ok(vec![0x6C, 0x0E])]);
+
+        let err = reader.send_apdu(&card, &[0x80, 0xB0, 0x00, 0x04, 0x02, 0x00, 0x20]).unwrap_err();
+        assert!(err.to_string().contains("SW1=6C"), "{}", err);
+        assert_eq!(card.sent.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_failed_photo_chunk_is_retried() {
+        let reader = test_reader(CardConfig::default());
+        let chunks = reader.config.photo_chunks.len();
+        let card = MockTransport::with_responses(vec![Ok(vec![0x01, 0x90, 0x00]), Ok(vec![0x6A, 0x82])]);
+
+        let photo = reader.read_photo_chunks(&card).unwrap();
+        assert_eq!(photo.iter().flatten().count(), chunks);
+        assert_eq!(photo[0].as_deref(), Some(&[0x01][..]));
+        // The failed second chunk is re-sent after the last one
+        let sent = card.sent.lock();
+        assert_eq!(sent.len(), chunks + 1);
+        assert_eq!(sent[chunks], sent[1]);
+    }
+
+    #[test]
+    fn test_missing_photo_chunk_yields_incomplete_photo() {
+        use crate::config::OutputConfig;
+
+        // SELECT and fields succeed, photo starts with a JPEG SOI, then chunk 2
+        // fails on both the first read and the retry
+        let config = CardConfig::default();
+        let fields = config.fields.len();
+        let chunks = config.photo_chunks.len();
+        let mut responses = vec![Ok(vec![0x90, 0x00])];
+        responses.extend(vec![Ok(vec![0x31, 0x90, 0x00]); fields]);
+        responses.push(Ok(vec![0xFF, 0xD8, 0x90, 0x00]));
+        responses.push(Ok(vec![0x6A, 0x82]));
+        responses.extend(vec![Ok(vec![0xAA, 0x90, 0x00]); chunks - 2]);
+        responses.push(Ok(vec![0x6A, 0x82]));
+        let card = MockTransport::with_responses(responses);
+
+        let data = test_reader(config).read_thai_id(&card).unwrap();
+        assert_eq!(data.photo_status, decoder::PhotoStatus::Incomplete);
+        assert!(data.photo.is_empty());
+        let payload = decoder::apply_output_config(&data, &OutputConfig::default());
+        assert_eq!(payload["photo_status"], "incomplete");
+        assert_eq!(payload["PhotoRaw"], "");
+    }
+
+    #[tokio::test]
+    async fn test_reader_unplugged_mid_read_is_not_a_card_failure() {
+        let config = CardConfig {
+            read_retry_attempts: 3,
+            read_retry_delay_ms: 0,
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        // SELECT succeeds, then the reader vanishes on the first field read
+        let card = Arc::new(MockTransport::with_responses(vec![
+            Ok(vec![0x90, 0x00]),
+            Err(pcsc::Error::ReaderUnavailable),
+        ]));
+
+        let err = reader.read_with_retries(&card).await.unwrap_err();
+        assert!(matches!(err.root(), ReaderError::NoReader(pcsc::Error::ReaderUnavailable)), "{}", err);
+        assert!(err.is_reader_gone());
+        // No read retries against a missing reader
+        assert_eq!(card.sent.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_card_error_is_retried_as_read_failure() {
+        let config = CardConfig {
+            read_retry_attempts: 2,
+            read_retry_delay_ms: 0,
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        let card = Arc::new(MockTransport::with_responses(vec![
+            Ok(vec![0x90, 0x00]),
+            Err(pcsc::Error::RemovedCard),
+            Ok(vec![0x90, 0x00]),
+            Err(pcsc::Error::RemovedCard),
+        ]));
+
+        let err = reader.read_with_retries(&card).await.unwrap_err();
+        assert!(matches!(err.root(), ReaderError::CardRemoved), "{}", err);
+        assert!(!err.is_reader_gone());
+        assert_eq!(card.sent.lock().len(), 4);
+    }
+
+    #[test]
+    fn test_read_timings_feed_benchmark_report() {
+        let config = CardConfig::default();
+        let chunks = config.photo_chunks.len();
+        let reader = test_reader(config);
+        // Every APDU (SELECT, each field, each photo chunk) takes 2 ms
+        let apdu_delay = Duration::from_millis(2);
+        let card = MockTransport { delay: apdu_delay, ..MockTransport::default() };
+
+        let reads: Vec<ReadTimings> = (0..3)
+            .map(|_| {
+                let timings = ReadTimings::default();
+                reader.read_thai_id_timed(&card, &timings).unwrap();
+                timings
+            })
+            .collect();
+        let report = BenchmarkReport::new("Mock Reader", reads, 0);
+
+        assert_eq!(report.reads, 3);
+        let stage = |name: &str| report.stages.iter().find(|s| s.name == name).unwrap();
+        assert_eq!(report.stages[0].name, "select");
+        for name in ["select", "citizen_id", "address", "photo"] {
+            let stats = stage(name);
+            assert_eq!(stats.samples, 3, "{}", name);
+            assert!(stats.min <= stats.mean && stats.mean <= stats.max, "{}", name);
+            // Nearest rank of 3 samples is the slowest
+            assert_eq!(stats.p95, stats.max, "{}", name);
+        }
+        assert!(stage("citizen_id").min >= apdu_delay);
+        // The photo stage spans all of its chunk APDUs
+        assert!(stage("photo").min >= apdu_delay * u32::try_from(chunks).unwrap());
+        assert!(report.stages.iter().all(|s| s.name != "nhso"));
+    }
+
+    #[tokio::test]
+    async fn test_stuck_read_times_out() {
+        let config = CardConfig {
+            read_timeout_ms: 50,
+            read_retry_attempts: 3,
+            read_retry_delay_ms: 0,
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        // Stalls on SELECT, then fails so the abandoned thread ends soon after
+        let card = Arc::new(MockTransport {
+            delay: Duration::from_millis(300),
+            ..MockTransport::with_responses(vec![Err(pcsc::Error::RemovedCard)])
+        });
+
+        let started = Instant::now();
+        let err = reader.read_with_retries(&card).await.unwrap_err();
+        assert!(matches!(err, ReaderError::Timeout(limit) if limit == Duration::from_millis(50)), "{}", err);
+        assert!(!err.is_reader_gone());
+        assert!(started.elapsed() < Duration::from_millis(300));
+        // The stuck read is abandoned, not retried
+        assert_eq!(card.sent.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_apdu_probe_parses_and_interprets() {
+        let select = parse_apdu_hex("00A4 0400 08 A000000054480001").unwrap();
+        assert_eq!(select[..5], [0x00, 0xA4, 0x04, 0x00, 0x08]);
+        assert!(parse_apdu_hex("80B0 000").unwrap_err().contains("odd"));
+        assert!(parse_apdu_hex("80B0 00ZZ").unwrap_err().contains("'Z'"));
+        assert!(parse_apdu_hex("80B0").is_err());
+
+        let reader = test_reader(CardConfig::default());
+        let card = MockTransport::with_responses(vec![
+            Ok(vec![0x90, 0x00]),
+            Ok(vec![0x31, 0x32, 0x90, 0x00]),
+            Ok(vec![0x6A, 0x82]),
+        ]);
+        let apdu = parse_apdu_hex("80B00004020011").unwrap();
+
+        let probe = reader.probe_apdu(&card, Some(&select), &apdu).unwrap();
+        assert!(probe.is_success());
+        assert_eq!(probe.to_string(), "Response (2 bytes): 31 32\nSW: 90 00 (Success)");
+        assert_eq!(card.sent.lock()[..2], [select.clone(), apdu.clone()]);
+
+        let probe = reader.probe_apdu(&card, None, &apdu).unwrap();
+        assert_eq!((probe.sw1, probe.sw2), (0x6A, 0x82));
+        assert_eq!(probe.meaning(), "File not found");
+
+        // Sent raw: 61 XX / 6C XX are reported, not followed up, and no size cap
+        let reader = test_reader(CardConfig {
+            max_field_bytes: 4,
+            ..CardConfig::default()
+        });
+        let mut long = vec![0x41; 200];
+        long.extend([0x90, 0x00]);
+        let card = MockTransport::with_responses(vec![Ok(vec![0x61, 0x10]), Ok(vec![0x6C, 0x0D]), Ok(long)]);
+        let probe = reader.probe_apdu(&card, Some(&select), &apdu).unwrap();
+        assert_eq!((probe.sw1, probe.sw2), (0x6C, 0x0D));
+        assert_eq!(card.sent.lock().len(), 2);
+        let probe = reader.probe_apdu(&card, None, &apdu).unwrap();
+        assert!(probe.is_success());
+        assert_eq!(probe.data.len(), 200);
+    }
+
+    #[test]
+    fn test_read_error_variants() {
+        let reader = test_reader(CardConfig::default());
+
+        // Applet missing: SELECT context around the status word
+        let card = MockTransport::with_responses(vec![Ok(vec![0x6A, 0x82])]);
+        let err = reader.read_thai_id(&card).unwrap_err();
+        assert!(matches!(err, ReaderError::Select(_)), "{}", err);
+        assert!(matches!(err.root(), ReaderError::Apdu { sw1: 0x6A, sw2: 0x82 }));
+        assert!(err.to_string().contains("File not found"));
+
+        // Card gone from the reader while reading a field
+        let card = MockTransport::with_responses(vec![Ok(vec![0x90, 0x00]), Err(pcsc::Error::NoSmartcard)]);
+        let err = reader.read_thai_id(&card).unwrap_err();
+        assert!(matches!(&err, ReaderError::Field { name, .. } if name == "citizen_id"), "{}", err);
+        assert!(matches!(err.root(), ReaderError::NoCard));
+
+        // Truncated response
+        let card = MockTransport::with_responses(vec![Ok(vec![0x90, 0x00]), Ok(vec![0x90])]);
+        let err = reader.read_thai_id(&card).unwrap_err();
+        assert!(matches!(err.root(), ReaderError::Decode(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_read_events_carry_read_span_fields() {
+        let logs = crate::logging::CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = CardConfig {
+            read_retry_attempts: 1,
+            read_retry_delay_ms: 0,
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        let card = Arc::new(MockTransport::with_responses(vec![Ok(vec![0x90, 0x00]), Err(pcsc::Error::RemovedCard)]));
+
+        let _ = reader.read_with_retries(&card).instrument(read_span("Test Reader 00")).await;
+
+        let output = logs.contents();
+        let line = output
+            .lines()
+            .find(|l| l.contains("Failed to read card data"))
+            .expect("read failure should be logged");
+        assert!(line.contains("card_read{read_id="), "{}", line);
+        assert!(line.contains("reader=Test Reader 00"), "{}", line);
+    }
+
+    #[test]
+    fn test_oversized_photo_is_discarded() {
+        let config = CardConfig {
+            max_photo_bytes: 4,
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        // Every chunk answers one byte, so the cap is crossed on chunk 5
+        let card = MockTransport::default();
+
+        let photo = reader.read_photo_chunks(&card).unwrap();
+        assert!(photo.is_empty());
+        assert_eq!(card.sent.lock().len(), 5);
+    }
+
+    #[test]
+    fn test_malformed_responses_are_decode_errors() {
+        let reader = test_reader(CardConfig {
+            max_field_bytes: 0,
+            ..CardConfig::default()
+        });
+        let apdu = [0x80, 0xB0, 0x00, 0x04, 0x02, 0x00, 0x0D];
+        let send = |responses: Vec<Result<Vec<u8>, pcsc::Error>>| {
+            let card = MockTransport::with_responses(responses);
+            let result = reader.send_apdu(&card, &apdu);
+            let sent = card.sent.lock().len();
+            (result, sent)
+        };
+
+        // Empty and one-byte responses, directly or after Wrong Le / GET RESPONSE
+        for responses in [
+            vec![Ok(vec![])],
+            vec![Ok(vec![0x90])],
+            vec![Ok(vec![0x6C, 0x0D]), Ok(vec![])],
+            vec![Ok(vec![0x31, 0x61, 0x02]), Ok(vec![0x00])],
+        ] {
+            let (result, _) = send(responses);
+            assert!(matches!(result, Err(ReaderError::Decode(_))), "{:?}", result);
+        }
+
+        // Chained data is assembled; an error status mid-chain is reported
+        let (result, _) = send(vec![Ok(vec![0x31, 0x61, 0x02]), Ok(vec![0x32, 0x33, 0x90, 0x00])]);
+        assert_eq!(result.unwrap(), b"123");
+        let (result, _) = send(vec![Ok(vec![0x61, 0x02]), Ok(vec![0x6A, 0x82])]);
+        assert!(matches!(result, Err(ReaderError::Apdu { sw1: 0x6A, sw2: 0x82 })), "{:?}", result);
+
+        // A card that keeps answering 61 XX without data is cut off
+        let (result, sent) = send(vec![Ok(vec![0x61, 0x00]); 100]);
+        assert!(matches!(result, Err(ReaderError::Decode(_))), "{:?}", result);
+        assert_eq!(sent, 1 + CardConfig::default().max_get_response_chain);
+    }
+
+    #[test]
+    fn test_endless_get_response_chain_terminates() {
+        let apdu = [0x80, 0xB0, 0x00, 0x04, 0x02, 0x00, 0x0D];
+
+        // Card always answers 61 01: stopped by the chain cap
+        let reader = test_reader(CardConfig {
+            max_get_response_chain: 5,
+            max_field_bytes: 0,
+            ..CardConfig::default()
+        });
+        let card = MockTransport::with_responses(vec![Ok(vec![0x61, 0x01]); 1000]);
+        let err = reader.send_apdu(&card, &apdu).unwrap_err();
+        assert!(err.to_string().contains("longer than 5 responses"), "{}", err);
+        assert_eq!(card.sent.lock().len(), 6);
+
+        // Same card sending a byte each time: stopped by the byte cap first
+        let reader = test_reader(CardConfig {
+            max_get_response_chain: 0,
+            max_field_bytes: 16,
+            ..CardConfig::default()
+        });
+        let card = MockTransport::with_responses(vec![Ok(vec![0xAA, 0x61, 0x01]); 1000]);
+        assert!(matches!(reader.send_apdu(&card, &apdu), Err(ReaderError::TooLong(16))));
+        assert_eq!(card.sent.lock().len(), 17);
+
+        // Neither cap configured: the hard cap still stops the chain
+        let reader = test_reader(CardConfig {
+            max_get_response_chain: 0,
+            max_field_bytes: 0,
+            ..CardConfig::default()
+        });
+        let card = MockTransport::with_responses(vec![Ok(vec![0xAA, 0x61, 0x01]); 1000]);
+        let err = reader.send_apdu(&card, &apdu).unwrap_err();
+        assert!(err.to_string().contains("longer than 256 responses"), "{}", err);
+        assert_eq!(card.sent.lock().len(), 1 + GET_RESPONSE_HARD_CAP);
+    }
+
+    #[test]
+    fn test_over_long_responses_are_rejected() {
+        let config = CardConfig {
+            max_field_bytes: 8,
+            max_photo_chunk_bytes: 16,
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        let field_apdu = [0x80, 0xB0, 0x00, 0x04, 0x02, 0x00, 0x0D];
+
+        let card = MockTransport::with_responses(vec![Ok(b"1101700230708\x90\x00".to_vec())]);
+        let err = reader.send_apdu(&card, &field_apdu).unwrap_err();
+        assert!(matches!(err, ReaderError::TooLong(8)), "{}", err);
+
+        // A GET RESPONSE chain that never ends is cut off once over the limit
+        let mut chain = vec![Ok(vec![0x61, 0x05])];
+        chain.extend(vec![Ok(vec![0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0x61, 0x05]); 100]);
+        let card = MockTransport::with_responses(chain);
+        let err = reader.send_apdu(&card, &field_apdu).unwrap_err();
+        assert!(matches!(err, ReaderError::TooLong(8)), "{}", err);
+        assert_eq!(card.sent.lock().len(), 3);
+
+        // Photo chunks have their own, larger cap: 13 bytes pass, 20 never do
+        let chunk_apdus = reader.config.photo_chunk_bytes();
+        let card = MockTransport {
+            by_apdu: [(chunk_apdus[0].clone(), vec![0xAA; 13]), (chunk_apdus[1].clone(), vec![0xAA; 20])].into(),
+            ..MockTransport::default()
+        };
+        let photo = reader.read_photo_chunks(&card).unwrap();
+        assert_eq!(photo[0].as_deref(), Some(&[0xAA; 13][..]));
+        assert!(photo[1].is_none());
+    }
+
+    #[test]
+    fn test_reader_gone_aborts_photo_read() {
+        let reader = test_reader(CardConfig::default());
+        let card = MockTransport::with_responses(vec![Ok(vec![0x01, 0x90, 0x00]), Err(pcsc::Error::ReaderUnavailable)]);
+
+        assert!(reader.read_photo_chunks(&card).is_err());
+        assert_eq!(card.sent.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_connection_config_maps_to_pcsc_flags() {
+        assert_eq!(pcsc_share_mode(CardShareMode::Shared), ShareMode::Shared);
+        assert_eq!(pcsc_share_mode(CardShareMode::Exclusive), ShareMode::Exclusive);
+        assert_eq!(pcsc_share_mode(CardShareMode::Direct), ShareMode::Direct);
+        assert_eq!(pcsc_protocols(CardProtocol::Any), Protocols::ANY);
+        assert_eq!(pcsc_protocols(CardProtocol::T0), Protocols::T0);
+        assert_eq!(pcsc_protocols(CardProtocol::T1), Protocols::T1);
+
+        let defaults = CardConfig::default();
+        assert_eq!(pcsc_share_mode(defaults.share_mode), ShareMode::Shared);
+        assert_eq!(pcsc_protocols(defaults.protocol), Protocols::ANY);
+    }
+
+    #[test]
+    fn test_select_falls_back_to_alternate_aid() {
+        let config = CardConfig {
+            fallback_select_apdus: vec!["00A4040008A000000054480002".to_owned()],
+            ..CardConfig::default()
+        };
+        let reader = test_reader(config);
+        let card = MockTransport::with_responses(vec![Ok(vec![0x6A, 0x82]), Ok(vec![0x90, 0x00])]);
+
+        let select = reader.config.select_apdu_bytes();
+        reader.select_applet(&card, &select).unwrap();
+
+        let sent = card.sent.lock();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].last(), Some(&0x02));
+    }
+
+    #[test]
+    fn test_disconnect_releases_context() {
+        let mut reader = test_reader(CardConfig::default());
+        reader.card_present.insert("Reader 0".to_string());
+
+        reader.disconnect();
+        assert!(!reader.is_context_healthy());
+        assert!(reader.card_present.is_empty());
+    }
+
+    #[test]
+    fn test_issuer_read_from_card_issuer_alias() {
+        let mut config = CardConfig::default();
+        for field in &mut config.fields {
+            if field.name == "issuer" {
+                field.name = "card_issuer".to_owned();
+            }
+        }
+        let card = MockTransport::with_fields(&config, &[("card_issuer", "กรมการปกครอง")]);
+        let reader = test_reader(config);
+
+        let data = reader.read_thai_id(&card).unwrap();
+        assert_eq!(data.issuer, "กรมการปกครอง");
+    }
+
+    #[test]
+    fn test_card_serial_optional() {
+        // Configured and present on the card
+        let config = CardConfig::default();
+        let card = MockTransport::with_fields(&config, &[("card_serial", "JT1234567890")]);
+        let data = test_reader(config).read_thai_id(&card).unwrap();
+        assert_eq!(data.card_serial, "JT1234567890");
+
+        // Configured but the card rejects the APDU
+        // (SELECT and the nine fields before it succeed, then 6A 82)
+        let mut responses = vec![Ok(vec![0x90, 0x00])];
+        responses.extend(vec![Ok(vec![0x31, 0x90, 0x00]); 9]);
+        responses.push(Ok(vec![0x6A, 0x82]));
+        let card = MockTransport::with_responses(responses);
+        let data = test_reader(CardConfig::default()).read_thai_id(&card).unwrap();
+        assert_eq!(data.card_serial, "");
+
+        // Not configured: never sent
+        let mut config = CardConfig::default();
+        config.fields.retain(|f| f.name != "card_serial");
+        let card = MockTransport::default();
+        let data = test_reader(config).read_thai_id(&card).unwrap();
+        assert_eq!(data.card_serial, "");
+        assert_eq!(card.sent.lock().len(), 1 + 9 + CardConfig::default().photo_chunks.len());
+    }
+
+    #[test]
+    fn test_english_address_optional() {
+        use crate::config::ApduCommand;
+
+        let mut config = CardConfig::default();
+        config.fields.push(ApduCommand {
+            name: "address_en".to_owned(),
+            apdu: "80B01600020096".to_owned(),
+            required: false,
+        });
+
+        // Second-generation card with the English address
+        let card = MockTransport::with_fields(
+            &config,
+            &[("address_en", "99/1#Moo 2##Sukhumvit Road#Bang Rak#Mueang#Chon Buri")],
+        );
+        let data = test_reader(config.clone()).read_thai_id(&card).unwrap();
+        assert_eq!(data.addr_house_no_en, "99/1");
+        assert_eq!(data.addr_road_en, "Sukhumvit Road");
+        assert_eq!(data.addr_province_en, "Chon Buri");
+        assert_eq!(data.address_en, "99/1 Moo 2 Sukhumvit Road Bang Rak Mueang Chon Buri");
+        let output = crate::decoder::apply_output_config(&data, &crate::config::OutputConfig::default());
+        assert_eq!(output["addrHouseNoEn"], "99/1");
+        assert_eq!(output["AddressEn"], data.address_en);
+
+        // Older card rejects the APDU: read succeeds without it
+        // (SELECT, the nine fields and card_serial succeed, then 6A 82)
+        let mut responses = vec![Ok(vec![0x90, 0x00])];
+        responses.extend(vec![Ok(vec![0x31, 0x90, 0x00]); 10]);
+        responses.push(Ok(vec![0x6A, 0x82]));
+        let card = MockTransport::with_responses(responses);
+        let data = test_reader(config).read_thai_id(&card).unwrap();
+        assert_eq!(data.address_en, "");
+        assert_eq!(data.addr_house_no_en, "");
+        let output = crate::decoder::apply_output_config(&data, &crate::config::OutputConfig::default());
+        assert!(output.get("addrHouseNoEn").is_none());
+    }
+
+    #[test]
+    fn test_required_field_failure_rejects_read() {
+        // SELECT succeeds, citizen_id (required) is rejected by the card
+        let card = MockTransport::with_responses(vec![Ok(vec![0x90, 0x00]), Ok(vec![0x6A, 0x82])]);
+        let err = test_reader(CardConfig::default()).read_thai_id(&card).unwrap_err();
+        assert!(matches!(&err, ReaderError::Field { name, .. } if name == "citizen_id"), "{}", err);
+        assert!(matches!(err.root(), ReaderError::Apdu { sw1: 0x6A, sw2: 0x82 }));
+
+        // A blank required field is a failed read, not a partial card
+        let config = CardConfig::default();
+        let card = MockTransport::with_fields(&config, &[("citizen_id", "             ")]);
+        let err = test_reader(config).read_thai_id(&card).unwrap_err();
+        assert!(matches!(err.root(), ReaderError::Empty), "{}", err);
+
+        // A rejected optional field (address, the ninth) is left empty instead
+        let mut responses = vec![Ok(vec![0x90, 0x00])];
+        responses.extend(vec![Ok(vec![0x31, 0x90, 0x00]); 8]);
+        responses.push(Ok(vec![0x6A, 0x82]));
+        let card = MockTransport::with_responses(responses);
+        let data = test_reader(CardConfig::default()).read_thai_id(&card).unwrap();
+        assert_eq!(data.citizen_id, "1");
+        assert!(data.address.trim().is_empty());
+
+        // Enforcement off: blank required fields pass as before
+        let config = CardConfig {
+            enforce_required: false,
+            ..CardConfig::default()
+        };
+        let card = MockTransport::with_fields(&config, &[("citizen_id", "             ")]);
+        assert!(test_reader(config).read_thai_id(&card).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_photo_is_neither_read_nor_emitted() {
+        use crate::config::OutputConfig;
+
+        let output = OutputConfig {
+            enabled_fields: vec!["Citizenid".to_string(), "Th_Firstname".to_string()],
+            ..OutputConfig::default()
+        };
+        assert!(!output.photo_enabled());
+
+        let config = CardConfig::default();
+        let field_count = config.fields.len();
+        let card = MockTransport::with_fields(&config, &[("citizen_id", "1101700230708")]);
+        let data = test_reader(config).with_photo(output.photo_enabled()).read_thai_id(&card).unwrap();
+
+        // SELECT and the fields only, no photo chunks
+        assert_eq!(card.sent.lock().len(), 1 + field_count);
+        assert!(data.photo.is_empty());
+        let payload = decoder::apply_output_config(&data, &output);
+        assert!(payload.get("PhotoRaw").is_none());
+        assert_eq!(payload["Citizenid"], "1101700230708");
+    }
+
+    #[test]
+    fn test_nhso_applet_read_when_configured() {
+        use crate::config::ApduCommand;
+
+        let main_inscl = ApduCommand {
+            name: "main_inscl".to_owned(),
+            apdu: "80B0000402003C".to_owned(),
+            required: false,
+        };
+        let config = CardConfig {
+            nhso_select_apdu: Some("00A4040008A000000054480083".to_owned()),
+            nhso_fields: vec![main_inscl.clone()],
+            ..CardConfig::default()
+        };
+        let field_count = config.fields.len();
+
+        // Card with the applet
+        let card = MockTransport {
+            by_apdu: [(main_inscl.to_bytes(), encoding_rs::WINDOWS_874.encode("(UCS) สิทธิหลักประกันสุขภาพ").0.into_owned())]
+                .into_iter()
+                .collect(),
+            ..MockTransport::default()
+        };
+        let data = test_reader(config.clone()).with_photo(false).read_thai_id(&card).unwrap();
+        assert_eq!(data.nhso["main_inscl"], "(UCS) สิทธิหลักประกันสุขภาพ");
+        assert_eq!(card.sent.lock().len(), 1 + field_count + 2);
+        let payload = decoder::apply_output_config(&data, &crate::config::OutputConfig::default());
+        assert_eq!(payload["nhso"]["main_inscl"], "(UCS) สิทธิหลักประกันสุขภาพ");
+
+        // Card without it: SELECT and the identity fields succeed, NHSO SELECT fails
+        let mut responses = vec![Ok(vec![0x90, 0x00])];
+        responses.extend(vec![Ok(vec![0x31, 0x90, 0x00]); field_count]);
+        responses.push(Ok(vec![0x6A, 0x82]));
+        let card = MockTransport::with_responses(responses);
+        let data = test_reader(config.clone()).with_photo(false).read_thai_id(&card).unwrap();
+        assert!(data.nhso.is_empty());
+        assert_eq!(card.sent.lock().len(), 1 + field_count + 1);
+        let payload = decoder::apply_output_config(&data, &crate::config::OutputConfig::default());
+        assert!(payload.get("nhso").is_none());
+
+        // A required NHSO field that fails fails the read, as identity fields do
+        let required = CardConfig {
+            nhso_fields: vec![ApduCommand { required: true, ..main_inscl }],
+            ..config.clone()
+        };
+        let mut responses = vec![Ok(vec![0x90, 0x00])];
+        responses.extend(vec![Ok(vec![0x31, 0x90, 0x00]); field_count]);
+        responses.extend([Ok(vec![0x90, 0x00]), Ok(vec![0x6A, 0x82])]);
+        let card = MockTransport::with_responses(responses);
+        let err = test_reader(required).with_photo(false).read_thai_id(&card).unwrap_err();
+        assert!(matches!(&err, ReaderError::Field { name, .. } if name == "main_inscl"), "{}", err);
+
+        // Not configured: no extra APDUs
+        let card = MockTransport::default();
+        test_reader(CardConfig::default()).with_photo(false).read_thai_id(&card).unwrap();
+        assert_eq!(card.sent.lock().len(), 1 + field_count);
+    }
+
+    #[test]
+    fn test_dates_read_from_date_field_aliases() {
+        let mut config = CardConfig::default();
+        for field in &mut config.fields {
+            match field.name.as_str() {
+                "issue" => field.name = "issue_date".to_owned(),
+                "expire" => field.name = "expire_date".to_owned(),
+                _ => {}
+            }
+        }
+        let card = MockTransport::with_fields(
+            &config,
+            &[("issue_date", "25660301"), ("expire_date", "25760412"), ("date_of_birth", "25200413")],
+        );
+        let reader = test_reader(config);
+
+        let data = reader.read_thai_id(&card).unwrap();
+        assert_eq!(data.issue, "2566/03/01");
+        assert_eq!(data.expire, "2576/04/12");
+        assert_eq!(data.birthday, "2520/04/13");
+    }
+
+    #[test]
+    fn test_english_name_components() {
+        let cases = [
+            ("Mr.#Somchai##Jaidee", ["Mr.", "Somchai", "", "Jaidee"]),
+            ("Somchai Jaidee", ["", "Somchai", "", "Jaidee"]),
+            ("Mrs. Somsri Jaidee", ["Mrs.", "Somsri", "", "Jaidee"]),
+        ];
+        for (raw, [prefix, first, middle, last]) in cases {
+            let config = CardConfig::default();
+            let card = MockTransport::with_fields(&config, &[("full_name_en", raw)]);
+            let reader = test_reader(config);
+
+            let data = reader.read_thai_id(&card).unwrap();
+            assert_eq!(data.en_prefix, prefix, "{}", raw);
+            assert_eq!(data.en_firstname, first, "{}", raw);
+            assert_eq!(data.en_middlename, middle, "{}", raw);
+            assert_eq!(data.en_lastname, last, "{}", raw);
+            assert!(!data.full_name_en.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_settled_card_connects_before_fixed_delay() {
+        let config = CardConfig {
+            card_settle_delay_ms: 1000,
+            card_settle_initial_ms: 20,
+            ..CardConfig::default()
+        };
+
+        // Unresponsive on the immediate attempt, connected on the first quick retry
+        let mut attempts = 0;
+        let started = Instant::now();
+        let result = connect_when_settled(&config, || {
+            attempts += 1;
+            if attempts < 2 { Err(pcsc::Error::UnresponsiveCard) } else { Ok(attempts) }
+        })
+        .await;
+        assert_eq!(result, Ok(2));
+        assert!(started.elapsed() < Duration::from_millis(config.card_settle_delay_ms));
+
+        // A card that never settles is given up on at the upper bound
+        let config = CardConfig {
+            card_settle_delay_ms: 100,
+            ..config
+        };
+        let started = Instant::now();
+        let result = connect_when_settled(&config, || Err::<(), _>(pcsc::Error::UnpoweredCard)).await;
+        assert_eq!(result, Err(pcsc::Error::UnpoweredCard));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        // Other errors are not retried
+        let mut attempts = 0;
+        let result = connect_when_settled(&config, || {
+            attempts += 1;
+            Err::<(), _>(pcsc::Error::SharingViolation)
+        })
+        .await;
+        assert_eq!(result, Err(pcsc::Error::SharingViolation));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_diagnostics_lists_backend_readers() {
+        struct MockBackend(Result<Vec<(String, pcsc::State)>, pcsc::Error>);
+
+        impl ReaderBackend for MockBackend {
+            fn reader_states(&self) -> Result<Vec<(String, pcsc::State)>, pcsc::Error> {
+                self.0.clone()
+            }
+        }
+
+        let backend = MockBackend(Ok(vec![
+            ("ACS ACR39U 00 00".to_string(), pcsc::State::PRESENT | pcsc::State::INUSE),
+            ("Generic Reader 01 00".to_string(), pcsc::State::EMPTY),
+        ]));
+        let diagnostics = diagnostics_with(&backend);
+        assert!(diagnostics.error.is_none());
+        assert_eq!(
+            diagnostics.readers,
+            [
+                ReaderDiagnostics { name: "ACS ACR39U 00 00".to_string(), state: "in_use" },
+                ReaderDiagnostics { name: "Generic Reader 01 00".to_string(), state: "empty" },
+            ]
+        );
+        assert!(diagnostics.to_string().contains("Generic Reader 01 00 [empty]"));
+
+        // No readers is a normal snapshot, a dead service is reported, not raised
+        let diagnostics = diagnostics_with(&MockBackend(Ok(Vec::new())));
+        assert!(diagnostics.readers.is_empty() && diagnostics.error.is_none());
+        assert!(diagnostics.to_string().contains("none attached"));
+        let diagnostics = diagnostics_with(&MockBackend(Err(pcsc::Error::NoService)));
+        assert!(diagnostics.error.is_some());
+    }
+
+    #[test]
+    fn test_reader_allow_and_deny_lists() {
+        let readers = || -> Vec<CString> {
+            ["ACS ACR39U ICC Reader 00 00", "Synaptics Fingerprint Sensor 00 00", "Generic Smart Card Reader 01 00"]
+                .into_iter()
+                .map(|name| CString::new(name).unwrap())
+                .collect()
+        };
+        let polled = |config: CardConfig| -> Vec<String> {
+            let mut filtered = HashSet::new();
+            test_reader(config)
+                .filter_readers(readers(), &mut filtered)
+                .iter()
+                .map(|name| name.to_string_lossy().to_string())
+                .collect()
+        };
+
+        // Default: every reader
+        assert_eq!(polled(CardConfig::default()).len(), 3);
+
+        // Substrings are case-insensitive, /.../ entries are regexes
+        let config = CardConfig {
+            reader_allowlist: vec!["acr39".to_owned(), "/^Generic .* 01 00$/".to_owned()],
+            ..CardConfig::default()
+        };
+        assert_eq!(polled(config), ["ACS ACR39U ICC Reader 00 00", "Generic Smart Card Reader 01 00"]);
+
+        let config = CardConfig {
+            reader_denylist: vec!["fingerprint".to_owned()],
+            ..CardConfig::default()
+        };
+        assert_eq!(polled(config), ["ACS ACR39U ICC Reader 00 00", "Generic Smart Card Reader 01 00"]);
+
+        // An invalid regex is a config error, not a pattern that never matches
+        assert!(ReaderPattern::parse("/[unclosed/").is_err());
+        let config = CardConfig {
+            reader_denylist: vec!["/[unclosed/".to_owned()],
+            ..CardConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        // The denylist wins over the allowlist
+        let config = CardConfig {
+            reader_allowlist: vec!["reader".to_owned()],
+            reader_denylist: vec!["generic".to_owned()],
+            ..CardConfig::default()
+        };
+        assert_eq!(polled(config), ["ACS ACR39U ICC Reader 00 00"]);
+    }
+
+    #[test]
+    fn test_context_failure_reports_disconnected_once() {
+        let mut reader = test_reader(CardConfig::default());
+        let events = RefCell::new(Vec::new());
+        let on_event = |event: decoder::CardEvent| {
+            if let decoder::CardEvent::Status(status) = event {
+                events.borrow_mut().push(status);
+            }
+        };
+
+        assert!(!reader.ensure_context(|| Err(pcsc::Error::NoService), &on_event));
+        assert!(!reader.ensure_context(|| Err(pcsc::Error::NoService), &on_event));
+        assert_eq!(*events.borrow(), [decoder::ReaderStatus::Disconnected]);
+
+        reader.report_status(decoder::ReaderStatus::Ready, &on_event);
+        reader.report_status(decoder::ReaderStatus::Ready, &on_event);
+        assert_eq!(
+            *events.borrow(),
+            [decoder::ReaderStatus::Disconnected, decoder::ReaderStatus::Ready]
+        );
+    }
+
+    #[test]
+    fn test_context_retry_exhaustion_is_a_clear_error() {
+        let config = CardConfig {
+            context_retry_attempts: 3,
+            context_retry_delay_ms: 0,
+            ..CardConfig::default()
+        };
+        let mut calls = 0;
+        let err = establish_with_retry::<()>(&config, || {
+            calls += 1;
+            Err(pcsc::Error::NoService)
+        })
+        .unwrap_err();
+
+        assert_eq!(calls, 3);
+        assert!(matches!(err, ContextError::ServiceUnavailable(_)));
+        assert!(err.to_string().contains("PC/SC service is not running"), "{}", err);
+    }
+
+    #[test]
+    fn test_context_retry_recovers() {
+        let config = CardConfig {
+            context_retry_attempts: 3,
+            context_retry_delay_ms: 0,
+            ..CardConfig::default()
+        };
+        let mut calls = 0;
+        let ctx = establish_with_retry(&config, || {
+            calls += 1;
+            if calls < 2 { Err(pcsc::Error::NoService) } else { Ok("ctx") }
+        });
+        assert_eq!(ctx.unwrap(), "ctx");
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,184 @@
+//! Hardware-free self-test of the decode pipeline (`--self-test`)
+//!
+//! Runs a bundled synthetic TIS-620 card fixture through the same decode,
+//! split, date-formatting and validation steps used for a real card, so an
+//! operator can tell a software/encoding problem apart from a bad card or
+//! reader.
+
+use crate::decoder;
+use crate::validation::{CardDataValidator, FieldCharsets};
+use encoding_rs::WINDOWS_874;
+
+/// Raw TIS-620 field bytes as they would come back from the card
+#[derive(Debug, Clone)]
+pub struct SelfTestFixture {
+    pub citizen_id: Vec<u8>,
+    pub full_name_th: Vec<u8>,
+    pub full_name_en: Vec<u8>,
+    pub date_of_birth: Vec<u8>,
+    pub gender: Vec<u8>,
+    pub address: Vec<u8>,
+}
+
+impl SelfTestFixture {
+    /// Synthetic card data with a valid citizen ID checksum
+    #[must_use]
+    pub fn known_good() -> Self {
+        let tis620 = |s: &str| WINDOWS_874.encode(s).0.into_owned();
+        Self {
+            citizen_id: tis620("1101700230708"),
+            full_name_th: tis620("นาย#สมชาย##ใจดี     "),
+            full_name_en: tis620("Mr.#Somchai##Jaidee     "),
+            date_of_birth: tis620("25330115"),
+            gender: tis620("1"),
+            address: tis620("99/1#หมู่ที่ 2###ตำบลบางรัก#อำเภอบางรัก#จังหวัดกรุงเทพมหานคร"),
+        }
+    }
+}
+
+/// Outcome of a single pipeline stage
+#[derive(Debug, Clone)]
+pub struct StageResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Results of all self-test stages
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub stages: Vec<StageResult>,
+}
+
+impl SelfTestReport {
+    /// `true` if every stage passed
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.stages.iter().all(|s| s.passed)
+    }
+
+    fn check(&mut self, name: &'static str, passed: bool, detail: impl Into<String>) {
+        self.stages.push(StageResult {
+            name,
+            passed,
+            detail: detail.into(),
+        });
+    }
+
+    /// Print a pass/fail line per stage to stdout
+    pub fn print(&self) {
+        println!("Smart Card Reader self-test");
+        for stage in &self.stages {
+            let status = if stage.passed { "PASS" } else { "FAIL" };
+            println!("  [{}] {:<22} {}", status, stage.name, stage.detail);
+        }
+        let passed = self.stages.iter().filter(|s| s.passed).count();
+        println!("{}/{} stages passed", passed, self.stages.len());
+    }
+}
+
+/// Run the decode pipeline against `fixture`
+#[must_use]
+pub fn run(fixture: &SelfTestFixture) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+
+    // Stage 1: plain TIS-620 decode
+    let citizen_id = decoder::decode_tis620(&fixture.citizen_id);
+    report.check(
+        "decode_tis620",
+        citizen_id.len() == 13 && citizen_id.chars().all(|c| c.is_ascii_digit()),
+        format!("citizen ID decoded to {} chars", citizen_id.chars().count()),
+    );
+
+    // Stage 2: Thai name split (prefix#first#middle#last)
+    let th = decoder::split_tis620(&fixture.full_name_th, 4);
+    let th_ok = !th[0].is_empty()
+        && !th[1].is_empty()
+        && !th[3].is_empty()
+        && th.iter().flat_map(|p| p.chars()).all(|c| c == ' ' || c == '.' || is_thai(c));
+    report.check("split_tis620 (TH name)", th_ok, format!("{} parts", th.iter().filter(|p| !p.is_empty()).count()));
+
+    // Stage 3: English name split
+    let en = decoder::split_tis620(&fixture.full_name_en, 4);
+    let en_ok = !en[1].is_empty() && !en[3].is_empty() && en.iter().all(|p| p.is_ascii());
+    report.check("split_tis620 (EN name)", en_ok, format!("{} parts", en.iter().filter(|p| !p.is_empty()).count()));
+
+    // Stage 4: address split
+    let address = decoder::split_tis620(&fixture.address, 8);
+    let addr_ok = address.iter().filter(|p| !p.is_empty()).count() >= 4
+        && address.iter().any(|p| p.chars().any(is_thai));
+    report.check(
+        "split_tis620 (address)",
+        addr_ok,
+        format!("{} non-empty parts", address.iter().filter(|p| !p.is_empty()).count()),
+    );
+
+    // Stage 5: Buddhist Era date formatting
+    let dob_raw = decoder::decode_tis620(&fixture.date_of_birth);
+    let dob = decoder::format_thai_date(&dob_raw);
+    report.check("format_thai_date", dob != dob_raw, dob);
+
+    // Stage 6: validation (dates are Buddhist Era, covered by stage 5)
+    let thai_name = th.join(" ");
+    let english_name = en.join(" ");
+    let full_address = address.join(" ");
+    let gender = decoder::decode_tis620(&fixture.gender);
+    let errors = CardDataValidator::validate_all(
+        Some(&citizen_id),
+        None,
+        None,
+        None,
+        Some(&gender),
+        Some(&thai_name),
+        Some(&english_name),
+        Some(&full_address),
+        &FieldCharsets::default(),
+    );
+    let detail = if errors.is_empty() {
+        "no validation errors".to_string()
+    } else {
+        errors
+            .iter()
+            .map(|(field, e)| format!("{}: {}", field, e))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    report.check("CardDataValidator", errors.is_empty(), detail);
+
+    report
+}
+
+fn is_thai(c: char) -> bool {
+    ('\u{0E00}'..='\u{0E7F}').contains(&c)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes_on_known_good() {
+        let report = run(&SelfTestFixture::known_good());
+        assert!(report.passed(), "{:?}", report.stages);
+        assert_eq!(report.stages.len(), 6);
+    }
+
+    #[test]
+    fn test_self_test_fails_on_corrupted_fixture() {
+        let mut fixture = SelfTestFixture::known_good();
+        // Flip the check digit and inject non-TIS-620 garbage into the Thai name
+        fixture.citizen_id[12] = b'9';
+        fixture.full_name_th = vec![0x80, 0x81, 0x23, 0xFF, 0xFE];
+
+        let report = run(&fixture);
+        assert!(!report.passed());
+
+        let failed: Vec<&str> = report.stages.iter().filter(|s| !s.passed).map(|s| s.name).collect();
+        assert!(failed.contains(&"split_tis620 (TH name)"));
+        assert!(failed.contains(&"CardDataValidator"));
+    }
+}
@@ -1,38 +1,588 @@
 use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    extract::{ConnectInfo, State},
-    http::{HeaderMap, StatusCode},
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::broadcast;
+use tower_http::cors::{Any, CorsLayer};
+use tracing::Instrument;
 
 use crate::audit_log::AuditLogger;
-use crate::config::SecurityConfig;
-use crate::rate_limiter::RateLimiter;
+use crate::build_info::BuildInfo;
+pub use crate::config::LagPolicy;
+use crate::config::{MessageLimitPolicy, OutputConfig, OutputFormat, SecurityConfig, ServerConfig};
+use crate::crypto::{constant_time_eq, CryptoService, EncryptFailurePolicy};
+use crate::decoder::{build_broadcast_message, card_json, event_json, CardEvent, PiiMask, ThaiIDData, ENVELOPE_DATA_KEY, TEST_EVENT_KEY};
+use crate::geo::{GeoFilter, GeoVerdict};
+use crate::rate_limiter::{MessageBucket, RateLimiter};
+use crate::stats::AppStats;
+use crate::validation::{validate_card, ValidationConfig};
 
 pub struct AppState {
     pub tx: broadcast::Sender<String>,
+    /// Server-global broadcast sequence, shared by all clients and reconnects
+    pub seq: AtomicU64,
+    pub lag_policy: LagPolicy,
     pub security: SecurityConfig,
     pub rate_limiter: Option<Arc<RateLimiter>>,
     pub audit_logger: Arc<AuditLogger>,
+    pub crypto: Option<Arc<CryptoService>>,
+    pub stats: Arc<AppStats>,
+    /// Set when `security.broadcast_masked` is on
+    pub pii_mask: Option<PiiMask>,
+    /// Open WebSocket connections, bounded by `security.max_total_connections`
+    pub active_connections: AtomicU32,
+    /// Field mapping applied to card data before broadcast
+    pub output: OutputConfig,
+    /// `server.max_message_bytes` (0 = no limit)
+    pub max_message_bytes: usize,
+    /// `server.delta_mode`: re-reads of the same card send WebSocket clients only changed fields
+    pub delta_mode: bool,
+    /// Citizen ID and unencrypted payload of the last card broadcast, for delta mode
+    pub last_card: parking_lot::Mutex<Option<(String, serde_json::Value)>>,
+    /// Latest card broadcast and its re-read delta, for delta mode on WebSocket clients
+    pub card_frame: parking_lot::Mutex<Option<CardFrame>>,
+    /// Citizen ID and unencrypted payload of the previous read, for
+    /// `output.change_detection` (kept across removals)
+    pub last_read: parking_lot::Mutex<Option<(String, serde_json::Value)>>,
+    /// Hardening settings in effect, for `GET /health/security`
+    pub security_status: SecurityStatus,
+    /// Repeated identical reads, for `server.stuck_card_reads`
+    pub stuck_card: parking_lot::Mutex<StuckCardDetector>,
+    /// Set when `security.geo_allow_countries` is on and its database loaded
+    pub geo_filter: Option<GeoFilter>,
+    /// Last `/health?detail=true` PC/SC snapshot and when it was taken
+    pub diagnostics: tokio::sync::Mutex<Option<(std::time::Instant, serde_json::Value)>>,
 }
 
+/// What to do with a card read, as decided by [`StuckCardDetector::observe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StuckVerdict {
+    /// Broadcast the read as usual
+    Send,
+    /// This read made the card count as stuck, after this many identical reads
+    Stuck(u32),
+    /// Repeat of a stuck card, dropped until removal
+    Suppress,
+}
+
+/// Spots a card re-read with the same data over and over (`server.stuck_card_reads`)
+#[derive(Debug)]
+pub struct StuckCardDetector {
+    reads: u32,
+    window: std::time::Duration,
+    suppress: bool,
+    /// Mapped payload of the current run, when it started and how many reads it has
+    last: Option<(serde_json::Value, std::time::Instant, u32)>,
+    stuck: bool,
+}
+
+impl StuckCardDetector {
+    #[must_use]
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            reads: config.stuck_card_reads,
+            window: std::time::Duration::from_secs(config.stuck_card_window_secs),
+            suppress: config.suppress_stuck_card,
+            last: None,
+            stuck: false,
+        }
+    }
+
+    /// `true` if `stuck_card_reads` is set
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.reads > 0
+    }
+
+    /// Count a read with the mapped `payload` at `now`
+    ///
+    /// A stuck card stays stuck, whatever the window, until [`Self::reset`]
+    /// or a read with other data.
+    pub fn observe(&mut self, payload: &serde_json::Value, now: std::time::Instant) -> StuckVerdict {
+        if !self.is_enabled() {
+            return StuckVerdict::Send;
+        }
+        let count = match &mut self.last {
+            Some((last, started, count))
+                if last == payload && (self.stuck || now.saturating_duration_since(*started) <= self.window) =>
+            {
+                *count += 1;
+                *count
+            }
+            _ => {
+                self.last = Some((payload.clone(), now, 1));
+                self.stuck = false;
+                1
+            }
+        };
+        if self.stuck {
+            return if self.suppress { StuckVerdict::Suppress } else { StuckVerdict::Send };
+        }
+        if count >= self.reads {
+            self.stuck = true;
+            return StuckVerdict::Stuck(count);
+        }
+        StuckVerdict::Send
+    }
+
+    /// Forget the current run (card removed)
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.stuck = false;
+    }
+}
+
+/// Which protections are on, as reported by `GET /health/security` (no secrets)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SecurityStatus {
+    pub tls: bool,
+    pub auth: bool,
+    pub encryption: bool,
+    pub rate_limiting: bool,
+    pub audit: bool,
+    pub cors_restricted: bool,
+}
+
+impl SecurityStatus {
+    #[must_use]
+    pub fn new(server: &ServerConfig, security: &SecurityConfig) -> Self {
+        Self {
+            tls: server.enable_tls,
+            auth: security.enable_authentication,
+            encryption: security.enable_encryption,
+            rate_limiting: security.enable_rate_limiting,
+            audit: security.enable_audit_logging,
+            cors_restricted: !server.cors_allow_all,
+        }
+    }
+}
+
+/// A live card broadcast in delta mode, with the delta WebSocket clients get instead
+///
+/// Only clients that received the card message the delta applies to are sent
+/// the delta; new, lagged and other clients get `full` as broadcast.
+#[derive(Debug)]
+pub struct CardFrame {
+    /// Message as sent on the broadcast channel
+    full: String,
+    seq: u64,
+    /// `seq` of the full card message the delta applies to, and the delta
+    delta: Option<(u64, String)>,
+}
+
+impl AppState {
+    /// Broadcast JSON for a card event, or `None` if it must not be sent
+    ///
+    /// Inserted cards are validated, mapped and (if enabled) field-encrypted;
+    /// every message is stamped and size-checked.
+    pub fn card_message(&self, event: &CardEvent) -> Option<String> {
+        let payload = match event {
+            CardEvent::Inserted(data) => match self.stuck_card_verdict(data) {
+                StuckVerdict::Send => {
+                    let Some((payload, delta)) = self.card_payload(data, true) else {
+                        self.forget_card();
+                        return None;
+                    };
+                    return self.card_broadcast(build_broadcast_message(payload, self.output.envelope), delta);
+                }
+                StuckVerdict::Stuck(reads) => {
+                    log::warn!("⚠️ Same card read {} times in a row, it may be stuck; asking to remove and reinsert it", reads);
+                    json!({ "mode": "cardstuck", "reads": reads })
+                }
+                StuckVerdict::Suppress => {
+                    log::debug!("Stuck card re-read, not broadcasting");
+                    return None;
+                }
+            },
+            other => {
+                // The next read after a removal is always sent in full
+                if matches!(other, CardEvent::Removed) {
+                    self.last_card.lock().take();
+                    self.card_frame.lock().take();
+                    self.stuck_card.lock().reset();
+                }
+                event_json(other, &self.output)
+            }
+        };
+        self.finish_message(payload)
+    }
+
+    /// Broadcast JSON for a `/api/test-event` card, marked `"test": true`
+    ///
+    /// Validated, mapped and encrypted like a real read, but leaves the reader
+    /// state (delta, change detection, stuck card) untouched.
+    pub fn test_card_message(&self, data: &ThaiIDData) -> Option<String> {
+        let (payload, _) = self.card_payload(data, false)?;
+        let mut payload = build_broadcast_message(payload, self.output.envelope);
+        if let Some(obj) = payload.as_object_mut() {
+            obj.insert(TEST_EVENT_KEY.to_string(), json!(true));
+        }
+        self.finish_message(payload)
+    }
+
+    /// Finish a live card read and, in delta mode, remember it with its `delta`
+    fn card_broadcast(&self, payload: serde_json::Value, delta: Option<serde_json::Value>) -> Option<String> {
+        let Some((msg, stamp)) = self.finish_stamped(payload) else {
+            self.forget_card();
+            return None;
+        };
+        if !self.delta_mode {
+            return Some(msg);
+        }
+        let mut frame = self.card_frame.lock();
+        // A delta only applies on top of the card message broadcast just before
+        let base_seq = frame.as_ref().map(|previous| previous.seq);
+        let delta = base_seq.zip(delta).and_then(|(base_seq, delta)| {
+            let mut delta = build_broadcast_message(delta, self.output.envelope);
+            self.stamp_as(&mut delta, &stamp);
+            match fit_message(delta, &self.output.output_key("PhotoRaw"), self.max_message_bytes) {
+                SizedMessage::Full(delta) | SizedMessage::PhotoDropped(delta, _) => Some((base_seq, delta)),
+                SizedMessage::Rejected(_) => None,
+            }
+        });
+        *frame = Some(CardFrame {
+            full: msg.clone(),
+            seq: stamp.0,
+            delta,
+        });
+        Some(msg)
+    }
+
+    /// Drop the delta base after a read that was not broadcast, so the next
+    /// read of the card is sent in full
+    fn forget_card(&self) {
+        self.last_card.lock().take();
+        self.card_frame.lock().take();
+    }
+
+    /// What a WebSocket client is sent for broadcast `msg`
+    ///
+    /// `last_card_seq` is the `seq` of the last card message the client got;
+    /// the re-read delta replaces `msg` only when it applies on top of that.
+    pub fn ws_frame(&self, msg: String, last_card_seq: &mut Option<u64>) -> String {
+        let frame = self.card_frame.lock();
+        let Some(frame) = frame.as_ref().filter(|frame| frame.full == msg) else {
+            return msg;
+        };
+        let received = last_card_seq.replace(frame.seq);
+        match &frame.delta {
+            Some((base_seq, delta)) if received == Some(*base_seq) => delta.clone(),
+            _ => msg,
+        }
+    }
+
+    /// Stamp and size-check `payload`, `None` if it is too large to send
+    fn finish_message(&self, payload: serde_json::Value) -> Option<String> {
+        self.finish_stamped(payload).map(|(msg, _)| msg)
+    }
+
+    /// [`Self::finish_message`], also returning the stamp given to the message
+    ///
+    /// Adds `seq` (strictly increasing per sent message) and `timestamp`
+    /// (RFC 3339 UTC) so clients can spot replayed or reordered frames. A
+    /// rejected payload does not use up a `seq`.
+    fn finish_stamped(&self, payload: serde_json::Value) -> Option<(String, Stamp)> {
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let photo_key = self.output.output_key("PhotoRaw");
+        let max_bytes = self.max_message_bytes;
+        loop {
+            // The size is checked with the seq the message would get; it is
+            // only taken once the message is known to fit
+            let previous = self.seq.load(Ordering::Acquire);
+            let stamp = (previous + 1, timestamp.clone());
+            let mut stamped = payload.clone();
+            self.stamp_as(&mut stamped, &stamp);
+
+            // Guard clients against oversized payloads (e.g. a corrupt photo)
+            let (msg, photo_dropped) = match fit_message(stamped, &photo_key, max_bytes) {
+                SizedMessage::Full(msg) => (msg, None),
+                SizedMessage::PhotoDropped(msg, size) => (msg, Some(size)),
+                SizedMessage::Rejected(size) => {
+                    log::error!("❌ Card message is {} bytes (limit {}). Payload rejected.", size, max_bytes);
+                    self.audit_logger.log_oversized_payload("payload_rejected", size, max_bytes);
+                    return None;
+                }
+            };
+            if self.seq.compare_exchange(previous, stamp.0, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                // Another message took this seq meanwhile
+                continue;
+            }
+            if let Some(size) = photo_dropped {
+                log::warn!("⚠️ Card message is {} bytes (limit {}), sending without photo", size, max_bytes);
+                self.audit_logger.log_oversized_payload("payload_photo_dropped", size, max_bytes);
+            }
+            return Some((msg, stamp));
+        }
+    }
+
+    /// Add `stamp` to `payload` under the `seq`/`timestamp` output keys
+    fn stamp_as(&self, payload: &mut serde_json::Value, (seq, timestamp): &Stamp) {
+        if let Some(obj) = payload.as_object_mut() {
+            obj.insert(self.output.output_key("seq"), json!(seq));
+            obj.insert(self.output.output_key("timestamp"), json!(timestamp));
+        }
+    }
+
+    /// Stuck card check for a read, `Send` when detection is off
+    fn stuck_card_verdict(&self, data: &ThaiIDData) -> StuckVerdict {
+        let mut detector = self.stuck_card.lock();
+        if !detector.is_enabled() {
+            return StuckVerdict::Send;
+        }
+        detector.observe(&card_json(data, &self.output), std::time::Instant::now())
+    }
+
+    /// Validated, mapped and encrypted `readsmartcard` payload
+    ///
+    /// Only `live` (card reader) reads feed change detection and delta mode;
+    /// in delta mode a re-read also yields its `readsmartcard_delta`.
+    fn card_payload(&self, data: &ThaiIDData, live: bool) -> Option<(serde_json::Value, Option<serde_json::Value>)> {
+        let validation = validate_card(
+            data,
+            &ValidationConfig {
+                block_on: self.security.block_on,
+                charsets: self.security.field_charsets(),
+                ..Default::default()
+            },
+        );
+
+        for (field, err) in validation.errors() {
+            self.audit_logger.log_validation_failure(
+                None,
+                field,
+                err.kind(),
+                err.message(),
+                err.is_security(),
+            );
+        }
+
+        if !validation.is_acceptable {
+            log::error!("❌ Card data failed validation (block_on = \"{}\"). Payload rejected.", self.security.block_on);
+            return None;
+        }
+
+        let mut payload = card_json(data, &self.output);
+        let changed = (live && self.output.change_detection).then(|| self.is_changed(&data.citizen_id, &payload));
+        let mut delta = (live && self.delta_mode)
+            .then(|| self.delta_payload(&data.citizen_id, &payload))
+            .flatten();
+        self.protect_payload(&mut payload, changed)?;
+        if let Some(delta) = &mut delta {
+            self.protect_payload(delta, changed)?;
+        }
+        Some((payload, delta))
+    }
+
+    /// Encrypt the protected fields of `payload` and add the change detection and
+    /// `photo_encrypted` fields; `None` if the payload must not be sent
+    fn protect_payload(&self, payload: &mut serde_json::Value, changed: Option<bool>) -> Option<()> {
+        let Some(obj) = payload.as_object_mut() else {
+            return Some(());
+        };
+        let photo_key = self.output.output_key("PhotoRaw");
+        let mut photo_encrypted = false;
+        let fields: Vec<String> = obj.keys().filter(|k| *k != "mode").cloned().collect();
+        for k in fields {
+            if k == photo_key && !self.security.should_encrypt_photo(obj[&k].as_str().map_or(0, str::len)) {
+                log::debug!("Photo within encrypt_photo_over_bytes, sending it unencrypted");
+                continue;
+            }
+            match self.encrypt_field(&k, &obj[&k]) {
+                Ok(Some(encrypted)) => {
+                    photo_encrypted |= k == photo_key;
+                    obj.insert(k, encrypted);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let policy = self.security.on_encrypt_failure;
+                    log::error!("❌ Failed to encrypt field '{}': {} (on_encrypt_failure = \"{}\")", k, e, policy);
+                    self.audit_logger.log_encryption_failure(&k, policy);
+                    match policy {
+                        EncryptFailurePolicy::Plaintext => {}
+                        EncryptFailurePolicy::Omit => {
+                            obj.remove(&k);
+                        }
+                        EncryptFailurePolicy::Abort => {
+                            log::error!("❌ Payload rejected to avoid sending '{}' unencrypted", k);
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+        if changed.is_some() && self.output.format == OutputFormat::Full {
+            let read_at = json!(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true));
+            let timestamps: serde_json::Map<String, serde_json::Value> = obj
+                .keys()
+                .filter(|k| *k != "mode")
+                .map(|k| (k.clone(), read_at.clone()))
+                .collect();
+            obj.insert(self.output.output_key("field_timestamps"), serde_json::Value::Object(timestamps));
+        }
+        if let Some(changed) = changed {
+            obj.insert(self.output.output_key("changed"), json!(changed));
+        }
+        // Tells clients whether the photo needs decrypting
+        if self.security.enable_encryption && self.crypto.is_some() && obj.contains_key(&photo_key) {
+            obj.insert(self.output.output_key("photo_encrypted"), json!(photo_encrypted));
+        }
+        Some(())
+    }
+
+    /// Whether `payload` differs from the previous read of `citizen_id` (`true`
+    /// for the first read or another card); remembers `payload` either way
+    fn is_changed(&self, citizen_id: &str, payload: &serde_json::Value) -> bool {
+        let previous = self.last_read.lock().replace((citizen_id.to_string(), payload.clone()));
+        previous.is_none_or(|(last_id, last)| last_id != citizen_id || last != *payload)
+    }
+
+    /// `readsmartcard_delta` with the fields that changed if `citizen_id` is the
+    /// card last broadcast, else `None`; remembers `payload` either way
+    fn delta_payload(&self, citizen_id: &str, payload: &serde_json::Value) -> Option<serde_json::Value> {
+        let previous = self.last_card.lock().replace((citizen_id.to_string(), payload.clone()));
+        let (_, last) = previous.filter(|(last_id, _)| last_id == citizen_id)?;
+
+        let mut delta = serde_json::Map::new();
+        delta.insert("mode".to_string(), json!("readsmartcard_delta"));
+        if let Some(fields) = payload.as_object() {
+            for (key, value) in fields {
+                if key != "mode" && last.get(key) != Some(value) {
+                    delta.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        log::debug!("Same card re-read, {} changed fields for clients holding the last read", delta.len() - 1);
+        Some(serde_json::Value::Object(delta))
+    }
+
+    /// Ciphertext for `value` if `key` is a protected field, `None` to send it as is
+    ///
+    /// Numbers (`output.numeric_fields`) are encrypted as their decimal text and
+    /// objects (`nhso`) member by member; other values and disabled encryption
+    /// leave the value unchanged.
+    fn encrypt_field(&self, key: &str, value: &serde_json::Value) -> anyhow::Result<Option<serde_json::Value>> {
+        if !self.security.should_encrypt_field(key) {
+            return Ok(None);
+        }
+        let Some(crypto) = &self.crypto else {
+            return Ok(None);
+        };
+        let plaintext = match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Object(members) => {
+                let mut encrypted = serde_json::Map::new();
+                for (name, member) in members {
+                    let member = self.encrypt_field(key, member)?.unwrap_or_else(|| member.clone());
+                    encrypted.insert(name.clone(), member);
+                }
+                log::debug!("🔒 Encrypted field: {}", key);
+                return Ok(Some(serde_json::Value::Object(encrypted)));
+            }
+            _ => return Ok(None),
+        };
+        let encrypted = crypto.encrypt_to_base64(&plaintext)?;
+        log::debug!("🔒 Encrypted field: {}", key);
+        Ok(Some(json!(encrypted)))
+    }
+
+    /// Take a server-wide connection slot; `false` if the cap is reached
+    pub fn try_acquire_connection(&self) -> bool {
+        let max = self.security.max_total_connections.unwrap_or(u32::MAX);
+        self.active_connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < max).then_some(n + 1))
+            .is_ok()
+    }
+
+    /// Give back a slot taken by [`Self::try_acquire_connection`]
+    pub fn release_connection(&self) {
+        let _ = self
+            .active_connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+    }
+}
+
+/// `seq` and `timestamp` given to a broadcast message
+pub type Stamp = (u64, String);
+
+/// Request body for the `/api/decrypt` debugging endpoint
+#[derive(Debug, Deserialize)]
+pub struct DecryptRequest {
+    /// Base64-encoded `nonce||ciphertext` as produced by the broadcast
+    pub data: String,
+}
+
+/// Response body for the `/api/decrypt` debugging endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DecryptResponse {
+    pub plaintext: String,
+}
+
+/// `?api_key=` on the WebSocket URL, for browsers that cannot set headers
+#[derive(Debug, Default, Deserialize)]
+pub struct WsAuthQuery {
+    pub api_key: Option<String>,
+}
+
+/// `?detail=true` on `/health` adds the PC/SC diagnostics snapshot
+#[derive(Debug, Default, Deserialize)]
+pub struct HealthQuery {
+    #[serde(default)]
+    pub detail: bool,
+}
+
+/// Authentication state of an upgraded socket
+enum SocketAuth {
+    /// Authenticated on upgrade (or authentication disabled)
+    Done { can_reveal: bool },
+    /// Must send `{"auth":"<key>"}` within `security.ws_auth_timeout_ms`
+    Pending,
+}
+
+/// First characters of an API key for audit entries; the rest is never logged
+fn key_hint(key: &str) -> &str {
+    key.get(..4).unwrap_or(key)
+}
+
+/// Monotonic id shared by every log line of one WebSocket connection
+static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(1);
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<WsAuthQuery>,
     headers: HeaderMap,
 ) -> Response {
-    let client_ip = addr.ip();
+    // Dual-stack `::` listeners report IPv4 peers as ::ffff:a.b.c.d
+    let client_ip = addr.ip().to_canonical();
+    let conn_id = NEXT_CONN_ID.fetch_add(1, Ordering::Relaxed);
+    let span = tracing::info_span!("ws_conn", conn_id, client = %client_ip);
+    let _entered = span.enter();
+
+    // Country allow-list, before any rate-limit accounting
+    if let Some(ref geo_filter) = state.geo_filter {
+        if let GeoVerdict::Blocked(country) = geo_filter.check(client_ip) {
+            tracing::warn!("⚠️ Connection from {} ({}) outside allowed countries", client_ip, country.as_deref().unwrap_or("unknown"));
+            state.audit_logger.log_geo_blocked(client_ip, country.as_deref());
+            return (StatusCode::FORBIDDEN, "Connections from your location are not allowed.").into_response();
+        }
+    }
 
     // Check rate limit if enabled
     if let Some(ref rate_limiter) = state.rate_limiter {
         // Check request rate limit
         if !rate_limiter.check_request(client_ip) {
-            log::warn!("⚠️ Rate limit exceeded for {}", client_ip);
+            tracing::warn!("⚠️ Rate limit exceeded for {}", client_ip);
             state.audit_logger.log_rate_limit(client_ip, "request");
             return (
                 StatusCode::TOO_MANY_REQUESTS,
@@ -43,7 +593,7 @@ pub async fn ws_handler(
 
         // Check connection limit
         if !rate_limiter.check_connection(client_ip) {
-            log::warn!("⚠️ Connection limit exceeded for {}", client_ip);
+            tracing::warn!("⚠️ Connection limit exceeded for {}", client_ip);
             state.audit_logger.log_rate_limit(client_ip, "connection");
             return (
                 StatusCode::TOO_MANY_REQUESTS,
@@ -52,72 +602,1945 @@ pub async fn ws_handler(
                 .into_response();
         }
     }
+    // Slots taken from here on are given back when this is dropped: on any
+    // rejection below, a failed upgrade or the end of the socket
+    let mut slots = ConnectionSlots {
+        state: state.clone(),
+        client_ip,
+        global: false,
+    };
+
+    // API key from the header, or the query string for browsers
+    let api_key = headers
+        .get(&state.security.api_key_header)
+        .and_then(|v| v.to_str().ok())
+        .or(query.api_key.as_deref());
 
     // Check authentication if enabled
+    let mut auth = SocketAuth::Done { can_reveal: state.security.can_reveal(api_key) };
     if state.security.enable_authentication {
-        let api_key = headers
-            .get(&state.security.api_key_header)
-            .and_then(|v| v.to_str().ok());
-
         match api_key {
             Some(key) if state.security.is_valid_key(key) => {
-                log::debug!("✓ Authentication successful");
-                // Log authentication success with first 4 chars of key as hint
-                let key_hint = if key.len() >= 4 {
-                    Some(&key[..4])
-                } else {
-                    Some(key)
-                };
-                state.audit_logger.log_auth_success(client_ip, key_hint);
+                tracing::debug!("✓ Authentication successful");
+                state.audit_logger.log_auth_success(client_ip, Some(key_hint(key)));
             }
             Some(_) => {
-                log::warn!("⚠️ Invalid API key provided");
+                tracing::warn!("⚠️ Invalid API key provided");
                 state.audit_logger.log_auth_failure(client_ip, "Invalid API key");
                 return (
                     StatusCode::UNAUTHORIZED,
-                    "Invalid API key. Provide a valid X-API-Key header.",
+                    format!("Invalid API key. Provide a valid {} header or api_key parameter.", state.security.api_key_header),
                 )
                     .into_response();
             }
             None => {
-                log::warn!("⚠️ No API key provided");
-                state.audit_logger.log_auth_failure(client_ip, "No API key provided");
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    format!("Authentication required. Provide {} header.", state.security.api_key_header),
-                )
-                    .into_response();
+                // Browsers authenticate with their first message after the upgrade
+                tracing::debug!("No API key on upgrade, waiting for an auth message");
+                auth = SocketAuth::Pending;
             }
         }
     }
 
+    // Server-wide cap, so many distinct IPs cannot exhaust the server
+    if !state.try_acquire_connection() {
+        let max_total = state.security.max_total_connections.unwrap_or(u32::MAX);
+        tracing::warn!("⚠️ Server connection cap of {} reached, rejecting {}", max_total, client_ip);
+        state.audit_logger.log_connection_cap(client_ip, max_total);
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server is at its connection limit. Please try again later.",
+        )
+            .into_response();
+    }
+    slots.global = true;
+
     // Log connection opened
     state.audit_logger.log_connection_open(client_ip);
 
-    ws.on_upgrade(move |socket| handle_socket(socket, state, client_ip))
+    let socket_span = span.clone();
+    let audit_logger = state.audit_logger.clone();
+    ws.on_failed_upgrade(move |e| {
+        tracing::warn!("⚠️ WebSocket upgrade for {} failed: {}", client_ip, e);
+        audit_logger.log_connection_close(client_ip, None, Some("upgrade_failed"));
+    })
+    .on_upgrade(move |socket| handle_socket(socket, state, client_ip, auth, slots).instrument(socket_span))
+}
+
+/// Wait for `{"auth":"<key>"}` as the first text frame, returning the key
+///
+/// The error is the close reason sent to the client.
+async fn authenticate_socket(
+    socket: &mut WebSocket,
+    state: &AppState,
+    client_ip: std::net::IpAddr,
+) -> Result<String, &'static str> {
+    let limit = std::time::Duration::from_millis(state.security.ws_auth_timeout_ms);
+    let first_text = tokio::time::timeout(limit, async {
+        loop {
+            match socket.recv().await {
+                Some(Ok(Message::Text(text))) => return Some(text),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+                Some(Ok(_)) => continue,
+            }
+        }
+    })
+    .await;
+
+    let text = match first_text {
+        Ok(Some(text)) => text,
+        Ok(None) => return Err("Closed before authentication"),
+        Err(_) => {
+            tracing::warn!("⚠️ No auth message from {} within {}ms", client_ip, limit.as_millis());
+            state.audit_logger.log_auth_failure(client_ip, "No auth message before timeout");
+            return Err("Authentication timeout");
+        }
+    };
+
+    let key = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|v| v.get("auth")?.as_str().map(str::to_owned));
+    match key {
+        Some(key) if state.security.is_valid_key(&key) => {
+            tracing::debug!("✓ Authentication successful (auth message)");
+            state.audit_logger.log_auth_success(client_ip, Some(key_hint(&key)));
+            let reply = serde_json::json!({ "mode": "auth", "status": "granted" });
+            let _ = socket.send(Message::Text(reply.to_string())).await;
+            Ok(key)
+        }
+        _ => {
+            tracing::warn!("⚠️ Invalid or missing API key in auth message");
+            state.audit_logger.log_auth_failure(client_ip, "Invalid API key in auth message");
+            Err("Invalid API key")
+        }
+    }
 }
 
-async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>, client_ip: std::net::IpAddr) {
+/// Per-IP (and, once taken, server-wide) connection slots of one WebSocket
+/// client, given back on drop
+struct ConnectionSlots {
+    state: Arc<AppState>,
+    client_ip: std::net::IpAddr,
+    /// Whether the server-wide slot was taken as well
+    global: bool,
+}
+
+impl Drop for ConnectionSlots {
+    fn drop(&mut self) {
+        if self.global {
+            self.state.release_connection();
+        }
+        if let Some(ref rate_limiter) = self.state.rate_limiter {
+            rate_limiter.release_connection(self.client_ip);
+            tracing::debug!("✓ Connection released for {}", self.client_ip);
+        }
+    }
+}
+
+/// Whether a client text frame is `{"command":"reveal"}`
+fn is_reveal_command(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text)
+        .is_ok_and(|v| v.get("command").and_then(|c| c.as_str()) == Some("reveal"))
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: Arc<AppState>,
+    client_ip: std::net::IpAddr,
+    auth: SocketAuth,
+    slots: ConnectionSlots,
+) {
     let connection_start = std::time::Instant::now();
+    let can_reveal = match auth {
+        SocketAuth::Done { can_reveal } => can_reveal,
+        SocketAuth::Pending => match authenticate_socket(&mut socket, &state, client_ip).await {
+            Ok(key) => state.security.can_reveal(Some(&key)),
+            Err(reason) => {
+                let close = CloseFrame {
+                    code: close_code::POLICY,
+                    reason: reason.into(),
+                };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                drop(slots);
+                let duration_ms = connection_start.elapsed().as_millis() as u64;
+                state.audit_logger.log_connection_close(client_ip, Some(duration_ms), Some("auth_failed"));
+                return;
+            }
+        },
+    };
     let mut rx = state.tx.subscribe();
+    state.stats.client_connected();
+    tracing::debug!("WebSocket client connected");
+
+    // Optional hard limit on session length
+    let session_limit = state.security.max_session_secs.map(std::time::Duration::from_secs);
+    let session_timeout = async {
+        match session_limit {
+            Some(limit) => tokio::time::sleep(limit).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(session_timeout);
+    let mut close_reason = None;
+    // Masked until an authorized client asks to reveal
+    let mut masked = state.pii_mask.is_some();
+    let mut message_limit = MessageLimit::new(&state.security);
+    // Seq of the last card message this client got, the base for delta mode
+    let mut last_card_seq = None;
 
     // Handle WebSocket messages
-    while let Ok(msg) = rx.recv().await {
+    loop {
+        let received = tokio::select! {
+            received = rx.recv() => received,
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(_) | Message::Binary(_))) if !message_limit.admit(&state, client_ip) => {
+                    if state.security.ws_message_limit_policy == MessageLimitPolicy::Drop {
+                        continue;
+                    }
+                    let close = CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "Too many messages".into(),
+                    };
+                    let _ = socket.send(Message::Close(Some(close))).await;
+                    close_reason = Some("message_rate_limit");
+                    break;
+                }
+                Some(Ok(Message::Text(text))) => {
+                    if state.pii_mask.is_some() && is_reveal_command(&text) {
+                        state.audit_logger.log_reveal(client_ip, can_reveal);
+                        let status = if can_reveal {
+                            tracing::warn!("🔓 Client {} switched to unmasked data", client_ip);
+                            masked = false;
+                            "granted"
+                        } else {
+                            tracing::warn!("⚠️ Client {} requested reveal without an authorized key", client_ip);
+                            "denied"
+                        };
+                        let reply = serde_json::json!({ "mode": "reveal", "status": status });
+                        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            () = &mut session_timeout => {
+                tracing::info!("⏱️ Session limit reached for {}, closing connection", client_ip);
+                let close = CloseFrame {
+                    code: close_code::POLICY,
+                    reason: "Session timeout, please reconnect".into(),
+                };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                close_reason = Some("session_timeout");
+                break;
+            }
+        };
+
+        let msg = match received {
+            Ok(msg) => msg,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // Skipped card messages leave nothing to apply a delta to
+                last_card_seq = None;
+                if handle_lag(&state, client_ip, skipped) {
+                    continue;
+                }
+                let close = CloseFrame {
+                    code: close_code::AGAIN,
+                    reason: format!("Client too slow, skipped {} messages", skipped).into(),
+                };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                close_reason = Some("client_lag");
+                break;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        let msg = state.ws_frame(msg, &mut last_card_seq);
+        let msg = match &state.pii_mask {
+            Some(mask) if masked => mask.apply_str(&msg),
+            _ => msg,
+        };
+
         if let Err(_e) = socket.send(Message::Text(msg)).await {
             // client disconnected
             break;
         }
     }
 
+    state.stats.client_disconnected();
+
     // Calculate connection duration
     let duration_ms = connection_start.elapsed().as_millis() as u64;
 
-    // Release connection slot when client disconnects
-    if let Some(ref rate_limiter) = state.rate_limiter {
-        rate_limiter.release_connection(client_ip);
-        log::debug!("✓ Connection released for {}", client_ip);
-    }
+    // Release connection slots when client disconnects
+    drop(slots);
 
     // Log connection closed
-    state.audit_logger.log_connection_close(client_ip, Some(duration_ms));
+    state.audit_logger.log_connection_close(client_ip, Some(duration_ms), close_reason);
+}
+
+/// Inbound message limit of one socket (`security.ws_message_rate`)
+struct MessageLimit {
+    bucket: Option<MessageBucket>,
+    /// Over the limit since the last admitted message
+    throttled: bool,
+}
+
+impl MessageLimit {
+    fn new(security: &SecurityConfig) -> Self {
+        Self {
+            bucket: MessageBucket::new(security.ws_message_rate, security.ws_message_burst),
+            throttled: false,
+        }
+    }
+
+    /// Whether the next client message is within the limit
+    ///
+    /// Only the first rejected message of a flood is logged and audited.
+    fn admit(&mut self, state: &AppState, client_ip: std::net::IpAddr) -> bool {
+        let Some(bucket) = &mut self.bucket else {
+            return true;
+        };
+        if bucket.try_take() {
+            self.throttled = false;
+            return true;
+        }
+        if !self.throttled {
+            self.throttled = true;
+            tracing::warn!("⚠️ Message rate limit exceeded by {} ({})", client_ip, state.security.ws_message_limit_policy);
+            state.audit_logger.log_rate_limit(client_ip, "message");
+        }
+        false
+    }
+}
+
+/// Log and audit a client that missed `skipped` broadcasts
+///
+/// Returns `true` if the client should keep streaming.
+fn handle_lag(state: &AppState, client_ip: std::net::IpAddr, skipped: u64) -> bool {
+    let disconnect = state.lag_policy == LagPolicy::Disconnect;
+    if disconnect {
+        tracing::warn!("⚠️ Client {} lagged, skipped {} messages. Disconnecting (lag_policy = \"{}\")",
+            client_ip, skipped, state.lag_policy);
+    } else {
+        tracing::warn!("⚠️ Client {} lagged, skipped {} messages. Resyncing", client_ip, skipped);
+    }
+    state.audit_logger.log_client_lag(client_ip, skipped, disconnect);
+    !disconnect
+}
+
+/// Clients listed by `/api/rate-limit-stats` when per-IP detail is enabled
+const RATE_LIMIT_STATS_TOP_N: usize = 10;
+
+/// Routes of the service: the WebSocket at `ws_path`, plus the fixed HTTP endpoints
+pub fn router(ws_path: &str, enable_decrypt_endpoint: bool, enable_test_events: bool) -> Router<Arc<AppState>> {
+    let mut app = Router::new()
+        .route(ws_path, get(ws_handler))
+        .route("/version", get(version_handler))
+        .route("/health", get(health_handler))
+        .route("/health/security", get(security_status_handler))
+        .route("/api/rate-limit-stats", get(rate_limit_stats_handler));
+    if enable_decrypt_endpoint {
+        app = app.route("/api/decrypt", post(decrypt_handler));
+    }
+    if enable_test_events {
+        app = app.route("/api/test-event", post(test_event_handler));
+    }
+    app
+}
+
+/// Bind the server port, retrying up to `server.bind_retries` times
+///
+/// The delay starts at `bind_retry_delay_ms` and doubles after each failure,
+/// riding out a port still held from a previous run.
+///
+/// # Errors
+/// Returns the last bind error once every attempt failed
+pub async fn bind_with_retry(server: &ServerConfig) -> std::io::Result<tokio::net::TcpListener> {
+    let addr = server.socket_addr();
+    retry_bind(
+        server.bind_retries,
+        std::time::Duration::from_millis(server.bind_retry_delay_ms),
+        || tokio::net::TcpListener::bind(addr),
+    )
+    .await
+}
+
+async fn retry_bind<L, F, Fut>(attempts: u8, mut delay: std::time::Duration, mut bind: F) -> std::io::Result<L>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = std::io::Result<L>>,
+{
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match bind().await {
+            Ok(listener) => {
+                if attempt > 1 {
+                    log::info!("✓ Server port bound on attempt {}/{}", attempt, attempts);
+                }
+                return Ok(listener);
+            }
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(e) => {
+                log::warn!("Failed to bind server port (attempt {}/{}): {}", attempt, attempts, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// CORS policy from the server settings
+///
+/// Origins are matched verbatim, so IPv6 origins must be bracketed: `http://[::1]:3000`.
+///
+/// `allow_post` is set when a POST endpoint (`/api/decrypt`, `/api/test-event`) is served.
+/// `api_key_header` is allowed besides `server.cors_headers`.
+pub fn cors_layer(server: &ServerConfig, api_key_header: &str, allow_post: bool) -> CorsLayer {
+    if server.cors_allow_all {
+        log::warn!("⚠️ CORS allow_all is ENABLED - This is INSECURE for production!");
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+    }
+
+    let allowed_origins = server.get_allowed_origins();
+    if allowed_origins.is_empty() {
+        log::error!("❌ CORS restricted mode enabled but NO allowed origins configured!");
+        log::error!("   Set ALLOWED_ORIGINS env var or add to config.toml");
+    } else {
+        log::info!("✓ CORS restricted to allowed origins: {:?}", allowed_origins);
+    }
+
+    let origins: Vec<HeaderValue> = allowed_origins
+        .iter()
+        .filter_map(|origin| match origin.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                log::warn!("⚠️ Ignoring invalid CORS origin {:?}", origin);
+                None
+            }
+        })
+        .collect();
+
+    let mut methods: Vec<Method> = server
+        .cors_methods
+        .iter()
+        .filter_map(|method| match method.to_ascii_uppercase().parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                log::warn!("⚠️ Ignoring invalid CORS method {:?}", method);
+                None
+            }
+        })
+        .collect();
+    if allow_post && !methods.contains(&Method::POST) {
+        methods.push(Method::POST);
+    }
+
+    let mut headers: Vec<header::HeaderName> = Vec::new();
+    for name in server.cors_headers.iter().map(String::as_str).chain([api_key_header]) {
+        match name.parse() {
+            Ok(value) if !headers.contains(&value) => headers.push(value),
+            Ok(_) => {}
+            Err(_) => log::warn!("⚠️ Ignoring invalid CORS header {:?}", name),
+        }
+    }
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+}
+
+/// Build metadata of the running binary (no authentication, contains no secrets)
+pub async fn version_handler() -> Json<BuildInfo> {
+    Json(BuildInfo::current())
+}
+
+/// Liveness plus self-diagnostics (no authentication, contains no PII)
+///
+/// `font` reports whether the window loaded a Thai font (not where from),
+/// `null` when running headless.
+pub async fn health_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<HealthQuery>,
+) -> Response {
+    // Reader names and PC/SC state are only for clients allowed on the WebSocket
+    if query.detail && state.security.enable_authentication {
+        let client_ip = addr.ip().to_canonical();
+        if let Some(response) = reject_without_api_key(&state, client_ip, &headers, "/health?detail=true") {
+            return response;
+        }
+    }
+    let font = state
+        .stats
+        .font_status()
+        .map(|status| json!({ "thai_loaded": status.thai_loaded() }));
+    let mut body = json!({
+        "status": "ok",
+        "version": crate::build_info::VERSION,
+        "font": font,
+    });
+    if query.detail {
+        body["diagnostics"] = cached_diagnostics(&state).await;
+    }
+    Json(body).into_response()
+}
+
+/// How long a `/health?detail=true` snapshot is reused before probing PC/SC again
+const DIAGNOSTICS_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// [`reader_diagnostics`], probed at most once per [`DIAGNOSTICS_TTL`]
+///
+/// Concurrent requests wait for the probe in flight instead of starting their own.
+async fn cached_diagnostics(state: &AppState) -> serde_json::Value {
+    let mut cached = state.diagnostics.lock().await;
+    if let Some((taken, snapshot)) = cached.as_ref() {
+        if taken.elapsed() < DIAGNOSTICS_TTL {
+            return snapshot.clone();
+        }
+    }
+    let snapshot = reader_diagnostics().await;
+    *cached = Some((std::time::Instant::now(), snapshot.clone()));
+    snapshot
+}
+
+/// PC/SC snapshot for `/health?detail=true`; null when built without `reader`
+async fn reader_diagnostics() -> serde_json::Value {
+    #[cfg(feature = "reader")]
+    return tokio::task::spawn_blocking(crate::reader::diagnostics)
+        .await
+        .map_or(serde_json::Value::Null, |diagnostics| json!(diagnostics));
+    #[cfg(not(feature = "reader"))]
+    serde_json::Value::Null
+}
+
+/// Check the API key of a debugging endpoint request, returning the 401 on failure
+///
+/// Required even when WebSocket authentication is disabled.
+fn reject_without_api_key(
+    state: &AppState,
+    client_ip: std::net::IpAddr,
+    headers: &HeaderMap,
+    endpoint: &str,
+) -> Option<Response> {
+    let api_key = headers
+        .get(&state.security.api_key_header)
+        .and_then(|v| v.to_str().ok());
+    let valid_keys = state.security.get_api_keys();
+
+    match api_key {
+        Some(key) if valid_keys.iter().any(|k| constant_time_eq(k, key)) => {
+            state.audit_logger.log_auth_success(client_ip, Some(key_hint(key)));
+            None
+        }
+        _ => {
+            tracing::warn!("⚠️ Unauthorized {} request from {}", endpoint, client_ip);
+            state
+                .audit_logger
+                .log_auth_failure(client_ip, &format!("Invalid or missing API key for {}", endpoint));
+            Some((
+                StatusCode::UNAUTHORIZED,
+                format!("Authentication required. Provide a valid {} header.", state.security.api_key_header),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Current rate limiter state (API key required)
+///
+/// Per-client detail is only included with `security.rate_limit_stats_per_ip`.
+pub async fn rate_limit_stats_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let client_ip = addr.ip().to_canonical();
+    if let Some(response) = reject_without_api_key(&state, client_ip, &headers, "/api/rate-limit-stats") {
+        return response;
+    }
+
+    let Some(ref rate_limiter) = state.rate_limiter else {
+        return Json(json!({ "enabled": false })).into_response();
+    };
+    let stats = if state.security.rate_limit_stats_per_ip {
+        rate_limiter.get_detailed_stats(RATE_LIMIT_STATS_TOP_N)
+    } else {
+        rate_limiter.get_stats()
+    };
+    let mut body = json!(stats);
+    body["enabled"] = json!(true);
+    Json(body).into_response()
+}
+
+/// Security posture of the running server (API key required)
+pub async fn security_status_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let client_ip = addr.ip().to_canonical();
+    if let Some(response) = reject_without_api_key(&state, client_ip, &headers, "/health/security") {
+        return response;
+    }
+    Json(state.security_status).into_response()
+}
+
+/// Broadcast a posted `ThaiIDData` as if it was read from a card (development only)
+///
+/// Goes through the same validation, mapping and encryption as a real read;
+/// the message carries `"test": true` and only WebSocket clients receive it.
+pub async fn test_event_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(data): Json<ThaiIDData>,
+) -> Response {
+    let client_ip = addr.ip().to_canonical();
+    if let Some(response) = reject_without_api_key(&state, client_ip, &headers, "/api/test-event") {
+        return response;
+    }
+
+    let Some(msg) = state.test_card_message(&data) else {
+        state.audit_logger.log_test_event(client_ip, false);
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Test card rejected by validation or message size limit.",
+        )
+            .into_response();
+    };
+
+    tracing::warn!("🧪 Broadcasting test card event from {}", client_ip);
+    state.audit_logger.log_test_event(client_ip, true);
+    let receivers = state.tx.send(msg).unwrap_or(0);
+    (StatusCode::ACCEPTED, Json(json!({ "receivers": receivers }))).into_response()
+}
+
+/// Decrypt a base64 blob produced by this service (debugging only)
+///
+/// Always requires a valid API key, even when WebSocket authentication is
+/// disabled, since it turns ciphertext back into PII.
+pub async fn decrypt_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<DecryptRequest>,
+) -> Response {
+    let client_ip = addr.ip().to_canonical();
+    if let Some(response) = reject_without_api_key(&state, client_ip, &headers, "/api/decrypt") {
+        return response;
+    }
+
+    let Some(ref crypto) = state.crypto else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Encryption is not enabled on this server.",
+        )
+            .into_response();
+    };
+
+    match crypto.decrypt_from_base64(&request.data) {
+        Ok(plaintext) => {
+            tracing::warn!("🔓 /api/decrypt used by {}", client_ip);
+            Json(DecryptResponse { plaintext }).into_response()
+        }
+        Err(e) => {
+            tracing::debug!("Decrypt request failed: {}", e);
+            (StatusCode::BAD_REQUEST, format!("Decryption failed: {}", e)).into_response()
+        }
+    }
+}
+
+/// Outcome of fitting a broadcast payload into `server.max_message_bytes`
+#[derive(Debug, PartialEq, Eq)]
+pub enum SizedMessage {
+    /// Within the limit, sent unchanged
+    Full(String),
+    /// Sent without the photo field; carries the original size
+    PhotoDropped(String, usize),
+    /// Too large even without the photo; carries the size
+    Rejected(usize),
+}
+
+/// Serialize `payload`, dropping `photo_key` if it exceeds `max_bytes` (0 = no limit)
+#[must_use]
+pub fn fit_message(mut payload: serde_json::Value, photo_key: &str, max_bytes: usize) -> SizedMessage {
+    let msg = payload.to_string();
+    if max_bytes == 0 || msg.len() <= max_bytes {
+        return SizedMessage::Full(msg);
+    }
+
+    let original_bytes = msg.len();
+    // The photo sits next to `mode`, or under `data` in a nested envelope
+    let photo_removed = payload.as_object_mut().is_some_and(|obj| {
+        obj.remove(photo_key).is_some()
+            || obj
+                .get_mut(ENVELOPE_DATA_KEY)
+                .and_then(serde_json::Value::as_object_mut)
+                .and_then(|data| data.remove(photo_key))
+                .is_some()
+    });
+    if photo_removed {
+        let trimmed = payload.to_string();
+        if trimmed.len() <= max_bytes {
+            return SizedMessage::PhotoDropped(trimmed, original_bytes);
+        }
+    }
+    SizedMessage::Rejected(original_bytes)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn test_state(crypto: Option<Arc<CryptoService>>) -> Arc<AppState> {
+        let (tx, _rx) = broadcast::channel(1);
+        let security = SecurityConfig {
+            api_keys: vec!["test-key".to_string()],
+            enable_decrypt_endpoint: true,
+            ..SecurityConfig::default()
+        };
+        Arc::new(AppState {
+            tx,
+            seq: AtomicU64::new(0),
+            lag_policy: LagPolicy::default(),
+            rate_limiter: None,
+            audit_logger: Arc::new(AuditLogger::new(false)),
+            crypto,
+            stats: Arc::new(AppStats::new()),
+            pii_mask: None,
+            active_connections: AtomicU32::new(0),
+            output: OutputConfig::default(),
+            max_message_bytes: ServerConfig::default().max_message_bytes,
+            delta_mode: false,
+            last_card: parking_lot::Mutex::new(None),
+            card_frame: parking_lot::Mutex::new(None),
+            last_read: parking_lot::Mutex::new(None),
+            security_status: SecurityStatus::new(&ServerConfig::default(), &security),
+            stuck_card: parking_lot::Mutex::new(StuckCardDetector::new(&ServerConfig::default())),
+            geo_filter: None,
+            diagnostics: tokio::sync::Mutex::new(None),
+            security,
+        })
+    }
+
+    fn addr() -> ConnectInfo<SocketAddr> {
+        ConnectInfo(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 40000))
+    }
+
+    fn headers_with_key(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-API-Key", key.parse().unwrap());
+        headers
+    }
+
+    async fn body_json(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_endpoint_round_trip() {
+        let crypto = Arc::new(CryptoService::from_base64_key(&crate::crypto::generate_key()).unwrap());
+        let encrypted = crypto.encrypt_to_base64("1234567890123").unwrap();
+        let state = test_state(Some(crypto));
+
+        let response = decrypt_handler(
+            State(state),
+            addr(),
+            headers_with_key("test-key"),
+            Json(DecryptRequest { data: encrypted }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let decoded: DecryptResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(decoded.plaintext, "1234567890123");
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_endpoint_requires_key() {
+        let crypto = Arc::new(CryptoService::from_base64_key(&crate::crypto::generate_key()).unwrap());
+        let encrypted = crypto.encrypt_to_base64("secret").unwrap();
+        let state = test_state(Some(crypto));
+
+        let response = decrypt_handler(
+            State(state.clone()),
+            addr(),
+            HeaderMap::new(),
+            Json(DecryptRequest { data: encrypted.clone() }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = decrypt_handler(
+            State(state),
+            addr(),
+            headers_with_key("wrong-key"),
+            Json(DecryptRequest { data: encrypted }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_endpoint_rejects_garbage() {
+        let crypto = Arc::new(CryptoService::from_base64_key(&crate::crypto::generate_key()).unwrap());
+        let state = test_state(Some(crypto));
+
+        let response = decrypt_handler(
+            State(state),
+            addr(),
+            headers_with_key("test-key"),
+            Json(DecryptRequest { data: "not-base64!".to_string() }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_endpoint_without_crypto() {
+        let state = test_state(None);
+
+        let response = decrypt_handler(
+            State(state),
+            addr(),
+            headers_with_key("test-key"),
+            Json(DecryptRequest { data: String::new() }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_test_event_is_broadcast() {
+        let state = test_state(None);
+        let mut rx = state.tx.subscribe();
+        let card = ThaiIDData {
+            citizen_id: "1234567890121".to_string(),
+            ..ThaiIDData::default()
+        };
+
+        let response = test_event_handler(
+            State(state.clone()),
+            addr(),
+            HeaderMap::new(),
+            Json(card.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = test_event_handler(State(state.clone()), addr(), headers_with_key("test-key"), Json(card)).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let msg: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(msg["mode"], "readsmartcard");
+        assert_eq!(msg["Citizenid"], "1234567890121");
+        assert_eq!(msg["test"], true);
+        assert_eq!(msg["seq"], 1);
+        // Reader state is left alone
+        assert!(state.last_card.lock().is_none());
+        assert!(state.last_read.lock().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_stats_endpoint() {
+        use crate::rate_limiter::RateLimitConfig;
+
+        let rate_limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+            max_requests: 2,
+            ..RateLimitConfig::default()
+        }));
+        let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+        for _ in 0..3 {
+            rate_limiter.check_request(client);
+        }
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.rate_limiter = Some(rate_limiter);
+        let state = Arc::new(state);
+
+        let stats = |state: Arc<AppState>, headers| async move {
+            let response = rate_limit_stats_handler(State(state), addr(), headers).await;
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+        };
+
+        assert_eq!(stats(state.clone(), HeaderMap::new()).await.0, StatusCode::UNAUTHORIZED);
+
+        // Counts only by default
+        let (status, body) = stats(state.clone(), headers_with_key("test-key")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tracked_ips"], 1);
+        assert_eq!(body["throttled_ips"], 1);
+        assert!(body.get("clients").is_none());
+
+        let mut state = Arc::into_inner(state).unwrap();
+        state.security.rate_limit_stats_per_ip = true;
+        let (_, body) = stats(Arc::new(state), headers_with_key("test-key")).await;
+        assert_eq!(body["clients"][0]["client"], "192.0.2.7");
+        assert_eq!(body["clients"][0]["requests"], 3);
+        assert_eq!(body["clients"][0]["throttled"], true);
+    }
+
+    #[tokio::test]
+    async fn test_security_status_reflects_config() {
+        let server = ServerConfig {
+            enable_tls: true,
+            cors_allow_all: false,
+            ..ServerConfig::default()
+        };
+        let security = SecurityConfig {
+            enable_authentication: true,
+            enable_encryption: true,
+            enable_rate_limiting: false,
+            enable_audit_logging: true,
+            ..SecurityConfig::default()
+        };
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.security_status = SecurityStatus::new(&server, &security);
+        let state = Arc::new(state);
+
+        let response = security_status_handler(State(state.clone()), addr(), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = security_status_handler(State(state), addr(), headers_with_key("test-key")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            status,
+            json!({
+                "tls": true,
+                "auth": true,
+                "encryption": true,
+                "rate_limiting": false,
+                "audit": true,
+                "cors_restricted": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_delta_mode_sends_changes_for_re_reads() {
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.delta_mode = true;
+        let card = |citizen_id: &str, firstname: &str| {
+            CardEvent::Inserted(ThaiIDData {
+                citizen_id: citizen_id.to_string(),
+                th_firstname: firstname.to_string(),
+                ..ThaiIDData::default()
+            })
+        };
+        // What a client that got every card message so far is sent
+        let mut last_card_seq = None;
+        let mut ws_message = |event: &CardEvent| -> serde_json::Value {
+            let msg = state.card_message(event).unwrap();
+            let broadcast: serde_json::Value = serde_json::from_str(&msg).unwrap();
+            assert_ne!(broadcast["mode"], "readsmartcard_delta", "the broadcast channel only carries full reads");
+            serde_json::from_str(&state.ws_frame(msg, &mut last_card_seq)).unwrap()
+        };
+
+        // First read: full payload
+        let full = ws_message(&card("1101700230708", "สมชาย"));
+        assert_eq!(full["mode"], "readsmartcard");
+        assert_eq!(full["Citizenid"], "1101700230708");
+
+        // Same card again: only what changed, besides seq/timestamp
+        let delta = ws_message(&card("1101700230708", "สมชาย"));
+        assert_eq!(delta["mode"], "readsmartcard_delta");
+        let mut keys: Vec<&str> = delta.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["mode", "seq", "timestamp"]);
+        let delta = ws_message(&card("1101700230708", "สมหญิง"));
+        assert_eq!(delta["mode"], "readsmartcard_delta");
+        assert_eq!(delta["Th_Firstname"], "สมหญิง");
+        assert!(delta.get("Citizenid").is_none());
+
+        // A client without the previous read (new or lagged) gets the full payload
+        let msg = state.card_message(&card("1101700230708", "สมหญิง")).unwrap();
+        let full: serde_json::Value = serde_json::from_str(&state.ws_frame(msg, &mut None)).unwrap();
+        assert_eq!(full["mode"], "readsmartcard");
+        assert_eq!(full["Citizenid"], "1101700230708");
+
+        // Another card, or the same one after removal: full payload again
+        let full = ws_message(&card("3100600123456", "สมชาย"));
+        assert_eq!(full["mode"], "readsmartcard");
+        ws_message(&CardEvent::Removed);
+        let full = ws_message(&card("3100600123456", "สมชาย"));
+        assert_eq!(full["mode"], "readsmartcard");
+        assert_eq!(full["Citizenid"], "3100600123456");
+    }
+
+    #[test]
+    fn test_change_detection_compares_with_previous_read() {
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.output.change_detection = true;
+        let card = |citizen_id: &str| {
+            CardEvent::Inserted(ThaiIDData {
+                citizen_id: citizen_id.to_string(),
+                th_firstname: "สมชาย".to_string(),
+                ..ThaiIDData::default()
+            })
+        };
+        let message = |state: &AppState, event: &CardEvent| -> serde_json::Value {
+            serde_json::from_str(&state.card_message(event).unwrap()).unwrap()
+        };
+
+        // First read, then the same card unchanged, even after removal
+        assert_eq!(message(&state, &card("1101700230708"))["changed"], true);
+        assert_eq!(message(&state, &card("1101700230708"))["changed"], false);
+        message(&state, &CardEvent::Removed);
+        let same = message(&state, &card("1101700230708"));
+        assert_eq!(same["changed"], false);
+        // Only the full format carries field timestamps
+        assert!(same.get("field_timestamps").is_none());
+
+        // A different citizen ID is a change
+        assert_eq!(message(&state, &card("3100600123456"))["changed"], true);
+
+        state.output.format = OutputFormat::Full;
+        message(&state, &card("3100600123456"));
+        let full = message(&state, &card("3100600123456"));
+        assert_eq!(full["changed"], false);
+        let timestamps = full["field_timestamps"].as_object().unwrap();
+        assert!(timestamps.get("mode").is_none());
+        assert!(timestamps["Citizenid"].as_str().is_some_and(|t| t.ends_with('Z')));
+        assert_eq!(timestamps["Citizenid"], timestamps["Th_Firstname"]);
+    }
+
+    #[test]
+    fn test_identical_reads_report_stuck_card() {
+        let state = Arc::into_inner(test_state(None)).unwrap();
+        let config = ServerConfig { stuck_card_reads: 3, ..ServerConfig::default() };
+        *state.stuck_card.lock() = StuckCardDetector::new(&config);
+        let card = |citizen_id: &str| {
+            CardEvent::Inserted(ThaiIDData {
+                citizen_id: citizen_id.to_string(),
+                ..ThaiIDData::default()
+            })
+        };
+        let mode = |event: &CardEvent| -> Option<String> {
+            let msg: serde_json::Value = serde_json::from_str(&state.card_message(event)?).unwrap();
+            Some(msg["mode"].as_str().unwrap().to_string())
+        };
+
+        assert_eq!(mode(&card("1101700230708")).as_deref(), Some("readsmartcard"));
+        assert_eq!(mode(&card("1101700230708")).as_deref(), Some("readsmartcard"));
+        let stuck: serde_json::Value = serde_json::from_str(&state.card_message(&card("1101700230708")).unwrap()).unwrap();
+        assert_eq!(stuck["mode"], "cardstuck");
+        assert_eq!(stuck["reads"], 3);
+        assert!(stuck.get("Citizenid").is_none());
+        // Suppressed until removal
+        assert_eq!(mode(&card("1101700230708")), None);
+        mode(&CardEvent::Removed);
+        assert_eq!(mode(&card("1101700230708")).as_deref(), Some("readsmartcard"));
+    }
+
+    #[test]
+    fn test_stuck_card_window_and_other_cards() {
+        use std::time::Duration;
+
+        let config = ServerConfig {
+            stuck_card_reads: 2,
+            stuck_card_window_secs: 10,
+            suppress_stuck_card: false,
+            ..ServerConfig::default()
+        };
+        let mut detector = StuckCardDetector::new(&config);
+        let (a, b) = (json!({ "Citizenid": "1" }), json!({ "Citizenid": "2" }));
+        let start = std::time::Instant::now();
+
+        // Reads too far apart, or of another card, start a new run
+        assert_eq!(detector.observe(&a, start), StuckVerdict::Send);
+        assert_eq!(detector.observe(&a, start + Duration::from_secs(11)), StuckVerdict::Send);
+        assert_eq!(detector.observe(&b, start + Duration::from_secs(12)), StuckVerdict::Send);
+        assert_eq!(detector.observe(&b, start + Duration::from_secs(13)), StuckVerdict::Stuck(2));
+        // Without suppression repeats are sent again
+        assert_eq!(detector.observe(&b, start + Duration::from_secs(60)), StuckVerdict::Send);
+        assert_eq!(detector.observe(&a, start + Duration::from_secs(61)), StuckVerdict::Send);
+
+        assert!(!StuckCardDetector::new(&ServerConfig::default()).is_enabled());
+    }
+
+    #[test]
+    fn test_card_message_validates_and_encrypts() {
+        let card = ThaiIDData {
+            citizen_id: "1101700230708".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            ..ThaiIDData::default()
+        };
+        let message = |state: &AppState, data: &ThaiIDData| {
+            state
+                .card_message(&CardEvent::Inserted(data.clone()))
+                .map(|msg| serde_json::from_str::<serde_json::Value>(&msg).unwrap())
+        };
+
+        // Valid card: mapped, stamped, in plaintext
+        let state = test_state(None);
+        let sent = message(&state, &card).unwrap();
+        assert_eq!(sent["mode"], "readsmartcard");
+        assert_eq!(sent["Citizenid"], "1101700230708");
+        assert_eq!(sent["Th_Firstname"], "สมชาย");
+        assert_eq!(sent["seq"], 1);
+
+        // Security threat in a field: dropped
+        let threat = ThaiIDData {
+            address: "<script>alert(1)</script>".to_string(),
+            ..card.clone()
+        };
+        assert!(message(&state, &threat).is_none());
+
+        // Encryption on: listed fields are ciphertext, the rest stay readable
+        let crypto = Arc::new(CryptoService::from_base64_key(&crate::crypto::generate_key()).unwrap());
+        let mut state = Arc::into_inner(test_state(Some(crypto.clone()))).unwrap();
+        state.security.enable_encryption = true;
+        state.security.encrypted_fields = vec!["Citizenid".to_string()];
+        let sent = message(&state, &card).unwrap();
+        let ciphertext = sent["Citizenid"].as_str().unwrap();
+        assert_ne!(ciphertext, "1101700230708");
+        assert_eq!(crypto.decrypt_from_base64(ciphertext).unwrap(), "1101700230708");
+        assert_eq!(sent["Th_Firstname"], "สมชาย");
+        assert_eq!(sent["mode"], "readsmartcard");
+
+        // Objects (nhso) are encrypted value by value
+        state.security.encrypted_fields = vec!["nhso".to_string()];
+        let nhso_card = ThaiIDData {
+            nhso: [("main_inscl".to_string(), "UCS".to_string())].into(),
+            ..card.clone()
+        };
+        let sent = message(&state, &nhso_card).unwrap();
+        let ciphertext = sent["nhso"]["main_inscl"].as_str().unwrap();
+        assert_eq!(crypto.decrypt_from_base64(ciphertext).unwrap(), "UCS");
+    }
+
+    #[test]
+    fn test_photo_encrypted_only_over_threshold() {
+        let crypto = Arc::new(CryptoService::from_base64_key(&crate::crypto::generate_key()).unwrap());
+        let mut state = Arc::into_inner(test_state(Some(crypto.clone()))).unwrap();
+        state.security.enable_encryption = true;
+        state.security.encrypted_fields = vec!["Citizenid".to_string(), "PhotoRaw".to_string()];
+        state.security.encrypt_photo_over_bytes = 8;
+        let message = |photo: &str| -> serde_json::Value {
+            let data = ThaiIDData {
+                citizen_id: "1101700230708".to_string(),
+                photo: photo.to_string(),
+                ..ThaiIDData::default()
+            };
+            serde_json::from_str(&state.card_message(&CardEvent::Inserted(data)).unwrap()).unwrap()
+        };
+
+        // At or below the threshold: plaintext photo, other fields still encrypted
+        let small = message("/9j/4AAQ");
+        assert_eq!(small["PhotoRaw"], "/9j/4AAQ");
+        assert_eq!(small["photo_encrypted"], false);
+        assert_ne!(small["Citizenid"], "1101700230708");
+
+        // Above it: encrypted
+        let large = message("/9j/4AAQSkZJRg==");
+        assert_eq!(large["photo_encrypted"], true);
+        let ciphertext = large["PhotoRaw"].as_str().unwrap();
+        assert_eq!(crypto.decrypt_from_base64(ciphertext).unwrap(), "/9j/4AAQSkZJRg==");
+
+        // No flag without encryption
+        let plain = test_state(None)
+            .card_message(&CardEvent::Inserted(ThaiIDData::default()))
+            .unwrap();
+        assert!(!plain.contains("photo_encrypted"));
+    }
+
+    #[test]
+    fn test_encrypt_failure_policies() {
+        let data = ThaiIDData {
+            citizen_id: "1234567890121".to_string(),
+            ..ThaiIDData::default()
+        };
+        let message = |policy| {
+            let mut state = Arc::into_inner(test_state(Some(Arc::new(CryptoService::failing())))).unwrap();
+            state.security.enable_encryption = true;
+            state.security.on_encrypt_failure = policy;
+            state
+                .card_message(&CardEvent::Inserted(data.clone()))
+                .map(|msg| serde_json::from_str::<serde_json::Value>(&msg).unwrap())
+        };
+
+        assert!(message(EncryptFailurePolicy::Abort).is_none());
+
+        let omitted = message(EncryptFailurePolicy::Omit).unwrap();
+        assert!(omitted.get("Citizenid").is_none());
+        assert_eq!(omitted["mode"], "readsmartcard");
+
+        let plaintext = message(EncryptFailurePolicy::Plaintext).unwrap();
+        assert_eq!(plaintext["Citizenid"], "1234567890121");
+    }
+
+    #[test]
+    fn test_fit_message_within_limit() {
+        let payload = serde_json::json!({"mode": "removedsmartcard"});
+        assert_eq!(
+            fit_message(payload.clone(), "PhotoRaw", 1024),
+            SizedMessage::Full(payload.to_string())
+        );
+        // 0 disables the limit
+        assert!(matches!(fit_message(payload, "PhotoRaw", 0), SizedMessage::Full(_)));
+    }
+
+    #[test]
+    fn test_fit_message_drops_oversized_photo() {
+        let payload = serde_json::json!({
+            "mode": "readsmartcard",
+            "Citizenid": "1234567890121",
+            "PhotoRaw": "A".repeat(10_000),
+        });
+
+        match fit_message(payload, "PhotoRaw", 1024) {
+            SizedMessage::PhotoDropped(msg, original_bytes) => {
+                assert!(original_bytes > 10_000);
+                assert!(!msg.contains("PhotoRaw"));
+                assert!(msg.contains("1234567890121"));
+            }
+            other => panic!("expected photo to be dropped, got {:?}", other),
+        }
+
+        let nested = serde_json::json!({
+            "mode": "readsmartcard",
+            "data": { "Citizenid": "1234567890121", "PhotoRaw": "A".repeat(10_000) },
+        });
+        match fit_message(nested, "PhotoRaw", 1024) {
+            SizedMessage::PhotoDropped(msg, _) => {
+                assert!(!msg.contains("PhotoRaw"));
+                assert!(msg.contains("1234567890121"));
+            }
+            other => panic!("expected nested photo to be dropped, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fit_message_rejects_oversized_payload() {
+        let payload = serde_json::json!({
+            "mode": "readsmartcard",
+            "Address": "ก".repeat(1_000),
+            "PhotoRaw": "A".repeat(10_000),
+        });
+        assert!(matches!(fit_message(payload.clone(), "PhotoRaw", 512), SizedMessage::Rejected(_)));
+        // Renamed photo field: nothing to drop
+        assert!(matches!(fit_message(payload, "photo", 10_500), SizedMessage::Rejected(_)));
+    }
+
+    #[test]
+    fn test_lag_policy_decides_whether_client_stays() {
+        let resync = test_state(None);
+        assert!(handle_lag(&resync, IpAddr::V4(Ipv4Addr::LOCALHOST), 3));
+
+        let mut disconnect = Arc::into_inner(test_state(None)).unwrap();
+        disconnect.lag_policy = LagPolicy::Disconnect;
+        assert!(!handle_lag(&disconnect, IpAddr::V4(Ipv4Addr::LOCALHOST), 3));
+    }
+
+    /// Serve `app` on an ephemeral localhost port
+    async fn spawn_server(app: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        serve(listener, app)
+    }
+
+    fn serve(listener: tokio::net::TcpListener, app: Router) -> SocketAddr {
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_bind_retries_transient_failure() {
+        let calls = AtomicU32::new(0);
+        let bind = || {
+            let call = calls.fetch_add(1, Ordering::Relaxed);
+            async move {
+                if call == 0 {
+                    Err(std::io::Error::from(std::io::ErrorKind::AddrInUse))
+                } else {
+                    Ok(call)
+                }
+            }
+        };
+
+        let start = std::time::Instant::now();
+        let bound = retry_bind(3, std::time::Duration::from_millis(20), bind).await.unwrap();
+        assert_eq!(bound, 1);
+        assert!(start.elapsed() >= std::time::Duration::from_millis(20));
+
+        // Gives up with the bind error once attempts run out
+        let calls = AtomicU32::new(0);
+        let always_busy = || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async { Err::<(), _>(std::io::Error::from(std::io::ErrorKind::AddrInUse)) }
+        };
+        let err = retry_bind(2, std::time::Duration::from_millis(1), always_busy).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_custom_ws_path() {
+        use tokio_tungstenite::tungstenite::Error as ClientError;
+
+        let app = router("/smartcard/ws", false, false).with_state(test_state(None));
+        let addr = spawn_server(app).await;
+
+        let (client, response) = tokio_tungstenite::connect_async(format!("ws://{}/smartcard/ws", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        drop(client);
+
+        match tokio_tungstenite::connect_async(format!("ws://{}/", addr)).await {
+            Err(ClientError::Http(response)) => assert_eq!(response.status(), StatusCode::NOT_FOUND),
+            other => panic!("expected 404 at /, got {:?}", other.map(|(_, r)| r.status())),
+        }
+    }
+
+    #[test]
+    fn test_ws_path_never_shadows_a_route() {
+        // axum panics on a path mounted twice
+        for route in crate::config::FIXED_ROUTES {
+            let server = crate::config::ServerConfig {
+                ws_path: route.to_string(),
+                ..crate::config::ServerConfig::default()
+            };
+            let _ = router(&server.websocket_path(), true, true);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_client_rate_limit_and_cors() {
+        use crate::rate_limiter::RateLimitConfig;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_tungstenite::tungstenite::Error as ClientError;
+
+        let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await else {
+            eprintln!("IPv6 loopback unavailable, skipping");
+            return;
+        };
+
+        let rate_limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+            max_connections: 1,
+            ..RateLimitConfig::default()
+        }));
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.rate_limiter = Some(rate_limiter.clone());
+        let server = ServerConfig {
+            host: "::1".parse().unwrap(),
+            cors_allow_all: false,
+            allowed_origins: vec!["http://[::1]:3000".to_string()],
+            ..ServerConfig::default()
+        };
+        let app = router("/", false, false)
+            .with_state(Arc::new(state))
+            .layer(cors_layer(&server, "X-API-Key", false));
+        let addr = serve(listener, app);
+
+        // The IPv6 peer gets a connection slot keyed by its address
+        let (_client, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap();
+        assert_eq!(rate_limiter.get_stats().total_active_connections, 1);
+        match tokio_tungstenite::connect_async(format!("ws://{}", addr)).await {
+            Err(ClientError::Http(response)) => {
+                assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS)
+            }
+            other => panic!("expected 429, got {:?}", other.map(|(_, r)| r.status())),
+        }
+
+        // Bracketed IPv6 origins are allowed verbatim
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET /version HTTP/1.1\r\nHost: {}\r\nOrigin: http://[::1]:3000\r\nConnection: close\r\n\r\n",
+            addr
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.to_ascii_lowercase().contains("access-control-allow-origin: http://[::1]:3000"),
+            "{response}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_allows_api_key_header() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = ServerConfig {
+            cors_allow_all: false,
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            cors_methods: vec!["get".to_string()],
+            ..ServerConfig::default()
+        };
+        let app = router("/", true, false)
+            .with_state(test_state(None))
+            .layer(cors_layer(&server, "X-Kiosk-Key", true));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = serve(listener, app);
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "OPTIONS /api/decrypt HTTP/1.1\r\nHost: {}\r\nOrigin: http://localhost:3000\r\n\
+             Access-Control-Request-Method: POST\r\nAccess-Control-Request-Headers: x-kiosk-key\r\n\
+             Connection: close\r\n\r\n",
+            addr
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let response = response.to_ascii_lowercase();
+
+        let allowed = |name: &str| {
+            response
+                .lines()
+                .find_map(|line| line.strip_prefix(&format!("{name}: ")))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let headers = allowed("access-control-allow-headers");
+        assert!(headers.contains("x-kiosk-key"), "{response}");
+        assert!(headers.contains("content-type") && headers.contains("authorization"), "{response}");
+        let methods = allowed("access-control-allow-methods");
+        assert!(methods.contains("get") && methods.contains("post"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn test_global_connection_cap() {
+        use tokio_tungstenite::tungstenite::Error as ClientError;
+
+        /// WebSocket handshake from a distinct loopback source address
+        async fn connect_from(
+            addr: SocketAddr,
+            source: Ipv4Addr,
+        ) -> Result<TestClient, ClientError> {
+            let socket = tokio::net::TcpSocket::new_v4().unwrap();
+            socket.bind(SocketAddr::new(IpAddr::V4(source), 0)).unwrap();
+            let stream = socket.connect(addr).await.unwrap();
+            let stream = tokio_tungstenite::MaybeTlsStream::Plain(stream);
+            tokio_tungstenite::client_async(format!("ws://{}", addr), stream)
+                .await
+                .map(|(client, _)| client)
+        }
+
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.security.max_total_connections = Some(2);
+        let state = Arc::new(state);
+        let addr = spawn_server(router("/", false, false).with_state(state.clone())).await;
+
+        let first = connect_from(addr, Ipv4Addr::new(127, 0, 0, 1)).await.unwrap();
+        let _second = connect_from(addr, Ipv4Addr::new(127, 0, 0, 2)).await.unwrap();
+        match connect_from(addr, Ipv4Addr::new(127, 0, 0, 3)).await {
+            Err(ClientError::Http(response)) => {
+                assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE)
+            }
+            other => panic!("expected 503, got {:?}", other.map(|_| ())),
+        }
+
+        // A disconnect frees its slot
+        drop(first);
+        for _ in 0..100 {
+            if state.active_connections.load(Ordering::Acquire) < 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(connect_from(addr, Ipv4Addr::new(127, 0, 0, 3)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_upgrades_release_their_slots() {
+        use crate::rate_limiter::RateLimitConfig;
+        use tokio_tungstenite::tungstenite::Error as ClientError;
+
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.security.enable_authentication = true;
+        state.rate_limiter = Some(Arc::new(RateLimiter::new(RateLimitConfig {
+            max_connections: 1,
+            ..RateLimitConfig::default()
+        })));
+        let state = Arc::new(state);
+        let addr = spawn_server(router("/", false, false).with_state(state.clone())).await;
+        let connect = |key: &'static str| tokio_tungstenite::connect_async(format!("ws://{}/?api_key={}", addr, key));
+
+        // A bad key after the per-IP slot was taken must not keep it
+        for _ in 0..2 {
+            match connect("wrong").await {
+                Err(ClientError::Http(response)) => assert_eq!(response.status(), StatusCode::UNAUTHORIZED),
+                other => panic!("expected 401, got {:?}", other.map(|_| ())),
+            }
+        }
+        let (client, _) = connect("test-key").await.unwrap();
+        assert_eq!(state.active_connections.load(Ordering::Acquire), 1);
+        drop(client);
+
+        // Slots held by a guard that never reaches handle_socket are given back
+        let guard = ConnectionSlots {
+            state: state.clone(),
+            client_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 9)),
+            global: state.try_acquire_connection(),
+        };
+        let rate_limiter = state.rate_limiter.as_ref().unwrap();
+        assert!(rate_limiter.check_connection(guard.client_ip));
+        drop(guard);
+        assert!(rate_limiter.check_connection(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 9))));
+    }
+
+    #[tokio::test]
+    async fn test_geo_filter_blocks_other_countries() {
+        use tokio_tungstenite::tungstenite::Error as ClientError;
+
+        // Stub resolver: 127.0.0.1 is in Thailand, 127.0.0.2 in the US
+        let resolver = |ip: IpAddr| match ip {
+            IpAddr::V4(v4) if v4 == Ipv4Addr::new(127, 0, 0, 1) => Some("TH".to_string()),
+            IpAddr::V4(v4) if v4 == Ipv4Addr::new(127, 0, 0, 2) => Some("US".to_string()),
+            _ => None,
+        };
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.geo_filter = Some(GeoFilter::new(&["TH".to_string()], Box::new(resolver)));
+        let addr = spawn_server(router("/", false, false).with_state(Arc::new(state))).await;
+
+        let connect_from = |source: Ipv4Addr| async move {
+            let socket = tokio::net::TcpSocket::new_v4().unwrap();
+            socket.bind(SocketAddr::new(IpAddr::V4(source), 0)).unwrap();
+            let stream = tokio_tungstenite::MaybeTlsStream::Plain(socket.connect(addr).await.unwrap());
+            tokio_tungstenite::client_async(format!("ws://{}", addr), stream).await
+        };
+
+        assert!(connect_from(Ipv4Addr::new(127, 0, 0, 1)).await.is_ok());
+        match connect_from(Ipv4Addr::new(127, 0, 0, 2)).await {
+            Err(ClientError::Http(response)) => assert_eq!(response.status(), StatusCode::FORBIDDEN),
+            other => panic!("expected 403, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    type TestClient = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Next text frame from `client`, parsed as JSON
+    async fn next_json(client: &mut TestClient) -> serde_json::Value {
+        use futures_util::StreamExt;
+
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_masked_and_reveal() {
+        use crate::config::OutputConfig;
+        use crate::decoder::{apply_output_config, ThaiIDData};
+        use futures_util::SinkExt;
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let output = OutputConfig::default();
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        let (tx, _rx) = broadcast::channel(8);
+        state.tx = tx.clone();
+        state.security.broadcast_masked = true;
+        state.security.reveal_keys = vec!["reveal-key".to_string()];
+        state.pii_mask = Some(PiiMask::new(&output));
+        let addr = spawn_server(router("/", false, false).with_state(Arc::new(state))).await;
+
+        let mut default_client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+        let mut request = format!("ws://{}", addr).into_client_request().unwrap();
+        request.headers_mut().insert("X-API-Key", "reveal-key".parse().unwrap());
+        let mut reveal_client = tokio_tungstenite::connect_async(request).await.unwrap().0;
+
+        // Replies also prove both handlers are subscribed before the broadcast
+        for (client, expected) in [(&mut default_client, "denied"), (&mut reveal_client, "granted")] {
+            client.send(ClientMessage::text(r#"{"command":"reveal"}"#)).await.unwrap();
+            assert_eq!(next_json(client).await["status"], expected);
+        }
+
+        let data = ThaiIDData {
+            citizen_id: "3100600123456".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            ..ThaiIDData::default()
+        };
+        tx.send(apply_output_config(&data, &output).to_string()).unwrap();
+
+        let id_key = output.output_key("Citizenid");
+        let masked = next_json(&mut default_client).await;
+        assert_eq!(masked[&id_key], crate::decoder::mask_citizen_id("3100600123456"));
+        assert_eq!(masked[output.output_key("Th_Firstname")], "ส****");
+        let full = next_json(&mut reveal_client).await;
+        assert_eq!(full[&id_key], "3100600123456");
+    }
+
+    #[tokio::test]
+    async fn test_delta_mode_full_read_for_new_clients() {
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        let (tx, _rx) = broadcast::channel(8);
+        state.tx = tx.clone();
+        state.delta_mode = true;
+        let state = Arc::new(state);
+        let addr = spawn_server(router("/", false, false).with_state(state.clone())).await;
+        let connect = || async {
+            let receivers = tx.receiver_count();
+            let client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+            while tx.receiver_count() == receivers {
+                tokio::task::yield_now().await;
+            }
+            client
+        };
+        let read = ThaiIDData {
+            citizen_id: "1101700230708".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            ..ThaiIDData::default()
+        };
+        let broadcast = || tx.send(state.card_message(&CardEvent::Inserted(read.clone())).unwrap()).unwrap();
+
+        let mut early = connect().await;
+        broadcast();
+        assert_eq!(next_json(&mut early).await["mode"], "readsmartcard");
+
+        // The re-read is a delta for the client holding the first read only
+        let mut late = connect().await;
+        broadcast();
+        assert_eq!(next_json(&mut early).await["mode"], "readsmartcard_delta");
+        let full = next_json(&mut late).await;
+        assert_eq!(full["mode"], "readsmartcard");
+        assert_eq!(full["Citizenid"], "1101700230708");
+
+        broadcast();
+        assert_eq!(next_json(&mut late).await["mode"], "readsmartcard_delta");
+    }
+
+    #[tokio::test]
+    async fn test_lagging_client_resyncs_or_is_disconnected() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        for policy in [LagPolicy::Resync, LagPolicy::Disconnect] {
+            let mut state = Arc::into_inner(test_state(None)).unwrap();
+            let (tx, _rx) = broadcast::channel(2);
+            state.tx = tx.clone();
+            state.lag_policy = policy;
+            let addr = spawn_server(router("/", false, false).with_state(Arc::new(state))).await;
+
+            let mut client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+            while tx.receiver_count() == 1 {
+                tokio::task::yield_now().await;
+            }
+            // Overrun the two-message channel before the handler gets to run
+            for n in 0..5 {
+                tx.send(serde_json::json!({ "n": n }).to_string()).unwrap();
+            }
+
+            if policy == LagPolicy::Resync {
+                // The skipped messages are gone, the client carries on with the rest
+                assert_eq!(next_json(&mut client).await["n"], 3);
+                assert_eq!(next_json(&mut client).await["n"], 4);
+                continue;
+            }
+            let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+                .await
+                .expect("lagging client should be closed by the server")
+                .unwrap()
+                .unwrap();
+            match msg {
+                ClientMessage::Close(Some(frame)) => {
+                    assert_eq!(frame.code, CloseCode::Again);
+                    assert!(frame.reason.contains("skipped 3 messages"), "{}", frame.reason);
+                }
+                other => panic!("expected close frame, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_timeout_closes_socket_and_releases_slot() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let rate_limiter = Arc::new(RateLimiter::default_config());
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.security.max_session_secs = Some(1);
+        state.rate_limiter = Some(rate_limiter.clone());
+        let addr = spawn_server(router("/", false, false).with_state(Arc::new(state))).await;
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap();
+        assert_eq!(rate_limiter.get_stats().total_active_connections, 1);
+
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+            .await
+            .expect("session should be closed by the server")
+            .unwrap()
+            .unwrap();
+        match msg {
+            ClientMessage::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Policy),
+            other => panic!("expected close frame, got {:?}", other),
+        }
+
+        // Slot is released once the handler finishes
+        for _ in 0..50 {
+            if rate_limiter.get_stats().total_active_connections == 0 {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("connection slot was not released");
+    }
+
+    #[tokio::test]
+    async fn test_message_bursts_are_throttled() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let limited_state = |policy: MessageLimitPolicy| {
+            let mut state = Arc::into_inner(test_state(None)).unwrap();
+            let (tx, _rx) = broadcast::channel(8);
+            state.tx = tx;
+            state.pii_mask = Some(PiiMask::new(&state.output));
+            state.security.ws_message_rate = 1;
+            state.security.ws_message_burst = 2;
+            state.security.ws_message_limit_policy = policy;
+            Arc::new(state)
+        };
+        let reveal = || ClientMessage::text(r#"{"command":"reveal"}"#);
+
+        // Drop: only the burst is answered, the connection stays up
+        let state = limited_state(MessageLimitPolicy::Drop);
+        let addr = spawn_server(router("/", false, false).with_state(state.clone())).await;
+        let mut client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+        for _ in 0..5 {
+            client.send(reveal()).await.unwrap();
+        }
+        for _ in 0..2 {
+            assert_eq!(next_json(&mut client).await["mode"], "reveal");
+        }
+        // After a refill one more command is answered; nothing dropped was queued
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        client.send(reveal()).await.unwrap();
+        assert_eq!(next_json(&mut client).await["mode"], "reveal");
+        state.tx.send(r#"{"mode":"readsmartcard"}"#.to_string()).unwrap();
+        assert_eq!(next_json(&mut client).await["mode"], "readsmartcard");
+
+        // Close: the flood ends the connection
+        let addr = spawn_server(router("/", false, false).with_state(limited_state(MessageLimitPolicy::Close))).await;
+        let mut client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+        for _ in 0..5 {
+            client.send(ClientMessage::text("{}")).await.unwrap();
+        }
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+            .await
+            .expect("flooding client should be closed")
+            .unwrap()
+            .unwrap();
+        match msg {
+            ClientMessage::Close(Some(frame)) => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                assert_eq!(frame.reason, "Too many messages");
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    }
+
+    /// Server requiring `test-key`, with `auth_timeout_ms` for first-message auth
+    async fn spawn_auth_server(auth_timeout_ms: u64) -> (SocketAddr, Arc<AppState>) {
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        let (tx, _rx) = broadcast::channel(8);
+        state.tx = tx;
+        state.security.enable_authentication = true;
+        state.security.ws_auth_timeout_ms = auth_timeout_ms;
+        let state = Arc::new(state);
+        (spawn_server(router("/", false, false).with_state(state.clone())).await, state)
+    }
+
+    #[tokio::test]
+    async fn test_ws_query_param_auth() {
+        use tokio_tungstenite::tungstenite::Error as ClientError;
+
+        let (addr, _state) = spawn_auth_server(5000).await;
+
+        let (_client, response) = tokio_tungstenite::connect_async(format!("ws://{}/?api_key=test-key", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+        match tokio_tungstenite::connect_async(format!("ws://{}/?api_key=wrong-key", addr)).await {
+            Err(ClientError::Http(response)) => assert_eq!(response.status(), StatusCode::UNAUTHORIZED),
+            other => panic!("expected 401, got {:?}", other.map(|(_, r)| r.status())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_first_message_auth() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let (addr, state) = spawn_auth_server(5000).await;
+
+        let mut client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+        client.send(ClientMessage::text(r#"{"auth":"test-key"}"#)).await.unwrap();
+        let reply = next_json(&mut client).await;
+        assert_eq!(reply["mode"], "auth");
+        assert_eq!(reply["status"], "granted");
+
+        // Subscribed only after authenticating
+        state.tx.send(r#"{"mode":"readsmartcard"}"#.to_string()).unwrap();
+        assert_eq!(next_json(&mut client).await["mode"], "readsmartcard");
+
+        let mut intruder = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+        intruder.send(ClientMessage::text(r#"{"auth":"wrong-key"}"#)).await.unwrap();
+        match intruder.next().await {
+            Some(Ok(ClientMessage::Close(Some(frame)))) => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                assert_eq!(frame.reason, "Invalid API key");
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_auth_timeout_closes_socket() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let (addr, state) = spawn_auth_server(100).await;
+
+        let mut client = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap().0;
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+            .await
+            .expect("unauthenticated socket should be closed")
+            .unwrap()
+            .unwrap();
+        match msg {
+            ClientMessage::Close(Some(frame)) => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                assert_eq!(frame.reason, "Authentication timeout");
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+        assert_eq!(state.tx.receiver_count(), 0);
+
+        // Slot is released once the handler finishes
+        for _ in 0..50 {
+            if state.active_connections.load(Ordering::Relaxed) == 0 {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("connection slot was not released");
+    }
+
+    #[test]
+    fn test_broadcast_seq_strictly_increases() {
+        let state = test_state(None);
+        let seqs: Vec<u64> = (0..3)
+            .map(|_| {
+                let msg = state.finish_message(serde_json::json!({"mode": "removedsmartcard"})).unwrap();
+                let payload: serde_json::Value = serde_json::from_str(&msg).unwrap();
+                assert!(payload["timestamp"].is_string());
+                payload["seq"].as_u64().unwrap()
+            })
+            .collect();
+        assert_eq!(seqs, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_rejected_message_does_not_use_a_seq() {
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.max_message_bytes = 100;
+        state.output.key_case = crate::decoder::KeyCase::Camel;
+        state.output.field_mapping.insert("seq".to_string(), "sequence_no".to_string());
+
+        let sent = |payload: serde_json::Value| {
+            state
+                .finish_message(payload)
+                .map(|msg| serde_json::from_str::<serde_json::Value>(&msg).unwrap())
+        };
+        assert_eq!(sent(json!({"mode": "removedsmartcard"})).unwrap()["sequenceNo"], 1);
+        assert!(sent(json!({"mode": "readsmartcard", "Address": "x".repeat(200)})).is_none());
+
+        // No gap after the rejected payload; stamp keys follow the output mapping
+        let next = sent(json!({"mode": "removedsmartcard"})).unwrap();
+        assert_eq!(next["sequenceNo"], 2);
+        assert!(next["timestamp"].is_string());
+        assert!(next.get("seq").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_version_endpoint_reports_crate_version() {
+        let Json(info) = version_handler().await;
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.commit.is_empty());
+        assert!(!info.built_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_font_status() {
+        use crate::stats::FontStatus;
+
+        let health = |state: &Arc<AppState>, detail: bool| {
+            health_handler(State(state.clone()), addr(), HeaderMap::new(), Query(HealthQuery { detail }))
+        };
+        let state = test_state(None);
+        let body = body_json(health(&state, false).await).await;
+        assert_eq!(body["status"], "ok");
+        assert!(body["font"].is_null(), "headless: no font status yet");
+
+        state.stats.set_font_status(FontStatus::Missing);
+        let body = body_json(health(&state, false).await).await;
+        assert_eq!(body["font"]["thai_loaded"], false);
+
+        state.stats.set_font_status(FontStatus::File { path: "fonts/NotoSansThai-Regular.ttf".into() });
+        let body = body_json(health(&state, false).await).await;
+        assert_eq!(body["font"], json!({ "thai_loaded": true }), "the font path is not exposed");
+        assert!(body.get("diagnostics").is_none());
+
+        let body = body_json(health(&state, true).await).await;
+        assert!(body.get("diagnostics").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_health_detail_requires_key_and_is_cached() {
+        let mut state = Arc::into_inner(test_state(None)).unwrap();
+        state.security.enable_authentication = true;
+        let state = Arc::new(state);
+        let health = |headers: HeaderMap, detail: bool| {
+            health_handler(State(state.clone()), addr(), headers, Query(HealthQuery { detail }))
+        };
+
+        // The plain check stays open, the PC/SC detail needs a key when auth is on
+        assert_eq!(health(HeaderMap::new(), false).await.status(), StatusCode::OK);
+        assert_eq!(health(HeaderMap::new(), true).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(health(headers_with_key("wrong"), true).await.status(), StatusCode::UNAUTHORIZED);
+        let first = body_json(health(headers_with_key("test-key"), true).await).await;
+
+        // A second request within the TTL reuses the snapshot
+        let taken = state.diagnostics.lock().await.as_ref().unwrap().0;
+        let second = body_json(health(headers_with_key("test-key"), true).await).await;
+        assert_eq!(first["diagnostics"], second["diagnostics"]);
+        assert_eq!(state.diagnostics.lock().await.as_ref().unwrap().0, taken);
+    }
 }
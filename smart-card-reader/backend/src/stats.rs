@@ -0,0 +1,298 @@
+//! Lightweight activity counters shared between the server, reader and UI
+//!
+//! All counters are atomics so the reader and WebSocket tasks never block on
+//! the UI. Read/failure counts roll over at local midnight or on demand.
+//! Per-reader statistics cover the whole session and sit behind a short-lived lock.
+//! The UI also reports here which Thai font it loaded, for `/health`, and
+//! reads back whether the WebSocket server failed to start.
+
+use chrono::{Datelike, Local};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Shared activity counters
+#[derive(Debug)]
+pub struct AppStats {
+    /// Currently connected WebSocket clients
+    clients: AtomicUsize,
+    /// Successful card reads since the last reset
+    reads: AtomicU64,
+    /// Failed card reads since the last reset
+    failures: AtomicU64,
+    /// Local day (days since CE) the read counters belong to
+    day: AtomicI32,
+    /// Session statistics per reader name
+    readers: Mutex<HashMap<String, ReaderStats>>,
+    /// Thai font picked by the UI, `None` until a window sets up its fonts
+    font: Mutex<Option<FontStatus>>,
+    /// Why the WebSocket server is not running, `None` while it serves
+    server_error: Mutex<Option<String>>,
+}
+
+/// Where the UI got its Thai font from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontStatus {
+    /// Loaded from a font file on disk
+    File { path: String },
+    /// Fell back to the copy embedded in the binary (`fonts.fallback_embedded`)
+    Embedded,
+    /// No Thai font: Thai text renders as boxes
+    Missing,
+}
+
+impl FontStatus {
+    /// Whether Thai text can be rendered
+    #[must_use]
+    pub fn thai_loaded(&self) -> bool {
+        !matches!(self, Self::Missing)
+    }
+}
+
+/// Read outcomes and latency of one reader over the session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReaderStats {
+    /// Successful reads
+    pub reads: u64,
+    /// Failed reads
+    pub failures: u64,
+    /// Cumulative time spent reading, successful or not
+    pub total_read_time: Duration,
+    /// Most recent read error
+    pub last_error: Option<String>,
+}
+
+impl ReaderStats {
+    /// Average read duration, `None` before the first read
+    #[must_use]
+    pub fn average_read_time(&self) -> Option<Duration> {
+        let attempts = self.reads + self.failures;
+        (attempts > 0).then(|| self.total_read_time / u32::try_from(attempts).unwrap_or(u32::MAX))
+    }
+
+    /// Fraction of reads that failed (0.0 before the first read)
+    #[must_use]
+    pub fn failure_rate(&self) -> f64 {
+        let attempts = self.reads + self.failures;
+        if attempts == 0 {
+            0.0
+        } else {
+            self.failures as f64 / attempts as f64
+        }
+    }
+}
+
+/// Point-in-time copy of [`AppStats`] for display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsSnapshot {
+    pub clients: usize,
+    pub reads: u64,
+    pub failures: u64,
+}
+
+impl Default for AppStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppStats {
+    /// Create zeroed counters for the current local day
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            clients: AtomicUsize::new(0),
+            reads: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            day: AtomicI32::new(today()),
+            readers: Mutex::new(HashMap::new()),
+            font: Mutex::new(None),
+            server_error: Mutex::new(None),
+        }
+    }
+
+    /// Record the Thai font the UI loaded
+    pub fn set_font_status(&self, status: FontStatus) {
+        *self.font.lock() = Some(status);
+    }
+
+    /// Thai font the UI loaded, `None` when no window has started
+    #[must_use]
+    pub fn font_status(&self) -> Option<FontStatus> {
+        self.font.lock().clone()
+    }
+
+    /// Record that the WebSocket server could not start
+    pub fn set_server_error(&self, error: impl Into<String>) {
+        *self.server_error.lock() = Some(error.into());
+    }
+
+    /// Why the WebSocket server is not running, `None` while it serves
+    #[must_use]
+    pub fn server_error(&self) -> Option<String> {
+        self.server_error.lock().clone()
+    }
+
+    /// Record a newly connected client
+    pub fn client_connected(&self) {
+        self.clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a disconnected client
+    pub fn client_disconnected(&self) {
+        let _ = self
+            .clients
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    /// Record a successful card read
+    pub fn record_read(&self) {
+        self.roll_over(today());
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a card read that failed after all retries
+    pub fn record_failure(&self) {
+        self.roll_over(today());
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one read on `reader` that took `duration`, failing with `error` if set
+    pub fn record_reader_read(&self, reader: &str, duration: Duration, error: Option<&str>) {
+        let mut readers = self.readers.lock();
+        let stats = readers.entry(reader.to_string()).or_default();
+        stats.total_read_time += duration;
+        match error {
+            Some(error) => {
+                stats.failures += 1;
+                stats.last_error = Some(error.to_string());
+            }
+            None => stats.reads += 1,
+        }
+    }
+
+    /// Per-reader statistics, sorted by reader name
+    #[must_use]
+    pub fn reader_stats(&self) -> Vec<(String, ReaderStats)> {
+        let mut stats: Vec<_> = self
+            .readers
+            .lock()
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.clone()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// Reset the read/failure counters (client count is live and kept)
+    pub fn reset(&self) {
+        self.reads.store(0, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Take a snapshot for display
+    #[must_use]
+    pub fn snapshot(&self) -> StatsSnapshot {
+        self.roll_over(today());
+        StatsSnapshot {
+            clients: self.clients.load(Ordering::Relaxed),
+            reads: self.reads.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reset the daily counters if `day` differs from the stored day
+    fn roll_over(&self, day: i32) {
+        if self.day.swap(day, Ordering::Relaxed) != day {
+            self.reset();
+        }
+    }
+}
+
+fn today() -> i32 {
+    Local::now().date_naive().num_days_from_ce()
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let stats = AppStats::new();
+        stats.client_connected();
+        stats.client_connected();
+        stats.client_disconnected();
+        stats.record_read();
+        stats.record_read();
+        stats.record_failure();
+
+        assert_eq!(
+            stats.snapshot(),
+            StatsSnapshot {
+                clients: 1,
+                reads: 2,
+                failures: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_client_count_never_underflows() {
+        let stats = AppStats::new();
+        stats.client_disconnected();
+        assert_eq!(stats.snapshot().clients, 0);
+    }
+
+    #[test]
+    fn test_reset_keeps_clients() {
+        let stats = AppStats::new();
+        stats.client_connected();
+        stats.record_read();
+        stats.reset();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.clients, 1);
+        assert_eq!(snapshot.reads, 0);
+    }
+
+    #[test]
+    fn test_daily_roll_over() {
+        let stats = AppStats::new();
+        stats.record_read();
+        stats.record_failure();
+
+        stats.roll_over(today() + 1);
+        assert_eq!(stats.reads.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.failures.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_reader_stats() {
+        let stats = AppStats::new();
+        stats.record_reader_read("Reader B", Duration::from_millis(800), None);
+        stats.record_reader_read("Reader A", Duration::from_millis(900), None);
+        stats.record_reader_read("Reader A", Duration::from_millis(1100), None);
+        stats.record_reader_read("Reader A", Duration::from_millis(1000), Some("APDU failed"));
+
+        let readers = stats.reader_stats();
+        assert_eq!(readers.len(), 2);
+        let (name, a) = &readers[0];
+        assert_eq!(name, "Reader A");
+        assert_eq!(a.reads, 2);
+        assert_eq!(a.failures, 1);
+        assert_eq!(a.total_read_time, Duration::from_millis(3000));
+        assert_eq!(a.average_read_time(), Some(Duration::from_millis(1000)));
+        assert!((a.failure_rate() - 1.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(a.last_error.as_deref(), Some("APDU failed"));
+
+        assert_eq!(readers[1].1.average_read_time(), Some(Duration::from_millis(800)));
+        assert_eq!(ReaderStats::default().average_read_time(), None);
+        assert_eq!(ReaderStats::default().failure_rate(), 0.0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Line templates for the stdout and webhook outputs (`output.template`)
+//!
+//! `{field}` placeholders are replaced with the field of the card message as
+//! broadcast (mapped key names, encrypted values stay ciphertext). Fields under
+//! a nested envelope's `data` are found too. `{{` and `}}` give literal braces;
+//! unknown fields render empty.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::decoder::{is_card_message, ENVELOPE_DATA_KEY};
+
+/// Quoting applied to substituted values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateEscape {
+    /// RFC 4180: values with a comma, quote or line break are double-quoted;
+    /// values starting with `=`, `+`, `-` or `@` get a leading `'` (formula injection)
+    #[default]
+    Csv,
+    /// Values inserted verbatim
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+/// Parsed `output.template`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+    escape: TemplateEscape,
+}
+
+impl Template {
+    /// Parse `source`; an unclosed `{` is kept as literal text
+    #[must_use]
+    pub fn parse(source: &str, escape: TemplateEscape) -> Self {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = source;
+        while let Some(i) = rest.find(['{', '}']) {
+            literal.push_str(&rest[..i]);
+            let tail = &rest[i..];
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                literal.push_str(&tail[..1]);
+                rest = &tail[2..];
+            } else if let Some(end) = tail.strip_prefix('{').and_then(|t| t.find('}')) {
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(Part::Field(tail[1..=end].trim().to_string()));
+                rest = &tail[end + 2..];
+            } else {
+                literal.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Self { parts, escape }
+    }
+
+    /// Fill the placeholders from `payload` (or its nested `data`)
+    #[must_use]
+    pub fn render(&self, payload: &Value) -> String {
+        let lookup = |field: &str| {
+            payload
+                .get(field)
+                .or_else(|| payload.get(ENVELOPE_DATA_KEY)?.get(field))
+        };
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => line.push_str(text),
+                Part::Field(field) => {
+                    let value = match lookup(field) {
+                        Some(Value::String(s)) => s.clone(),
+                        Some(Value::Null) => String::new(),
+                        Some(other) => other.to_string(),
+                        None => {
+                            log::debug!("Template field '{}' not in the message, left empty", field);
+                            String::new()
+                        }
+                    };
+                    line.push_str(&self.escape(value));
+                }
+            }
+        }
+        line
+    }
+
+    /// Rendered line for a card message, `None` for other messages
+    #[must_use]
+    pub fn render_message(&self, msg: &str) -> Option<String> {
+        let payload: Value = serde_json::from_str(msg).ok()?;
+        is_card_message(&payload).then(|| self.render(&payload))
+    }
+
+    fn escape(&self, value: String) -> String {
+        if self.escape != TemplateEscape::Csv {
+            return value;
+        }
+        // Spreadsheets run cells starting with these as formulas
+        let value = if value.starts_with(['=', '+', '-', '@']) { format!("'{}", value) } else { value };
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_placeholders_are_substituted() {
+        let payload = json!({
+            "mode": "readsmartcard",
+            "Citizenid": "1101700230708",
+            "Th_Firstname": "สมชาย",
+            "Th_Lastname": "ใจดี",
+            "seq": 7,
+        });
+        let template = Template::parse("{Citizenid},{Th_Firstname} {Th_Lastname},{seq}", TemplateEscape::Csv);
+        assert_eq!(template.render(&payload), "1101700230708,สมชาย ใจดี,7");
+
+        // Missing fields render empty, braces can be escaped, stray ones are literal
+        let template = Template::parse("{{id}}={ Citizenid };{Missing};{open", TemplateEscape::None);
+        assert_eq!(template.render(&payload), "{id}=1101700230708;;{open");
+
+        // Nested envelope fields are found under `data`
+        let nested = json!({ "mode": "readsmartcard", "data": { "Citizenid": "1101700230708" } });
+        assert_eq!(Template::parse("{Citizenid}", TemplateEscape::Csv).render(&nested), "1101700230708");
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        let payload = json!({ "Address": "99 หมู่ 4, ตำบล \"บางรัก\"" });
+        let csv = Template::parse("{Address}", TemplateEscape::Csv);
+        assert_eq!(csv.render(&payload), "\"99 หมู่ 4, ตำบล \"\"บางรัก\"\"\"");
+        let raw = Template::parse("{Address}", TemplateEscape::None);
+        assert_eq!(raw.render(&payload), "99 หมู่ 4, ตำบล \"บางรัก\"");
+
+        // Formula-looking cells are neutralised with a leading quote
+        let payload = json!({ "a": "=HYPERLINK(\"x\")", "b": "+1", "c": "-2", "d": "@SUM(A1)", "e": "ok" });
+        let csv = Template::parse("{a};{b};{c};{d};{e}", TemplateEscape::Csv);
+        assert_eq!(csv.render(&payload), "\"'=HYPERLINK(\"\"x\"\")\";'+1;'-2;'@SUM(A1);ok");
+        let raw = Template::parse("{b}", TemplateEscape::None);
+        assert_eq!(raw.render(&payload), "+1");
+    }
+
+    #[test]
+    fn test_only_card_messages_are_rendered() {
+        let template = Template::parse("{Citizenid}", TemplateEscape::Csv);
+        assert_eq!(
+            template.render_message(r#"{"mode":"readsmartcard","Citizenid":"1"}"#).as_deref(),
+            Some("1")
+        );
+        assert!(template.render_message(r#"{"mode":"removedsmartcard"}"#).is_none());
+        assert!(template.render_message("not json").is_none());
+    }
+}
@@ -0,0 +1,324 @@
+//! TLS configuration loading and certificate hot-reload
+//!
+//! Loads the certificate (PEM pair or PKCS#12 bundle) with retries, so a
+//! briefly missing file during rotation does not kill the process, and
+//! watches the files for changes so rotated certificates are picked up
+//! without a restart.
+
+use crate::config::TlsFormat;
+use axum_server::tls_rustls::RustlsConfig;
+use p12_keystore::{KeyStore, Pkcs12ImportPolicy};
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Environment variable holding the PKCS#12 bundle passphrase
+pub const TLS_P12_PASSWORD_ENV: &str = "TLS_P12_PASSWORD";
+
+/// Install aws-lc-rs as the process-wide rustls crypto provider
+///
+/// The webhook's reqwest client is built without a provider of its own and
+/// panics without one. Does nothing if a provider is already installed.
+pub fn install_crypto_provider() {
+    let _ = tokio_rustls::rustls::crypto::aws_lc_rs::default_provider().install_default();
+}
+
+/// Load TLS configuration from certificate and key files
+///
+/// For [`TlsFormat::Pkcs12`], `cert_path` is the bundle and `key_path` is
+/// ignored. Retries up to `attempts` times, waiting `delay` between attempts.
+///
+/// # Errors
+/// Returns the last error if every attempt fails
+pub async fn load_tls_config(
+    format: TlsFormat,
+    cert_path: &str,
+    key_path: &str,
+    attempts: u8,
+    delay: Duration,
+) -> anyhow::Result<RustlsConfig> {
+    let attempts = attempts.max(1);
+    let mut last_error = None;
+
+    for attempt in 1..=attempts {
+        match load_once(format, cert_path, key_path).await {
+            Ok(config) => {
+                if attempt > 1 {
+                    log::info!("✓ TLS config loaded on attempt {}/{}", attempt, attempts);
+                }
+                return Ok(config);
+            }
+            Err(e) => {
+                log::warn!("Failed to load TLS config (attempt {}/{}): {}", attempt, attempts, e);
+                last_error = Some(e);
+                if attempt < attempts {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Failed to load TLS config")))
+}
+
+async fn load_once(format: TlsFormat, cert_path: &str, key_path: &str) -> anyhow::Result<RustlsConfig> {
+    match format {
+        TlsFormat::Pem => RustlsConfig::from_pem_file(cert_path, key_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to load TLS config: {}", e)),
+        TlsFormat::Pkcs12 => {
+            let (certs, key) = read_pkcs12(cert_path, &pkcs12_password())?;
+            RustlsConfig::from_der(certs, key)
+                .await
+                .map_err(|e| anyhow::anyhow!("Invalid certificate in PKCS#12 bundle {}: {}", cert_path, e))
+        }
+    }
+}
+
+fn pkcs12_password() -> String {
+    std::env::var(TLS_P12_PASSWORD_ENV).unwrap_or_default()
+}
+
+/// Read a PKCS#12 bundle into a DER certificate chain and private key
+///
+/// # Errors
+/// Distinguishes a missing file, a wrong password and a malformed bundle
+pub fn read_pkcs12(path: &str, password: &str) -> anyhow::Result<(Vec<Vec<u8>>, Vec<u8>)> {
+    let data = std::fs::read(path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("PKCS#12 file not found: {}", path)
+        } else {
+            anyhow::anyhow!("Failed to read PKCS#12 file {}: {}", path, e)
+        }
+    })?;
+
+    let keystore = KeyStore::from_pkcs12(&data, password, Pkcs12ImportPolicy::Strict).map_err(|e| match e {
+        p12_keystore::error::Error::MacError(_) => anyhow::anyhow!(
+            "Wrong password for PKCS#12 bundle {} (check {})",
+            path,
+            TLS_P12_PASSWORD_ENV
+        ),
+        other => anyhow::anyhow!("Failed to parse PKCS#12 bundle {}: {}", path, other),
+    })?;
+
+    let (_, chain) = keystore
+        .private_key_chain()
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse PKCS#12 bundle {}: no private key with certificate", path))?;
+
+    let certs = chain.certs().iter().map(|c| c.as_der().to_vec()).collect();
+    Ok((certs, chain.key().as_der().to_vec()))
+}
+
+/// Polls certificate files for modification-time changes
+pub struct CertFileWatcher {
+    paths: Vec<PathBuf>,
+    last_modified: Vec<Option<SystemTime>>,
+}
+
+impl CertFileWatcher {
+    /// Create a watcher, recording the current modification times
+    #[must_use]
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let last_modified = paths.iter().map(|p| modified_time(p)).collect();
+        Self {
+            paths,
+            last_modified,
+        }
+    }
+
+    /// Returns `true` if any watched file changed since the last poll
+    ///
+    /// Files that are temporarily missing are not reported as changed,
+    /// so a rotation in progress is picked up once the new file lands.
+    pub fn poll_changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in self.paths.iter().zip(self.last_modified.iter_mut()) {
+            let current = modified_time(path);
+            if current.is_some() && current != *last {
+                *last = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watch the certificate/key files and reload `config` when they change
+///
+/// Runs until the task is dropped. Failed reloads keep the previous
+/// certificate in place and are retried on the next change.
+pub async fn watch_and_reload(
+    config: RustlsConfig,
+    format: TlsFormat,
+    cert_path: String,
+    key_path: String,
+    interval: Duration,
+) {
+    let paths = match format {
+        TlsFormat::Pem => vec![PathBuf::from(&cert_path), PathBuf::from(&key_path)],
+        TlsFormat::Pkcs12 => vec![PathBuf::from(&cert_path)],
+    };
+    watch_files(paths, interval, || {
+        let config = config.clone();
+        let cert_path = cert_path.clone();
+        let key_path = key_path.clone();
+        async move {
+            let result = match format {
+                TlsFormat::Pem => config
+                    .reload_from_pem_file(&cert_path, &key_path)
+                    .await
+                    .map_err(anyhow::Error::from),
+                TlsFormat::Pkcs12 => match read_pkcs12(&cert_path, &pkcs12_password()) {
+                    Ok((certs, key)) => config.reload_from_der(certs, key).await.map_err(anyhow::Error::from),
+                    Err(e) => Err(e),
+                },
+            };
+            match result {
+                Ok(()) => log::info!("🔄 TLS certificate reloaded from {}", cert_path),
+                Err(e) => log::error!("❌ Failed to reload TLS certificate: {}", e),
+            }
+        }
+    })
+    .await;
+}
+
+/// Poll `paths` every `interval` and run `on_change` whenever one changes
+async fn watch_files<F, Fut>(paths: Vec<PathBuf>, interval: Duration, mut on_change: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut watcher = CertFileWatcher::new(paths);
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        if watcher.poll_changed() {
+            on_change().await;
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("scr-tls-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_watcher_detects_change() {
+        let cert = temp_path("cert.pem");
+        std::fs::write(&cert, "old").unwrap();
+        let file = std::fs::File::options().write(true).open(&cert).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+
+        let mut watcher = CertFileWatcher::new(vec![cert.clone()]);
+        assert!(!watcher.poll_changed());
+
+        std::fs::write(&cert, "new").unwrap();
+        let file = std::fs::File::options().write(true).open(&cert).unwrap();
+        file.set_modified(SystemTime::now()).unwrap();
+
+        assert!(watcher.poll_changed());
+        assert!(!watcher.poll_changed());
+
+        std::fs::remove_file(&cert).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_changed_pem_triggers_reload() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let cert = temp_path("reload.pem");
+        std::fs::write(&cert, "old").unwrap();
+        let file = std::fs::File::options().write(true).open(&cert).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let counter = reloads.clone();
+        let task = tokio::spawn(watch_files(vec![cert.clone()], Duration::from_millis(10), move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(reloads.load(Ordering::SeqCst), 0);
+
+        // Rotate atomically, as cert-manager does
+        let staged = temp_path("reload.pem.new");
+        std::fs::write(&staged, "new").unwrap();
+        std::fs::rename(&staged, &cert).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+
+        task.abort();
+        std::fs::remove_file(&cert).unwrap();
+    }
+
+    #[test]
+    fn test_watcher_ignores_missing_file() {
+        let cert = temp_path("missing.pem");
+        let mut watcher = CertFileWatcher::new(vec![cert]);
+        assert!(!watcher.poll_changed());
+    }
+
+    const TEST_P12: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost-test.p12");
+    const TEST_P12_PASSWORD: &str = "test-password";
+
+    #[tokio::test]
+    async fn test_load_pkcs12_into_rustls_config() {
+        let (certs, key) = read_pkcs12(TEST_P12, TEST_P12_PASSWORD).unwrap();
+        assert_eq!(certs.len(), 1);
+        assert!(!key.is_empty());
+
+        let config = RustlsConfig::from_der(certs, key).await;
+        assert!(config.is_ok());
+    }
+
+    #[test]
+    fn test_pkcs12_error_messages() {
+        let wrong_password = read_pkcs12(TEST_P12, "nope").unwrap_err().to_string();
+        assert!(wrong_password.contains("Wrong password"), "{}", wrong_password);
+
+        let missing = read_pkcs12("/nonexistent/bundle.p12", TEST_P12_PASSWORD)
+            .unwrap_err()
+            .to_string();
+        assert!(missing.contains("not found"), "{}", missing);
+
+        let garbage = temp_path("garbage.p12");
+        std::fs::write(&garbage, b"not a pkcs12 bundle").unwrap();
+        let parse = read_pkcs12(garbage.to_str().unwrap(), TEST_P12_PASSWORD)
+            .unwrap_err()
+            .to_string();
+        assert!(parse.contains("Failed to parse"), "{}", parse);
+        std::fs::remove_file(&garbage).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_tls_config_retries_then_fails() {
+        let result = load_tls_config(
+            TlsFormat::Pem,
+            "/nonexistent/cert.pem",
+            "/nonexistent/key.pem",
+            2,
+            Duration::from_millis(1),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
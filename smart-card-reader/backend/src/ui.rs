@@ -1,10 +1,16 @@
-use crate::config::FontConfig;
-use crate::decoder::{format_thai_date, CardEvent, ThaiIDData};
+use crate::config::{FontConfig, PhotoRetention, ReadFeedback, UiConfig};
+use crate::decoder::{format_thai_date, format_thai_date_thai_numerals, mask_citizen_id, CardEvent, ReaderStatus, ThaiIDData};
+use crate::last_read::{LastReadCache, RestoredRead};
+use crate::stats::{AppStats, FontStatus};
 use chrono::Local;
 use eframe::egui;
+use std::collections::VecDeque;
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
 const MAX_LOGS: usize = 100;
+/// Duration of the status bar color pulse on a successful read
+const FLASH_DURATION: std::time::Duration = std::time::Duration::from_millis(800);
 
 // ---------------------------------------------------------------------------
 // Language
@@ -37,7 +43,6 @@ struct T {
     en_firstname: &'static str,
     en_middlename: &'static str,
     en_lastname: &'static str,
-    name_en: &'static str,
     birthday: &'static str,
     sex: &'static str,
     issuer: &'static str,
@@ -46,6 +51,20 @@ struct T {
     address: &'static str,
     insert_card: &'static str,
     insert_card_hint: &'static str,
+    stats_clients: &'static str,
+    stats_reads: &'static str,
+    stats_failures: &'static str,
+    btn_reset_stats: &'static str,
+    raw_payload: &'static str,
+    btn_raw_payload: &'static str,
+    no_payload: &'static str,
+    history: &'static str,
+    no_history: &'static str,
+    history_hint: &'static str,
+    reader_disconnected: &'static str,
+    no_reader: &'static str,
+    card_stuck: &'static str,
+    server_down: &'static str,
 }
 
 const EN: T = T {
@@ -68,7 +87,6 @@ const EN: T = T {
     en_firstname: "First Name (EN):",
     en_middlename: "Middle Name (EN):",
     en_lastname: "Last Name (EN):",
-    name_en: "Name (EN):",
     birthday: "Date of Birth:",
     sex: "Sex:",
     issuer: "Card Issuer:",
@@ -77,6 +95,20 @@ const EN: T = T {
     address: "Address:",
     insert_card: "Please insert a Thai ID card",
     insert_card_hint: "Card data will appear here automatically.",
+    stats_clients: "Clients:",
+    stats_reads: "Reads today:",
+    stats_failures: "Failures:",
+    btn_reset_stats: "Reset",
+    raw_payload: "Raw payload",
+    btn_raw_payload: "{ } Raw JSON",
+    no_payload: "No payload broadcast yet",
+    history: "History",
+    no_history: "No reads yet",
+    history_hint: "Show data to open an entry",
+    reader_disconnected: "⚠ Reader disconnected",
+    no_reader: "⚠ No reader detected",
+    card_stuck: "⚠ Card stuck - remove and reinsert it",
+    server_down: "⚠ WebSocket server not running",
 };
 
 const TH: T = T {
@@ -99,7 +131,6 @@ const TH: T = T {
     en_firstname: "First Name (EN):",
     en_middlename: "Middle Name (EN):",
     en_lastname: "Last Name (EN):",
-    name_en: "ชื่อ-นามสกุล (อังกฤษ):",
     birthday: "วันเกิด:",
     sex: "เพศ:",
     issuer: "หน่วยงานออกบัตร:",
@@ -108,6 +139,20 @@ const TH: T = T {
     address: "ที่อยู่:",
     insert_card: "กรุณาใส่บัตรประชาชน",
     insert_card_hint: "ข้อมูลจะแสดงที่นี่โดยอัตโนมัติ",
+    stats_clients: "ไคลเอนต์:",
+    stats_reads: "อ่านวันนี้:",
+    stats_failures: "ล้มเหลว:",
+    btn_reset_stats: "รีเซ็ต",
+    raw_payload: "ข้อมูลดิบ (JSON)",
+    btn_raw_payload: "{ } JSON ดิบ",
+    no_payload: "ยังไม่มีข้อมูลที่ส่งออก",
+    history: "ประวัติการอ่าน",
+    no_history: "ยังไม่มีการอ่านบัตร",
+    history_hint: "แสดงข้อมูลเพื่อเปิดรายการ",
+    reader_disconnected: "⚠ เครื่องอ่านบัตรถูกถอดออก",
+    no_reader: "⚠ ไม่พบเครื่องอ่านบัตร",
+    card_stuck: "⚠ บัตรค้าง - กรุณาถอดแล้วเสียบบัตรใหม่",
+    server_down: "⚠ WebSocket server ไม่ทำงาน",
 };
 
 fn t(lang: Language) -> &'static T {
@@ -117,6 +162,236 @@ fn t(lang: Language) -> &'static T {
     }
 }
 
+/// Pretty-print a JSON payload, falling back to the raw text if it does not parse
+fn pretty_json(raw: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(raw)
+        .and_then(|v| serde_json::to_string_pretty(&v))
+        .unwrap_or_else(|_| raw.to_string())
+}
+
+/// Bounding box of photos kept with [`PhotoRetention::Thumbnail`]
+const THUMBNAIL_SIZE: (u32, u32) = (90, 120);
+
+/// Decode a base64 card photo (JPEG, or any format the `image` crate detects)
+fn decode_photo(base64_photo: &str) -> Result<image::DynamicImage, String> {
+    use base64::Engine;
+    let photo_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_photo)
+        .map_err(|e| format!("Failed to decode photo base64: {}", e))?;
+
+    image::load_from_memory_with_format(&photo_bytes, image::ImageFormat::Jpeg)
+        .or_else(|_| image::load_from_memory(&photo_bytes))
+        .map_err(|e| format!("Failed to decode photo image: {}", e))
+}
+
+/// Photo to keep in memory after display, per `ui.photo_retention`
+///
+/// A photo that cannot be thumbnailed is dropped rather than kept at full size.
+fn retained_photo(photo: &str, retention: PhotoRetention) -> String {
+    match retention {
+        PhotoRetention::Full => photo.to_string(),
+        PhotoRetention::None => String::new(),
+        PhotoRetention::Thumbnail if photo.is_empty() => String::new(),
+        PhotoRetention::Thumbnail => {
+            use base64::Engine;
+            let thumbnail = decode_photo(photo)
+                .map(|img| img.thumbnail(THUMBNAIL_SIZE.0, THUMBNAIL_SIZE.1).to_rgb8());
+            let mut jpeg = std::io::Cursor::new(Vec::new());
+            match thumbnail.map(|img| img.write_to(&mut jpeg, image::ImageFormat::Jpeg)) {
+                Ok(Ok(())) => base64::engine::general_purpose::STANDARD.encode(jpeg.into_inner()),
+                _ => String::new(),
+            }
+        }
+    }
+}
+
+/// A past card read kept in memory for the history panel
+struct HistoryEntry {
+    /// Full record; `None` for a masked entry restored from the last-read cache
+    data: Option<ThaiIDData>,
+    summary: String,
+}
+
+impl HistoryEntry {
+    fn new(data: ThaiIDData, time: &str) -> Self {
+        Self {
+            summary: history_summary(&data, time),
+            data: Some(data),
+        }
+    }
+
+    fn summary(&self) -> &str {
+        &self.summary
+    }
+}
+
+impl From<RestoredRead> for HistoryEntry {
+    fn from(restored: RestoredRead) -> Self {
+        Self {
+            data: restored.data,
+            summary: restored.summary,
+        }
+    }
+}
+
+/// Masked one-line summary: time, masked ID, name initials, reader
+fn history_summary(data: &ThaiIDData, time: &str) -> String {
+    let initial = |s: &str| s.chars().next().map(|c| format!("{c}.")).unwrap_or_default();
+    format!(
+        "{} {} {}{} ({})",
+        time,
+        mask_citizen_id(&data.citizen_id),
+        initial(&data.th_firstname),
+        initial(&data.th_lastname),
+        data.reader_name,
+    )
+}
+
+/// Fixed-capacity ring buffer of recent reads, newest first
+struct ReadHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl ReadHistory {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(entry);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Feedback actions to run for a card event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct FeedbackActions {
+    sound: bool,
+    flash: bool,
+}
+
+/// Card date as shown in the window: Thai digits and "พ.ศ." with `ui.thai_numerals`
+/// in the Thai UI, else "DD MMM YYYY" with Arabic numerals
+fn display_date(date: &str, lang: Language, thai_numerals: bool) -> String {
+    if thai_numerals && lang == Language::Th {
+        format_thai_date_thai_numerals(date)
+    } else {
+        format_thai_date(date)
+    }
+}
+
+/// Whether card data read at `last_read` is due for `ui.auto_clear_secs` purging at `now`
+fn auto_clear_due(
+    last_read: Option<std::time::Instant>,
+    now: std::time::Instant,
+    after: Option<std::time::Duration>,
+) -> bool {
+    match (last_read, after) {
+        (Some(last_read), Some(after)) => now.saturating_duration_since(last_read) >= after,
+        _ => false,
+    }
+}
+
+/// `true` for the server's `cardstuck` status message
+fn is_card_stuck(payload: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(payload)
+        .is_ok_and(|msg| msg.get("mode").and_then(serde_json::Value::as_str) == Some("cardstuck"))
+}
+
+/// Time left before revealed data is masked again, `None` without a countdown
+/// (not revealed, or `ui.reveal_secs = 0`); zero once it is due
+fn reveal_remaining(
+    revealed_at: Option<std::time::Instant>,
+    now: std::time::Instant,
+    reveal_for: Option<std::time::Duration>,
+) -> Option<std::time::Duration> {
+    let elapsed = now.saturating_duration_since(revealed_at?);
+    Some(reveal_for?.saturating_sub(elapsed))
+}
+
+/// Decide which feedback to give for a card event (only successful reads)
+fn read_feedback_actions(event: &CardEvent, mode: ReadFeedback) -> FeedbackActions {
+    match event {
+        CardEvent::Inserted(_) => FeedbackActions {
+            sound: mode.sound(),
+            flash: mode.flash(),
+        },
+        CardEvent::Removed
+        | CardEvent::ReaderDisconnected(_)
+        | CardEvent::ReaderConnected(_)
+        | CardEvent::Status(_)
+        | CardEvent::ReadFailed { .. } => {
+            FeedbackActions::default()
+        }
+    }
+}
+
+/// One line per reader: read counts, failure rate and average latency
+fn reader_stats_summary(stats: &AppStats) -> String {
+    let readers = stats.reader_stats();
+    if readers.is_empty() {
+        return "No reads yet".to_string();
+    }
+    readers
+        .iter()
+        .map(|(name, reader)| {
+            let average_ms = reader.average_read_time().map_or(0, |d| d.as_millis());
+            let mut line = format!(
+                "{}: {} ok, {} failed ({:.0}%), avg {} ms",
+                name,
+                reader.reads,
+                reader.failures,
+                reader.failure_rate() * 100.0,
+                average_ms
+            );
+            if let Some(error) = &reader.last_error {
+                line.push_str(&format!("\n  last error: {}", error));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Play a short beep without blocking the UI thread
+#[cfg(feature = "sound")]
+fn play_beep() {
+    use rodio::Source;
+    std::thread::spawn(|| {
+        let Ok((_stream, handle)) = rodio::OutputStream::try_default() else {
+            log::warn!("No audio output device available for read feedback");
+            return;
+        };
+        let Ok(sink) = rodio::Sink::try_new(&handle) else {
+            return;
+        };
+        sink.append(
+            rodio::source::SineWave::new(880.0)
+                .take_duration(std::time::Duration::from_millis(150))
+                .amplify(0.25),
+        );
+        sink.sleep_until_end();
+    });
+}
+
+#[cfg(not(feature = "sound"))]
+fn play_beep() {
+    log::debug!("Read feedback sound requested but built without the `sound` feature");
+}
+
 fn get_font_paths(font_config: &FontConfig) -> Vec<std::path::PathBuf> {
     let mut paths = Vec::new();
 
@@ -184,26 +459,62 @@ fn get_font_paths(font_config: &FontConfig) -> Vec<std::path::PathBuf> {
     paths
 }
 
-fn setup_fonts(ctx: &egui::Context, font_config: &FontConfig) {
-    let mut fonts = egui::FontDefinitions::default();
-    let mut font_loaded = false;
+/// NotoSansThai baked into the binary, used when `fonts.fallback_embedded` is on
+/// (SIL Open Font License, see `fonts/OFL.txt`)
+#[cfg(feature = "embedded-font")]
+const EMBEDDED_THAI_FONT: &[u8] = include_bytes!("../fonts/NotoSansThai-Regular.ttf");
 
-    // Try to load NotoSansThai Regular font
+/// `true` if `data` parses as a font with Thai glyphs
+///
+/// egui panics on font data it cannot parse, so nothing else is handed to it.
+fn is_thai_font(data: &[u8]) -> bool {
+    use ab_glyph::Font;
+
+    ab_glyph::FontRef::try_from_slice(data).is_ok_and(|font| font.glyph_id('ก').0 != 0)
+}
+
+/// Find the Thai font: the first readable path holding a usable font, then
+/// the embedded copy if allowed
+fn load_thai_font(font_config: &FontConfig) -> (FontStatus, Option<Vec<u8>>) {
     log::info!("Searching for Thai font...");
     for path in get_font_paths(font_config) {
         log::debug!("Checking font path: {:?}", path);
-        if let Ok(font_data) = std::fs::read(&path) {
-            let font_data = egui::FontData::from_owned(font_data);
-            fonts
-                .font_data
-                .insert("noto_sans_thai".to_owned(), std::sync::Arc::new(font_data));
-            log::info!("✓ Loaded Thai font from: {:?}", path);
-            font_loaded = true;
-            break;
+        let Ok(font_data) = std::fs::read(&path) else {
+            continue;
+        };
+        if !is_thai_font(&font_data) {
+            log::warn!("⚠️ Skipping {:?}: not a font with Thai glyphs", path);
+            continue;
         }
+        log::info!("✓ Loaded Thai font from: {:?}", path);
+        let status = FontStatus::File { path: path.display().to_string() };
+        return (status, Some(font_data));
     }
+    if font_config.fallback_embedded {
+        #[cfg(feature = "embedded-font")]
+        {
+            if is_thai_font(EMBEDDED_THAI_FONT) {
+                log::info!("✓ Loaded embedded Thai font (no font file found)");
+                return (FontStatus::Embedded, Some(EMBEDDED_THAI_FONT.to_vec()));
+            }
+            log::error!("❌ Embedded Thai font is not a usable font");
+        }
+        #[cfg(not(feature = "embedded-font"))]
+        log::warn!("⚠️ fonts.fallback_embedded = true but built without the `embedded-font` feature");
+    }
+    (FontStatus::Missing, None)
+}
+
+fn setup_fonts(ctx: &egui::Context, font_config: &FontConfig) -> FontStatus {
+    let mut fonts = egui::FontDefinitions::default();
+    let (status, font_data) = load_thai_font(font_config);
+
+    if let Some(font_data) = font_data {
+        let font_data = egui::FontData::from_owned(font_data);
+        fonts
+            .font_data
+            .insert("noto_sans_thai".to_owned(), std::sync::Arc::new(font_data));
 
-    if font_loaded {
         // Add font to both Proportional and Monospace families
         fonts
             .families
@@ -252,6 +563,7 @@ fn setup_fonts(ctx: &egui::Context, font_config: &FontConfig) {
         log::warn!("Thai text will display as boxes. Please ensure NotoSansThai-Regular.ttf is available.");
         ctx.set_fonts(fonts);
     }
+    status
 }
 
 // Embedded flag images (PNG bytes baked into binary)
@@ -260,24 +572,86 @@ const FLAG_GB_PNG: &[u8] = include_bytes!("../assets/flag_gb.png");
 
 pub struct SmartCardApp {
     rx: Receiver<CardEvent>,
+    rx_payload: Receiver<String>,
+    /// Pretty-printed copy of the last broadcast JSON
+    raw_payload: Option<String>,
+    show_raw_payload: bool,
     card_data: Option<ThaiIDData>,
     logs: Vec<String>,
     photo_texture: Option<egui::TextureHandle>,
     flag_th: Option<egui::TextureHandle>,
     flag_gb: Option<egui::TextureHandle>,
     last_read_time: Option<String>,
-    fonts_configured: bool,
+    /// Thai font found by `setup_fonts`, `None` until the first frame
+    font_status: Option<FontStatus>,
     ws_url: String,
     font_config: FontConfig,
     data_hidden: bool,
     lang: Language,
     dark_mode: bool,
+    stats: Arc<AppStats>,
+    read_feedback: ReadFeedback,
+    flash_started: Option<std::time::Instant>,
+    history: ReadHistory,
+    /// On-disk cache of the last read, if `ui.persist_last_read` is on
+    last_read_cache: Option<LastReadCache>,
+    clear_last_read_on_removal: bool,
+    photo_retention: PhotoRetention,
+    /// Photo column shown (`ui.show_photo`, honoring the output config)
+    show_photo: bool,
+    /// Name of the reader that was unplugged mid-read, until it returns
+    disconnected_reader: Option<String>,
+    /// Server reported a `cardstuck` status, until the card is removed
+    card_stuck: bool,
+    /// Last PC/SC availability reported by the monitor
+    reader_status: Option<ReaderStatus>,
+    /// `ui.auto_clear_secs`, `None` when disabled
+    auto_clear_after: Option<std::time::Duration>,
+    /// When the card data still held in memory was read
+    last_read_at: Option<std::time::Instant>,
+    /// `ui.thai_numerals`
+    thai_numerals: bool,
+    /// `ui.reveal_secs`, `None` to stay revealed until toggled off
+    reveal_for: Option<std::time::Duration>,
+    /// When "Show Data" was last clicked, while the data is revealed
+    revealed_at: Option<std::time::Instant>,
 }
 
 impl SmartCardApp {
-    pub fn new(rx: Receiver<CardEvent>, ws_url: String, font_config: FontConfig) -> Self {
+    pub fn new(
+        rx: Receiver<CardEvent>,
+        rx_payload: Receiver<String>,
+        ws_url: String,
+        font_config: FontConfig,
+        ui_config: UiConfig,
+        stats: Arc<AppStats>,
+        last_read_cache: Option<LastReadCache>,
+    ) -> Self {
+        if ui_config.read_feedback.sound() && !cfg!(feature = "sound") {
+            log::warn!("⚠️ ui.read_feedback = \"{}\" but built without the `sound` feature - no beep will play", ui_config.read_feedback);
+        }
+
+        let mut history = ReadHistory::new(ui_config.history_len);
+        let mut last_read_time = None;
+        let mut last_read_at = None;
+        if let Some(cache) = &last_read_cache {
+            match cache.load() {
+                Ok(Some(restored)) => {
+                    // The cache keeps wall-clock text only: auto_clear_secs counts from startup
+                    last_read_at = Some(std::time::Instant::now());
+                    last_read_time = Some(restored.time.clone());
+                    history.push(restored.into());
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("⚠️ Failed to load last-read cache {}: {}", cache.path().display(), e),
+            }
+        }
+
         Self {
             rx,
+            rx_payload,
+            raw_payload: None,
+            show_raw_payload: false,
             card_data: None,
             logs: vec![format!(
                 "[{}] Application started",
@@ -286,13 +660,31 @@ impl SmartCardApp {
             photo_texture: None,
             flag_th: None,
             flag_gb: None,
-            last_read_time: None,
-            fonts_configured: false,
+            last_read_time,
+            font_status: None,
             ws_url,
             font_config,
             data_hidden: true,
             lang: Language::Th,
             dark_mode: true,
+            stats,
+            read_feedback: ui_config.read_feedback,
+            flash_started: None,
+            history,
+            last_read_cache,
+            clear_last_read_on_removal: ui_config.clear_last_read_on_removal,
+            photo_retention: ui_config.photo_retention,
+            show_photo: ui_config.show_photo,
+            disconnected_reader: None,
+            card_stuck: false,
+            reader_status: None,
+            auto_clear_after: (ui_config.auto_clear_secs > 0)
+                .then(|| std::time::Duration::from_secs(ui_config.auto_clear_secs)),
+            last_read_at,
+            thai_numerals: ui_config.thai_numerals,
+            reveal_for: (ui_config.reveal_secs > 0)
+                .then(|| std::time::Duration::from_secs(ui_config.reveal_secs)),
+            revealed_at: None,
         }
     }
 
@@ -302,6 +694,54 @@ impl SmartCardApp {
         self.add_log("Card removed - data cleared");
     }
 
+    /// Purge card data, photo, raw payload, history and the last-read cache
+    /// once `ui.auto_clear_secs` passed
+    fn auto_clear(&mut self, now: std::time::Instant) {
+        if !auto_clear_due(self.last_read_at, now, self.auto_clear_after) {
+            return;
+        }
+        self.last_read_at = None;
+        self.card_data = None;
+        self.photo_texture = None;
+        self.raw_payload = None;
+        self.history.clear();
+        if let Some(cache) = &self.last_read_cache {
+            if let Err(e) = cache.clear() {
+                log::warn!("⚠️ Failed to clear last-read cache: {}", e);
+            }
+        }
+        let secs = self.auto_clear_after.map_or(0, |after| after.as_secs());
+        log::info!("🧹 Card data auto-cleared after {}s without a read", secs);
+        self.add_log(&format!("Card data auto-cleared after {}s", secs));
+    }
+
+    /// Show or mask the card data, starting the `ui.reveal_secs` countdown on reveal
+    fn toggle_reveal(&mut self, now: std::time::Instant) {
+        self.data_hidden = !self.data_hidden;
+        self.revealed_at = (!self.data_hidden).then_some(now);
+    }
+
+    /// Mask the card data again once its reveal countdown ran out
+    fn expire_reveal(&mut self, now: std::time::Instant) {
+        if reveal_remaining(self.revealed_at, now, self.reveal_for) == Some(std::time::Duration::ZERO) {
+            self.data_hidden = true;
+            self.revealed_at = None;
+            self.add_log("Card data masked again after reveal timeout");
+        }
+    }
+
+    /// Add a read to the history and the last-read cache, if enabled
+    fn remember_read(&mut self, data: &ThaiIDData, time: &str) {
+        let entry = HistoryEntry::new(data.clone(), time);
+        if let Some(cache) = &self.last_read_cache {
+            if let Err(e) = cache.save(time, entry.summary(), data) {
+                log::warn!("⚠️ Failed to write last-read cache {}: {}", cache.path().display(), e);
+            }
+        }
+        self.history.push(entry);
+        self.last_read_at = Some(std::time::Instant::now());
+    }
+
     fn add_log(&mut self, message: &str) {
         let timestamp = Local::now().format("%H:%M:%S");
         self.logs.push(format!("[{}] {}", timestamp, message));
@@ -332,32 +772,14 @@ impl SmartCardApp {
     }
 
     fn load_photo_texture(&mut self, ctx: &egui::Context, base64_photo: &str) {
-        // Decode base64 to bytes
-        use base64::Engine;
-        let photo_bytes = match base64::engine::general_purpose::STANDARD.decode(base64_photo) {
-            Ok(bytes) => bytes,
+        let img = match decode_photo(base64_photo) {
+            Ok(img) => img,
             Err(e) => {
-                self.add_log(&format!("Failed to decode photo base64: {}", e));
+                self.add_log(&e);
                 return;
             }
         };
 
-        // Try to load as JPEG
-        let img = match image::load_from_memory_with_format(&photo_bytes, image::ImageFormat::Jpeg)
-        {
-            Ok(img) => img,
-            Err(_) => {
-                // Try without format hint
-                match image::load_from_memory(&photo_bytes) {
-                    Ok(img) => img,
-                    Err(e) => {
-                        self.add_log(&format!("Failed to decode photo image: {}", e));
-                        return;
-                    }
-                }
-            }
-        };
-
         let rgba = img.to_rgba8();
         let size = [rgba.width() as usize, rgba.height() as usize];
         let pixels = rgba.into_raw();
@@ -369,6 +791,12 @@ impl SmartCardApp {
 }
 
 impl eframe::App for SmartCardApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Drop retained PII before the process exits
+        self.history.clear();
+        self.card_data = None;
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Apply theme
         if self.dark_mode {
@@ -378,9 +806,10 @@ impl eframe::App for SmartCardApp {
         }
 
         // Setup fonts only once
-        if !self.fonts_configured {
-            setup_fonts(ctx, &self.font_config);
-            self.fonts_configured = true;
+        if self.font_status.is_none() {
+            let status = setup_fonts(ctx, &self.font_config);
+            self.stats.set_font_status(status.clone());
+            self.font_status = Some(status);
         }
 
         // Load flag textures once
@@ -388,8 +817,16 @@ impl eframe::App for SmartCardApp {
 
         // Check for card events
         while let Ok(event) = self.rx.try_recv() {
+            let feedback = read_feedback_actions(&event, self.read_feedback);
+            if feedback.sound {
+                play_beep();
+            }
+            if feedback.flash {
+                self.flash_started = Some(std::time::Instant::now());
+            }
+
             match event {
-                CardEvent::Inserted(data) => {
+                CardEvent::Inserted(mut data) => {
                     let id = &data.citizen_id;
                     let masked = if id.len() > 4 {
                         format!("{}{}", "*".repeat(id.len() - 4), &id[id.len() - 4..])
@@ -399,31 +836,106 @@ impl eframe::App for SmartCardApp {
                     self.add_log(&format!("Card read: {}", masked));
                     self.last_read_time = Some(Local::now().format("%H:%M:%S").to_string());
 
-                    // Load photo texture
-                    if !data.photo.is_empty() {
+                    // Load photo texture, then keep only what photo_retention allows
+                    if self.show_photo && !data.photo.is_empty() {
                         self.load_photo_texture(ctx, &data.photo);
                     }
+                    data.photo = retained_photo(&data.photo, self.photo_retention);
 
+                    let time = Local::now().format("%H:%M:%S").to_string();
+                    self.remember_read(&data, &time);
                     self.card_data = Some(data);
                 }
                 CardEvent::Removed => {
                     self.clear_card_data();
+                    self.card_stuck = false;
+                    if self.clear_last_read_on_removal {
+                        if let Some(cache) = &self.last_read_cache {
+                            if let Err(e) = cache.clear() {
+                                log::warn!("⚠️ Failed to clear last-read cache: {}", e);
+                            }
+                        }
+                    }
+                }
+                CardEvent::ReaderDisconnected(reader) => {
+                    self.card_data = None;
+                    self.photo_texture = None;
+                    self.add_log(&format!("Reader disconnected: {}", reader));
+                    self.disconnected_reader = Some(reader);
+                }
+                CardEvent::ReaderConnected(reader) => {
+                    self.add_log(&format!("Reader reconnected: {}", reader));
+                    self.disconnected_reader = None;
                 }
+                CardEvent::Status(status) => {
+                    self.add_log(&format!("Reader status: {}", status));
+                    self.reader_status = Some(status);
+                }
+                CardEvent::ReadFailed { reader, error } => {
+                    self.add_log(&format!("Read failed on {}: {}", reader, error));
+                }
+            }
+        }
+
+        // Mirror the exact broadcast JSON for the raw payload view
+        while let Ok(payload) = self.rx_payload.try_recv() {
+            if is_card_stuck(&payload) && !self.card_stuck {
+                self.add_log("Card stuck - same data read repeatedly");
+                self.card_stuck = true;
             }
+            self.raw_payload = Some(pretty_json(&payload));
         }
 
+        // Bound PII residency even if a removal event was missed
+        self.auto_clear(std::time::Instant::now());
+        self.expire_reveal(std::time::Instant::now());
+
         // Request continuous repaints to check for new data
         ctx.request_repaint_after(std::time::Duration::from_millis(100));
 
+        // Status bar color pulse after a successful read
+        let mut status_frame = egui::Frame::side_top_panel(&ctx.style());
+        if let Some(started) = self.flash_started {
+            let elapsed = started.elapsed();
+            if elapsed < FLASH_DURATION {
+                let strength = 1.0 - elapsed.as_secs_f32() / FLASH_DURATION.as_secs_f32();
+                let pulse = egui::Color32::from_rgb(34, 197, 94);
+                status_frame.fill = status_frame.fill.lerp_to_gamma(pulse, strength * 0.6);
+                ctx.request_repaint();
+            } else {
+                self.flash_started = None;
+            }
+        }
+
         // Top panel - Status bar
         let tr = t(self.lang);
-        egui::TopBottomPanel::top("status_bar").show(ctx, |ui| {
+        egui::TopBottomPanel::top("status_bar").frame(status_frame).show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.label(egui::RichText::new(tr.app_title).size(16.0));
                 ui.separator();
-                ui.label(format!("{} {}", tr.websocket, self.ws_url));
+                if let Some(error) = self.stats.server_error() {
+                    ui.label(
+                        egui::RichText::new(format!("{} ({})", tr.server_down, error))
+                            .color(egui::Color32::from_rgb(239, 68, 68)),
+                    );
+                } else {
+                    ui.label(format!("{} {}", tr.websocket, self.ws_url));
+                }
                 ui.separator();
-                if let Some(time) = &self.last_read_time {
+                if self.card_stuck {
+                    ui.label(
+                        egui::RichText::new(tr.card_stuck).color(egui::Color32::from_rgb(239, 68, 68)),
+                    );
+                } else if let Some(reader) = &self.disconnected_reader {
+                    ui.label(
+                        egui::RichText::new(format!("{} ({})", tr.reader_disconnected, reader))
+                            .color(egui::Color32::from_rgb(239, 68, 68)),
+                    );
+                } else if self.reader_status == Some(ReaderStatus::Disconnected) {
+                    ui.label(
+                        egui::RichText::new(tr.no_reader).color(egui::Color32::from_rgb(239, 68, 68)),
+                    );
+                } else if let Some(time) = &self.last_read_time {
                     ui.label(format!("{} {}", tr.last_read, time));
                 } else {
                     ui.label(tr.waiting);
@@ -473,13 +985,28 @@ impl eframe::App for SmartCardApp {
                         self.dark_mode = !self.dark_mode;
                     }
 
+                    ui.separator();
+
+                    // Raw payload toggle
+                    if ui
+                        .add(egui::Button::new(tr.btn_raw_payload).selected(self.show_raw_payload))
+                        .clicked()
+                    {
+                        self.show_raw_payload = !self.show_raw_payload;
+                    }
+
                     // Show/hide toggle - only when card data is present
                     if self.card_data.is_some() {
                         ui.separator();
                         let (label, color) = if self.data_hidden {
-                            (tr.btn_show, egui::Color32::from_rgb(129, 140, 248))
+                            (tr.btn_show.to_string(), egui::Color32::from_rgb(129, 140, 248))
                         } else {
-                            (tr.btn_hide, egui::Color32::from_rgb(148, 163, 184))
+                            // Countdown to re-masking with ui.reveal_secs
+                            let label = match reveal_remaining(self.revealed_at, std::time::Instant::now(), self.reveal_for) {
+                                Some(left) => format!("{} ({}s)", tr.btn_hide, left.as_secs() + u64::from(left.subsec_nanos() > 0)),
+                                None => tr.btn_hide.to_string(),
+                            };
+                            (label, egui::Color32::from_rgb(148, 163, 184))
                         };
                         if ui
                             .add(
@@ -488,13 +1015,25 @@ impl eframe::App for SmartCardApp {
                             )
                             .clicked()
                         {
-                            self.data_hidden = !self.data_hidden;
+                            self.toggle_reveal(std::time::Instant::now());
                         }
                     }
                 });
             });
         });
 
+        // Font warning banner - English only, since Thai would not render here
+        if self.font_status.as_ref().is_some_and(|status| !status.thai_loaded()) {
+            egui::TopBottomPanel::top("font_warning")
+                .frame(egui::Frame::side_top_panel(&ctx.style()).fill(egui::Color32::from_rgb(127, 29, 29)))
+                .show(ctx, |ui| {
+                    ui.label(
+                        egui::RichText::new("⚠ Thai font not loaded — text may not display")
+                            .color(egui::Color32::WHITE),
+                    );
+                });
+        }
+
         // Bottom panel - Logs (full width)
         let tr = t(self.lang);
         egui::TopBottomPanel::bottom("logs_panel")
@@ -502,7 +1041,30 @@ impl eframe::App for SmartCardApp {
             .min_height(120.0)
             .default_height(160.0)
             .show(ctx, |ui| {
-                ui.label(egui::RichText::new(tr.logs).size(13.0).strong());
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(tr.logs).size(13.0).strong());
+
+                    // Read-only activity counters
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button(tr.btn_reset_stats).clicked() {
+                            self.stats.reset();
+                        }
+                        let snapshot = self.stats.snapshot();
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "{} {} | {} {} | {} {}",
+                                tr.stats_clients,
+                                snapshot.clients,
+                                tr.stats_reads,
+                                snapshot.reads,
+                                tr.stats_failures,
+                                snapshot.failures,
+                            ))
+                            .size(13.0),
+                        )
+                        .on_hover_text(reader_stats_summary(&self.stats));
+                    });
+                });
                 egui::ScrollArea::both()
                     .stick_to_bottom(true)
                     .auto_shrink([false, false])
@@ -517,9 +1079,50 @@ impl eframe::App for SmartCardApp {
                     });
             });
 
+        // Right panel - Read history (masked summaries)
+        let tr = t(self.lang);
+        let mut selected: Option<ThaiIDData> = None;
+        egui::SidePanel::right("history_panel")
+            .resizable(false)
+            .default_width(230.0)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(tr.history).size(13.0).strong());
+                if self.data_hidden && !self.history.entries.is_empty() {
+                    ui.label(egui::RichText::new(tr.history_hint).size(11.0).weak());
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_salt("history_scroll")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        if self.history.entries.is_empty() {
+                            ui.label(egui::RichText::new(tr.no_history).weak());
+                        }
+                        for entry in &self.history.entries {
+                            let row = ui.add_enabled(
+                                !self.data_hidden && entry.data.is_some(),
+                                egui::Button::new(egui::RichText::new(entry.summary()).size(12.0))
+                                    .frame(false),
+                            );
+                            if row.clicked() {
+                                selected = entry.data.clone();
+                            }
+                        }
+                    });
+            });
+        if let Some(data) = selected {
+            self.photo_texture = None;
+            if self.show_photo && !data.photo.is_empty() {
+                self.load_photo_texture(ctx, &data.photo);
+            }
+            self.card_data = Some(data);
+        }
+
         // Central panel - Card data
         let data_hidden = self.data_hidden;
+        let show_photo = self.show_photo;
         let tr = t(self.lang);
+        let (lang, thai_numerals) = (self.lang, self.thai_numerals);
         egui::CentralPanel::default().show(ctx, |ui| {
             if let Some(data) = &self.card_data {
                 // Helper: masked value when hidden
@@ -533,79 +1136,77 @@ impl eframe::App for SmartCardApp {
                     .show(ui, |ui| {
                         ui.horizontal_top(|ui| {
                             // Left side - Photo
-                            ui.vertical(|ui| {
-                                ui.heading(tr.photo);
-                                let bg_color = if self.dark_mode {
-                                    egui::Color32::from_rgb(40, 45, 60)
-                                } else {
-                                    egui::Color32::from_rgb(230, 235, 245)
-                                };
-                                let text_color = if self.dark_mode {
-                                    egui::Color32::from_rgb(100, 116, 139)
-                                } else {
-                                    egui::Color32::from_rgb(100, 116, 139)
-                                };
-
-                                if data_hidden {
-                                    let (rect, _) = ui.allocate_exact_size(
-                                        egui::vec2(PHOTO_W, PHOTO_H),
-                                        egui::Sense::hover(),
-                                    );
-                                    ui.painter().rect_filled(rect, 8.0, bg_color);
-                                    ui.painter().text(
-                                        rect.center(),
-                                        egui::Align2::CENTER_CENTER,
-                                        "🔒",
-                                        egui::FontId::proportional(36.0),
-                                        text_color,
-                                    );
-                                } else if let Some(texture) = &self.photo_texture {
-                                    // Calculate scaled size maintaining aspect ratio
-                                    let tex_size = texture.size_vec2();
-                                    let aspect = tex_size.x / tex_size.y;
-                                    let container_aspect = PHOTO_W / PHOTO_H;
-
-                                    let (scaled_w, scaled_h) = if aspect > container_aspect {
-                                        // Image is wider - fit to width
-                                        (PHOTO_W, PHOTO_W / aspect)
+                            if show_photo {
+                                ui.vertical(|ui| {
+                                    ui.heading(tr.photo);
+                                    let bg_color = if self.dark_mode {
+                                        egui::Color32::from_rgb(40, 45, 60)
                                     } else {
-                                        // Image is taller - fit to height
-                                        (PHOTO_H * aspect, PHOTO_H)
+                                        egui::Color32::from_rgb(230, 235, 245)
                                     };
-
-                                    // Allocate space for the scaled image (not full container)
-                                    let (rect, _) = ui.allocate_exact_size(
-                                        egui::vec2(scaled_w, scaled_h),
-                                        egui::Sense::hover(),
-                                    );
-
-                                    // Draw background frame exactly matching image size
-                                    ui.painter().rect_filled(rect, 8.0, bg_color);
-
-                                    // Draw image filling the frame
-                                    ui.painter().image(
-                                        texture.id(),
-                                        rect,
-                                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                                        egui::Color32::WHITE,
-                                    );
-                                } else {
-                                    let (rect, _) = ui.allocate_exact_size(
-                                        egui::vec2(PHOTO_W, PHOTO_H),
-                                        egui::Sense::hover(),
-                                    );
-                                    ui.painter().rect_filled(rect, 8.0, bg_color);
-                                    ui.painter().text(
-                                        rect.center(),
-                                        egui::Align2::CENTER_CENTER,
-                                        tr.no_photo,
-                                        egui::FontId::proportional(14.0),
-                                        text_color,
-                                    );
-                                }
-                            });
-
-                            ui.separator();
+                                    let text_color = egui::Color32::from_rgb(100, 116, 139);
+
+                                    if data_hidden {
+                                        let (rect, _) = ui.allocate_exact_size(
+                                            egui::vec2(PHOTO_W, PHOTO_H),
+                                            egui::Sense::hover(),
+                                        );
+                                        ui.painter().rect_filled(rect, 8.0, bg_color);
+                                        ui.painter().text(
+                                            rect.center(),
+                                            egui::Align2::CENTER_CENTER,
+                                            "🔒",
+                                            egui::FontId::proportional(36.0),
+                                            text_color,
+                                        );
+                                    } else if let Some(texture) = &self.photo_texture {
+                                        // Calculate scaled size maintaining aspect ratio
+                                        let tex_size = texture.size_vec2();
+                                        let aspect = tex_size.x / tex_size.y;
+                                        let container_aspect = PHOTO_W / PHOTO_H;
+
+                                        let (scaled_w, scaled_h) = if aspect > container_aspect {
+                                            // Image is wider - fit to width
+                                            (PHOTO_W, PHOTO_W / aspect)
+                                        } else {
+                                            // Image is taller - fit to height
+                                            (PHOTO_H * aspect, PHOTO_H)
+                                        };
+
+                                        // Allocate space for the scaled image (not full container)
+                                        let (rect, _) = ui.allocate_exact_size(
+                                            egui::vec2(scaled_w, scaled_h),
+                                            egui::Sense::hover(),
+                                        );
+
+                                        // Draw background frame exactly matching image size
+                                        ui.painter().rect_filled(rect, 8.0, bg_color);
+
+                                        // Draw image filling the frame
+                                        ui.painter().image(
+                                            texture.id(),
+                                            rect,
+                                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                                            egui::Color32::WHITE,
+                                        );
+                                    } else {
+                                        let (rect, _) = ui.allocate_exact_size(
+                                            egui::vec2(PHOTO_W, PHOTO_H),
+                                            egui::Sense::hover(),
+                                        );
+                                        ui.painter().rect_filled(rect, 8.0, bg_color);
+                                        ui.painter().text(
+                                            rect.center(),
+                                            egui::Align2::CENTER_CENTER,
+                                            tr.no_photo,
+                                            egui::FontId::proportional(14.0),
+                                            text_color,
+                                        );
+                                    }
+                                });
+
+                                ui.separator();
+                            }
 
                             // Right side - Card details
                             ui.vertical(|ui| {
@@ -696,7 +1297,7 @@ impl eframe::App for SmartCardApp {
                                         ui.label(if data_hidden {
                                             mask("")
                                         } else {
-                                            format_thai_date(&data.birthday)
+                                            display_date(&data.birthday, lang, thai_numerals)
                                         });
                                         ui.end_row();
 
@@ -721,7 +1322,7 @@ impl eframe::App for SmartCardApp {
                                         ui.label(if data_hidden {
                                             mask("")
                                         } else {
-                                            format_thai_date(&data.issue)
+                                            display_date(&data.issue, lang, thai_numerals)
                                         });
                                         ui.end_row();
 
@@ -729,7 +1330,7 @@ impl eframe::App for SmartCardApp {
                                         ui.label(if data_hidden {
                                             mask("")
                                         } else {
-                                            format_thai_date(&data.expire)
+                                            display_date(&data.expire, lang, thai_numerals)
                                         });
                                         ui.end_row();
 
@@ -744,6 +1345,27 @@ impl eframe::App for SmartCardApp {
                                     });
                             });
                         }); // horizontal_top
+
+                        if self.show_raw_payload {
+                            ui.add_space(10.0);
+                            ui.separator();
+                            ui.heading(tr.raw_payload);
+                            egui::ScrollArea::both()
+                                .id_salt("raw_payload_scroll")
+                                .max_height(260.0)
+                                .auto_shrink([false, true])
+                                .show(ui, |ui| {
+                                    let text = match &self.raw_payload {
+                                        _ if data_hidden => mask(""),
+                                        Some(payload) => payload.clone(),
+                                        None => tr.no_payload.to_string(),
+                                    };
+                                    ui.add(
+                                        egui::Label::new(egui::RichText::new(text).monospace())
+                                            .wrap_mode(egui::TextWrapMode::Extend),
+                                    );
+                                });
+                        }
                     }); // ScrollArea
             } else {
                 ui.centered_and_justified(|ui| {
@@ -758,3 +1380,351 @@ impl eframe::App for SmartCardApp {
         });
     }
 }
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_json() {
+        let pretty = pretty_json(r#"{"Th_Firstname":"ทดสอบ","mode":"readsmartcard"}"#);
+        assert_eq!(
+            pretty,
+            "{\n  \"Th_Firstname\": \"ทดสอบ\",\n  \"mode\": \"readsmartcard\"\n}"
+        );
+    }
+
+    #[test]
+    fn test_read_feedback_only_on_insert() {
+        let inserted = CardEvent::Inserted(ThaiIDData::default());
+
+        assert_eq!(
+            read_feedback_actions(&inserted, ReadFeedback::Both),
+            FeedbackActions { sound: true, flash: true }
+        );
+        assert_eq!(
+            read_feedback_actions(&inserted, ReadFeedback::Flash),
+            FeedbackActions { sound: false, flash: true }
+        );
+        assert_eq!(
+            read_feedback_actions(&inserted, ReadFeedback::None),
+            FeedbackActions::default()
+        );
+        assert_eq!(
+            read_feedback_actions(&CardEvent::Removed, ReadFeedback::Both),
+            FeedbackActions::default()
+        );
+    }
+
+    #[test]
+    fn test_font_path_resolution_order() {
+        use std::path::PathBuf;
+
+        let config = FontConfig {
+            custom_paths: vec!["custom/a.ttf".to_string(), "custom/b.ttf".to_string()],
+            ..FontConfig::default()
+        };
+        let paths = get_font_paths(&config);
+        let position = |path: &str| paths.iter().position(|p| p == &PathBuf::from(path)).unwrap();
+
+        // Custom paths first, in config order
+        assert_eq!(paths[..2], [PathBuf::from("custom/a.ttf"), PathBuf::from("custom/b.ttf")]);
+        // Then next to the executable, then the working directory, then system fonts
+        let cwd = position("fonts/NotoSansThai-Regular.ttf");
+        assert!(paths[2..cwd].iter().all(|p| p.is_absolute() && p.ends_with("fonts/NotoSansThai-Regular.ttf")));
+        assert!(cwd < position("C:\\Windows\\Fonts\\LeelawUI.ttf"));
+        assert!(position("C:\\Windows\\Fonts\\LeelawUI.ttf") < position("/usr/share/fonts/truetype/noto/NotoSansThai-Regular.ttf"));
+        assert_eq!(paths.last(), Some(&PathBuf::from("/Library/Fonts/NotoSansThai-Regular.ttf")));
+
+        // System search off: only the custom paths
+        let custom_only = FontConfig { use_system_fonts: false, ..config };
+        assert_eq!(get_font_paths(&custom_only).len(), 2);
+    }
+
+    #[cfg(feature = "embedded-font")]
+    #[test]
+    fn test_embedded_thai_font_parses() {
+        use ab_glyph::Font;
+
+        let font = ab_glyph::FontRef::try_from_slice(EMBEDDED_THAI_FONT).expect("embedded Thai font must be a TTF");
+        assert_ne!(font.glyph_id('ก').0, 0, "embedded font has no Thai glyphs");
+    }
+
+    #[test]
+    fn test_thai_font_falls_back_to_embedded() {
+        let font_file = std::env::temp_dir().join(format!("scr-font-{}.ttf", std::process::id()));
+        std::fs::write(&font_file, b"<!DOCTYPE html>").unwrap();
+        let missing = FontConfig {
+            custom_paths: vec!["/nonexistent/NotoSansThai-Regular.ttf".to_string()],
+            use_system_fonts: false,
+            fallback_embedded: true,
+        };
+
+        // A readable file that is not a font is skipped, not reported as loaded
+        let unparsable = FontConfig {
+            custom_paths: vec![font_file.display().to_string()],
+            fallback_embedded: false,
+            ..missing.clone()
+        };
+        let (status, data) = load_thai_font(&unparsable);
+        assert!(status == FontStatus::Missing && data.is_none());
+        std::fs::remove_file(&font_file).unwrap();
+
+        // No usable file: the embedded copy, unless built without it
+        let (status, data) = load_thai_font(&missing);
+        #[cfg(feature = "embedded-font")]
+        {
+            assert_eq!(status, FontStatus::Embedded);
+            assert_eq!(data.map(|d| d.len()), Some(EMBEDDED_THAI_FONT.len()));
+        }
+        #[cfg(not(feature = "embedded-font"))]
+        assert!(status == FontStatus::Missing && data.is_none());
+
+        let (status, data) = load_thai_font(&FontConfig { fallback_embedded: false, ..missing });
+        assert_eq!(status, FontStatus::Missing);
+        assert!(!status.thai_loaded() && data.is_none());
+    }
+
+    fn history_entry(citizen_id: &str) -> HistoryEntry {
+        let data = ThaiIDData {
+            citizen_id: citizen_id.to_string(),
+            th_firstname: "สมชาย".to_string(),
+            th_lastname: "ใจดี".to_string(),
+            reader_name: "ACS ACR39U".to_string(),
+            ..ThaiIDData::default()
+        };
+        HistoryEntry::new(data, "10:00:00")
+    }
+
+    #[test]
+    fn test_history_eviction() {
+        let mut history = ReadHistory::new(2);
+        history.push(history_entry("1111111111111"));
+        history.push(history_entry("2222222222222"));
+        history.push(history_entry("3333333333333"));
+
+        let ids: Vec<&str> = history.entries.iter().map(|e| e.data.as_ref().unwrap().citizen_id.as_str()).collect();
+        assert_eq!(ids, vec!["3333333333333", "2222222222222"]);
+
+        history.clear();
+        assert!(history.entries.is_empty());
+    }
+
+    fn test_app(config: UiConfig, cache: Option<LastReadCache>) -> SmartCardApp {
+        let (_tx, rx) = std::sync::mpsc::channel();
+        let (_tx_payload, rx_payload) = std::sync::mpsc::channel();
+        SmartCardApp::new(
+            rx,
+            rx_payload,
+            String::new(),
+            FontConfig::default(),
+            config,
+            Arc::new(AppStats::new()),
+            cache,
+        )
+    }
+
+    #[test]
+    fn test_last_read_cache_disabled_writes_nothing() {
+        let path = std::env::temp_dir().join(format!("scr-ui-last-read-off-{}.json", std::process::id()));
+        let config = UiConfig {
+            last_read_path: path.display().to_string(),
+            ..UiConfig::default()
+        };
+        let cache = LastReadCache::from_config(&config, None);
+        let mut app = test_app(config, cache);
+
+        let data = history_entry("3100600123456").data.unwrap();
+        app.remember_read(&data, "10:00:00");
+        assert_eq!(app.history.entries.len(), 1);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_last_read_cache_restores_masked_summary() {
+        let path = std::env::temp_dir().join(format!("scr-ui-last-read-on-{}.json", std::process::id()));
+        let config = UiConfig {
+            persist_last_read: true,
+            last_read_path: path.display().to_string(),
+            ..UiConfig::default()
+        };
+        let mut app = test_app(config.clone(), LastReadCache::from_config(&config, None));
+        let data = history_entry("3100600123456").data.unwrap();
+        app.remember_read(&data, "10:00:00");
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("3100600123456"));
+
+        // A restart without encryption only gets the masked, non-clickable summary back
+        let restarted = test_app(config.clone(), LastReadCache::from_config(&config, None));
+        let entry = &restarted.history.entries[0];
+        assert_eq!(entry.summary(), app.history.entries[0].summary());
+        assert!(entry.data.is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_display_date_thai_numerals_only_in_thai() {
+        assert_eq!(display_date("25670115", Language::Th, true), "๑๕ ม.ค. พ.ศ. ๒๕๖๗");
+        assert_eq!(display_date("25670115", Language::En, true), "15 ม.ค. 2567");
+        assert_eq!(display_date("25670115", Language::Th, false), "15 ม.ค. 2567");
+    }
+
+    #[test]
+    fn test_auto_clear_due() {
+        use std::time::{Duration, Instant};
+
+        let read = Instant::now();
+        let after = Some(Duration::from_secs(30));
+        assert!(!auto_clear_due(Some(read), read + Duration::from_secs(29), after));
+        assert!(auto_clear_due(Some(read), read + Duration::from_secs(30), after));
+        // Disabled, or nothing read yet
+        assert!(!auto_clear_due(Some(read), read + Duration::from_secs(3600), None));
+        assert!(!auto_clear_due(None, read + Duration::from_secs(3600), after));
+        // A clock that appears to go backwards never clears early
+        assert!(!auto_clear_due(Some(read + Duration::from_secs(5)), read, after));
+    }
+
+    #[test]
+    fn test_auto_clear_purges_card_and_history() {
+        use std::time::Duration;
+
+        let config = UiConfig {
+            auto_clear_secs: 30,
+            ..UiConfig::default()
+        };
+        let mut app = test_app(config, None);
+        let data = history_entry("3100600123456").data.unwrap();
+        app.remember_read(&data, "10:00:00");
+        app.card_data = Some(data);
+        app.raw_payload = Some("{}".to_string());
+        let read_at = app.last_read_at.unwrap();
+
+        app.auto_clear(read_at + Duration::from_secs(10));
+        assert!(app.card_data.is_some());
+        assert_eq!(app.history.entries.len(), 1);
+
+        app.auto_clear(read_at + Duration::from_secs(30));
+        assert!(app.card_data.is_none() && app.raw_payload.is_none());
+        assert!(app.history.entries.is_empty());
+        assert!(app.last_read_at.is_none());
+    }
+
+    #[test]
+    fn test_auto_clear_covers_restored_cache() {
+        use std::time::Duration;
+
+        let path = std::env::temp_dir().join(format!("scr-ui-last-read-clear-{}.json", std::process::id()));
+        let config = UiConfig {
+            persist_last_read: true,
+            last_read_path: path.display().to_string(),
+            auto_clear_secs: 30,
+            ..UiConfig::default()
+        };
+        let mut app = test_app(config.clone(), LastReadCache::from_config(&config, None));
+        let data = history_entry("3100600123456").data.unwrap();
+        app.remember_read(&data, "10:00:00");
+
+        // A restored entry starts the timer at startup and the purge removes the file
+        let mut restarted = test_app(config.clone(), LastReadCache::from_config(&config, None));
+        assert_eq!(restarted.history.entries.len(), 1);
+        let restored_at = restarted.last_read_at.unwrap();
+        restarted.auto_clear(restored_at + Duration::from_secs(30));
+        assert!(restarted.history.entries.is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_card_stuck_status_detected() {
+        assert!(is_card_stuck(r#"{"mode":"cardstuck","reads":3,"seq":4}"#));
+        assert!(!is_card_stuck(r#"{"mode":"readsmartcard","Citizenid":"1"}"#));
+        assert!(!is_card_stuck("not json"));
+    }
+
+    #[test]
+    fn test_reveal_remaining() {
+        use std::time::{Duration, Instant};
+
+        let shown = Instant::now();
+        let ten = Some(Duration::from_secs(10));
+        assert_eq!(reveal_remaining(Some(shown), shown + Duration::from_secs(3), ten), Some(Duration::from_secs(7)));
+        assert_eq!(reveal_remaining(Some(shown), shown + Duration::from_secs(10), ten), Some(Duration::ZERO));
+        assert_eq!(reveal_remaining(Some(shown), shown + Duration::from_secs(60), ten), Some(Duration::ZERO));
+        // Not revealed, or no countdown configured
+        assert_eq!(reveal_remaining(None, shown, ten), None);
+        assert_eq!(reveal_remaining(Some(shown), shown + Duration::from_secs(60), None), None);
+    }
+
+    #[test]
+    fn test_reveal_masks_again_after_reveal_secs() {
+        use std::time::Duration;
+
+        let config = UiConfig {
+            reveal_secs: 10,
+            ..UiConfig::default()
+        };
+        let mut app = test_app(config, None);
+        let shown = std::time::Instant::now();
+        app.toggle_reveal(shown);
+        assert!(!app.data_hidden);
+
+        app.expire_reveal(shown + Duration::from_secs(5));
+        assert!(!app.data_hidden);
+        app.expire_reveal(shown + Duration::from_secs(10));
+        assert!(app.data_hidden && app.revealed_at.is_none());
+
+        // 0 = revealed until toggled off
+        let mut app = test_app(UiConfig::default(), None);
+        app.toggle_reveal(shown);
+        app.expire_reveal(shown + Duration::from_secs(3600));
+        assert!(!app.data_hidden);
+        app.toggle_reveal(shown);
+        assert!(app.data_hidden);
+    }
+
+    #[test]
+    fn test_retained_photo() {
+        use base64::Engine;
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(360, 480, image::Rgb([200, 180, 160]))
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+        let photo = base64::engine::general_purpose::STANDARD.encode(jpeg.into_inner());
+
+        assert_eq!(retained_photo(&photo, PhotoRetention::Full), photo);
+        assert_eq!(retained_photo(&photo, PhotoRetention::None), "");
+
+        let thumbnail = retained_photo(&photo, PhotoRetention::Thumbnail);
+        assert!(thumbnail.len() < photo.len());
+        let img = decode_photo(&thumbnail).unwrap();
+        assert_eq!((img.width(), img.height()), THUMBNAIL_SIZE);
+
+        // Undecodable photos are dropped, not kept at full size
+        assert_eq!(retained_photo("not base64!", PhotoRetention::Thumbnail), "");
+        assert_eq!(retained_photo("", PhotoRetention::Thumbnail), "");
+    }
+
+    #[test]
+    fn test_history_zero_capacity() {
+        let mut history = ReadHistory::new(0);
+        history.push(history_entry("1111111111111"));
+        assert!(history.entries.is_empty());
+    }
+
+    #[test]
+    fn test_history_summary_is_masked() {
+        let summary = history_entry("3100600123456").summary().to_string();
+        assert_eq!(
+            summary,
+            format!("10:00:00 {} ส.ใ. (ACS ACR39U)", mask_citizen_id("3100600123456"))
+        );
+        assert!(!summary.contains("3100600123456"));
+    }
+
+    #[test]
+    fn test_pretty_json_invalid_passthrough() {
+        assert_eq!(pretty_json("not json"), "not json");
+    }
+}
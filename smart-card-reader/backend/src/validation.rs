@@ -5,7 +5,9 @@
 //! - Protection against injection attacks
 //! - Early detection of corrupted or invalid data
 
+use crate::decoder::ThaiIDData;
 use regex::Regex;
+use serde::Deserialize;
 use std::sync::OnceLock;
 
 /// Type of validation error with security classification
@@ -29,6 +31,34 @@ impl std::fmt::Display for ValidationError {
     }
 }
 
+impl ValidationError {
+    /// Severity name: "Format", "Integrity" or "Security"
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ValidationError::Format(_) => "Format",
+            ValidationError::Integrity(_) => "Integrity",
+            ValidationError::Security(_) => "Security",
+        }
+    }
+
+    /// Error details without the severity prefix
+    #[must_use]
+    pub fn message(&self) -> &str {
+        match self {
+            ValidationError::Format(msg)
+            | ValidationError::Integrity(msg)
+            | ValidationError::Security(msg) => msg,
+        }
+    }
+
+    /// Whether this error indicates a possible injection attempt
+    #[must_use]
+    pub fn is_security(&self) -> bool {
+        matches!(self, ValidationError::Security(_))
+    }
+}
+
 /// Validation result with structural error
 pub type ValidationResult = Result<(), ValidationError>;
 
@@ -72,18 +102,37 @@ impl ThaiCitizenIdValidator {
             return false;
         }
 
-        // Calculate sum: multiply each of first 12 digits by (13 - position)
-        let sum: u32 = digits[..12]
+        Self::check_digit(&digits[..12]) == digits[12]
+    }
+
+    /// Modulo 11 check digit for the first 12 digits
+    fn check_digit(digits: &[u32]) -> u32 {
+        // Multiply each digit by (13 - position)
+        let sum: u32 = digits
             .iter()
             .enumerate()
             .map(|(i, &digit)| digit * (13 - i as u32))
             .sum();
 
-        // Calculate check digit
-        let calculated_check = (11 - (sum % 11)) % 10;
-        let provided_check = digits[12];
+        (11 - (sum % 11)) % 10
+    }
 
-        calculated_check == provided_check
+    /// Human-readable result for `--check-id`; `Err` carries the reason the ID is invalid
+    pub fn check(citizen_id: &str) -> Result<String, String> {
+        let clean_id = citizen_id.trim();
+        match Self::validate(clean_id) {
+            Ok(()) => Ok(format!("{}: valid", clean_id)),
+            Err(ValidationError::Integrity(_)) => {
+                let digits: Vec<u32> = clean_id.chars().filter_map(|c| c.to_digit(10)).collect();
+                Err(format!(
+                    "{}: invalid checksum (computed check digit {}, provided {})",
+                    clean_id,
+                    Self::check_digit(&digits[..12]),
+                    digits[12]
+                ))
+            }
+            Err(e) => Err(format!("{}: {}", clean_id, e)),
+        }
     }
 }
 
@@ -133,12 +182,80 @@ impl DateValidator {
     }
 }
 
+/// Punctuation allowed in names by default (besides Thai, ASCII letters/digits and spaces)
+pub const DEFAULT_NAME_PUNCTUATION: &str = ".,-'#()";
+/// Punctuation allowed in addresses by default, e.g. `99/1` or `A&B Tower`
+pub const DEFAULT_ADDRESS_PUNCTUATION: &str = ".,-'#()/&:";
+
+/// Characters used for markup, script or command injection
+///
+/// Outside the allowlist they make a field a security threat; other unlisted
+/// characters are an ordinary format error.
+pub const INJECTION_CHARS: &str = "<>{}[]\\|;&$`";
+
+/// Allowlist of characters permitted in a free-text field
+///
+/// Thai script, letters of any script (e.g. accented Latin), ASCII digits and
+/// spaces are always allowed; any other character must appear in `punctuation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharsetPolicy {
+    pub punctuation: String,
+}
+
+impl CharsetPolicy {
+    pub fn new(punctuation: impl Into<String>) -> Self {
+        Self {
+            punctuation: punctuation.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn allows(&self, c: char) -> bool {
+        c.is_alphabetic()
+            || c.is_ascii_digit()
+            || c == ' '
+            || ('\u{0E00}'..='\u{0E7F}').contains(&c)
+            || self.punctuation.contains(c)
+    }
+
+    /// Reject text containing any character outside the allowlist
+    ///
+    /// Injection characters ([`INJECTION_CHARS`]) and control characters are
+    /// security threats, anything else a format error.
+    pub fn check(&self, text: &str) -> ValidationResult {
+        match text.chars().find(|&c| !self.allows(c)) {
+            Some(c) if c.is_control() || INJECTION_CHARS.contains(c) => Err(ValidationError::Security(format!(
+                "Contains disallowed character {:?}",
+                c
+            ))),
+            Some(c) => Err(ValidationError::Format(format!("Contains unexpected character {:?}", c))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Character allowlists per validated field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldCharsets {
+    pub name: CharsetPolicy,
+    pub address: CharsetPolicy,
+}
+
+impl Default for FieldCharsets {
+    fn default() -> Self {
+        Self {
+            name: CharsetPolicy::new(DEFAULT_NAME_PUNCTUATION),
+            address: CharsetPolicy::new(DEFAULT_ADDRESS_PUNCTUATION),
+        }
+    }
+}
+
 /// Name validator for Thai names
 pub struct NameValidator;
 
 impl NameValidator {
-    /// Validate name (Thai or English characters, spaces allowed)
-    pub fn validate(name: &str) -> ValidationResult {
+    /// Validate name (Thai or English characters, spaces and `charset` punctuation allowed)
+    pub fn validate(name: &str, charset: &CharsetPolicy) -> ValidationResult {
         let clean_name = name.trim();
 
         // Check minimum length
@@ -154,15 +271,8 @@ impl NameValidator {
             )));
         }
 
-        // Check for suspicious characters (potential injection)
-        let suspicious_chars = ['<', '>', '{', '}', '[', ']', '\\', '|', ';', '&', '$'];
-        if clean_name.chars().any(|c| suspicious_chars.contains(&c)) {
-            return Err(ValidationError::Security(
-                "Contains suspicious characters".to_string(),
-            ));
-        }
-
-        Ok(())
+        // Reject anything outside the allowlist (potential injection)
+        charset.check(clean_name)
     }
 }
 
@@ -189,8 +299,8 @@ impl GenderValidator {
 pub struct AddressValidator;
 
 impl AddressValidator {
-    /// Validate address (basic sanitization)
-    pub fn validate(address: &str) -> ValidationResult {
+    /// Validate address (basic sanitization against `charset`)
+    pub fn validate(address: &str, charset: &CharsetPolicy) -> ValidationResult {
         let clean_address = address.trim();
 
         // Check minimum length
@@ -208,15 +318,8 @@ impl AddressValidator {
             )));
         }
 
-        // Check for suspicious characters (potential injection)
-        let suspicious_chars = ['<', '>', '{', '}', '[', ']', '\\', '|', ';', '&', '$'];
-        if clean_address.chars().any(|c| suspicious_chars.contains(&c)) {
-            return Err(ValidationError::Security(
-                "Contains suspicious characters".to_string(),
-            ));
-        }
-
-        Ok(())
+        // Reject anything outside the allowlist (potential injection)
+        charset.check(clean_address)
     }
 }
 
@@ -225,6 +328,7 @@ pub struct CardDataValidator;
 
 impl CardDataValidator {
     /// Validate all card data fields
+    #[allow(clippy::too_many_arguments)]
     pub fn validate_all(
         citizen_id: Option<&str>,
         birth_date: Option<&str>,
@@ -234,6 +338,7 @@ impl CardDataValidator {
         thai_name: Option<&str>,
         english_name: Option<&str>,
         address: Option<&str>,
+        charsets: &FieldCharsets,
     ) -> Vec<(String, ValidationError)> {
         let mut errors = Vec::new();
 
@@ -270,19 +375,19 @@ impl CardDataValidator {
 
         // Validate names
         if let Some(name) = thai_name {
-            if let Err(e) = NameValidator::validate(name) {
+            if let Err(e) = NameValidator::validate(name, &charsets.name) {
                 errors.push(("Thai name".to_string(), e));
             }
         }
         if let Some(name) = english_name {
-            if let Err(e) = NameValidator::validate(name) {
+            if let Err(e) = NameValidator::validate(name, &charsets.name) {
                 errors.push(("English name".to_string(), e));
             }
         }
 
         // Validate address
         if let Some(addr) = address {
-            if let Err(e) = AddressValidator::validate(addr) {
+            if let Err(e) = AddressValidator::validate(addr, &charsets.address) {
                 errors.push(("Address".to_string(), e));
             }
         }
@@ -291,6 +396,157 @@ impl CardDataValidator {
     }
 }
 
+/// Which validation severities prevent a card from being broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockOn {
+    /// Only security threats
+    #[default]
+    Security,
+    /// Security threats and integrity errors (e.g. citizen ID checksum)
+    Integrity,
+    /// Any validation error
+    Any,
+    /// Never block (errors are still logged)
+    None,
+}
+
+impl BlockOn {
+    /// Whether `error` blocks the broadcast under this policy
+    #[must_use]
+    pub fn blocks(self, error: &ValidationError) -> bool {
+        match self {
+            Self::Security => error.is_security(),
+            Self::Integrity => !matches!(error, ValidationError::Format(_)),
+            Self::Any => true,
+            Self::None => false,
+        }
+    }
+}
+
+impl std::fmt::Display for BlockOn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Security => write!(f, "security"),
+            Self::Integrity => write!(f, "integrity"),
+            Self::Any => write!(f, "any"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Options for [`validate_card`]
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    /// Validate birth/issue/expire dates
+    pub check_dates: bool,
+    /// Severities that make the card unacceptable
+    pub block_on: BlockOn,
+    /// Characters permitted in names and addresses
+    pub charsets: FieldCharsets,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            check_dates: true,
+            block_on: BlockOn::Security,
+            charsets: FieldCharsets::default(),
+        }
+    }
+}
+
+/// Aggregate validation outcome for one card
+#[derive(Debug, Clone, Default)]
+pub struct CardValidation {
+    /// Whether the card may be broadcast to clients
+    pub is_acceptable: bool,
+    /// Security errors (field, error)
+    pub security_threats: Vec<(String, ValidationError)>,
+    /// Format/integrity errors (field, error)
+    pub warnings: Vec<(String, ValidationError)>,
+}
+
+impl CardValidation {
+    /// All errors, security threats first
+    pub fn errors(&self) -> impl Iterator<Item = &(String, ValidationError)> {
+        self.security_threats.iter().chain(self.warnings.iter())
+    }
+}
+
+/// Validate a decoded card and decide whether it is acceptable
+///
+/// Acceptance follows `config.block_on`; by default only security threats
+/// reject the card.
+#[must_use]
+pub fn validate_card<'a>(data: &'a ThaiIDData, config: &ValidationConfig) -> CardValidation {
+    let thai_name = crate::decoder::full_thai_name(data);
+    let date = |d: &'a str| config.check_dates.then_some(d);
+
+    let mut errors = CardDataValidator::validate_all(
+        Some(&data.citizen_id),
+        date(&data.birthday),
+        date(&data.issue),
+        date(&data.expire),
+        Some(&data.sex),
+        Some(&thai_name),
+        Some(&data.full_name_en),
+        Some(&data.address),
+        &config.charsets,
+    );
+
+    // The English address is optional (card.fields "address_en"); its parts
+    // and the Thai ones are emitted as separate fields, so each is checked too
+    if !data.address_en.trim().is_empty() {
+        if let Err(e) = AddressValidator::validate(&data.address_en, &config.charsets.address) {
+            errors.push(("English address".to_string(), e));
+        }
+    }
+    let parts = [
+        ("Address house no", &data.addr_house_no),
+        ("Address village no", &data.addr_village_no),
+        ("Address road", &data.addr_road),
+        ("Address lane", &data.addr_lane),
+        ("Address tambol", &data.addr_tambol),
+        ("Address amphur", &data.addr_amphur),
+        ("Address province", &data.addr_province),
+        ("English address house no", &data.addr_house_no_en),
+        ("English address village no", &data.addr_village_no_en),
+        ("English address road", &data.addr_road_en),
+        ("English address lane", &data.addr_lane_en),
+        ("English address tambol", &data.addr_tambol_en),
+        ("English address amphur", &data.addr_amphur_en),
+        ("English address province", &data.addr_province_en),
+    ];
+    for (field, value) in parts {
+        if let Err(e) = config.charsets.address.check(value.trim()) {
+            errors.push((field.to_string(), e));
+        }
+    }
+
+    // NHSO values are free text (rights codes, hospital names, dates) read
+    // from a second applet; blank ones are allowed
+    for (name, value) in &data.nhso {
+        if let Err(e) = config.charsets.address.check(value.trim()) {
+            errors.push((format!("NHSO {}", name), e));
+        }
+    }
+
+    let (security_threats, warnings): (Vec<_>, Vec<_>) =
+        errors.into_iter().partition(|(_, e)| e.is_security());
+
+    let is_acceptable = !security_threats
+        .iter()
+        .chain(warnings.iter())
+        .any(|(_, e)| config.block_on.blocks(e));
+
+    CardValidation {
+        is_acceptable,
+        security_threats,
+        warnings,
+    }
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -318,6 +574,30 @@ mod tests {
         assert!(ThaiCitizenIdValidator::validate("1-2345-67890-12").is_err());
     }
 
+    #[test]
+    fn test_check_id_valid() {
+        assert_eq!(
+            ThaiCitizenIdValidator::check(" 1234567890121 "),
+            Ok("1234567890121: valid".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_id_wrong_checksum() {
+        assert_eq!(
+            ThaiCitizenIdValidator::check("1234567890123"),
+            Err("1234567890123: invalid checksum (computed check digit 1, provided 3)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_id_malformed() {
+        let err = ThaiCitizenIdValidator::check("12345").unwrap_err();
+        assert!(err.contains("Invalid length"), "{err}");
+        let err = ThaiCitizenIdValidator::check("123456789012a").unwrap_err();
+        assert!(err.contains("non-digit"), "{err}");
+    }
+
     #[test]
     fn test_valid_dates() {
         assert!(DateValidator::validate("19900115").is_ok());
@@ -333,18 +613,101 @@ mod tests {
 
     #[test]
     fn test_name_validation() {
-        assert!(NameValidator::validate("นายทดสอบ ระบบ").is_ok());
-        assert!(NameValidator::validate("Test User").is_ok());
+        assert!(NameValidator::validate("นายทดสอบ ระบบ", &FieldCharsets::default().name).is_ok());
+        assert!(NameValidator::validate("Test User", &FieldCharsets::default().name).is_ok());
         assert!(matches!(
-            NameValidator::validate(""),
+            NameValidator::validate("", &FieldCharsets::default().name),
             Err(ValidationError::Format(_))
         )); // Empty
         assert!(matches!(
-            NameValidator::validate("<script>alert()</script>"),
+            NameValidator::validate("<script>alert()</script>", &FieldCharsets::default().name),
             Err(ValidationError::Security(_))
         )); // Injection
     }
 
+    #[test]
+    fn test_address_allowlist_defaults() {
+        let FieldCharsets { name, address } = FieldCharsets::default();
+        // Unit numbers and '&' are legitimate in Thai addresses
+        assert!(AddressValidator::validate("99/1 หมู่ที่ 2 ซอย A&B", &address).is_ok());
+        assert!(AddressValidator::validate("123 ถนนสุขุมวิท; DROP TABLE", &address).is_err());
+        assert!(NameValidator::validate("Mr.#Somchai##Jaidee", &name).is_ok());
+        assert!(NameValidator::validate("Somchai/Jaidee", &name).is_err());
+    }
+
+    #[test]
+    fn test_custom_charset() {
+        let strict = CharsetPolicy::new("");
+        assert!(matches!(
+            AddressValidator::validate("99/1 ถนนสุขุมวิท", &strict),
+            Err(ValidationError::Format(msg)) if msg.contains("'/'")
+        ));
+        assert!(AddressValidator::validate("99 ถนนสุขุมวิท", &strict).is_ok());
+        assert!(NameValidator::validate("A;B", &CharsetPolicy::new(";")).is_ok());
+    }
+
+    #[test]
+    fn test_unlisted_characters_are_not_all_threats() {
+        let FieldCharsets { name, address } = FieldCharsets::default();
+        // Accented Latin letters are letters
+        assert!(NameValidator::validate("José Müller", &name).is_ok());
+        // Unusual but harmless punctuation is a format error, not a threat
+        for text in ["O\"Brien", "A+B", "x@y", "a_b"] {
+            assert!(
+                matches!(NameValidator::validate(text, &name), Err(ValidationError::Format(_))),
+                "{}",
+                text
+            );
+        }
+        // Injection and control characters stay security threats
+        for text in ["A<B", "A;B", "A`B", "A\u{0}B"] {
+            assert!(
+                matches!(AddressValidator::validate(text, &address), Err(ValidationError::Security(_))),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_card_accepts_slash_address() {
+        let mut card = clean_card();
+        card.address = "99/1 อาคาร A&B ห้อง 12/3 ตำบลบางรัก".to_string();
+        let result = validate_card(&card, &ValidationConfig::default());
+        assert!(result.is_acceptable, "{:?}", result.security_threats);
+    }
+
+    #[test]
+    fn test_validate_card_checks_english_address() {
+        let mut card = clean_card();
+        card.address_en = "99/1 Moo 2, Bang Rak, Bangkok".to_string();
+        card.addr_tambol_en = "Bang Rak".to_string();
+        assert!(validate_card(&card, &ValidationConfig::default()).is_acceptable);
+
+        card.address_en = "99 <script>".to_string();
+        let result = validate_card(&card, &ValidationConfig::default());
+        assert!(!result.is_acceptable);
+        assert_eq!(result.security_threats[0].0, "English address");
+
+        card.address_en = "99 Bang Rak".to_string();
+        card.addr_road_en = "Road;DROP".to_string();
+        let result = validate_card(&card, &ValidationConfig::default());
+        assert_eq!(result.security_threats[0].0, "English address road");
+    }
+
+    #[test]
+    fn test_validate_card_checks_nhso() {
+        let mut card = clean_card();
+        card.nhso.insert("main_inscl".to_string(), "UCS".to_string());
+        card.nhso.insert("main_hosp_name".to_string(), String::new());
+        assert!(validate_card(&card, &ValidationConfig::default()).is_acceptable);
+
+        card.nhso.insert("sub_inscl".to_string(), "<script>".to_string());
+        let result = validate_card(&card, &ValidationConfig::default());
+        assert!(!result.is_acceptable);
+        assert_eq!(result.security_threats[0].0, "NHSO sub_inscl");
+    }
+
     #[test]
     fn test_gender_validation() {
         assert!(GenderValidator::validate("1").is_ok());
@@ -356,14 +719,108 @@ mod tests {
 
     #[test]
     fn test_address_validation() {
-        assert!(AddressValidator::validate("123 ถนนสุขุมวิท").is_ok());
+        assert!(AddressValidator::validate("123 ถนนสุขุมวิท", &FieldCharsets::default().address).is_ok());
         assert!(matches!(
-            AddressValidator::validate(""),
+            AddressValidator::validate("", &FieldCharsets::default().address),
             Err(ValidationError::Format(_))
         )); // Empty
         assert!(matches!(
-            AddressValidator::validate("123<script>"),
+            AddressValidator::validate("123<script>", &FieldCharsets::default().address),
             Err(ValidationError::Security(_))
         )); // Injection
     }
+
+    fn clean_card() -> ThaiIDData {
+        ThaiIDData {
+            citizen_id: "1101700230708".to_string(),
+            th_prefix: "นาย".to_string(),
+            th_firstname: "สมชาย".to_string(),
+            th_lastname: "ใจดี".to_string(),
+            full_name_en: "Mr. Somchai Jaidee".to_string(),
+            birthday: "19900115".to_string(),
+            issue: "20200101".to_string(),
+            expire: "20300101".to_string(),
+            sex: "1".to_string(),
+            address: "99/1 หมู่ที่ 2 ตำบลบางรัก อำเภอบางรัก กรุงเทพมหานคร".to_string(),
+            ..ThaiIDData::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_card_clean() {
+        let result = validate_card(&clean_card(), &ValidationConfig::default());
+        assert!(result.is_acceptable);
+        assert!(result.security_threats.is_empty());
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_validate_card_format_warning_only() {
+        let mut card = clean_card();
+        card.sex = "9".to_string();
+        card.citizen_id = "1101700230709".to_string();
+
+        let result = validate_card(&card, &ValidationConfig::default());
+        assert!(result.is_acceptable);
+        assert!(result.security_threats.is_empty());
+        let kinds: Vec<&str> = result.warnings.iter().map(|(_, e)| e.kind()).collect();
+        assert_eq!(kinds, vec!["Integrity", "Format"]);
+    }
+
+    #[test]
+    fn test_validate_card_security_threat() {
+        let mut card = clean_card();
+        card.address = "<script>alert(1)</script>".to_string();
+
+        let result = validate_card(&card, &ValidationConfig::default());
+        assert!(!result.is_acceptable);
+        assert_eq!(result.security_threats.len(), 1);
+        assert_eq!(result.security_threats[0].0, "Address");
+        assert_eq!(result.errors().count(), 1);
+    }
+
+    #[test]
+    fn test_validate_card_skips_dates() {
+        let mut card = clean_card();
+        card.birthday = "2533/01/15".to_string();
+
+        assert!(!validate_card(&card, &ValidationConfig::default()).warnings.is_empty());
+        let config = ValidationConfig {
+            check_dates: false,
+            ..ValidationConfig::default()
+        };
+        assert!(validate_card(&card, &config).warnings.is_empty());
+    }
+
+    #[test]
+    fn test_block_on_policies() {
+        let format = ValidationError::Format("bad gender".into());
+        let integrity = ValidationError::Integrity("bad checksum".into());
+        let security = ValidationError::Security("injection".into());
+
+        let decisions = |policy: BlockOn| {
+            [&format, &integrity, &security].map(|e| policy.blocks(e))
+        };
+        assert_eq!(decisions(BlockOn::Security), [false, false, true]);
+        assert_eq!(decisions(BlockOn::Integrity), [false, true, true]);
+        assert_eq!(decisions(BlockOn::Any), [true, true, true]);
+        assert_eq!(decisions(BlockOn::None), [false, false, false]);
+    }
+
+    #[test]
+    fn test_validate_card_respects_block_on() {
+        let mut card = clean_card();
+        card.citizen_id = "1101700230709".to_string(); // checksum failure
+
+        let policy = |block_on| ValidationConfig {
+            block_on,
+            ..ValidationConfig::default()
+        };
+        assert!(validate_card(&card, &policy(BlockOn::Security)).is_acceptable);
+        assert!(!validate_card(&card, &policy(BlockOn::Integrity)).is_acceptable);
+        assert!(!validate_card(&card, &policy(BlockOn::Any)).is_acceptable);
+
+        card.address = "<script>".to_string();
+        assert!(validate_card(&card, &policy(BlockOn::None)).is_acceptable);
+    }
 }